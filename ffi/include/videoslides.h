@@ -0,0 +1,94 @@
+#ifndef VIDEOSLIDES_H
+#define VIDEOSLIDES_H
+
+/* Generated by cbindgen from ffi/src/lib.rs, do not edit. */
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+// Pipeline stage reported to the progress callback
+typedef enum VseStage {
+  VseStage_Syncing = 0,
+  VseStage_Extracting = 1,
+  VseStage_Comparing = 2,
+} VseStage;
+
+// Lets one thread stop a `vse_run` running on another
+typedef struct VseCancelToken VseCancelToken;
+
+// Extraction settings, created by `vse_config_new`
+typedef struct VseConfig VseConfig;
+
+// Outcome of a successful `vse_run`
+typedef struct VseResult VseResult;
+
+// Progress callback: `context` is passed through untouched, `total` is -1 when unknown
+typedef void (*VseProgressFn)(void *context, enum VseStage stage, uintptr_t done, int64_t total);
+
+// One kept slide; `file` points into the owning `VseResult`
+typedef struct VseSlide {
+  uintptr_t index;
+  const char *file;
+  double timestamp;
+} VseSlide;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+// Default settings for extracting slides from `input_file` (UTF-8 outside Unix).
+// Returns NULL if the path is NULL or, outside Unix, not valid UTF-8.
+struct VseConfig *vse_config_new(const char *input_file);
+
+// Directory frames are extracted to and kept slides stay in. Returns 0 on success.
+int32_t vse_config_set_output_dir(struct VseConfig *config, const char *output_dir);
+
+// Frames sampled per second of video. Returns 0 on success.
+int32_t vse_config_set_fps(struct VseConfig *config, uint32_t fps);
+
+// Largest share of differing pixels for two frames to count as the same slide. Returns 0 on success.
+int32_t vse_config_set_threshold(struct VseConfig *config,
+                                 double threshold);
+
+// Leave regions that change in every frame (embedded videos) out of the comparison. Returns 0 on success.
+int32_t vse_config_set_ignore_embedded_video(struct VseConfig *config,
+                                             bool enabled);
+
+void vse_config_free(struct VseConfig *config);
+
+struct VseCancelToken *vse_cancel_token_new(void);
+
+// Ask the run using this token to stop; safe to call from any thread
+void vse_cancel_token_cancel(const struct VseCancelToken *token);
+
+void vse_cancel_token_free(struct VseCancelToken *token);
+
+// Run the pipeline, blocking until it finishes.
+//
+// `progress` and `cancel` may be NULL. On failure NULL is returned and, if
+// `error` is not NULL, `*error` receives a message to free with `vse_string_free`.
+struct VseResult *vse_run(const struct VseConfig *config,
+                          VseProgressFn progress,
+                          void *context,
+                          const struct VseCancelToken *cancel,
+                          char **error);
+
+uintptr_t vse_result_slide_count(const struct VseResult *result);
+
+// Fill `slide` with the slide at position `i`. Returns 0 on success, -1 if out of range.
+int32_t vse_result_slide(const struct VseResult *result, uintptr_t i, struct VseSlide *slide);
+
+// The full manifest as JSON, owned by `result`
+const char *vse_result_manifest_json(const struct VseResult *result);
+
+void vse_result_free(struct VseResult *result);
+
+// Free an error message returned by `vse_run`
+void vse_string_free(char *s);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* VIDEOSLIDES_H */
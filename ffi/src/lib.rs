@@ -0,0 +1,247 @@
+//! C interface to the slide extractor, so native applications can run the
+//! pipeline in-process instead of shelling out and parsing stdout.
+//!
+//! Typical use: create a config with `vse_config_new`, adjust it with the
+//! setters, call `vse_run`, walk the slides with `vse_result_slide`, then free
+//! everything with the matching `*_free` function. Strings returned by the
+//! library stay valid until the object that owns them is freed, except error
+//! messages, which the caller frees with `vse_string_free`.
+
+#![allow(clippy::missing_safety_doc)]
+
+use std::ffi::{c_char, c_void, CStr, CString};
+use std::path::PathBuf;
+use std::ptr;
+
+use video_slide_extractor::{run_with, CancellationToken, Config, Manifest, Stage};
+
+/// Extraction settings, created by `vse_config_new`
+pub struct VseConfig(Config);
+
+/// Outcome of a successful `vse_run`
+pub struct VseResult {
+    manifest: Manifest,
+    files: Vec<CString>,
+    json: CString,
+}
+
+/// Lets one thread stop a `vse_run` running on another
+pub struct VseCancelToken(CancellationToken);
+
+/// Pipeline stage reported to the progress callback
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VseStage {
+    Syncing = 0,
+    Extracting = 1,
+    Comparing = 2,
+}
+
+/// Progress callback: `context` is passed through untouched, `total` is -1 when unknown
+pub type VseProgressFn = Option<extern "C" fn(context: *mut c_void, stage: VseStage, done: usize, total: i64)>;
+
+/// One kept slide; `file` points into the owning `VseResult`
+#[repr(C)]
+pub struct VseSlide {
+    pub index: usize,
+    pub file: *const c_char,
+    pub timestamp: f64,
+}
+
+/// A path as the platform spells it: any bytes on Unix, UTF-8 elsewhere
+unsafe fn to_path(s: *const c_char) -> Option<PathBuf> {
+    if s.is_null() {
+        return None;
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::ffi::OsStrExt;
+        Some(std::ffi::OsStr::from_bytes(CStr::from_ptr(s).to_bytes()).into())
+    }
+    #[cfg(not(unix))]
+    {
+        CStr::from_ptr(s).to_str().ok().map(PathBuf::from)
+    }
+}
+
+/// Default settings for extracting slides from `input_file` (UTF-8 outside Unix).
+/// Returns NULL if the path is NULL or, outside Unix, not valid UTF-8.
+#[no_mangle]
+pub unsafe extern "C" fn vse_config_new(input_file: *const c_char) -> *mut VseConfig {
+    match to_path(input_file) {
+        Some(input_file) => Box::into_raw(Box::new(VseConfig(Config::new(input_file)))),
+        None => ptr::null_mut(),
+    }
+}
+
+/// Directory frames are extracted to and kept slides stay in. Returns 0 on success.
+#[no_mangle]
+pub unsafe extern "C" fn vse_config_set_output_dir(config: *mut VseConfig, output_dir: *const c_char) -> i32 {
+    match (config.as_mut(), to_path(output_dir)) {
+        (Some(config), Some(output_dir)) => {
+            config.0.output_dir = output_dir;
+            0
+        }
+        _ => -1,
+    }
+}
+
+/// Frames sampled per second of video. Returns 0 on success.
+#[no_mangle]
+pub unsafe extern "C" fn vse_config_set_fps(config: *mut VseConfig, fps: u32) -> i32 {
+    match config.as_mut() {
+        Some(config) if fps > 0 => {
+            config.0.fps = fps;
+            0
+        }
+        _ => -1,
+    }
+}
+
+/// Largest share of differing pixels for two frames to count as the same slide. Returns 0 on success.
+#[no_mangle]
+pub unsafe extern "C" fn vse_config_set_threshold(config: *mut VseConfig, threshold: f64) -> i32 {
+    match config.as_mut() {
+        Some(config) if (0.0..=1.0).contains(&threshold) => {
+            config.0.threshold = threshold;
+            0
+        }
+        _ => -1,
+    }
+}
+
+/// Leave regions that change in every frame (embedded videos) out of the comparison. Returns 0 on success.
+#[no_mangle]
+pub unsafe extern "C" fn vse_config_set_ignore_embedded_video(config: *mut VseConfig, enabled: bool) -> i32 {
+    match config.as_mut() {
+        Some(config) => {
+            config.0.ignore_embedded_video = enabled;
+            0
+        }
+        None => -1,
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn vse_config_free(config: *mut VseConfig) {
+    if !config.is_null() {
+        drop(Box::from_raw(config));
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn vse_cancel_token_new() -> *mut VseCancelToken {
+    Box::into_raw(Box::new(VseCancelToken(CancellationToken::new())))
+}
+
+/// Ask the run using this token to stop; safe to call from any thread
+#[no_mangle]
+pub unsafe extern "C" fn vse_cancel_token_cancel(token: *const VseCancelToken) {
+    if let Some(token) = token.as_ref() {
+        token.0.cancel();
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn vse_cancel_token_free(token: *mut VseCancelToken) {
+    if !token.is_null() {
+        drop(Box::from_raw(token));
+    }
+}
+
+/// Run the pipeline, blocking until it finishes.
+///
+/// `progress` and `cancel` may be NULL. On failure NULL is returned and, if
+/// `error` is not NULL, `*error` receives a message to free with `vse_string_free`.
+#[no_mangle]
+pub unsafe extern "C" fn vse_run(
+    config: *const VseConfig,
+    progress: VseProgressFn,
+    context: *mut c_void,
+    cancel: *const VseCancelToken,
+    error: *mut *mut c_char,
+) -> *mut VseResult {
+    let fail = |message: String| {
+        if !error.is_null() {
+            *error = CString::new(message.replace('\0', " ")).unwrap_or_default().into_raw();
+        }
+        ptr::null_mut()
+    };
+
+    let Some(config) = config.as_ref() else {
+        return fail("config is NULL".to_string());
+    };
+    let token = cancel.as_ref().map(|t| t.0.clone()).unwrap_or_default();
+
+    // Raw pointers aren't Send; the callback is only ever invoked on this thread
+    let context = context as usize;
+    let report = |p: video_slide_extractor::Progress| {
+        if let Some(callback) = progress {
+            let stage = match p.stage {
+                Stage::Syncing => VseStage::Syncing,
+                Stage::Extracting => VseStage::Extracting,
+                Stage::Comparing => VseStage::Comparing,
+            };
+            callback(context as *mut c_void, stage, p.done, p.total.map(|t| t as i64).unwrap_or(-1));
+        }
+    };
+
+    let manifest = match run_with(&config.0, report, &token) {
+        Ok(manifest) => manifest,
+        Err(e) => return fail(e.to_string()),
+    };
+    let json = match manifest.to_json() {
+        Ok(json) => CString::new(json).unwrap_or_default(),
+        Err(e) => return fail(e.to_string()),
+    };
+    let files = manifest
+        .slides
+        .iter()
+        .map(|slide| CString::new(slide.file.as_str()).unwrap_or_default())
+        .collect();
+
+    Box::into_raw(Box::new(VseResult { manifest, files, json }))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn vse_result_slide_count(result: *const VseResult) -> usize {
+    result.as_ref().map(|r| r.manifest.slides.len()).unwrap_or(0)
+}
+
+/// Fill `slide` with the slide at position `i`. Returns 0 on success, -1 if out of range.
+#[no_mangle]
+pub unsafe extern "C" fn vse_result_slide(result: *const VseResult, i: usize, slide: *mut VseSlide) -> i32 {
+    let (Some(result), Some(out)) = (result.as_ref(), slide.as_mut()) else {
+        return -1;
+    };
+    match result.manifest.slides.get(i) {
+        Some(s) => {
+            out.index = s.index;
+            out.file = result.files[i].as_ptr();
+            out.timestamp = s.timestamp;
+            0
+        }
+        None => -1,
+    }
+}
+
+/// The full manifest as JSON, owned by `result`
+#[no_mangle]
+pub unsafe extern "C" fn vse_result_manifest_json(result: *const VseResult) -> *const c_char {
+    result.as_ref().map(|r| r.json.as_ptr()).unwrap_or(ptr::null())
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn vse_result_free(result: *mut VseResult) {
+    if !result.is_null() {
+        drop(Box::from_raw(result));
+    }
+}
+
+/// Free an error message returned by `vse_run`
+#[no_mangle]
+pub unsafe extern "C" fn vse_string_free(s: *mut c_char) {
+    if !s.is_null() {
+        drop(CString::from_raw(s));
+    }
+}
@@ -0,0 +1,4 @@
+fn main() {
+    // Addons resolve the N-API symbols at load time, which macOS has to be told about
+    napi_build::setup();
+}
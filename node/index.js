@@ -0,0 +1,26 @@
+// Event-emitting wrapper around the native addon.
+//
+//   const run = extractSlides('talk.mp4', { outputDir: 'slides' });
+//   run.on('progress', (p) => ...).on('slide', (s) => ...).on('done', (manifest) => ...);
+//   run.on('error', (err) => ...);
+//   run.cancel();
+
+const { EventEmitter } = require('events');
+const native = require('./videoslides.node');
+
+function extractSlides(input, options) {
+  const emitter = new EventEmitter();
+  const token = new native.CancelToken();
+  emitter.cancel = () => token.cancel();
+
+  native
+    .extract(input, options, (event) => emitter.emit(event.type, event), token)
+    .then(
+      (manifest) => emitter.emit('done', manifest),
+      (err) => emitter.emit('error', err),
+    );
+
+  return emitter;
+}
+
+module.exports = { extractSlides, CancelToken: native.CancelToken };
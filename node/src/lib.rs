@@ -0,0 +1,153 @@
+//! Node.js bindings, built as a native addon with napi-rs.
+//!
+//! `extract()` resolves with the manifest once the run is over and reports
+//! `progress` and `slide` events to a callback as they happen; `index.js`
+//! wraps that in an `EventEmitter`.
+
+
+use napi::bindgen_prelude::*;
+use napi::threadsafe_function::{ErrorStrategy, ThreadsafeFunction, ThreadsafeFunctionCallMode};
+use napi::{Env, JsFunction, JsObject};
+use napi_derive::napi;
+use serde_json::{json, Value};
+use tokio_stream::StreamExt;
+
+use video_slide_extractor::{run_stream, CancellationToken, Config, SlideEvent, SyncMode};
+
+/// Settings of one extraction; anything left out uses the command line default
+#[napi(object)]
+pub struct ExtractOptions {
+    pub output_dir: Option<String>,
+    pub fps: Option<u32>,
+    pub threshold: Option<f64>,
+    pub low_threshold: Option<f64>,
+    pub camera: Option<String>,
+    /// "offset" or "audio"
+    pub sync: Option<String>,
+    pub camera_offset: Option<f64>,
+    pub compare_stride: Option<u32>,
+    pub gpu: Option<bool>,
+    pub ignore_embedded_video: Option<bool>,
+    pub motion_streak: Option<u32>,
+    pub settle_frames: Option<u32>,
+    pub min_confidence: Option<f64>,
+    pub merge_revisits: Option<bool>,
+    pub find_links: Option<bool>,
+    pub trim_idle: Option<f64>,
+}
+
+/// Stops the extraction it was passed to
+#[napi]
+pub struct CancelToken {
+    token: CancellationToken,
+}
+
+#[napi]
+impl CancelToken {
+    #[napi(constructor)]
+    #[allow(clippy::new_without_default)]
+    pub fn new() -> Self {
+        CancelToken { token: CancellationToken::new() }
+    }
+
+    #[napi]
+    pub fn cancel(&self) {
+        self.token.cancel();
+    }
+}
+
+fn to_config(input: String, options: Option<ExtractOptions>) -> Result<Config> {
+    let mut config = Config::new(input);
+    let Some(options) = options else {
+        return Ok(config);
+    };
+    if let Some(output_dir) = options.output_dir {
+        config.output_dir = output_dir.into();
+    }
+    if let Some(fps) = options.fps {
+        config.fps = fps;
+    }
+    if let Some(threshold) = options.threshold {
+        config.threshold = threshold;
+    }
+    config.low_threshold = options.low_threshold;
+    config.camera_file = options.camera.map(Into::into);
+    config.sync = match options.sync.as_deref() {
+        None | Some("offset") => SyncMode::Offset,
+        Some("audio") => SyncMode::Audio,
+        Some(other) => {
+            return Err(Error::new(
+                Status::InvalidArg,
+                format!("sync must be \"offset\" or \"audio\", not {:?}", other),
+            ))
+        }
+    };
+    config.camera_offset = options.camera_offset.unwrap_or(0.0);
+    if let Some(compare_stride) = options.compare_stride {
+        config.compare_stride = compare_stride;
+    }
+    config.gpu = options.gpu.unwrap_or(false);
+    config.ignore_embedded_video = options.ignore_embedded_video.unwrap_or(false);
+    if let Some(motion_streak) = options.motion_streak {
+        config.motion_streak = motion_streak;
+    }
+    if let Some(settle_frames) = options.settle_frames {
+        config.settle_frames = settle_frames;
+    }
+    config.min_confidence = options.min_confidence;
+    config.merge_revisits = options.merge_revisits.unwrap_or(false);
+    config.find_links = options.find_links.unwrap_or(false);
+    config.trim_idle = options.trim_idle;
+    Ok(config)
+}
+
+/// Extract the slides of `input`, calling `on_event` with each progress and slide event
+#[napi(
+    ts_args_type = "input: string, options: ExtractOptions | undefined | null, onEvent: (event: any) => void, cancel?: CancelToken",
+    ts_return_type = "Promise<any>"
+)]
+pub fn extract(
+    env: Env,
+    input: String,
+    options: Option<ExtractOptions>,
+    on_event: JsFunction,
+    cancel: Option<&CancelToken>,
+) -> Result<JsObject> {
+    let config = to_config(input, options)?;
+    let cancel = cancel.map(|c| c.token.clone()).unwrap_or_default();
+    let on_event: ThreadsafeFunction<Value, ErrorStrategy::Fatal> =
+        on_event.create_threadsafe_function(0, |ctx| Ok(vec![ctx.value]))?;
+
+    env.execute_tokio_future(
+        async move {
+            tokio::fs::create_dir_all(&config.output_dir).await?;
+            let output_dir = config.output_dir.clone();
+            let mut events = Box::pin(run_stream(config, cancel));
+
+            while let Some(event) = events.next().await {
+                let event = match event {
+                    SlideEvent::Progress(p) => json!({
+                        "type": "progress",
+                        "stage": p.stage,
+                        "done": p.done,
+                        "total": p.total,
+                    }),
+                    SlideEvent::Slide(slide) => json!({
+                        "type": "slide",
+                        "index": slide.index,
+                        "path": output_dir.join(&slide.file).to_string_lossy(),
+                        "timestamp": slide.timestamp,
+                        "cameraTimestamp": slide.camera_timestamp,
+                    }),
+                    SlideEvent::Finished(manifest) => {
+                        return serde_json::to_value(&manifest).map_err(|e| Error::from_reason(e.to_string()))
+                    }
+                    SlideEvent::Failed(e) => return Err(Error::from_reason(e.to_string())),
+                };
+                on_event.call(event, ThreadsafeFunctionCallMode::NonBlocking);
+            }
+            Err(Error::from_reason("pipeline stopped without a result"))
+        },
+        |env, manifest| env.to_js_value(&manifest),
+    )
+}
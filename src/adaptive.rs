@@ -0,0 +1,165 @@
+//! `--adaptive`: sample the video sparsely first and at the full `Config::fps`
+//! (1, or what `--auto-fps` picks) only around the changes that turns up, so a
+//! mostly static lecture gets frame-accurate transitions for a fraction of the
+//! decoding.
+//!
+//! The pipeline still sees a frame for every `Config::fps` step: where two sparse
+//! samples match, the earlier one stands in for the frames between them. A
+//! slide shown and taken down again between two sparse samples is missed.
+
+use image::DynamicImage;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::compare::{prefilter, prefilter_kernel, Comparer};
+use crate::config::{Config, WorkspacePolicy};
+use crate::error::Error;
+use crate::extract::{check_input, move_file, sample_window, sampled_frames};
+use crate::memory::fit_budget;
+use crate::probe::probe;
+use crate::progress::{CancellationToken, Progress, Stage};
+use crate::runlog::RunLog;
+use crate::source::{open_frame, Frame, FrameSource};
+use crate::workspace::Workspace;
+
+/// Frames sampled every `adaptive` seconds, and at `fps` between those that differ
+pub struct AdaptiveSource {
+    config: Config,
+    log: RunLog,
+    frames_dir: Option<Workspace>,
+    /// The sparse samples, in order
+    coarse: Vec<PathBuf>,
+    /// Full-rate frames of the stretch after each sparse sample that changed, if it did
+    dense: Vec<Option<Vec<PathBuf>>>,
+    /// `fps` steps between two sparse samples
+    step: usize,
+    total: usize,
+    position: usize,
+    /// The last sparse sample decoded, by index, reused for the steps it stands in for
+    cached: Option<(usize, DynamicImage)>,
+}
+
+impl AdaptiveSource {
+    pub fn new(config: &Config) -> Result<Self, io::Error> {
+        let interval = config.adaptive.unwrap_or(1.0);
+        Ok(AdaptiveSource {
+            config: config.clone(),
+            log: RunLog::open(config.log_file.as_deref())?,
+            frames_dir: None,
+            coarse: Vec::new(),
+            dense: Vec::new(),
+            step: ((interval * config.fps as f64).round() as usize).max(1),
+            total: 0,
+            position: 0,
+            cached: None,
+        })
+    }
+}
+
+impl FrameSource for AdaptiveSource {
+    fn prepare(&mut self, progress: &dyn Fn(Progress), cancel: &CancellationToken) -> Result<(), Error> {
+        check_input(&self.config.input_file)?;
+        fit_budget(&mut self.config, &self.log);
+        let config = &self.config;
+        let frames_dir = self.frames_dir.insert(Workspace::create(config)?);
+        let seconds = self.step as f64 / config.fps as f64;
+
+        let coarse_dir = frames_dir.path().join("coarse");
+        fs::create_dir(&coarse_dir)?;
+        sample_window(config, &coarse_dir, 0.0, config.duration, &format!("{}/{}", config.fps, self.step))?;
+        self.coarse = sampled_frames(&coarse_dir)?;
+
+        // Only the stretches between sparse samples that differ are sampled again
+        let mut comparer = Comparer::new(config, &self.log);
+        let coarse_threshold = config.coarse_threshold.unwrap_or(config.threshold);
+        let kernel = prefilter_kernel(config);
+        let mut previous: Option<DynamicImage> = None;
+        self.dense = vec![None; self.coarse.len()];
+        for (index, path) in self.coarse.iter().enumerate() {
+            if cancel.is_cancelled() {
+                return Err(Error::Cancelled);
+            }
+            let mut image = open_frame(path).map_err(|source| Error::BadFrame { path: path.clone(), source })?;
+            if let Some(kernel) = kernel {
+                image = prefilter(&image, kernel);
+            }
+            if let Some(previous) = previous.as_ref() {
+                if comparer.difference_ratio(previous, &image) > coarse_threshold {
+                    let window = index - 1;
+                    let window_dir = frames_dir.path().join(format!("window_{:06}", window));
+                    fs::create_dir(&window_dir)?;
+                    sample_window(config, &window_dir, window as f64 * seconds, Some(seconds), &config.fps.to_string())?;
+                    self.dense[window] = Some(sampled_frames(&window_dir)?);
+                }
+            }
+            previous = Some(image);
+            progress(Progress { stage: Stage::Extracting, done: index + 1, total: Some(self.coarse.len()) });
+        }
+
+        let changed = self.dense.iter().filter(|dense| dense.is_some()).count();
+        self.log.info(format_args!(
+            "Sampled every {}s, and at {} fps in {} of {} stretch(es) with a change.",
+            seconds,
+            config.fps,
+            changed,
+            self.coarse.len().saturating_sub(1)
+        ));
+
+        // The last sparse sample stands in for the rest of the video, as far as its length is known
+        let known = self.coarse.len() * self.step;
+        let expected = probe(&config.input_file).ok().map(|info| {
+            let duration = config.duration.map_or(info.duration, |duration| duration.min(info.duration));
+            (duration * config.fps as f64).ceil() as usize
+        });
+        self.total = match expected {
+            Some(expected) if known > 0 => expected.clamp(known - self.step + 1, known),
+            _ => known,
+        };
+        Ok(())
+    }
+
+    fn remaining(&self) -> Option<usize> {
+        Some(self.total - self.position)
+    }
+
+    fn next_frame(&mut self) -> Result<Option<Frame>, Error> {
+        if self.position >= self.total {
+            return Ok(None);
+        }
+        let (window, offset) = (self.position / self.step, self.position % self.step);
+        self.position += 1;
+        let name = format!("frame_{:06}.png", self.position);
+
+        if let Some(path) = self.dense[window].as_ref().and_then(|dense| dense.get(offset)) {
+            let image = open_frame(path).map_err(|source| Error::BadFrame { path: path.clone(), source })?;
+            return Ok(Some(Frame { image, name, path: Some(path.clone()) }));
+        }
+        let image = match self.cached.take() {
+            Some((index, image)) if index == window => image,
+            _ => {
+                let path = &self.coarse[window];
+                open_frame(path).map_err(|source| Error::BadFrame { path: path.clone(), source })?
+            }
+        };
+        self.cached = Some((window, image.clone()));
+        Ok(Some(Frame { image, name, path: None }))
+    }
+
+    fn keep(&mut self, name: &str, path: Option<&Path>, image: &DynamicImage, output_dir: &Path) -> Result<PathBuf, Error> {
+        let destination = output_dir.join(name);
+        match path {
+            Some(path) => move_file(path, &destination)?,
+            // A sparse sample stands in for several frames, so it is written out anew
+            None => image.save(&destination).map_err(|e| io::Error::other(format!("Error saving image: {}", e)))?,
+        }
+        Ok(destination)
+    }
+
+    fn discard(&mut self, path: Option<&Path>) -> Result<(), Error> {
+        match path {
+            Some(path) if self.config.workspace != WorkspacePolicy::Keep => Ok(fs::remove_file(path)?),
+            _ => Ok(()),
+        }
+    }
+}
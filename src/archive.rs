@@ -0,0 +1,45 @@
+use std::fs::File;
+use std::io::{self, Error, Seek, Write};
+use std::path::Path;
+
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+use crate::config::SidecarFormat;
+use crate::links::{self, LINKS_FILE};
+use crate::manifest::{Manifest, MANIFEST_FILE};
+use crate::sidecar;
+
+/// Bundle the kept slides and the manifest of a finished run into one zip,
+/// with a sidecar in `sidecars` format next to each slide if set
+pub fn write_archive<W: Write + Seek>(
+    output_dir: &Path,
+    manifest: &Manifest,
+    sidecars: Option<SidecarFormat>,
+    writer: W,
+) -> Result<W, Error> {
+    let mut zip = ZipWriter::new(writer);
+
+    // PNGs are already compressed, deflating them again only costs time
+    let stored = SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+    let deflated = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+
+    for slide in &manifest.slides {
+        let mut file = File::open(output_dir.join(&slide.file))?;
+        zip.start_file(slide.file.as_str(), stored).map_err(Error::other)?;
+        io::copy(&mut file, &mut zip)?;
+        if let Some(format) = sidecars {
+            zip.start_file(sidecar::file_name(&slide.file, format), deflated).map_err(Error::other)?;
+            zip.write_all(sidecar::contents(slide, format)?.as_bytes())?;
+        }
+    }
+
+    zip.start_file(MANIFEST_FILE, deflated).map_err(Error::other)?;
+    zip.write_all(manifest.to_json()?.as_bytes())?;
+    if let Some(markdown) = links::markdown(manifest) {
+        zip.start_file(LINKS_FILE, deflated).map_err(Error::other)?;
+        zip.write_all(markdown.as_bytes())?;
+    }
+
+    zip.finish().map_err(Error::other)
+}
@@ -0,0 +1,167 @@
+//! The recording's sound, for what the picture doesn't tell: the pauses in
+//! speech slide changes fall in, and the applause and long silences between
+//! the talks of a conference recording.
+//!
+//! The audio is decoded to mono at 8kHz and looked at in 20ms blocks, each
+//! with its loudness and how often the signal crosses zero: hiss-like sound
+//! such as clapping crosses far more often than a voice. Levels are taken
+//! relative to the loudest speech, so they follow the level of the microphone.
+
+use std::io::{self, BufReader, Read};
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+use crate::config::Config;
+use crate::error::Error;
+use crate::manifest::Interval;
+
+/// Sample rate ffmpeg resamples the audio to
+const SAMPLE_RATE: usize = 8000;
+/// Samples in one block, 20ms
+const BLOCK_SIZE: usize = 160;
+/// Seconds in one block
+const BLOCK_SECONDS: f64 = BLOCK_SIZE as f64 / SAMPLE_RATE as f64;
+/// Share of the blocks quieter than the loudness speech is measured at
+const SPEECH_PERCENTILE: f64 = 0.95;
+/// Decibels below speech applause is at least as loud as
+const APPLAUSE_DB: f64 = 12.0;
+/// Share of the samples of a block crossing zero from which it sounds like noise rather than a voice
+const APPLAUSE_CROSSINGS: f64 = 0.3;
+/// Share of the blocks of a second that must sound like applause for the second to be applause
+const APPLAUSE_SHARE: f64 = 0.8;
+
+/// 20ms of audio
+#[derive(Debug, Clone, Copy)]
+pub struct Block {
+    /// Root mean square of the samples
+    pub level: f64,
+    /// Share of the samples whose sign differs from the one before
+    pub crossings: f64,
+}
+
+/// ffmpeg decoding `config.input_file`'s audio, up to `config.duration`, to mono at
+/// `sample_rate`, with no output yet
+fn audio_command(config: &Config, sample_rate: usize) -> Command {
+    let mut command = Command::new("ffmpeg");
+    command.arg("-v").arg("error").arg("-i").arg(&config.input_file);
+    if let Some(duration) = config.duration {
+        command.arg("-t").arg(duration.to_string());
+    }
+    command.args(["-vn", "-ac", "1", "-ar", &sample_rate.to_string()]);
+    command
+}
+
+/// Write `config.input_file`'s audio to `path` as a WAV file at `sample_rate`, for other tools
+pub fn write_wav(config: &Config, sample_rate: usize, path: &Path) -> Result<(), Error> {
+    let output = audio_command(config, sample_rate)
+        .arg("-y")
+        .arg(path)
+        .stdin(Stdio::null())
+        .output()
+        .map_err(Error::ffmpeg_spawn)?;
+    if !output.status.success() {
+        return Err(Error::ffmpeg_failed(&config.input_file, output.status, String::from_utf8_lossy(&output.stderr).trim()));
+    }
+    Ok(())
+}
+
+/// The blocks of `config.input_file`'s audio, up to `config.duration`
+pub fn read_blocks(config: &Config) -> Result<Vec<Block>, Error> {
+    let mut child = audio_command(config, SAMPLE_RATE)
+        .args(["-f", "s16le", "-"])
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(Error::ffmpeg_spawn)?;
+
+    let mut stdout = BufReader::new(child.stdout.take().expect("stdout is piped"));
+    let mut blocks = Vec::new();
+    let mut buffer = [0u8; BLOCK_SIZE * 2];
+    loop {
+        let mut filled = 0;
+        while filled < buffer.len() {
+            match stdout.read(&mut buffer[filled..])? {
+                0 => break,
+                n => filled += n,
+            }
+        }
+        if filled < 2 {
+            break;
+        }
+        let samples: Vec<f64> = buffer[..filled / 2 * 2].chunks_exact(2).map(|bytes| i16::from_le_bytes([bytes[0], bytes[1]]) as f64).collect();
+        let energy: f64 = samples.iter().map(|sample| sample * sample).sum();
+        let crossings = samples.windows(2).filter(|pair| (pair[0] < 0.0) != (pair[1] < 0.0)).count();
+        blocks.push(Block { level: (energy / samples.len() as f64).sqrt(), crossings: crossings as f64 / samples.len() as f64 });
+        if filled < buffer.len() {
+            break;
+        }
+    }
+
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        return Err(Error::ffmpeg_failed(&config.input_file, output.status, String::from_utf8_lossy(&output.stderr).trim()));
+    }
+    if blocks.is_empty() {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "the video has no audio").into());
+    }
+    Ok(blocks)
+}
+
+/// How loud the loudest speech in `blocks` is, 0 for a silent track
+fn speech_level(blocks: &[Block]) -> f64 {
+    let mut levels: Vec<f64> = blocks.iter().map(|block| block.level).collect();
+    levels.sort_by(f64::total_cmp);
+    levels.get(((levels.len().max(1) - 1) as f64 * SPEECH_PERCENTILE) as usize).copied().unwrap_or_default()
+}
+
+/// Runs of entries of `matches` that hold lasting at least `min_seconds`, in seconds, with
+/// `block_seconds` seconds to each entry
+fn stretches(matches: &[bool], block_seconds: f64, min_seconds: f64) -> Vec<Interval> {
+    let mut found = Vec::new();
+    let mut start = None;
+    // An entry past the end that doesn't hold closes a run up to it
+    for (i, &matching) in matches.iter().chain([false].iter()).enumerate() {
+        match (matching, start) {
+            (true, None) => start = Some(i),
+            (false, Some(first)) => {
+                if (i - first) as f64 * block_seconds >= min_seconds {
+                    found.push(Interval { start: first as f64 * block_seconds, end: i as f64 * block_seconds });
+                }
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    found
+}
+
+/// Stretches of at least `min_seconds` more than `below_db` decibels quieter than speech
+pub fn quiet_stretches(blocks: &[Block], below_db: f64, min_seconds: f64) -> Vec<Interval> {
+    let speech = speech_level(blocks);
+    // A silent track has no speech to pause
+    if speech <= 0.0 {
+        return Vec::new();
+    }
+    let quiet = speech * 10f64.powf(-below_db / 20.0);
+    let matches: Vec<bool> = blocks.iter().map(|block| block.level <= quiet).collect();
+    stretches(&matches, BLOCK_SECONDS, min_seconds)
+}
+
+/// Stretches of applause at least `min_seconds` long: loud, hiss-like sound through nearly
+/// every block of each second
+pub fn applause(blocks: &[Block], min_seconds: f64) -> Vec<Interval> {
+    let loud = speech_level(blocks) * 10f64.powf(-APPLAUSE_DB / 20.0);
+    if loud <= 0.0 {
+        return Vec::new();
+    }
+    let per_second = (1.0 / BLOCK_SECONDS).round() as usize;
+    let seconds: Vec<bool> = blocks
+        .chunks(per_second)
+        .map(|second| {
+            let clapping = second.iter().filter(|block| block.level >= loud && block.crossings >= APPLAUSE_CROSSINGS).count();
+            clapping as f64 >= second.len() as f64 * APPLAUSE_SHARE
+        })
+        .collect();
+    stretches(&seconds, per_second as f64 * BLOCK_SECONDS, min_seconds)
+}
@@ -0,0 +1,202 @@
+use std::collections::HashSet;
+use std::fs;
+use std::any::Any;
+use std::io::Error;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use crate::config::Config;
+use crate::manifest::MANIFEST_FILE;
+use crate::pipeline::run;
+use crate::queue::{JobQueue, QueuedJob};
+use crate::webhook::{self, BatchSummary, JobCompletion, Outcome};
+
+/// Name of the per-job log written inside each job's output directory
+pub const JOB_LOG_FILE: &str = "job.log";
+
+/// Settings shared by every video of a batch
+#[derive(Debug, Clone)]
+pub struct BatchOptions {
+    /// Each video gets its own subdirectory in here
+    pub output_dir: PathBuf,
+    /// Videos processed at the same time
+    pub workers: usize,
+    /// Where queued jobs are kept so an interrupted batch can resume
+    pub spool_dir: PathBuf,
+    /// URL that receives a JSON notification as each video completes
+    pub webhook: Option<String>,
+    /// Slack incoming webhook that receives a summary once the batch is done
+    pub slack_webhook: Option<String>,
+    /// Addresses mailed a summary once the batch is done
+    pub email: Vec<String>,
+}
+
+/// How one video of a batch turned out
+#[derive(Debug, Clone)]
+pub struct BatchResult {
+    pub input_file: PathBuf,
+    pub output_dir: PathBuf,
+    pub slides: Option<usize>,
+    pub error: Option<String>,
+}
+
+/// Extract slides from every input with at most `options.workers` running at once.
+///
+/// `template` supplies the extraction settings; its input, output directory and
+/// log file are replaced per video. Jobs left over from an interrupted batch on
+/// the same spool directory run first, and inputs they already cover are not
+/// queued twice.
+pub fn run_batch(inputs: &[PathBuf], template: &Config, options: &BatchOptions) -> Result<Vec<BatchResult>, Error> {
+    fs::create_dir_all(&options.output_dir)?;
+    let started = Instant::now();
+
+    let mut queue = JobQueue::open(&options.spool_dir)?;
+    let restored = queue.pending();
+    if !restored.is_empty() {
+        tracing::info!("Resuming {} job(s) from an interrupted batch.", restored.len());
+    }
+
+    let mut queued_inputs: HashSet<PathBuf> = restored.iter().map(|job| job.config.input_file.clone()).collect();
+    let mut used_dirs: HashSet<PathBuf> = restored.iter().map(|job| job.config.output_dir.clone()).collect();
+    let mut jobs = Vec::new();
+    let mut next_id = queue.last_id() + 1;
+
+    for input in inputs {
+        if !queued_inputs.insert(input.clone()) {
+            continue;
+        }
+
+        // Name the output after the video, disambiguating videos with the same stem
+        let stem = input
+            .file_stem()
+            .map(|s| s.to_string_lossy().into_owned())
+            .unwrap_or_else(|| format!("video-{}", next_id));
+        let mut output_dir = options.output_dir.join(&stem);
+        if used_dirs.contains(&output_dir) {
+            output_dir = options.output_dir.join(format!("{}-{}", stem, next_id));
+        }
+        used_dirs.insert(output_dir.clone());
+
+        let mut config = template.clone();
+        config.input_file = input.clone();
+        if let Some(destination) = &template.output {
+            // Keep each video's results apart at the destination too
+            let name = output_dir.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+            config.output = Some(format!("{}/{}", destination.trim_end_matches('/'), name));
+        }
+        config.log_file = Some(output_dir.join(JOB_LOG_FILE));
+        config.output_dir = output_dir;
+
+        jobs.push(QueuedJob { id: next_id, config });
+        next_id += 1;
+    }
+
+    let results = Arc::new(Mutex::new(Vec::new()));
+    let worker_results = Arc::clone(&results);
+    let webhook = options.webhook.clone();
+    queue.start(options.workers, move |job| {
+        // A panic fails the video like any other error, instead of leaving it out of the results
+        let result = panic::catch_unwind(AssertUnwindSafe(|| run_job(&job.config)))
+            .unwrap_or_else(|payload| panicked(&job.config, payload));
+        match &result.error {
+            Some(error) => tracing::error!("{}: failed: {}", result.input_file.display(), error),
+            None => tracing::info!(
+                "{}: {} slide(s) in {}",
+                result.input_file.display(),
+                result.slides.unwrap_or(0),
+                result.output_dir.display()
+            ),
+        }
+
+        if let Some(url) = &webhook {
+            if let Err(e) = webhook::notify(url, &completion(&result)) {
+                tracing::warn!("{}", e);
+            }
+        }
+
+        worker_results.lock().unwrap().push((job.id, result));
+    });
+
+    for job in jobs {
+        queue.submit(job)?;
+    }
+    queue.finish();
+
+    let mut results = std::mem::take(&mut *results.lock().unwrap());
+    results.sort_by_key(|(id, _)| *id);
+    let results: Vec<BatchResult> = results.into_iter().map(|(_, result)| result).collect();
+
+    let summary = BatchSummary { results: &results, elapsed: started.elapsed() };
+    if let Some(url) = &options.slack_webhook {
+        if let Err(e) = webhook::notify_slack(url, &summary) {
+            tracing::warn!("Could not post the batch summary to Slack: {}", e);
+        }
+    }
+    if !options.email.is_empty() {
+        if let Err(e) = webhook::send_email(&options.email, &summary) {
+            tracing::warn!("Could not mail the batch summary: {}", e);
+        }
+    }
+    Ok(results)
+}
+
+fn completion(result: &BatchResult) -> JobCompletion {
+    let output_dir = &result.output_dir;
+    JobCompletion {
+        video_id: output_dir.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default(),
+        input_file: result.input_file.display().to_string(),
+        outcome: if result.error.is_some() { Outcome::Failed } else { Outcome::Finished },
+        slide_count: result.slides,
+        manifest_path: result
+            .slides
+            .map(|_| output_dir.join(MANIFEST_FILE).to_string_lossy().into_owned()),
+        manifest_url: None,
+        error: result.error.clone(),
+    }
+}
+
+fn run_job(config: &Config) -> BatchResult {
+    let outcome = fs::create_dir_all(&config.output_dir)
+        .map_err(crate::Error::from)
+        .and_then(|_| run(config));
+    BatchResult {
+        input_file: config.input_file.clone(),
+        output_dir: config.output_dir.clone(),
+        slides: outcome.as_ref().ok().map(|manifest| manifest.slides.len()),
+        error: outcome.err().map(|e| e.to_string()),
+    }
+}
+
+/// The result of a job that panicked with `payload`
+fn panicked(config: &Config, payload: Box<dyn Any + Send>) -> BatchResult {
+    let message = payload
+        .downcast_ref::<&str>()
+        .map(|message| message.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "unknown cause".to_string());
+    BatchResult {
+        input_file: config.input_file.clone(),
+        output_dir: config.output_dir.clone(),
+        slides: None,
+        error: Some(format!("panicked: {}", message)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_panic_is_a_failed_result() {
+        let config = Config::new("talk.mp4");
+        let payload = panic::catch_unwind(|| panic!("out of {}", "frames")).unwrap_err();
+        let result = panicked(&config, payload);
+        assert_eq!(result.input_file, PathBuf::from("talk.mp4"));
+        assert_eq!(result.slides, None);
+        assert_eq!(result.error.as_deref(), Some("panicked: out of frames"));
+        let payload = panic::catch_unwind(|| panic!("no frames")).unwrap_err();
+        assert_eq!(panicked(&config, payload).error.as_deref(), Some("panicked: no frames"));
+    }
+}
@@ -0,0 +1,118 @@
+//! `bench`: time the ways of comparing frames against each other on a sample
+//! of a video, so the fastest one that still finds the right slides can be
+//! picked before running the whole thing.
+
+use std::time::{Duration, Instant};
+
+use crate::config::Config;
+use crate::dedup::{Deduplicator, Settled, Stabilizer};
+use crate::error::Error;
+use crate::extract::extract_frames;
+use crate::progress::CancellationToken;
+use crate::runlog::RunLog;
+use crate::source::{DirectorySource, FrameSource};
+use crate::workspace::Workspace;
+
+/// What to try on the sample
+#[derive(Debug, Clone)]
+pub struct BenchOptions {
+    /// Seconds from the start of the video that are sampled
+    pub sample: f64,
+    /// Thresholds each metric is run with
+    pub thresholds: Vec<f64>,
+    /// Values of `--compare-stride` tried, each one a metric of its own
+    pub strides: Vec<u32>,
+}
+
+/// How one metric did with one threshold
+#[derive(Debug, Clone)]
+pub struct BenchResult {
+    /// The settings that make up the metric, as command line flags
+    pub metric: String,
+    pub threshold: f64,
+    /// Frames compared
+    pub frames: usize,
+    /// Time spent comparing them, decoding left out
+    pub elapsed: Duration,
+    /// Slides the run would keep
+    pub slides: usize,
+}
+
+impl BenchResult {
+    pub fn frames_per_second(&self) -> f64 {
+        self.frames as f64 / self.elapsed.as_secs_f64().max(f64::EPSILON)
+    }
+}
+
+/// The metrics worth comparing: plain pixel differences at each stride, with
+/// moving regions left out, and on the GPU when built with it
+pub(crate) fn metrics(config: &Config, strides: &[u32]) -> Vec<(String, Config)> {
+    let mut metrics = Vec::new();
+    for &stride in strides {
+        let mut metric = config.clone();
+        metric.compare_stride = stride.max(1);
+        metric.ignore_embedded_video = false;
+        metric.gpu = false;
+        metrics.push((format!("--compare-stride {}", stride.max(1)), metric));
+    }
+
+    let mut motion = config.clone();
+    motion.compare_stride = 1;
+    motion.ignore_embedded_video = true;
+    motion.gpu = false;
+    metrics.push((format!("--ignore-embedded-video --motion-streak {}", config.motion_streak), motion));
+
+    if cfg!(feature = "gpu") {
+        let mut gpu = config.clone();
+        gpu.compare_stride = 1;
+        gpu.ignore_embedded_video = false;
+        gpu.gpu = true;
+        metrics.push(("--gpu".to_string(), gpu));
+    }
+    metrics
+}
+
+/// Sample the first `options.sample` seconds of `config.input_file` and run
+/// every metric with every threshold over the frames
+pub fn run_bench(config: &Config, options: &BenchOptions) -> Result<Vec<BenchResult>, Error> {
+    let log = RunLog::open(config.log_file.as_deref())?;
+    let mut config = config.clone();
+    config.duration = Some(options.sample);
+
+    let mut frames_dir = Workspace::create(&config)?;
+    extract_frames(&config, frames_dir.path(), &log, &|_| {}, &CancellationToken::new())?;
+
+    let mut results = Vec::new();
+    for (metric, metric_config) in metrics(&config, &options.strides) {
+        for &threshold in &options.thresholds {
+            let mut run_config = metric_config.clone();
+            run_config.threshold = threshold;
+
+            let mut source = DirectorySource::open(frames_dir.path())?;
+            let mut dedup = Deduplicator::new(&run_config, &log);
+            let mut stabilizer = Stabilizer::new(&run_config);
+            let (mut frames, mut slides, mut elapsed) = (0, 0, Duration::ZERO);
+            loop {
+                let frame = match source.next_frame() {
+                    Ok(Some(frame)) => frame,
+                    Ok(None) => break,
+                    Err(Error::BadFrame { .. }) => continue,
+                    Err(e) => return Err(e),
+                };
+                let start = Instant::now();
+                let decision = dedup.observe(frame.image);
+                elapsed += start.elapsed();
+
+                if let Settled::Commit { .. } = stabilizer.settle(frames, &decision) {
+                    slides += 1;
+                }
+                frames += 1;
+            }
+            slides += stabilizer.finish().map_or(0, |_| 1);
+
+            results.push(BenchResult { metric: metric.clone(), threshold, frames, elapsed, slides });
+        }
+    }
+    frames_dir.succeeded();
+    Ok(results)
+}
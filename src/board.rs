@@ -0,0 +1,365 @@
+//! `--whiteboard`: slides from a filmed whiteboard or blackboard lecture,
+//! where the lecturer walking past the board would otherwise be a change in
+//! every frame.
+//!
+//! The board is found in the first frame as the largest area in its most
+//! common colour, and every frame is cut down to it. A picture of the board is
+//! built up from the tiles that stayed still for a second, so the lecturer
+//! moving in front of it is left out; one standing still for longer becomes
+//! part of it until they move on. The pipeline is shown the board at its
+//! fullest for every frame until it is erased: once a good part of the writing
+//! is gone, the board so far becomes a slide and the next one starts from what
+//! is left. `run_stream` rejects `--whiteboard`.
+
+use image::{DynamicImage, GenericImageView, RgbImage};
+use std::collections::VecDeque;
+use std::mem;
+
+use crate::config::{Config, Crop};
+use crate::error::Error;
+use crate::progress::{CancellationToken, Progress};
+use crate::runlog::RunLog;
+use crate::source::{Frame, FrameSource};
+
+/// Edge length in pixels of the square tiles the board is built up from
+const TILE_SIZE: u32 = 32;
+/// Largest difference in any channel for a pixel to count as the board's colour
+const BOARD_STEP: u8 = 40;
+/// Share of a tile's pixels in the board's colour for the tile to be part of the board
+const TILE_BOARD: f64 = 0.5;
+/// Smallest share of the frame the board takes; below it the whole frame is used
+const MIN_BOARD: f64 = 0.2;
+/// Difference in any channel from which a pixel changed between two frames
+const CHANGE_STEP: u8 = 24;
+/// Share of a tile's pixels that must change for the tile to count as moving
+const TILE_CHANGE: f64 = 0.05;
+/// Seconds a tile must stay still before the board picture takes it over
+const STILL_SECONDS: f64 = 1.0;
+/// Difference in brightness (0 to 255) from the board from which a pixel is writing
+const INK_STEP: i32 = 60;
+/// Share of the board's pixels that must be written on before it can count as erased
+const MIN_INK: f64 = 0.002;
+/// Share of the writing that must disappear for the board to count as erased
+const ERASED: f64 = 0.3;
+
+/// The frames of another source cut down to the board, each showing the
+/// board as it was right before it was next erased
+pub struct BoardSource<'a> {
+    inner: &'a mut dyn FrameSource,
+    log: RunLog,
+    /// Consecutive still comparisons before a tile is taken over
+    still_frames: u32,
+    board: Option<Board>,
+    /// Frames read since the board was last erased, or the reason they could not be decoded
+    segment: VecDeque<Result<String, Error>>,
+    /// Frames of an erased board, being handed out with its fullest picture
+    ready: Option<(VecDeque<Result<String, Error>>, DynamicImage)>,
+    finished: bool,
+}
+
+impl<'a> BoardSource<'a> {
+    pub fn new(config: &Config, inner: &'a mut dyn FrameSource, log: &RunLog) -> Self {
+        BoardSource {
+            inner,
+            log: log.clone(),
+            still_frames: ((STILL_SECONDS * config.fps as f64).round() as u32).max(1),
+            board: None,
+            segment: VecDeque::new(),
+            ready: None,
+            finished: false,
+        }
+    }
+
+    /// Read frames until the board is erased or they run out, readying the ones read before
+    fn read_segment(&mut self) -> Result<(), Error> {
+        loop {
+            let frame = match self.inner.next_frame() {
+                Ok(Some(frame)) => frame,
+                Ok(None) => {
+                    self.finished = true;
+                    if let Some(board) = self.board.take() {
+                        self.ready = Some((mem::take(&mut self.segment), DynamicImage::ImageRgb8(board.fullest)));
+                    }
+                    return Ok(());
+                }
+                Err(e @ Error::BadFrame { .. }) => {
+                    self.segment.push_back(Err(e));
+                    continue;
+                }
+                Err(e) => return Err(e),
+            };
+            // Only the board's picture is ever kept
+            self.inner.discard(frame.path.as_deref())?;
+
+            let erased = match self.board.as_mut() {
+                Some(board) if board.frame_size == frame.image.dimensions() => board.observe(&frame.image),
+                _ => {
+                    let board = Board::new(&frame.image, self.still_frames);
+                    let region = board.region;
+                    self.log.info(format_args!(
+                        "Taking the board to be the {}x{} area at x={} y={}.",
+                        region.width, region.height, region.x, region.y
+                    ));
+                    // The frame size changed, the board found before is done with
+                    self.board.replace(board).map(|board| board.fullest)
+                }
+            };
+            if let Some(fullest) = erased {
+                self.log.debug(format_args!("The board is erased at frame {}.", frame.name));
+                self.ready = Some((mem::take(&mut self.segment), DynamicImage::ImageRgb8(fullest)));
+                self.segment.push_back(Ok(frame.name));
+                return Ok(());
+            }
+            self.segment.push_back(Ok(frame.name));
+        }
+    }
+}
+
+impl FrameSource for BoardSource<'_> {
+    fn prepare(&mut self, progress: &dyn Fn(Progress), cancel: &CancellationToken) -> Result<(), Error> {
+        self.inner.prepare(progress, cancel)
+    }
+
+    fn remaining(&self) -> Option<usize> {
+        let read = self.segment.len() + self.ready.as_ref().map_or(0, |(frames, _)| frames.len());
+        self.inner.remaining().map(|remaining| remaining + read)
+    }
+
+    fn next_frame(&mut self) -> Result<Option<Frame>, Error> {
+        loop {
+            if let Some((frames, image)) = self.ready.as_mut() {
+                match frames.pop_front() {
+                    Some(Ok(name)) => return Ok(Some(Frame { image: image.clone(), name, path: None })),
+                    Some(Err(e)) => return Err(e),
+                    None => self.ready = None,
+                }
+            } else if self.finished {
+                return Ok(None);
+            } else {
+                self.read_segment()?;
+            }
+        }
+    }
+
+    fn succeeded(&mut self) {
+        self.inner.succeeded();
+    }
+}
+
+/// The picture of the board built up so far, and how much is written on it
+struct Board {
+    /// Size of the frames the board was found in
+    frame_size: (u32, u32),
+    region: Crop,
+    /// Brightness of the board itself
+    background: i32,
+    columns: u32,
+    rows: u32,
+    still_frames: u32,
+    picture: RgbImage,
+    /// The last frame, cut down to the board
+    last: RgbImage,
+    /// Consecutive comparisons each tile stayed still in
+    still: Vec<u32>,
+    /// Pixels of writing in each tile of `picture`
+    ink: Vec<u64>,
+    /// Least writing since the board was last erased, and whether more was written since
+    floor: u64,
+    written: bool,
+    /// Most writing since the board was last erased, and the picture that had it
+    peak: u64,
+    fullest: RgbImage,
+}
+
+impl Board {
+    fn new(image: &DynamicImage, still_frames: u32) -> Self {
+        let (region, color) = find_board(image);
+        let picture = image.crop_imm(region.x, region.y, region.width, region.height).to_rgb8();
+        let columns = region.width.div_ceil(TILE_SIZE);
+        let rows = region.height.div_ceil(TILE_SIZE);
+        let mut board = Board {
+            frame_size: image.dimensions(),
+            region,
+            background: luma(color),
+            columns,
+            rows,
+            still_frames,
+            last: picture.clone(),
+            fullest: picture.clone(),
+            picture,
+            still: vec![0; (columns * rows) as usize],
+            ink: vec![0; (columns * rows) as usize],
+            floor: 0,
+            written: false,
+            peak: 0,
+        };
+        for ty in 0..rows {
+            for tx in 0..columns {
+                board.ink[(ty * columns + tx) as usize] = board.count_ink(tx, ty);
+            }
+        }
+        board.peak = board.ink.iter().sum();
+        board.floor = board.peak;
+        board
+    }
+
+    /// Take the next frame into the picture; returns the fullest picture if it erased the board
+    fn observe(&mut self, image: &DynamicImage) -> Option<RgbImage> {
+        let region = self.region;
+        let frame = image.crop_imm(region.x, region.y, region.width, region.height).to_rgb8();
+        for ty in 0..self.rows {
+            for tx in 0..self.columns {
+                let index = (ty * self.columns + tx) as usize;
+                let (x0, y0, x1, y1) = self.tile_bounds(tx, ty);
+                let mut changed = 0;
+                for y in y0..y1 {
+                    for x in x0..x1 {
+                        let (a, b) = (frame.get_pixel(x, y).0, self.last.get_pixel(x, y).0);
+                        if a.iter().zip(b).any(|(&a, b)| a.abs_diff(b) > CHANGE_STEP) {
+                            changed += 1;
+                        }
+                    }
+                }
+                if changed as f64 > TILE_CHANGE * ((x1 - x0) * (y1 - y0)) as f64 {
+                    self.still[index] = 0;
+                    continue;
+                }
+                self.still[index] += 1;
+                if self.still[index] >= self.still_frames {
+                    for y in y0..y1 {
+                        for x in x0..x1 {
+                            self.picture.put_pixel(x, y, *frame.get_pixel(x, y));
+                        }
+                    }
+                    self.ink[index] = self.count_ink(tx, ty);
+                }
+            }
+        }
+        self.last = frame;
+
+        let ink: u64 = self.ink.iter().sum();
+        let min_ink = (MIN_INK * (region.width * region.height) as f64) as u64;
+        if !self.written {
+            // Still being wiped: the board starts from what is left once that is done
+            self.floor = self.floor.min(ink);
+            if ink < self.peak {
+                self.peak = ink;
+                self.fullest = self.picture.clone();
+            }
+            self.written = ink >= self.floor + min_ink;
+        }
+        if self.written && (ink as f64) <= self.peak as f64 * (1.0 - ERASED) {
+            self.peak = ink;
+            self.floor = ink;
+            self.written = false;
+            return Some(mem::replace(&mut self.fullest, self.picture.clone()));
+        }
+        if ink > self.peak {
+            self.peak = ink;
+            self.fullest = self.picture.clone();
+        }
+        None
+    }
+
+    /// Pixel bounds of a tile of the board, cut off at its edges
+    fn tile_bounds(&self, tx: u32, ty: u32) -> (u32, u32, u32, u32) {
+        let x1 = ((tx + 1) * TILE_SIZE).min(self.region.width);
+        let y1 = ((ty + 1) * TILE_SIZE).min(self.region.height);
+        (tx * TILE_SIZE, ty * TILE_SIZE, x1, y1)
+    }
+
+    /// Pixels of a tile of the picture that stand out from the board
+    fn count_ink(&self, tx: u32, ty: u32) -> u64 {
+        let (x0, y0, x1, y1) = self.tile_bounds(tx, ty);
+        let mut ink = 0;
+        for y in y0..y1 {
+            for x in x0..x1 {
+                if (luma(self.picture.get_pixel(x, y).0) - self.background).abs() > INK_STEP {
+                    ink += 1;
+                }
+            }
+        }
+        ink
+    }
+}
+
+fn luma([r, g, b]: [u8; 3]) -> i32 {
+    (299 * r as i32 + 587 * g as i32 + 114 * b as i32) / 1000
+}
+
+/// The board in `image`, the largest connected area of tiles mostly in the
+/// frame's most common colour, and that colour
+fn find_board(image: &DynamicImage) -> (Crop, [u8; 3]) {
+    let rgb = image.to_rgb8();
+    let (width, height) = rgb.dimensions();
+
+    // Most common colour at 16 levels per channel, as the middle of its range
+    let mut histogram = vec![0u32; 16 * 16 * 16];
+    for pixel in rgb.pixels() {
+        let [r, g, b] = pixel.0.map(|channel| channel as usize >> 4);
+        histogram[r << 8 | g << 4 | b] += 1;
+    }
+    let common = (0..histogram.len()).max_by_key(|&key| histogram[key]).unwrap_or(0);
+    let color = [common >> 8, common >> 4 & 15, common & 15].map(|level| (level << 4 | 8) as u8);
+
+    let columns = width.div_ceil(TILE_SIZE);
+    let rows = height.div_ceil(TILE_SIZE);
+    let mut board_tiles = vec![false; (columns * rows) as usize];
+    for ty in 0..rows {
+        for tx in 0..columns {
+            let (x1, y1) = (((tx + 1) * TILE_SIZE).min(width), ((ty + 1) * TILE_SIZE).min(height));
+            let mut matching = 0;
+            for y in ty * TILE_SIZE..y1 {
+                for x in tx * TILE_SIZE..x1 {
+                    if rgb.get_pixel(x, y).0.iter().zip(color).all(|(&channel, c)| channel.abs_diff(c) <= BOARD_STEP) {
+                        matching += 1;
+                    }
+                }
+            }
+            let pixels = (x1 - tx * TILE_SIZE) * (y1 - ty * TILE_SIZE);
+            board_tiles[(ty * columns + tx) as usize] = matching as f64 >= TILE_BOARD * pixels as f64;
+        }
+    }
+
+    // Flood fill each group of board tiles, keeping the bounds of the largest
+    let mut visited = vec![false; board_tiles.len()];
+    let mut largest = (0, (0, 0, 0, 0));
+    for start in 0..board_tiles.len() {
+        if visited[start] || !board_tiles[start] {
+            continue;
+        }
+        let (mut size, mut bounds) = (0, (u32::MAX, u32::MAX, 0, 0));
+        let mut stack = vec![start];
+        visited[start] = true;
+        while let Some(index) = stack.pop() {
+            let (tx, ty) = (index as u32 % columns, index as u32 / columns);
+            size += 1;
+            bounds = (bounds.0.min(tx), bounds.1.min(ty), bounds.2.max(tx), bounds.3.max(ty));
+            for (nx, ny) in [(tx.wrapping_sub(1), ty), (tx + 1, ty), (tx, ty.wrapping_sub(1)), (tx, ty + 1)] {
+                if nx >= columns || ny >= rows {
+                    continue;
+                }
+                let neighbour = (ny * columns + nx) as usize;
+                if !visited[neighbour] && board_tiles[neighbour] {
+                    visited[neighbour] = true;
+                    stack.push(neighbour);
+                }
+            }
+        }
+        if size > largest.0 {
+            largest = (size, bounds);
+        }
+    }
+
+    let (x0, y0, x1, y1) = largest.1;
+    let region = Crop {
+        x: x0 * TILE_SIZE,
+        y: y0 * TILE_SIZE,
+        width: ((x1 + 1) * TILE_SIZE).min(width) - x0 * TILE_SIZE,
+        height: ((y1 + 1) * TILE_SIZE).min(height) - y0 * TILE_SIZE,
+    };
+    if largest.0 == 0 || ((region.width * region.height) as f64) < MIN_BOARD * (width * height) as f64 {
+        return (Crop { x: 0, y: 0, width, height }, color);
+    }
+    (region, color)
+}
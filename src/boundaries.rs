@@ -0,0 +1,166 @@
+//! `--split-decks`: find where one deck ends and the next begins in a long
+//! recording with several speakers (a day-long workshop), and give each deck
+//! a directory and manifest of its own.
+//!
+//! A new deck begins where the slides' theme colour changes and stays
+//! changed, or with `--deck-gap` after a stretch of constant change (a camera
+//! on the room, a video) at least that many seconds long. With `--deck-audio`
+//! it also begins after applause of at least 3 seconds or 10 seconds of
+//! silence, between the talks of a conference recording whose speakers share
+//! a template.
+
+use image::{DynamicImage, GenericImageView};
+use std::fs;
+use std::io;
+
+use crate::audio;
+use crate::catalog;
+use crate::config::Config;
+use crate::confidence::Evidence;
+use crate::manifest::{Interval, Manifest, Slide, SCHEMA_VERSION};
+use crate::runlog::RunLog;
+
+/// Share of the width and height along each edge the theme colour is taken from
+const BORDER: f64 = 0.05;
+/// Distance between theme colours (RGB, each 0 to 1) above which two slides look like different decks
+const THEME_DISTANCE: f64 = 0.2;
+/// Seconds of applause that end a talk
+const MIN_APPLAUSE: f64 = 3.0;
+/// Seconds of silence that end a talk, and decibels below speech silence is
+const MIN_SILENCE: f64 = 10.0;
+const SILENCE_DB: f64 = 30.0;
+
+/// Mean colour of the border of `image`, where slide templates put their background and bars
+pub fn theme(image: &DynamicImage) -> [f64; 3] {
+    let (width, height) = image.dimensions();
+    let (band_x, band_y) = (((width as f64 * BORDER) as u32).max(1), ((height as f64 * BORDER) as u32).max(1));
+    let rgb = image.to_rgb8();
+
+    let mut sum = [0.0; 3];
+    let mut count = 0.0_f64;
+    for (x, y, pixel) in rgb.enumerate_pixels() {
+        if x < band_x || x >= width.saturating_sub(band_x) || y < band_y || y >= height.saturating_sub(band_y) {
+            for (channel, value) in sum.iter_mut().zip(pixel.0) {
+                *channel += value as f64 / 255.0;
+            }
+            count += 1.0;
+        }
+    }
+    sum.map(|channel| channel / count.max(1.0))
+}
+
+fn distance(a: [f64; 3], b: [f64; 3]) -> f64 {
+    a.iter().zip(b).map(|(a, b)| (a - b).powi(2)).sum::<f64>().sqrt()
+}
+
+/// Number the decks of `manifest` if `config.split_decks` asks for it, from
+/// the evidence gathered for each slide, in order
+pub fn assign_decks(config: &Config, manifest: &mut Manifest, evidence: &[Evidence], log: &RunLog) {
+    if !config.split_decks || manifest.slides.is_empty() {
+        return;
+    }
+    let themes: Vec<[f64; 3]> = evidence.iter().map(|evidence| evidence.theme).collect();
+    let starts: Vec<f64> = manifest.slides.iter().map(|slide| slide.timestamp).collect();
+    let gaps: Vec<f64> = change_gaps(config, manifest).iter().map(|&(_, end)| end).collect();
+    let (applause, silences) = audio_breaks(config, log);
+    let ends_between = |ends: &[f64], i: usize| ends.iter().any(|&end| starts[i - 1] < end && end <= starts[i]);
+
+    let mut deck = 1;
+    for (i, slide) in manifest.slides.iter_mut().enumerate() {
+        if i > 0 {
+            // A single slide in other colours (a photo, a video still) is not a new deck
+            let theme_changed = distance(themes[i - 1], themes[i]) > THEME_DISTANCE
+                && themes.get(i + 1).is_none_or(|&next| distance(themes[i - 1], next) > THEME_DISTANCE);
+            let reason = if theme_changed {
+                Some("the theme changed")
+            } else if ends_between(&gaps, i) {
+                Some("after a stretch without slides")
+            } else if ends_between(&applause, i) {
+                Some("after applause")
+            } else if ends_between(&silences, i) {
+                Some("after a long silence")
+            } else {
+                None
+            };
+            if let Some(reason) = reason {
+                deck += 1;
+                log.info(format_args!("Deck {} begins at {:.1}s ({}).", deck, slide.timestamp, reason));
+            }
+        }
+        slide.deck = Some(deck);
+    }
+}
+
+/// Stretches of at least `config.deck_gap` seconds in which every frame differed from the one before
+fn change_gaps(config: &Config, manifest: &Manifest) -> Vec<(f64, f64)> {
+    let mut gaps = Vec::new();
+    let Some(deck_gap) = config.deck_gap else {
+        return gaps;
+    };
+    let mut run_start: Option<f64> = None;
+    for frame in &manifest.frames {
+        let changed = frame.difference.is_some_and(|difference| difference > frame.threshold);
+        match (changed, run_start) {
+            (true, None) => run_start = Some(frame.timestamp),
+            (false, Some(start)) => {
+                if frame.timestamp - start >= deck_gap {
+                    gaps.push((start, frame.timestamp));
+                }
+                run_start = None;
+            }
+            _ => {}
+        }
+    }
+    gaps
+}
+
+/// Where the stretches of applause and of silence in the audio end, if `config.deck_audio` asks for them
+fn audio_breaks(config: &Config, log: &RunLog) -> (Vec<f64>, Vec<f64>) {
+    if !config.deck_audio {
+        return (Vec::new(), Vec::new());
+    }
+    let blocks = match audio::read_blocks(config) {
+        Ok(blocks) => blocks,
+        Err(e) => {
+            log.warn(format_args!("Not splitting decks at applause or silence, the audio could not be read: {}", e));
+            return (Vec::new(), Vec::new());
+        }
+    };
+    let ends = |intervals: Vec<Interval>| intervals.iter().map(|interval| interval.end).collect();
+    (ends(audio::applause(&blocks, MIN_APPLAUSE)), ends(audio::quiet_stretches(&blocks, SILENCE_DB, MIN_SILENCE)))
+}
+
+/// Move the slides of each deck into a `deck-N` subdirectory with a manifest
+/// of its own, leaving `manifest` describing all of them from the output directory
+pub fn split_output(config: &Config, manifest: &mut Manifest) -> Result<(), io::Error> {
+    let Some(last) = manifest.slides.iter().filter_map(|slide| slide.deck).max() else {
+        return Ok(());
+    };
+    for deck in 1..=last {
+        let name = format!("deck-{}", deck);
+        let deck_dir = config.output_dir.join(&name);
+        fs::create_dir_all(&deck_dir)?;
+
+        let mut slides: Vec<Slide> = Vec::new();
+        for slide in manifest.slides.iter_mut().filter(|slide| slide.deck == Some(deck)) {
+            fs::rename(config.output_dir.join(&slide.file), deck_dir.join(&slide.file))?;
+            slides.push(Slide { index: slides.len() + 1, ..slide.clone() });
+            slide.file = format!("{}/{}", name, slide.file);
+        }
+        let mut deck_manifest = Manifest {
+            version: SCHEMA_VERSION,
+            sources: manifest.sources.clone(),
+            slides,
+            bad_frames: Vec::new(),
+            frames: Vec::new(),
+            resolution_changes: Vec::new(),
+            content: manifest.content,
+            catalog: None,
+            fingerprint: None,
+        };
+        // Each deck has a title slide of its own
+        catalog::describe(&config.input_file, &mut deck_manifest);
+        deck_manifest.write(&deck_dir)?;
+    }
+    Ok(())
+}
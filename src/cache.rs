@@ -0,0 +1,110 @@
+//! `--frame-cache`: keep the frames sampled from a video in a cache directory
+//! and reuse them on the next run of the same video, so trying another
+//! threshold or comparison setting skips ffmpeg entirely.
+//!
+//! The frames are cached whole rather than as thumbnails or hashes, since the
+//! kept slides are copied from them. Entries are keyed by the input's hash and
+//! the settings that decide which frames are sampled (`fps`, `duration` and
+//! `crop`), and are only written once ffmpeg finished, so an interrupted run
+//! leaves nothing half-sampled behind. Nothing is ever evicted; delete the
+//! directory to reclaim the space.
+
+use image::DynamicImage;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::config::{Config, CropArea};
+use crate::error::Error;
+use crate::extract::{check_input, extract_frames};
+use crate::fingerprint::hash_input;
+use crate::memory::fit_budget;
+use crate::progress::{CancellationToken, Progress};
+use crate::runlog::RunLog;
+use crate::s3::hex;
+use crate::source::{DirectorySource, Frame, FrameSource};
+
+/// The settings that decide which frames ffmpeg samples
+#[derive(Serialize)]
+struct Sampling {
+    input: String,
+    fps: u32,
+    duration: Option<f64>,
+    crop: Option<CropArea>,
+}
+
+/// Frames of `config.input_file` from the cache, sampled into it first if
+/// they aren't there yet; the cached files are left alone and kept ones copied
+pub struct CachedSource {
+    config: Config,
+    log: RunLog,
+    frames: Option<DirectorySource>,
+}
+
+impl CachedSource {
+    pub fn new(config: &Config) -> Result<Self, io::Error> {
+        Ok(CachedSource { config: config.clone(), log: RunLog::open(config.log_file.as_deref())?, frames: None })
+    }
+}
+
+/// Directory in `cache_dir` holding the frames `config` samples
+fn entry_dir(cache_dir: &Path, config: &Config) -> Result<PathBuf, io::Error> {
+    let sampling = Sampling {
+        input: hash_input(&config.input_file)?,
+        fps: config.fps,
+        duration: config.duration,
+        crop: config.crop,
+    };
+    let json = serde_json::to_vec(&sampling).expect("sampling settings serialize");
+    Ok(cache_dir.join(hex(&Sha256::digest(json))))
+}
+
+impl FrameSource for CachedSource {
+    fn prepare(&mut self, progress: &dyn Fn(Progress), cancel: &CancellationToken) -> Result<(), Error> {
+        check_input(&self.config.input_file)?;
+        let cache_dir = self.config.frame_cache.clone().expect("a cached source has a cache directory");
+        fs::create_dir_all(&cache_dir)?;
+        let entry = entry_dir(&cache_dir, &self.config)?;
+
+        if entry.is_dir() {
+            self.log.info(format_args!("Reusing the frames sampled before from {}.", entry.display()));
+        } else {
+            // Sampled next to the entry and renamed into place once complete
+            fit_budget(&mut self.config, &self.log);
+            let partial = tempfile::Builder::new().prefix(".partial-").tempdir_in(&cache_dir)?;
+            extract_frames(&self.config, partial.path(), &self.log, progress, cancel)?;
+            match fs::rename(partial.path(), &entry) {
+                Ok(()) => {
+                    // Already moved, nothing left to delete
+                    let _ = partial.into_path();
+                    self.log.info(format_args!("Cached the sampled frames in {}.", entry.display()));
+                }
+                // Another run of the same video got there first, its frames are as good
+                Err(_) if entry.is_dir() => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+        self.frames = Some(DirectorySource::open(&entry)?);
+        Ok(())
+    }
+
+    fn remaining(&self) -> Option<usize> {
+        self.frames.as_ref().and_then(|frames| frames.remaining())
+    }
+
+    fn next_frame(&mut self) -> Result<Option<Frame>, Error> {
+        match self.frames.as_mut() {
+            Some(frames) => frames.next_frame(),
+            None => Err(Error::Io(io::Error::other("frames requested before the cache was opened"))),
+        }
+    }
+
+    fn keep(&mut self, name: &str, path: Option<&Path>, image: &DynamicImage, output_dir: &Path) -> Result<PathBuf, Error> {
+        match self.frames.as_mut() {
+            Some(frames) => frames.keep(name, path, image, output_dir),
+            None => Err(Error::Io(io::Error::other("frame kept before the cache was opened"))),
+        }
+    }
+}
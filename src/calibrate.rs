@@ -0,0 +1,234 @@
+//! `calibrate`: how well each way of comparing frames tells two frames of
+//! the same slide from frames of different slides in one recording, whose
+//! codec and noise decide how far apart the two kinds of pairs score.
+//!
+//! Consecutive sampled frames are paired up. With ground-truth slide times
+//! (as for `evaluate`) a pair is of different slides when a slide appears
+//! between its frames, and pairs close to a change are left out as
+//! transitions. Without, pairs are split where the differences of the
+//! plain pixel comparison fall into two groups, which is right for a
+//! recording with clear changes and low noise.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use crate::bench::metrics;
+use crate::config::Config;
+use crate::dedup::Deduplicator;
+use crate::error::Error;
+use crate::evaluate::read_truth;
+use crate::extract::extract_frames;
+use crate::progress::CancellationToken;
+use crate::runlog::RunLog;
+use crate::source::{DirectorySource, FrameSource};
+use crate::workspace::Workspace;
+
+/// Smallest difference on the log scale scores are compared on, for identical frames
+const FLOOR: f64 = 1e-6;
+
+/// What to calibrate on
+#[derive(Debug, Clone)]
+pub struct CalibrateOptions {
+    /// Seconds from the start of the video that are sampled
+    pub sample: f64,
+    /// Values of `--compare-stride` tried, each one a metric of its own
+    pub strides: Vec<u32>,
+    /// File with the time each slide appears, to label the pairs by
+    pub truth: Option<PathBuf>,
+    /// With `truth`, seconds around each change in which pairs are left out
+    pub tolerance: f64,
+}
+
+/// How one metric scores the two kinds of pairs
+#[derive(Debug, Clone)]
+pub struct Calibration {
+    /// The settings that make up the metric, as command line flags
+    pub metric: String,
+    /// Median difference of same-slide pairs
+    pub same_median: f64,
+    /// Largest difference of a same-slide pair
+    pub same_max: f64,
+    /// Median difference of different-slide pairs
+    pub different_median: f64,
+    /// Smallest difference of a different-slide pair
+    pub different_min: f64,
+    /// Distance between the two groups' means in pooled standard deviations, on a log scale
+    pub separation: f64,
+    /// Threshold that gets the most pairs right
+    pub threshold: f64,
+    /// Pairs that threshold gets wrong
+    pub errors: usize,
+}
+
+/// The result of a calibration
+#[derive(Debug, Clone)]
+pub struct CalibrationReport {
+    pub same_pairs: usize,
+    pub different_pairs: usize,
+    /// Whether the pairs were labelled from ground truth
+    pub from_truth: bool,
+    pub metrics: Vec<Calibration>,
+}
+
+impl CalibrationReport {
+    /// The metric that separates the pairs best, the first of equals
+    pub fn best(&self) -> Option<&Calibration> {
+        self.metrics.iter().rev().max_by(|a, b| a.separation.total_cmp(&b.separation).then(b.errors.cmp(&a.errors)))
+    }
+}
+
+fn median(sorted: &[f64]) -> f64 {
+    sorted.get(sorted.len() / 2).copied().unwrap_or(f64::NAN)
+}
+
+fn mean_variance(values: impl Iterator<Item = f64> + Clone) -> (f64, f64) {
+    let count = values.clone().count().max(1) as f64;
+    let mean = values.clone().sum::<f64>() / count;
+    (mean, values.map(|value| (value - mean).powi(2)).sum::<f64>() / count)
+}
+
+/// Threshold splitting `scores` (difference, of different slides) with the fewest pairs on the
+/// wrong side, preferring the widest gap, and how many pairs it gets wrong
+fn best_threshold(scores: &mut [(f64, bool)]) -> (f64, usize) {
+    scores.sort_by(|a, b| a.0.total_cmp(&b.0));
+    let different = scores.iter().filter(|(_, different)| *different).count();
+    // Everything above the threshold counts as a change
+    let (mut best, mut best_errors, mut best_gap) = (0.0, usize::MAX, 0.0);
+    let mut same_above = scores.len() - different;
+    let mut different_below = 0;
+    for split in 0..=scores.len() {
+        let below = split.checked_sub(1).map_or(0.0, |i| scores[i].0);
+        let above = scores.get(split).map_or(1.0, |score| score.0);
+        let errors = same_above + different_below;
+        let gap = (above.max(FLOOR) / below.max(FLOOR)).ln();
+        if below < above && (errors < best_errors || errors == best_errors && gap > best_gap) {
+            best = (below.max(FLOOR) * above.max(FLOOR)).sqrt();
+            (best_errors, best_gap) = (errors, gap);
+        }
+        if let Some(&(_, is_different)) = scores.get(split) {
+            if is_different {
+                different_below += 1;
+            } else {
+                same_above -= 1;
+            }
+        }
+    }
+    (best, best_errors)
+}
+
+/// Split `differences` into two groups where the between-group variance of their logarithms is largest (Otsu)
+fn split_point(differences: &[f64]) -> f64 {
+    let mut logs: Vec<f64> = differences.iter().map(|difference| difference.max(FLOOR).log10()).collect();
+    logs.sort_by(f64::total_cmp);
+    let total: f64 = logs.iter().sum();
+    let (mut best, mut best_variance, mut sum) = (f64::INFINITY, -1.0, 0.0);
+    for split in 1..logs.len() {
+        sum += logs[split - 1];
+        let (low, high) = (split as f64, (logs.len() - split) as f64);
+        let variance = low * high * (sum / low - (total - sum) / high).powi(2);
+        if variance > best_variance && logs[split - 1] < logs[split] {
+            best_variance = variance;
+            best = 10f64.powf((logs[split - 1] + logs[split]) / 2.0);
+        }
+    }
+    best
+}
+
+/// Differences of consecutive frames in `frames_dir` under the metric `config`, with the position of the later frame
+fn pair_differences(config: &Config, frames_dir: &Path, log: &RunLog) -> Result<Vec<(usize, f64)>, Error> {
+    let mut source = DirectorySource::open(frames_dir)?;
+    let mut dedup = Deduplicator::new(config, log);
+    let mut differences = Vec::new();
+    let mut position = 0;
+    loop {
+        let frame = match source.next_frame() {
+            Ok(Some(frame)) => frame,
+            Ok(None) => break,
+            Err(Error::BadFrame { .. }) => continue,
+            Err(e) => return Err(e),
+        };
+        // A change of resolution is a different slide under any metric
+        let decision = dedup.observe(frame.image);
+        if let (Some(difference), None) = (decision.difference, decision.resized) {
+            differences.push((position, difference));
+        }
+        position += 1;
+    }
+    Ok(differences)
+}
+
+/// Sample the first `options.sample` seconds of `config.input_file` and score same-slide
+/// and different-slide pairs of frames with every metric
+pub fn calibrate(config: &Config, options: &CalibrateOptions) -> Result<CalibrationReport, Error> {
+    let log = RunLog::open(config.log_file.as_deref())?;
+    let mut config = config.clone();
+    config.duration = Some(options.sample);
+
+    let mut frames_dir = Workspace::create(&config)?;
+    extract_frames(&config, frames_dir.path(), &log, &|_| {}, &CancellationToken::new())?;
+
+    let metrics = metrics(&config, &options.strides);
+    let mut scored = Vec::with_capacity(metrics.len());
+    for (metric, metric_config) in metrics {
+        scored.push((metric, pair_differences(&metric_config, frames_dir.path(), &log)?));
+    }
+
+    // Label each pair by the later frame's position: true for different slides, left out near a change
+    let fps = config.fps as f64;
+    let labels: HashMap<usize, bool> = match &options.truth {
+        Some(truth) => {
+            let changes = read_truth(truth)?;
+            scored.first().map_or(HashMap::new(), |(_, pairs)| {
+                pairs
+                    .iter()
+                    .filter_map(|&(position, _)| {
+                        let (start, end) = ((position - 1) as f64 / fps, position as f64 / fps);
+                        let near = |time: f64| changes.iter().any(|&change| (change - time).abs() <= options.tolerance);
+                        if changes.iter().any(|&change| start < change && change <= end) {
+                            Some((position, true))
+                        } else if near(start) || near(end) {
+                            None
+                        } else {
+                            Some((position, false))
+                        }
+                    })
+                    .collect()
+            })
+        }
+        None => scored.first().map_or(HashMap::new(), |(_, pairs)| {
+            let split = split_point(&pairs.iter().map(|&(_, difference)| difference).collect::<Vec<_>>());
+            pairs.iter().map(|&(position, difference)| (position, difference > split)).collect()
+        }),
+    };
+
+    let mut report = CalibrationReport {
+        same_pairs: labels.values().filter(|different| !**different).count(),
+        different_pairs: labels.values().filter(|different| **different).count(),
+        from_truth: options.truth.is_some(),
+        metrics: Vec::new(),
+    };
+    for (metric, pairs) in scored {
+        let mut scores: Vec<(f64, bool)> =
+            pairs.iter().filter_map(|&(position, difference)| Some((difference, *labels.get(&position)?))).collect();
+        let (threshold, errors) = best_threshold(&mut scores);
+
+        let same: Vec<f64> = scores.iter().filter(|(_, different)| !different).map(|&(score, _)| score).collect();
+        let different: Vec<f64> = scores.iter().filter(|(_, different)| *different).map(|&(score, _)| score).collect();
+        let (same_mean, same_variance) = mean_variance(same.iter().map(|score| score.max(FLOOR).log10()));
+        let (different_mean, different_variance) = mean_variance(different.iter().map(|score| score.max(FLOOR).log10()));
+        let pooled = ((same_variance + different_variance) / 2.0).sqrt().max(FLOOR);
+
+        report.metrics.push(Calibration {
+            metric,
+            same_median: median(&same),
+            same_max: same.last().copied().unwrap_or(f64::NAN),
+            different_median: median(&different),
+            different_min: different.first().copied().unwrap_or(f64::NAN),
+            separation: if same.is_empty() || different.is_empty() { 0.0 } else { (different_mean - same_mean) / pooled },
+            threshold,
+            errors,
+        });
+    }
+    frames_dir.succeeded();
+    Ok(report)
+}
@@ -0,0 +1,53 @@
+//! `--canvas`: letterbox the kept slides onto one shape, so a deck gathered
+//! from 4:3 and 16:9 sources doesn't jump between aspect ratios.
+//!
+//! The canvas is as large as the largest slide needs; each slide is scaled
+//! to fit it and centred on `--canvas-background`.
+
+use image::imageops::{self, FilterType};
+use image::{GenericImageView, Rgb, RgbImage};
+use std::io;
+
+use crate::config::Config;
+use crate::manifest::Manifest;
+use crate::runlog::RunLog;
+use crate::source::open_frame;
+
+/// Pad the slides of `manifest` in the output directory onto the canvas `config.canvas` asks for
+pub fn pad_slides(config: &Config, manifest: &Manifest, log: &RunLog) -> Result<(), io::Error> {
+    let Some(aspect) = config.canvas else {
+        return Ok(());
+    };
+    let open = |file: &str| {
+        let path = config.output_dir.join(file);
+        open_frame(&path).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", path.display(), e)))
+    };
+
+    // Wide enough for the widest slide and tall enough for the tallest
+    let mut width = 0_f64;
+    for slide in &manifest.slides {
+        let (slide_width, slide_height) = open(&slide.file)?.dimensions();
+        width = width.max(slide_width as f64).max(slide_height as f64 * aspect.width as f64 / aspect.height as f64);
+    }
+    let canvas_width = width.round() as u32;
+    let canvas_height = (width * aspect.height as f64 / aspect.width as f64).round() as u32;
+
+    let mut padded = 0;
+    for slide in &manifest.slides {
+        let image = open(&slide.file)?;
+        if image.dimensions() == (canvas_width, canvas_height) {
+            continue;
+        }
+        let fitted = image.resize(canvas_width, canvas_height, FilterType::Lanczos3).to_rgb8();
+        let mut canvas = RgbImage::from_pixel(canvas_width, canvas_height, Rgb(config.canvas_background.0));
+        let x = (canvas_width - fitted.width()) / 2;
+        let y = (canvas_height - fitted.height()) / 2;
+        imageops::replace(&mut canvas, &fitted, x as i64, y as i64);
+        canvas.save(config.output_dir.join(&slide.file)).map_err(io::Error::other)?;
+        padded += 1;
+    }
+    if padded > 0 {
+        log.info(format_args!("Padded {} slide(s) onto a {}x{} ({}) canvas.", padded, canvas_width, canvas_height, aspect));
+    }
+    Ok(())
+}
@@ -0,0 +1,157 @@
+//! What the recorded deck as a whole is: its title, speaker, course and date,
+//! for the manifest and the exports' document properties, so exported files
+//! are catalogued without being tagged by hand.
+//!
+//! Each comes from the first of these that has it:
+//!
+//! - the title slide, the first one, as read with `--ocr`: its title from
+//!   `--summarize` or its first line, a line like `Prof. Smith` or `Presented
+//!   by Jane Doe` as the speaker, a course code like `CS 101` and a date;
+//! - the footer, a last line of text that at least half the slides share,
+//!   split at separators like `|` and read the same way;
+//! - the input's file name, like `CS101_lecture3_2024-03-12.mp4`, for the
+//!   course code and date.
+//!
+//! Dates are written as `YYYY-MM-DD` and are read from that form, `YYYYMMDD`,
+//! `March 12, 2024` and `12 March 2024`; `3/12/2024` is left alone since it
+//! reads either way.
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::manifest::{Catalog, Manifest};
+
+/// Share of the slides a last line must end to be the footer
+const FOOTER_SHARE: f64 = 0.5;
+/// Slides a footer needs to be told apart from a one-off last line
+const FOOTER_MIN_SLIDES: usize = 3;
+/// What a line naming the speaker starts with, and whether it is part of the name
+const SPEAKER_PREFIXES: [(&str, bool); 10] = [
+    ("presented by", false),
+    ("speaker:", false),
+    ("instructor:", false),
+    ("lecturer:", false),
+    ("by ", false),
+    ("professor ", true),
+    ("prof. ", true),
+    ("prof ", true),
+    ("dr. ", true),
+    ("dr ", true),
+];
+const MONTHS: [&str; 12] =
+    ["january", "february", "march", "april", "may", "june", "july", "august", "september", "october", "november", "december"];
+
+/// Fill in `manifest.catalog` from its slides' text and the name of `input_file`
+pub fn describe(input_file: &Path, manifest: &mut Manifest) {
+    let mut catalog = Catalog::default();
+    if let Some(slide) = manifest.slides.first() {
+        let text = slide.text.as_deref().unwrap_or_default();
+        let lines: Vec<&str> = text.lines().map(str::trim).filter(|line| !line.is_empty()).collect();
+        read_fields(&mut catalog, &lines);
+        catalog.title = slide.title.clone().or_else(|| {
+            lines
+                .iter()
+                .find(|line| speaker(line).is_none() && !is_course_code(line) && date(line).is_none())
+                .map(|line| line.to_string())
+        });
+    }
+    if let Some(footer) = footer(manifest) {
+        let parts: Vec<&str> = footer.split(['|', '·', '•', '–', '—']).map(str::trim).filter(|part| !part.is_empty()).collect();
+        read_fields(&mut catalog, &parts);
+    }
+    let stem = input_file.file_stem().unwrap_or_default().to_string_lossy();
+    catalog.course = catalog.course.or_else(|| course_code(&stem));
+    catalog.date = catalog.date.or_else(|| date(&stem));
+
+    manifest.catalog = (catalog != Catalog::default()).then_some(catalog);
+}
+
+/// Fill in the speaker, course and date of `catalog` not known yet from `lines`
+fn read_fields(catalog: &mut Catalog, lines: &[&str]) {
+    for line in lines {
+        catalog.speaker = catalog.speaker.take().or_else(|| speaker(line));
+        catalog.course = catalog.course.take().or_else(|| course_code(line));
+        catalog.date = catalog.date.take().or_else(|| date(line));
+    }
+}
+
+/// The last line of text most slides of `manifest` share, if enough do
+fn footer(manifest: &Manifest) -> Option<String> {
+    let mut counts: HashMap<&str, usize> = HashMap::new();
+    for slide in &manifest.slides {
+        if let Some(last) = slide.text.as_deref().and_then(|text| text.lines().map(str::trim).rfind(|line| !line.is_empty())) {
+            *counts.entry(last).or_default() += 1;
+        }
+    }
+    let needed = ((manifest.slides.len() as f64 * FOOTER_SHARE).ceil() as usize).max(FOOTER_MIN_SLIDES);
+    counts.into_iter().filter(|&(_, count)| count >= needed).max_by_key(|&(_, count)| count).map(|(line, _)| line.to_string())
+}
+
+/// The speaker named by `line`, if it reads like it names one
+fn speaker(line: &str) -> Option<String> {
+    SPEAKER_PREFIXES.iter().find_map(|&(prefix, keep)| {
+        let start = line.get(..prefix.len()).filter(|start| start.eq_ignore_ascii_case(prefix))?;
+        let rest = line[start.len()..].trim();
+        (!rest.is_empty()).then(|| if keep { line.trim() } else { rest }.to_string())
+    })
+}
+
+/// A course code like `CS 101`, `MATH2001` or `EE 364A` in `text`, written without a space
+fn course_code(text: &str) -> Option<String> {
+    let words: Vec<&str> = text.split(|c: char| !c.is_ascii_alphanumeric()).filter(|word| !word.is_empty()).collect();
+    let is_subject = |word: &str| (2..=5).contains(&word.len()) && word.chars().all(|c| c.is_ascii_uppercase());
+    let is_number = |word: &str| {
+        let digits = word.chars().take_while(char::is_ascii_digit).count();
+        (3..=4).contains(&digits) && word[digits..].chars().all(|c| c.is_ascii_uppercase()) && word.len() - digits <= 1
+    };
+    for (i, word) in words.iter().enumerate() {
+        let letters = word.chars().take_while(char::is_ascii_uppercase).count();
+        if letters < word.len() && is_subject(&word[..letters]) && is_number(&word[letters..]) {
+            return Some(word.to_string());
+        }
+        if is_subject(word) && words.get(i + 1).is_some_and(|next| is_number(next)) {
+            return Some(format!("{}{}", word, words[i + 1]));
+        }
+    }
+    None
+}
+
+/// Whether `line` is a course code and nothing else
+fn is_course_code(line: &str) -> bool {
+    let letters: String = line.chars().filter(char::is_ascii_alphanumeric).collect();
+    course_code(line).is_some_and(|code| code == letters)
+}
+
+/// A date in `text` as `YYYY-MM-DD`
+fn date(text: &str) -> Option<String> {
+    let words: Vec<&str> = text.split(|c: char| !c.is_alphanumeric()).filter(|word| !word.is_empty()).collect();
+    let number = |word: &str| word.parse::<u32>().ok().filter(|_| word.chars().all(|c| c.is_ascii_digit()));
+    let month = |word: &str| {
+        let word = word.to_lowercase();
+        (word.len() >= 3).then(|| MONTHS.iter().position(|month| month.starts_with(&word)).map(|i| i as u32 + 1)).flatten()
+    };
+    let valid = |year: u32, month: u32, day: u32| {
+        ((1990..=2099).contains(&year) && (1..=12).contains(&month) && (1..=31).contains(&day))
+            .then(|| format!("{:04}-{:02}-{:02}", year, month, day))
+    };
+    for (i, word) in words.iter().enumerate() {
+        let next = |n: usize| words.get(i + n).copied().unwrap_or_default();
+        let found = if word.len() == 8 && number(word).is_some() {
+            // 20240312
+            valid(number(&word[..4])?, number(&word[4..6])?, number(&word[6..])?)
+        } else if word.len() == 4 && next(1).len() <= 2 && next(2).len() <= 2 {
+            // 2024-03-12
+            number(word).zip(number(next(1))).zip(number(next(2))).and_then(|((year, month), day)| valid(year, month, day))
+        } else if let Some(month) = month(word) {
+            // March 12, 2024
+            number(next(1)).zip(number(next(2))).and_then(|(day, year)| valid(year, month, day))
+        } else {
+            // 12 March 2024
+            number(word).zip(month(next(1))).zip(number(next(2))).and_then(|((day, month), year)| valid(year, month, day))
+        };
+        if found.is_some() {
+            return found;
+        }
+    }
+    None
+}
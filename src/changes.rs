@@ -0,0 +1,140 @@
+//! What changed at each slide transition: the box around the change and
+//! whether the whole slide changed or only part of it, so downstream tools
+//! can tell a new slide from an animation step (a bullet appearing, a
+//! highlight moving) and filter on it.
+//!
+//! Each slide is compared with the slide kept before it, tile by tile, so
+//! compression noise scattered over the frame doesn't stretch the box. With
+//! `--save-diffs` the comparison is also saved in `diffs/` for reviewers:
+//! the slide before on the left, the slide on the right faded where nothing
+//! changed, with the changed pixels in red and the box around them.
+
+use image::{Rgb, RgbImage};
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::config::Config;
+use crate::manifest::{Change, ChangeKind, Manifest};
+use crate::source::open_frame;
+
+/// Edge length in pixels of the square tiles changes are located in
+const TILE_SIZE: u32 = 32;
+/// Share of a tile's pixels that must differ for the tile to count as changed
+const TILE_CHANGE_RATIO: f64 = 0.05;
+/// Share of the frame the box must cover for the whole slide to count as changed
+const FULL_CHANGE_AREA: f64 = 0.5;
+/// Subdirectory of the output directory the diffs are saved in
+pub const DIFFS_DIR: &str = "diffs";
+/// Colour of the changed pixels and the box around them in a diff
+const HIGHLIGHT: Rgb<u8> = Rgb([230, 30, 30]);
+/// Width in pixels of the box drawn around the change
+const BOX_WIDTH: u32 = 3;
+/// Gap in pixels between the two slides of a diff
+const GAP: u32 = 16;
+
+/// The change from `previous` to `current`, which must have the same size; `None` if no tile changed
+pub fn locate(previous: &RgbImage, current: &RgbImage) -> Option<Change> {
+    let (width, height) = current.dimensions();
+    let columns = width.div_ceil(TILE_SIZE);
+    let mut diffs = vec![0u32; (columns * height.div_ceil(TILE_SIZE)) as usize];
+    for (x, y, pixel) in current.enumerate_pixels() {
+        if previous.get_pixel(x, y) != pixel {
+            diffs[((y / TILE_SIZE) * columns + x / TILE_SIZE) as usize] += 1;
+        }
+    }
+
+    // Box around the changed tiles, in pixels
+    let (mut x0, mut y0, mut x1, mut y1) = (u32::MAX, u32::MAX, 0, 0);
+    let mut changed_pixels = 0u64;
+    for (index, &count) in diffs.iter().enumerate() {
+        let (tx, ty) = (index as u32 % columns, index as u32 / columns);
+        let (right, bottom) = (((tx + 1) * TILE_SIZE).min(width), ((ty + 1) * TILE_SIZE).min(height));
+        let pixels = (right - tx * TILE_SIZE) * (bottom - ty * TILE_SIZE);
+        if count as f64 / pixels as f64 > TILE_CHANGE_RATIO {
+            (x0, y0, x1, y1) = (x0.min(tx * TILE_SIZE), y0.min(ty * TILE_SIZE), x1.max(right), y1.max(bottom));
+            changed_pixels += pixels as u64;
+        }
+    }
+    if changed_pixels == 0 {
+        return None;
+    }
+
+    let area = ((x1 - x0) * (y1 - y0)) as f64 / (width * height) as f64;
+    Some(Change {
+        kind: if area >= FULL_CHANGE_AREA { ChangeKind::Full } else { ChangeKind::Partial },
+        x: x0,
+        y: y0,
+        width: x1 - x0,
+        height: y1 - y0,
+        area,
+    })
+}
+
+/// `previous` and `current` side by side, `current` faded where it is the same, its changed
+/// pixels in red and `change` boxed
+pub fn diff_image(previous: &RgbImage, current: &RgbImage, change: &Change) -> RgbImage {
+    let (width, height) = current.dimensions();
+    let mut diff = RgbImage::from_pixel(width * 2 + GAP, height, Rgb([255, 255, 255]));
+    image::imageops::replace(&mut diff, previous, 0, 0);
+    for (x, y, pixel) in current.enumerate_pixels() {
+        let shown = if previous.get_pixel(x, y) != pixel {
+            HIGHLIGHT
+        } else {
+            // A quarter of the colour over white
+            Rgb(pixel.0.map(|channel| 191 + channel / 4))
+        };
+        diff.put_pixel(width + GAP + x, y, shown);
+    }
+    for y in change.y..change.y + change.height {
+        for x in change.x..change.x + change.width {
+            let edge = x < change.x + BOX_WIDTH
+                || x + BOX_WIDTH >= change.x + change.width
+                || y < change.y + BOX_WIDTH
+                || y + BOX_WIDTH >= change.y + change.height;
+            if edge {
+                diff.put_pixel(width + GAP + x, y, HIGHLIGHT);
+            }
+        }
+    }
+    diff
+}
+
+/// Save the diff of the slide stored as `file` into `output_dir`'s `diffs` directory, returning its path there
+fn save_diff(output_dir: &Path, file: &str, diff: &RgbImage) -> Result<String, io::Error> {
+    let stem = Path::new(file).file_stem().unwrap_or_default().to_string_lossy();
+    let name = format!("{}/{}_diff.png", DIFFS_DIR, stem);
+    fs::create_dir_all(output_dir.join(DIFFS_DIR))?;
+    diff.save(output_dir.join(&name)).map_err(|e| io::Error::other(format!("Error saving image: {}", e)))?;
+    Ok(name)
+}
+
+/// Record for every slide of `manifest` but the first what changed since the slide before it,
+/// saving the diffs with `save_diffs`
+pub fn record_changes(config: &Config, manifest: &mut Manifest) -> Result<(), io::Error> {
+    let open = |file: &str| {
+        let path = config.output_dir.join(file);
+        open_frame(&path).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", path.display(), e)))
+    };
+
+    let mut previous: Option<RgbImage> = None;
+    for slide in &mut manifest.slides {
+        let current = open(&slide.file)?.to_rgb8();
+        slide.change = match &previous {
+            // Shown at another size (the screen was shared again) is a new slide
+            Some(previous) if previous.dimensions() != current.dimensions() => {
+                let (width, height) = current.dimensions();
+                Some(Change { kind: ChangeKind::Full, x: 0, y: 0, width, height, area: 1.0 })
+            }
+            Some(previous) => locate(previous, &current),
+            None => None,
+        };
+        if let (true, Some(previous), Some(change)) = (config.save_diffs, &previous, &slide.change) {
+            if previous.dimensions() == current.dimensions() {
+                slide.diff = Some(save_diff(&config.output_dir, &slide.file, &diff_image(previous, &current, change))?);
+            }
+        }
+        previous = Some(current);
+    }
+    Ok(())
+}
@@ -0,0 +1,135 @@
+//! Tagging each slide with what kind of content it shows, so a programming
+//! lecture can be exported as its code slides only.
+//!
+//! The guess comes from colour and edge statistics of a small copy of the
+//! slide, and how much text it has:
+//!
+//! - a photo needs many colours to cover most of it and has no plain background,
+//! - code is dense text on a dark background, as in an editor's dark theme,
+//! - a chart or diagram has a plain background with saturated flat areas,
+//! - text is dense text on a plain background,
+//! - anything sparser, such as a title slide, is plain.
+//!
+//! How dense the text is comes from the characters of the slide's `--ocr`
+//! text for its area when it was read, and from its share of fine edges
+//! otherwise, which diagrams with many lines can pass for. Code in a light
+//! theme reads as text.
+
+use image::imageops::FilterType;
+use image::{DynamicImage, GenericImageView};
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::io;
+use std::path::Path;
+
+use crate::manifest::{Manifest, SlideKind};
+use crate::source::open_frame;
+
+/// Width slides are scaled to before they are measured
+const WIDTH: u32 = 256;
+/// Share of the slide the colours counted for a photo must cover
+const COVERAGE: f64 = 0.9;
+/// Colours (at 16 levels per channel) a photo needs to cover `COVERAGE` of it
+const PHOTO_COLORS: usize = 150;
+/// Share of the slide in its most common colour, from which that colour is a plain background
+const BACKGROUND_SHARE: f64 = 0.3;
+/// Brightness (0 to 255) of a background below which it is dark
+const DARK: f64 = 96.0;
+/// Brightness step between neighbouring pixels from which there is an edge
+const EDGE_STEP: i32 = 64;
+/// Share of edge pixels from which a slide is dense with text
+const TEXT_EDGES: f64 = 0.03;
+/// Characters of OCR text per 1000 pixels of the small copy from which a slide is dense with
+/// text, some 150 on a 16:9 slide
+const TEXT_CHARACTERS: f64 = 4.0;
+/// Spread of the channels of a pixel from which it is saturated
+const SATURATED: u8 = 80;
+/// Share of the pixels off the background that must be saturated for a chart or diagram
+const DIAGRAM_SATURATION: f64 = 0.3;
+/// Share of the slide off the background a chart or diagram takes at least
+const DIAGRAM_AREA: f64 = 0.05;
+
+/// What `image` shows, with `text` the text read off it if it was
+pub fn classify(image: &DynamicImage, text: Option<&str>) -> SlideKind {
+    let (width, height) = image.dimensions();
+    let height = ((height as f64 * WIDTH as f64 / width.max(1) as f64).round() as u32).max(1);
+    let small = image.resize_exact(WIDTH, height, FilterType::Triangle);
+    let rgb = small.to_rgb8();
+    let pixels = (WIDTH * height) as f64;
+
+    // Colours at 16 levels per channel, the most common first
+    let mut histogram: HashMap<[u8; 3], usize> = HashMap::new();
+    for pixel in rgb.pixels() {
+        *histogram.entry(pixel.0.map(|channel| channel >> 4)).or_default() += 1;
+    }
+    let mut colors: Vec<([u8; 3], usize)> = histogram.into_iter().collect();
+    colors.sort_by_key(|&(_, count)| Reverse(count));
+    let (background, background_count) = colors[0];
+    let background_share = background_count as f64 / pixels;
+    let mut covered = 0;
+    let needed = colors
+        .iter()
+        .take_while(|&&(_, count)| {
+            covered += count;
+            (covered as f64) < COVERAGE * pixels
+        })
+        .count()
+        + 1;
+    if needed >= PHOTO_COLORS && background_share < BACKGROUND_SHARE {
+        return SlideKind::Photo;
+    }
+
+    let luma = small.to_luma8();
+    let mut edges = 0;
+    for y in 0..height.saturating_sub(1) {
+        for x in 0..WIDTH - 1 {
+            let here = luma.get_pixel(x, y)[0] as i32;
+            let step = (luma.get_pixel(x + 1, y)[0] as i32 - here).abs() + (luma.get_pixel(x, y + 1)[0] as i32 - here).abs();
+            if step >= EDGE_STEP {
+                edges += 1;
+            }
+        }
+    }
+    let edge_share = edges as f64 / pixels;
+    let dense_text = match text {
+        Some(text) => text.chars().filter(|c| !c.is_whitespace()).count() as f64 * 1000.0 / pixels >= TEXT_CHARACTERS,
+        None => edge_share >= TEXT_EDGES,
+    };
+
+    let (mut foreground, mut saturated) = (0, 0);
+    for pixel in rgb.pixels() {
+        if pixel.0.map(|channel| channel >> 4) == background {
+            continue;
+        }
+        foreground += 1;
+        let (max, min) = (pixel.0.iter().max().unwrap(), pixel.0.iter().min().unwrap());
+        if max - min >= SATURATED {
+            saturated += 1;
+        }
+    }
+    let saturation = saturated as f64 / foreground.max(1) as f64;
+    // Back from 16 levels to the middle of its range
+    let background_luma: f64 =
+        background.iter().zip([0.299, 0.587, 0.114]).map(|(&level, weight)| (level as f64 * 16.0 + 8.0) * weight).sum();
+
+    let plain_background = background_share >= BACKGROUND_SHARE;
+    if plain_background && background_luma < DARK && dense_text {
+        SlideKind::Code
+    } else if plain_background && saturation >= DIAGRAM_SATURATION && foreground as f64 >= DIAGRAM_AREA * pixels {
+        SlideKind::Diagram
+    } else if dense_text {
+        SlideKind::Text
+    } else {
+        SlideKind::Plain
+    }
+}
+
+/// Tag every slide of `manifest` without a kind from its image in `output_dir` and its text, if read
+pub fn tag_kinds(output_dir: &Path, manifest: &mut Manifest) -> Result<(), io::Error> {
+    for slide in manifest.slides.iter_mut().filter(|slide| slide.kind.is_none()) {
+        let path = output_dir.join(&slide.file);
+        let image = open_frame(&path).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", path.display(), e)))?;
+        slide.kind = Some(classify(&image, slide.text.as_deref()));
+    }
+    Ok(())
+}
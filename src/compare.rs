@@ -0,0 +1,174 @@
+use image::imageops::FilterType;
+use image::{DynamicImage, GenericImageView, RgbImage};
+
+use crate::config::{Config, Prefilter};
+#[cfg(feature = "gpu")]
+use crate::gpu::GpuDiff;
+use crate::runlog::RunLog;
+
+/// Pixels in `start..end` along one axis that sampling every `stride`th pixel looks at
+pub fn sampled(start: u32, end: u32, stride: u32) -> u64 {
+    (end.div_ceil(stride) - start.div_ceil(stride)) as u64
+}
+
+/// 64-bit difference hash: whether each pixel of a 9x8 greyscale thumbnail is brighter than its right neighbour
+pub fn perceptual_hash(image: &DynamicImage) -> u64 {
+    let thumbnail = image.resize_exact(9, 8, FilterType::Triangle).to_luma8();
+    let mut hash = 0;
+    for y in 0..8 {
+        for x in 0..8 {
+            hash = hash << 1 | u64::from(thumbnail.get_pixel(x, y)[0] > thumbnail.get_pixel(x + 1, y)[0]);
+        }
+    }
+    hash
+}
+
+/// Kernel width of `--prefilter` unless given
+const DEFAULT_PREFILTER_SIZE: u32 = 3;
+
+/// The blur frames go through before they are compared, with its kernel width
+pub fn prefilter_kernel(config: &Config) -> Option<(Prefilter, u32)> {
+    config.prefilter.map(|filter| (filter, config.prefilter_size.unwrap_or(DEFAULT_PREFILTER_SIZE)))
+}
+
+/// `image` blurred with `filter` over a kernel `size` pixels wide, made odd
+pub fn prefilter(image: &DynamicImage, (filter, size): (Prefilter, u32)) -> DynamicImage {
+    let radius = size / 2;
+    match filter {
+        // Sigma for the kernel width the way OpenCV picks it
+        Prefilter::Gaussian => image.blur(0.3 * (radius as f32 - 1.0) + 0.8),
+        Prefilter::Median => DynamicImage::ImageRgb8(median(&image.to_rgb8(), radius)),
+    }
+}
+
+/// `image` with each channel's histogram matched to that channel of `reference`, so a frame
+/// brighter or darker all over, as a camera's auto-exposure makes it, looks like the reference
+pub fn match_histogram(image: &DynamicImage, reference: &DynamicImage) -> DynamicImage {
+    let (mut rgb, reference) = (image.to_rgb8(), reference.to_rgb8());
+    for channel in 0..3 {
+        let cumulative = |image: &RgbImage| {
+            let mut histogram = [0u64; 256];
+            for pixel in image.pixels() {
+                histogram[pixel[channel] as usize] += 1;
+            }
+            let mut total = 0;
+            histogram.map(|count| {
+                total += count;
+                total as f64 / (image.width() as u64 * image.height() as u64).max(1) as f64
+            })
+        };
+        let (from, to) = (cumulative(&rgb), cumulative(&reference));
+        // Each level goes to the first level of the reference that at least as many pixels are at or below
+        let levels: [u8; 256] = std::array::from_fn(|level| to.iter().position(|&share| share >= from[level]).unwrap_or(255) as u8);
+        for pixel in rgb.pixels_mut() {
+            pixel[channel] = levels[pixel[channel] as usize];
+        }
+    }
+    DynamicImage::ImageRgb8(rgb)
+}
+
+/// Each channel of each pixel replaced by its median over the `2 * radius + 1` square around it
+fn median(image: &RgbImage, radius: u32) -> RgbImage {
+    let (width, height) = image.dimensions();
+    let mut window = Vec::with_capacity(((2 * radius + 1) * (2 * radius + 1)) as usize);
+    RgbImage::from_fn(width, height, |x, y| {
+        let mut pixel = [0u8; 3];
+        for (channel, value) in pixel.iter_mut().enumerate() {
+            window.clear();
+            for wy in y.saturating_sub(radius)..(y + radius + 1).min(height) {
+                for wx in x.saturating_sub(radius)..(x + radius + 1).min(width) {
+                    window.push(image.get_pixel(wx, wy)[channel]);
+                }
+            }
+            let middle = window.len() / 2;
+            *value = *window.select_nth_unstable(middle).1;
+        }
+        image::Rgb(pixel)
+    })
+}
+
+/// Counts the pixels that differ between two frames, in a compute shader when
+/// `--gpu` was given and an adapter was found, on the CPU otherwise
+pub struct Comparer {
+    /// Only every `stride`th pixel in each direction is looked at
+    stride: u32,
+    #[cfg(feature = "gpu")]
+    gpu: Option<GpuDiff>,
+    #[cfg(feature = "gpu")]
+    log: RunLog,
+}
+
+impl Comparer {
+    pub fn new(config: &Config, log: &RunLog) -> Self {
+        #[cfg(feature = "gpu")]
+        let gpu = match config.gpu.then(GpuDiff::new) {
+            Some(Ok(gpu)) => {
+                log.info(format_args!("Comparing frames on {}.", gpu.adapter));
+                Some(gpu)
+            }
+            Some(Err(e)) => {
+                log.warn(format_args!("No usable GPU ({}), comparing frames on the CPU.", e));
+                None
+            }
+            None => None,
+        };
+        #[cfg(not(feature = "gpu"))]
+        if config.gpu {
+            log.warn("Built without GPU support, comparing frames on the CPU.");
+        }
+
+        Comparer {
+            stride: config.compare_stride.max(1),
+            #[cfg(feature = "gpu")]
+            gpu,
+            #[cfg(feature = "gpu")]
+            log: log.clone(),
+        }
+    }
+
+    pub fn stride(&self) -> u32 {
+        self.stride
+    }
+
+    /// Differing pixels per `tile_size` square, row by row; the frames must have the same size
+    pub fn tile_diffs(&mut self, img1: &DynamicImage, img2: &DynamicImage, tile_size: u32) -> Vec<u64> {
+        #[cfg(feature = "gpu")]
+        if let Some(gpu) = self.gpu.as_mut() {
+            match gpu.tile_diffs(img1, img2, self.stride, tile_size) {
+                Ok(tile_diffs) => return tile_diffs,
+                Err(e) => {
+                    self.log.warn(format_args!("GPU comparison failed ({}), comparing frames on the CPU from now on.", e));
+                    self.gpu = None;
+                }
+            }
+        }
+
+        let (width, height) = img1.dimensions();
+        let columns = width.div_ceil(tile_size);
+        let mut tile_diffs = vec![0u64; (columns * height.div_ceil(tile_size)) as usize];
+
+        let stride = self.stride as usize;
+        for x in (0..width).step_by(stride) {
+            for y in (0..height).step_by(stride) {
+                if img1.get_pixel(x, y) != img2.get_pixel(x, y) {
+                    tile_diffs[((y / tile_size) * columns + x / tile_size) as usize] += 1;
+                }
+            }
+        }
+        tile_diffs
+    }
+
+    /// Compare two images and return the share of pixels that differ, 1 if their sizes differ
+    pub fn difference_ratio(&mut self, img1: &DynamicImage, img2: &DynamicImage) -> f64 {
+        if img1.dimensions() != img2.dimensions() {
+            return 1.0;
+        }
+
+        // A single tile covering the whole frame
+        let (width, height) = img1.dimensions();
+        let diff_count: u64 = self.tile_diffs(img1, img2, width.max(height).max(1)).iter().sum();
+
+        let total_pixels = sampled(0, width, self.stride) * sampled(0, height, self.stride);
+        (diff_count as f64) / (total_pixels as f64)
+    }
+}
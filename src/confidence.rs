@@ -0,0 +1,131 @@
+//! How much to trust each kept slide, so review tools can show the doubtful
+//! ones first: a slide that was on screen for a moment, barely cleared the
+//! threshold, or was captured blurred mid-transition is likely a false
+//! detection.
+//!
+//! Slides streamed while a run is in progress have no confidence yet; how
+//! long a slide persisted is only known once the next one appears.
+
+use image::DynamicImage;
+use std::fs;
+use std::io;
+
+use crate::boundaries;
+use crate::config::Config;
+use crate::dedup::Decision;
+use crate::manifest::{Confidence, Manifest};
+use crate::runlog::RunLog;
+
+/// Seconds on screen from which a slide counts as fully persistent
+const FULL_PERSISTENCE: f64 = 10.0;
+/// Laplacian variance at which a frame counts as half sharp
+const HALF_SHARPNESS: f64 = 100.0;
+
+/// What is known about a slide when it is committed
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Evidence {
+    /// Difference of the frame the slide appeared at, `None` for the first frame
+    pub difference: Option<f64>,
+    /// Threshold that difference was held against
+    pub threshold: f64,
+    /// Sharpness of the kept frame, 0 to 1
+    pub sharpness: f64,
+    /// Mean colour of the kept frame's border, to tell decks apart
+    pub theme: [f64; 3],
+}
+
+impl Evidence {
+    /// Evidence for a slide that appeared with `change` and is kept as `image`
+    pub fn new(change: Option<Decision>, image: &DynamicImage) -> Self {
+        Evidence {
+            difference: change.and_then(|change| change.difference),
+            threshold: change.map_or(0.0, |change| change.threshold),
+            sharpness: sharpness(image),
+            theme: boundaries::theme(image),
+        }
+    }
+}
+
+/// Edge contrast of `image` from the variance of its Laplacian, 0 (flat or blurred) to 1 (crisp)
+pub fn sharpness(image: &DynamicImage) -> f64 {
+    let luma = image.to_luma8();
+    let (width, height) = luma.dimensions();
+    if width < 3 || height < 3 {
+        return 0.0;
+    }
+
+    let (mut sum, mut sum_sq, mut count) = (0.0, 0.0, 0.0);
+    for y in 1..height - 1 {
+        for x in 1..width - 1 {
+            let at = |x: u32, y: u32| luma.get_pixel(x, y)[0] as f64;
+            let laplacian = at(x - 1, y) + at(x + 1, y) + at(x, y - 1) + at(x, y + 1) - 4.0 * at(x, y);
+            sum += laplacian;
+            sum_sq += laplacian * laplacian;
+            count += 1.0;
+        }
+    }
+    let mean = sum / count;
+    let variance = sum_sq / count - mean * mean;
+    variance / (variance + HALF_SHARPNESS)
+}
+
+/// How far a difference cleared the threshold it was held against, 0 (just) to 1
+pub fn distinctness(difference: Option<f64>, threshold: f64) -> f64 {
+    match difference {
+        Some(difference) if difference > 0.0 => (1.0 - threshold / difference).clamp(0.0, 1.0),
+        Some(_) => 0.0,
+        // Nothing came before the first slide
+        None => 1.0,
+    }
+}
+
+/// Score the slides of `manifest` from the evidence gathered for each, in
+/// order, and the intervals they were shown in
+pub fn score(manifest: &mut Manifest, evidence: &[Evidence]) {
+    for (slide, evidence) in manifest.slides.iter_mut().zip(evidence) {
+        let persisted: f64 = slide.shown.iter().map(|interval| interval.end - interval.start).sum();
+        let distinctness = distinctness(evidence.difference, evidence.threshold);
+        let persistence = (persisted / FULL_PERSISTENCE).min(1.0);
+        slide.confidence = Some(Confidence {
+            score: (persistence + distinctness + evidence.sharpness) / 3.0,
+            persisted,
+            distinctness,
+            sharpness: evidence.sharpness,
+        });
+    }
+}
+
+/// Apply `--min-confidence`: delete the slides scored below it and number the rest again
+pub fn filter(config: &Config, manifest: &mut Manifest, log: &RunLog) -> Result<(), io::Error> {
+    let Some(min_confidence) = config.min_confidence else {
+        return Ok(());
+    };
+
+    let before = manifest.slides.len();
+    let mut kept = Vec::with_capacity(before);
+    for slide in manifest.slides.drain(..) {
+        match slide.confidence {
+            Some(confidence) if confidence.score < min_confidence => {
+                log.debug(format_args!(
+                    "Dropping slide {} at {:.1}s, confidence {:.2} < {}.",
+                    slide.file, slide.timestamp, confidence.score, min_confidence
+                ));
+                fs::remove_file(config.output_dir.join(&slide.file))?;
+            }
+            _ => kept.push(slide),
+        }
+    }
+    for (index, slide) in kept.iter_mut().enumerate() {
+        slide.index = index + 1;
+    }
+    manifest.slides = kept;
+
+    if manifest.slides.len() < before {
+        log.info(format_args!(
+            "Dropped {} slide(s) with a confidence below {}.",
+            before - manifest.slides.len(),
+            min_confidence
+        ));
+    }
+    Ok(())
+}
@@ -0,0 +1,875 @@
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+/// How the camera recording is aligned with the screen recording
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SyncMode {
+    /// Use the value of --camera-offset as-is
+    Offset,
+    /// Cross-correlate the audio tracks of both recordings
+    Audio,
+}
+
+/// What to do with a sampled frame that cannot be decoded (e.g. truncated by a killed run)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BadFramePolicy {
+    /// Fail the run
+    #[default]
+    Stop,
+    /// Leave the frame out and carry on
+    Skip,
+    /// Leave the frame out and delete its file
+    Delete,
+}
+
+/// Blur applied to both frames of a comparison, never to the slides kept
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Prefilter {
+    /// Smooths dithering and the grain of a noisy capture
+    Gaussian,
+    /// Removes isolated speckles of compression noise while keeping edges sharp
+    Median,
+}
+
+/// Filter ffmpeg runs each row of a sampled frame's PNG through before compressing it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PngPrediction {
+    /// No filter, the fastest
+    None,
+    Sub,
+    Up,
+    Avg,
+    Paeth,
+    /// The best filter for each row, the smallest and slowest
+    Mixed,
+}
+
+impl PngPrediction {
+    /// Value of ffmpeg's `-pred`
+    pub fn ffmpeg_name(self) -> &'static str {
+        match self {
+            PngPrediction::None => "none",
+            PngPrediction::Sub => "sub",
+            PngPrediction::Up => "up",
+            PngPrediction::Avg => "avg",
+            PngPrediction::Paeth => "paeth",
+            PngPrediction::Mixed => "mixed",
+        }
+    }
+}
+
+/// An area of the frame, each side a share (0 to 1) of the frame's width or height
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Mask {
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+}
+
+/// What to do once the kept slides approach `--max-slides` or `--max-output-size`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LimitPolicy {
+    /// Fail the run when a limit is exceeded
+    #[default]
+    Stop,
+    /// Raise the threshold while slides pile up faster than the video plays, failing only if a limit is still exceeded
+    Raise,
+}
+
+/// What happens to a run's working directory of sampled frames once it ends
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum WorkspacePolicy {
+    /// Leave it behind with every frame that was not kept, to look at what the slides were picked from
+    Keep,
+    /// Leave it behind only if the run failed
+    KeepOnError,
+    /// Remove it
+    #[default]
+    Delete,
+}
+
+/// Export kept up to date while the video is processed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LiveFormat {
+    Html,
+    Markdown,
+    Ndjson,
+}
+
+/// What reads the text of the slides with `ocr`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OcrBackend {
+    /// `tesseract` on the PATH
+    #[default]
+    Tesseract,
+    /// An endpoint taking Google Cloud Vision's `images:annotate` requests
+    Http { url: String },
+}
+
+/// `tesseract`, or the URL of an endpoint
+impl FromStr for OcrBackend {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "tesseract" => Ok(OcrBackend::Tesseract),
+            url if url.starts_with("http://") || url.starts_with("https://") => Ok(OcrBackend::Http { url: url.to_string() }),
+            other => Err(format!("{:?} is neither \"tesseract\" nor an http:// or https:// URL", other)),
+        }
+    }
+}
+
+impl fmt::Display for OcrBackend {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            OcrBackend::Tesseract => write!(f, "tesseract"),
+            OcrBackend::Http { url } => write!(f, "{}", url),
+        }
+    }
+}
+
+/// Where `diarize` learns who spoke when
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Diarization {
+    /// An RTTM file a diarization tool wrote beforehand
+    Rttm { path: PathBuf },
+    /// A command given the recording's audio as a WAV file that prints RTTM
+    Command { command: String },
+}
+
+/// A path ending in `.rttm`, or a command
+impl FromStr for Diarization {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "" => Err("the diarization is neither an .rttm file nor a command".to_string()),
+            path if path.to_ascii_lowercase().ends_with(".rttm") => Ok(Diarization::Rttm { path: PathBuf::from(path) }),
+            command => Ok(Diarization::Command { command: command.to_string() }),
+        }
+    }
+}
+
+impl fmt::Display for Diarization {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Diarization::Rttm { path } => write!(f, "{}", path.display()),
+            Diarization::Command { command } => write!(f, "{}", command),
+        }
+    }
+}
+
+/// File format of the per-slide sidecars
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SidecarFormat {
+    Json,
+    Yaml,
+}
+
+impl SidecarFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            SidecarFormat::Json => "json",
+            SidecarFormat::Yaml => "yaml",
+        }
+    }
+
+    pub fn content_type(self) -> &'static str {
+        match self {
+            SidecarFormat::Json => "application/json",
+            SidecarFormat::Yaml => "application/yaml",
+        }
+    }
+}
+
+/// A rectangle of the video to keep, in pixels
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Crop {
+    pub width: u32,
+    pub height: u32,
+    pub x: u32,
+    pub y: u32,
+}
+
+impl Crop {
+    /// The ffmpeg filter that cuts this rectangle out of each frame
+    pub fn filter(&self) -> String {
+        format!("crop={}:{}:{}:{}", self.width, self.height, self.x, self.y)
+    }
+}
+
+/// `WxH+X+Y`, as in X11 geometry strings
+impl FromStr for Crop {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("{:?} is not a geometry like 1920x1080+1920+0", s);
+        let (size, offset) = s.split_once('+').ok_or_else(invalid)?;
+        let (width, height) = size.split_once('x').ok_or_else(invalid)?;
+        let (x, y) = offset.split_once('+').ok_or_else(invalid)?;
+        let number = |part: &str| part.trim().parse::<u32>().map_err(|_| invalid());
+        Ok(Crop { width: number(width)?, height: number(height)?, x: number(x)?, y: number(y)? })
+    }
+}
+
+impl fmt::Display for Crop {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}x{}+{}+{}", self.width, self.height, self.x, self.y)
+    }
+}
+
+/// A length along one side of the video
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Length {
+    Pixels(u32),
+    /// A percentage of that side
+    Percent { percent: f64 },
+}
+
+impl Length {
+    /// The length as an ffmpeg expression, `side` being the variable of the side it is measured along
+    fn expression(self, side: &str) -> String {
+        match self {
+            Length::Pixels(pixels) => pixels.to_string(),
+            Length::Percent { percent } => format!("{}*{}/100", side, percent),
+        }
+    }
+}
+
+/// `N` pixels or `N%`
+impl FromStr for Length {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        match s.strip_suffix('%') {
+            Some(percent) => match percent.trim().parse::<f64>() {
+                Ok(percent) if (0.0..=100.0).contains(&percent) => Ok(Length::Percent { percent }),
+                _ => Err(format!("{:?} is not a percentage from 0% to 100%", s)),
+            },
+            None => s.parse().map(Length::Pixels).map_err(|_| format!("{:?} is not a number of pixels", s)),
+        }
+    }
+}
+
+impl fmt::Display for Length {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Length::Pixels(pixels) => write!(f, "{}", pixels),
+            Length::Percent { percent } => write!(f, "{}%", percent),
+        }
+    }
+}
+
+/// A rectangle of the video to keep, in pixels or percentages of its size
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct CropArea {
+    pub width: Length,
+    pub height: Length,
+    pub x: Length,
+    pub y: Length,
+}
+
+impl CropArea {
+    /// The ffmpeg filter that cuts this rectangle out of each frame
+    pub fn filter(&self) -> String {
+        format!(
+            "crop={}:{}:{}:{}",
+            self.width.expression("iw"),
+            self.height.expression("ih"),
+            self.x.expression("iw"),
+            self.y.expression("ih")
+        )
+    }
+}
+
+impl From<Crop> for CropArea {
+    fn from(crop: Crop) -> Self {
+        CropArea { width: Length::Pixels(crop.width), height: Length::Pixels(crop.height), x: Length::Pixels(crop.x), y: Length::Pixels(crop.y) }
+    }
+}
+
+/// `WxH+X+Y` or `X,Y,W,H`, each in pixels or as a percentage like `75%`
+impl FromStr for CropArea {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let [x, y, width, height] = s.split(',').collect::<Vec<_>>()[..] {
+            return Ok(CropArea { width: width.parse()?, height: height.parse()?, x: x.parse()?, y: y.parse()? });
+        }
+        let invalid = || format!("{:?} is not a geometry like 1920x1080+1920+0 or 0,0,75%,100%", s);
+        let (size, offset) = s.split_once('+').ok_or_else(invalid)?;
+        let (width, height) = size.split_once('x').ok_or_else(invalid)?;
+        let (x, y) = offset.split_once('+').ok_or_else(invalid)?;
+        Ok(CropArea { width: width.parse()?, height: height.parse()?, x: x.parse()?, y: y.parse()? })
+    }
+}
+
+impl fmt::Display for CropArea {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}x{}+{}+{}", self.width, self.height, self.x, self.y)
+    }
+}
+
+/// How many monitors a recording spans
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MonitorSplit {
+    /// Guess from the aspect ratio, taking each monitor to be 16:9
+    Auto,
+    /// This many monitors of equal width
+    Count(u32),
+}
+
+impl FromStr for MonitorSplit {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "auto" => Ok(MonitorSplit::Auto),
+            _ => match s.parse() {
+                Ok(count) if count > 0 => Ok(MonitorSplit::Count(count)),
+                _ => Err(format!("{:?} is neither \"auto\" nor a number of monitors", s)),
+            },
+        }
+    }
+}
+
+/// Shape of a picture as width to height, e.g. 16:9
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AspectRatio {
+    pub width: u32,
+    pub height: u32,
+}
+
+/// `W:H`
+impl FromStr for AspectRatio {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("{:?} is not an aspect ratio like 16:9", s);
+        let (width, height) = s.split_once(':').ok_or_else(invalid)?;
+        let number = |part: &str| part.trim().parse::<u32>().ok().filter(|&n| n > 0).ok_or_else(invalid);
+        Ok(AspectRatio { width: number(width)?, height: number(height)? })
+    }
+}
+
+impl fmt::Display for AspectRatio {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}", self.width, self.height)
+    }
+}
+
+/// An RGB colour, black by default
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Color(pub [u8; 3]);
+
+/// `#RRGGBB`, the `#` optional
+impl FromStr for Color {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("{:?} is not a colour like #1e1e1e", s);
+        let hex = s.strip_prefix('#').unwrap_or(s);
+        if hex.len() != 6 || !hex.is_ascii() {
+            return Err(invalid());
+        }
+        let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).map_err(|_| invalid());
+        Ok(Color([channel(0)?, channel(2)?, channel(4)?]))
+    }
+}
+
+impl fmt::Display for Color {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "#{:02x}{:02x}{:02x}", self.0[0], self.0[1], self.0[2])
+    }
+}
+
+/// How two frames are judged to show different slides
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Metric {
+    /// The share of differing pixels is above `threshold`
+    #[default]
+    Pixel,
+    /// Several measures agree, fewer false merges and false splits than any one of them
+    Ensemble(Ensemble),
+}
+
+/// Measures of an `ensemble` metric; those left unset take no part
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct Ensemble {
+    /// Share of differing pixels above which frames differ, in place of `threshold`
+    pub pixel: Option<f64>,
+    /// Bits of the 64-bit perceptual hashes above which frames differ
+    pub phash: Option<u32>,
+    /// Frames differ when any measure says so rather than all of them
+    pub any: bool,
+}
+
+/// `pixel`, or `ensemble:` with measures joined by `&` (all) or `|` (any),
+/// e.g. `ensemble:pixel>0.01&phash>6`
+impl FromStr for Metric {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s == "pixel" {
+            return Ok(Metric::Pixel);
+        }
+        let invalid = |why: &str| format!("{:?} is not \"pixel\" or an ensemble like ensemble:pixel>0.01&phash>6: {}", s, why);
+        let terms = s.strip_prefix("ensemble:").ok_or_else(|| invalid("unknown metric"))?;
+        let any = terms.contains('|');
+        if any && terms.contains('&') {
+            return Err(invalid("mixes & and |"));
+        }
+
+        let mut ensemble = Ensemble { any, ..Ensemble::default() };
+        for term in terms.split(if any { '|' } else { '&' }) {
+            let (measure, value) = term.split_once('>').ok_or_else(|| invalid("a measure is not compared with >"))?;
+            match measure.trim() {
+                "pixel" if ensemble.pixel.is_none() => {
+                    let value = value.trim().parse().ok().filter(|value| (0.0..=1.0).contains(value));
+                    ensemble.pixel = Some(value.ok_or_else(|| invalid("pixel takes a share from 0 to 1"))?);
+                }
+                "phash" if ensemble.phash.is_none() => {
+                    let value = value.trim().parse().ok().filter(|&value| value <= 64);
+                    ensemble.phash = Some(value.ok_or_else(|| invalid("phash takes a number of bits up to 64"))?);
+                }
+                "pixel" | "phash" => return Err(invalid("a measure is given twice")),
+                _ => return Err(invalid("measures are pixel and phash")),
+            }
+        }
+        Ok(Metric::Ensemble(ensemble))
+    }
+}
+
+impl fmt::Display for Metric {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let Metric::Ensemble(ensemble) = self else {
+            return write!(f, "pixel");
+        };
+        let terms: Vec<String> = [
+            ensemble.pixel.map(|pixel| format!("pixel>{}", pixel)),
+            ensemble.phash.map(|phash| format!("phash>{}", phash)),
+        ]
+        .into_iter()
+        .flatten()
+        .collect();
+        write!(f, "ensemble:{}", terms.join(if ensemble.any { "|" } else { "&" }))
+    }
+}
+
+/// Settings for one extraction run
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Config {
+    /// Video file to extract slides from (the screen capture)
+    pub input_file: PathBuf,
+    /// Directory to store extracted frames; the kept slides stay here
+    pub output_dir: PathBuf,
+    /// Where to deliver the slides and manifest once done: a directory or `s3://bucket/prefix`
+    pub output: Option<String>,
+    /// Also deliver a zip of the slides and manifest under this file name
+    pub archive: Option<String>,
+    /// Frames sampled per second of video
+    pub fps: u32,
+    /// Choose `fps`, and sparse sampling for slow videos, from how often a few probed stretches change
+    #[serde(default)]
+    pub auto_fps: bool,
+    /// Only sample the first this many seconds of the video, all of it when unset
+    #[serde(default)]
+    pub duration: Option<f64>,
+    /// Only look at this area of the video, which is all the slides show
+    #[serde(default)]
+    pub crop: Option<CropArea>,
+    /// The recording spans several monitors side by side; without `monitor` each becomes a deck of its own
+    #[serde(default)]
+    pub split_monitors: Option<MonitorSplit>,
+    /// With `split_monitors`, the monitor the slides are on, counted from 1 at the left
+    #[serde(default)]
+    pub monitor: Option<u32>,
+    /// Largest share of differing pixels for two frames to count as the same slide
+    pub threshold: f64,
+    /// How frames are told apart; an ensemble's pixel measure takes the place of `threshold`
+    #[serde(default)]
+    pub metric: Metric,
+    /// With hysteresis: once a frame starts a slide, the frames after it are the same change, never a
+    /// new slide, until one differs from the one before it by at most this share; `threshold` when unset
+    #[serde(default)]
+    pub low_threshold: Option<f64>,
+    /// Room camera recording of the same session
+    pub camera_file: Option<PathBuf>,
+    /// How to align the camera recording with the screen recording
+    pub sync: SyncMode,
+    /// Seconds the camera clock runs ahead of the screen clock, used with `SyncMode::Offset`
+    pub camera_offset: f64,
+    /// Largest offset in seconds considered when syncing by audio
+    pub max_sync_offset: f64,
+    /// Compare only every Nth pixel in each direction; 1 compares all of them
+    #[serde(default)]
+    pub compare_stride: u32,
+    /// Compare frames on the GPU when one is available (needs the `gpu` feature)
+    #[serde(default)]
+    pub gpu: bool,
+    /// Blur both frames of each comparison this way first, to suppress dithering and compression noise
+    #[serde(default)]
+    pub prefilter: Option<Prefilter>,
+    /// Width of the prefilter's kernel in pixels, made odd; 3 when unset
+    #[serde(default)]
+    pub prefilter_size: Option<u32>,
+    /// Count differing pixels in areas that look like text this many times, so small edits to text
+    /// are not lost on a mostly empty slide; not used with `ignore_embedded_video`
+    #[serde(default)]
+    pub text_weight: Option<f64>,
+    /// Match each frame's brightness to the frame it is compared against first, so flicker and a camera's
+    /// auto-exposure drift don't count as changes
+    #[serde(default)]
+    pub normalize_brightness: bool,
+    /// Areas left out of the comparison, like where a video call app shows chat popups and recording banners
+    #[serde(default)]
+    pub masks: Vec<Mask>,
+    /// Leave regions that change in every frame (embedded videos) out of the comparison
+    pub ignore_embedded_video: bool,
+    /// Consecutive frames a region must keep changing in before it is ignored
+    pub motion_streak: u32,
+    /// Leave clocks, dates and counters along the top and bottom edges out of the comparison
+    /// once they are seen changing on their own
+    #[serde(default)]
+    pub ignore_live_footer: bool,
+    /// The video films a whiteboard or blackboard: compare the board without the lecturer in front of it
+    /// and keep it as it was right before each time it is erased; `run_stream` rejects it
+    #[serde(default)]
+    pub whiteboard: bool,
+    /// The video is handwritten on a tablet: snapshot the page once no ink was added for this many seconds
+    /// and right before writing disappears from it, instead of looking for slide changes; `run_stream`
+    /// rejects it
+    #[serde(default)]
+    pub ink_pause: Option<f64>,
+    /// A frame that scrolls the previous one is the same slide, whose image is extended with the rows
+    /// scrolled into view
+    #[serde(default)]
+    pub stitch_scrolls: bool,
+    /// A frame zoomed into part of the slide, or panning around it zoomed in, is the same slide
+    #[serde(default)]
+    pub ignore_zoom: bool,
+    /// With `ignore_zoom`, keep the closest view of each zoom in a `zooms` directory next to the slides
+    #[serde(default)]
+    pub save_zooms: bool,
+    /// Save each slide next to the one before it with what changed highlighted, in `diffs/`
+    #[serde(default)]
+    pub save_diffs: bool,
+    /// Consecutive similar frames needed after a change before the slide is committed, the last of them
+    /// being kept; 1 commits the changed frame right away
+    #[serde(default)]
+    pub settle_frames: u32,
+    /// File names for the kept slides, e.g. `{video}_{index:03}_{timestamp}`; the frame's name when unset
+    #[serde(default)]
+    pub name_template: Option<String>,
+    /// Delete the kept slides whose confidence score is below this, 0 to 1
+    #[serde(default)]
+    pub min_confidence: Option<f64>,
+    /// Keep a slide the presenter returns to once, with every interval it was shown
+    #[serde(default)]
+    pub merge_revisits: bool,
+    /// Largest difference to an earlier slide that still counts as showing it again; `threshold` when unset
+    #[serde(default)]
+    pub revisit_threshold: Option<f64>,
+    /// Decode the QR codes on the kept slides, and with `ocr` the web addresses in their text, into
+    /// the manifest and `links.md`
+    #[serde(default)]
+    pub find_links: bool,
+    /// Read the text off the kept slides with Tesseract into the manifest
+    #[serde(default)]
+    pub ocr: bool,
+    /// Slides read at once with `ocr`; one per CPU when unset
+    #[serde(default)]
+    pub ocr_threads: Option<usize>,
+    /// What reads the text with `ocr`
+    #[serde(default)]
+    pub ocr_backend: OcrBackend,
+    /// Read slides not in English again with Tesseract's model for the language detected in them
+    #[serde(default)]
+    pub ocr_by_language: bool,
+    /// Base URL of an OpenAI-compatible API (ending in `/v1`) asked for a title and summary of
+    /// each slide from its `ocr` text
+    #[serde(default)]
+    pub summarize: Option<String>,
+    /// Model asked with `summarize`; `gpt-4o-mini` when unset
+    #[serde(default)]
+    pub summarize_model: Option<String>,
+    /// Also send `summarize` a small copy of each slide
+    #[serde(default)]
+    pub summarize_images: bool,
+    /// Tag each slide with who spoke longest while it was up, as this diarization says
+    #[serde(default)]
+    pub diarize: Option<Diarization>,
+    /// Give each deck of a recording with several its own directory and manifest
+    #[serde(default)]
+    pub split_decks: bool,
+    /// With `split_decks`, seconds of constant change (no slide on screen) after which a new deck begins
+    #[serde(default)]
+    pub deck_gap: Option<f64>,
+    /// With `split_decks`, also begin a new deck after applause or a long silence in the audio
+    #[serde(default)]
+    pub deck_audio: bool,
+    /// Seconds a screen opening or closing the recording must stay up unchanged to be left out as idle
+    #[serde(default)]
+    pub trim_idle: Option<f64>,
+    /// Phrases whose short OCR text marks a screen as idle anywhere in the recording, with
+    /// `trim_idle` and `ocr`; a built-in list of the usual ones like "be right back" when unset
+    #[serde(default)]
+    pub idle_keywords: Option<Vec<String>>,
+    /// Move each slide's timestamp to the nearest pause in the audio up to this many seconds away
+    #[serde(default)]
+    pub snap_to_pauses: Option<f64>,
+    /// Write a file in this format next to each kept slide with its manifest entry
+    #[serde(default)]
+    pub sidecars: Option<SidecarFormat>,
+    /// Write `report.html`, showing the decision on every sampled frame as a thumbnail
+    #[serde(default)]
+    pub report: bool,
+    /// Exports written into the output directory as slides are kept, not only once the run is done
+    #[serde(default)]
+    pub live: Vec<LiveFormat>,
+    /// Pad every kept slide onto a canvas of this shape, so a deck from mixed sources keeps one aspect ratio
+    #[serde(default)]
+    pub canvas: Option<AspectRatio>,
+    /// Colour of the padding added for `canvas`
+    #[serde(default)]
+    pub canvas_background: Color,
+    /// What to do with frames that cannot be decoded
+    #[serde(default)]
+    pub on_bad_frame: BadFramePolicy,
+    /// Most slides a run may keep
+    #[serde(default)]
+    pub max_slides: Option<usize>,
+    /// Most bytes the kept slides may take up
+    #[serde(default)]
+    pub max_output_size: Option<u64>,
+    /// What to do when the slides approach those limits
+    #[serde(default)]
+    pub on_limit: LimitPolicy,
+    /// Seconds ffmpeg may take to sample the whole video before it is killed
+    #[serde(default)]
+    pub ffmpeg_timeout: Option<u64>,
+    /// Seconds ffmpeg may go without producing a frame before it is considered stuck and killed
+    #[serde(default)]
+    pub stall_timeout: Option<u64>,
+    /// Run ffmpeg once more with `-err_detect ignore_err` if it fails or gets stuck
+    #[serde(default)]
+    pub retry_ignore_errors: bool,
+    /// Times ffmpeg is run again when it fails or gets stuck, carrying on after the last frame it wrote
+    #[serde(default)]
+    pub retries: u32,
+    /// Seconds before the first of those retries, doubling with each; 2 when unset
+    #[serde(default)]
+    pub retry_backoff: Option<f64>,
+    /// Sample one frame every this many seconds first and at `fps` only between those that differ;
+    /// `run_stream` rejects it
+    #[serde(default)]
+    pub adaptive: Option<f64>,
+    /// With `adaptive`, largest difference between two sparse samples that leaves the stretch
+    /// between them unsampled; `threshold` when unset
+    #[serde(default)]
+    pub coarse_threshold: Option<f64>,
+    /// Process the input even if the output directory already holds slides from the same input and settings
+    #[serde(default)]
+    pub force: bool,
+    /// Threads ffmpeg may use for decoding and filtering, all cores when unset
+    pub ffmpeg_threads: Option<u32>,
+    /// zlib level, 0 (fastest, largest) to 9 (smallest), ffmpeg compresses the sampled frames with,
+    /// the kept slides among them; ffmpeg's default when unset
+    #[serde(default)]
+    pub png_compression: Option<u32>,
+    /// Filter ffmpeg runs the sampled frames through before compressing them; ffmpeg's default when unset
+    #[serde(default)]
+    pub png_prediction: Option<PngPrediction>,
+    /// Stretches of the input sampled at once by as many ffmpeg processes; one when 0 or 1.
+    /// `run_stream` rejects more than one
+    #[serde(default)]
+    pub segments: u32,
+    /// Bytes the run should stay within; ffmpeg gets fewer threads to fit
+    #[serde(default)]
+    pub max_memory: Option<u64>,
+    /// Keep the sampled frames here and reuse them on later runs of the same video and sampling settings,
+    /// instead of `adaptive` and `in_memory`; `run_stream` rejects it
+    #[serde(default)]
+    pub frame_cache: Option<PathBuf>,
+    /// Where the sampled frames are kept while they are compared, the system's temp directory when unset
+    #[serde(default)]
+    pub tmp_dir: Option<PathBuf>,
+    /// Whether the run's working directory under `tmp_dir` is left behind when it ends
+    #[serde(default)]
+    pub workspace: WorkspacePolicy,
+    /// Bytes the working directories left under `tmp_dir` may take together; the oldest are
+    /// removed to make room when a run leaves its own behind
+    #[serde(default)]
+    pub workspace_cap: Option<u64>,
+    /// Extract even when the sampled frames look like they won't fit on the disk
+    #[serde(default)]
+    pub skip_space_check: bool,
+    /// Leave the kept slides without the source, timestamp and index otherwise written into them
+    #[serde(default)]
+    pub skip_metadata: bool,
+    /// Have ffmpeg hand the sampled frames over in memory instead of writing them to `tmp_dir`;
+    /// `run_stream` rejects it
+    #[serde(default)]
+    pub in_memory: bool,
+    /// Also send the run's messages and ffmpeg's output here
+    pub log_file: Option<PathBuf>,
+}
+
+impl Config {
+    /// Default settings for extracting slides from `input_file`
+    pub fn new(input_file: impl Into<PathBuf>) -> Self {
+        Config {
+            input_file: input_file.into(),
+            output_dir: PathBuf::from("frames"),
+            output: None,
+            archive: None,
+            fps: 1,
+            auto_fps: false,
+            duration: None,
+            crop: None,
+            split_monitors: None,
+            monitor: None,
+            threshold: 0.01,
+            metric: Metric::Pixel,
+            low_threshold: None,
+            camera_file: None,
+            sync: SyncMode::Offset,
+            camera_offset: 0.0,
+            max_sync_offset: 120.0,
+            compare_stride: 1,
+            gpu: false,
+            prefilter: None,
+            prefilter_size: None,
+            text_weight: None,
+            normalize_brightness: false,
+            masks: Vec::new(),
+            ignore_embedded_video: false,
+            ignore_live_footer: false,
+            motion_streak: 3,
+            whiteboard: false,
+            ink_pause: None,
+            stitch_scrolls: false,
+            ignore_zoom: false,
+            save_zooms: false,
+            save_diffs: false,
+            settle_frames: 1,
+            name_template: None,
+            min_confidence: None,
+            merge_revisits: false,
+            revisit_threshold: None,
+            find_links: false,
+            ocr: false,
+            ocr_threads: None,
+            ocr_backend: OcrBackend::Tesseract,
+            ocr_by_language: false,
+            summarize: None,
+            summarize_model: None,
+            summarize_images: false,
+            diarize: None,
+            split_decks: false,
+            deck_gap: None,
+            deck_audio: false,
+            trim_idle: None,
+            idle_keywords: None,
+            snap_to_pauses: None,
+            sidecars: None,
+            report: false,
+            live: Vec::new(),
+            canvas: None,
+            canvas_background: Color::default(),
+            on_bad_frame: BadFramePolicy::Stop,
+            max_slides: None,
+            max_output_size: None,
+            on_limit: LimitPolicy::Stop,
+            ffmpeg_timeout: None,
+            stall_timeout: None,
+            retry_ignore_errors: false,
+            retries: 0,
+            retry_backoff: None,
+            adaptive: None,
+            coarse_threshold: None,
+            force: false,
+            ffmpeg_threads: None,
+            png_compression: None,
+            png_prediction: None,
+            segments: 0,
+            max_memory: None,
+            frame_cache: None,
+            tmp_dir: None,
+            workspace: WorkspacePolicy::Delete,
+            workspace_cap: None,
+            skip_space_check: false,
+            skip_metadata: false,
+            in_memory: false,
+            log_file: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn crop_area_parses_geometry_and_list() {
+        let geometry: CropArea = "1920x1080+1920+0".parse().unwrap();
+        assert_eq!(geometry, CropArea { width: Length::Pixels(1920), height: Length::Pixels(1080), x: Length::Pixels(1920), y: Length::Pixels(0) });
+        let list: CropArea = "0,0,75%,100%".parse().unwrap();
+        assert_eq!(list.width, Length::Percent { percent: 75.0 });
+        assert_eq!(list.height, Length::Percent { percent: 100.0 });
+        assert_eq!(list.x, Length::Pixels(0));
+        assert_eq!(geometry.to_string(), "1920x1080+1920+0");
+    }
+
+    #[test]
+    fn crop_area_rejects_malformed() {
+        assert!("1920x1080".parse::<CropArea>().is_err());
+        assert!("1920+1080+0+0".parse::<CropArea>().is_err());
+        assert!("0,0,150%,100%".parse::<CropArea>().is_err());
+        assert!("0,0,100".parse::<CropArea>().is_err());
+    }
+
+    #[test]
+    fn metric_parses_ensembles() {
+        assert_eq!("pixel".parse::<Metric>(), Ok(Metric::Pixel));
+        let all = "ensemble:pixel>0.01&phash>6".parse::<Metric>().unwrap();
+        assert_eq!(all, Metric::Ensemble(Ensemble { pixel: Some(0.01), phash: Some(6), any: false }));
+        let any = "ensemble:phash>10|pixel>0.5".parse::<Metric>().unwrap();
+        assert_eq!(any, Metric::Ensemble(Ensemble { pixel: Some(0.5), phash: Some(10), any: true }));
+        assert_eq!(all.to_string(), "ensemble:pixel>0.01&phash>6");
+        assert_eq!(any.to_string().parse::<Metric>(), Ok(any));
+    }
+
+    #[test]
+    fn metric_rejects_malformed_ensembles() {
+        for metric in [
+            "ssim",
+            "ensemble:pixel>0.01&phash>6|pixel>0.1",
+            "ensemble:pixel<0.01",
+            "ensemble:pixel>2",
+            "ensemble:phash>65",
+            "ensemble:pixel>0.1&pixel>0.2",
+            "ensemble:ssim>0.9",
+        ] {
+            assert!(metric.parse::<Metric>().is_err(), "{} parsed", metric);
+        }
+    }
+}
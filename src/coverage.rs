@@ -0,0 +1,233 @@
+//! `coverage`: compare the slides of a finished run with the speaker's own
+//! deck, to see which pages were never shown, which were shown out of order
+//! and which slides are not in the deck (a demo, a live poll).
+//!
+//! The deck is a PDF, rendered with `pdftoppm` from poppler; a PowerPoint,
+//! OpenDocument or Keynote file, turned into a PDF by LibreOffice (`soffice`)
+//! first; or a directory of page images named in page order. Slides and pages
+//! are matched by a perceptual hash of their layout, which survives the
+//! scaling and compression of a recording.
+//!
+//! With `--page-map` the times each page was on screen are written as JSON,
+//! for players that jump the video to a page picked in the PDF.
+
+use serde::Serialize;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+pub use crate::compare::perceptual_hash;
+use crate::error::Error;
+use crate::lock::try_lock_dir;
+use crate::manifest::{Interval, Manifest};
+use crate::pipeline::sort_frames;
+
+/// Resolution pages are rendered at; the hash only needs a rough image
+const RENDER_DPI: u32 = 50;
+
+/// Which deck page a kept slide shows
+#[derive(Debug, Clone, PartialEq)]
+pub struct SlideMatch {
+    /// Index of the slide in the run's manifest
+    pub slide: usize,
+    /// Seconds from the start of the screen recording
+    pub timestamp: f64,
+    /// Deck page counted from 1, `None` if the slide is not in the deck
+    pub page: Option<usize>,
+    /// Bits the hashes of the slide and the closest page differ in, of 64
+    pub distance: u32,
+    /// Every time the slide was on screen
+    pub shown: Vec<Interval>,
+}
+
+/// When one deck page was on screen, an entry of the page map
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PageTimes {
+    /// Counted from 1
+    pub page: usize,
+    /// In seconds from the start of the screen recording, in order
+    pub shown: Vec<Interval>,
+}
+
+/// How the slides of a run cover a deck
+#[derive(Debug, Clone, PartialEq)]
+pub struct Coverage {
+    pub pages: usize,
+    pub slides: Vec<SlideMatch>,
+    /// Pages no slide shows
+    pub unshown: Vec<usize>,
+    /// Pages brought up after a later page, in the order they were shown
+    pub out_of_order: Vec<usize>,
+}
+
+impl Coverage {
+    /// Slides that match no page of the deck
+    pub fn not_in_deck(&self) -> impl Iterator<Item = &SlideMatch> {
+        self.slides.iter().filter(|slide| slide.page.is_none())
+    }
+
+    /// Share of the deck's pages that were shown, 1 for an empty deck
+    pub fn ratio(&self) -> f64 {
+        if self.pages == 0 {
+            return 1.0;
+        }
+        (self.pages - self.unshown.len()) as f64 / self.pages as f64
+    }
+
+    /// The times each shown page was on screen, by page
+    pub fn page_map(&self) -> Vec<PageTimes> {
+        let mut map: Vec<PageTimes> = Vec::new();
+        for page in 1..=self.pages {
+            let mut shown: Vec<Interval> =
+                self.slides.iter().filter(|slide| slide.page == Some(page)).flat_map(|slide| slide.shown.iter().copied()).collect();
+            if shown.is_empty() {
+                continue;
+            }
+            shown.sort_by(|a, b| a.start.total_cmp(&b.start));
+            map.push(PageTimes { page, shown });
+        }
+        map
+    }
+}
+
+/// Write the page map of `coverage` to `path` as JSON: `{"pages": [{"page": 14, "shown": [{"start": 1651.0, "end": 1862.0}]}]}`
+pub fn write_page_map(path: &Path, coverage: &Coverage) -> Result<(), io::Error> {
+    #[derive(Serialize)]
+    struct PageMap {
+        pages: Vec<PageTimes>,
+    }
+    let json = serde_json::to_string_pretty(&PageMap { pages: coverage.page_map() }).map_err(io::Error::other)?;
+    fs::write(path, json)
+}
+
+/// Run a helper program, telling it apart from a failure if it is not installed
+fn run_tool(mut command: Command, name: &str, input: &Path) -> Result<(), io::Error> {
+    let output = command.stdout(Stdio::null()).stderr(Stdio::piped()).output().map_err(|e| match e.kind() {
+        io::ErrorKind::NotFound => io::Error::new(io::ErrorKind::NotFound, format!("{} was not found", name)),
+        _ => e,
+    })?;
+    if !output.status.success() {
+        return Err(io::Error::other(format!(
+            "{} failed on {} ({}): {}",
+            name,
+            input.display(),
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(())
+}
+
+/// Image files in `dir`, in page order
+fn page_images(dir: &Path) -> Result<Vec<PathBuf>, io::Error> {
+    let mut pages: Vec<PathBuf> = fs::read_dir(dir)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<Vec<_>, _>>()?
+        .into_iter()
+        .filter(|path| {
+            let extension = path.extension().and_then(|s| s.to_str()).map(str::to_ascii_lowercase);
+            matches!(extension.as_deref(), Some("png" | "jpg" | "jpeg"))
+        })
+        .collect();
+    sort_frames(&mut pages);
+    Ok(pages)
+}
+
+/// Render the pages of `deck` as images into `work_dir`, in order
+pub fn render_deck(deck: &Path, work_dir: &Path) -> Result<Vec<PathBuf>, io::Error> {
+    if deck.is_dir() {
+        return page_images(deck);
+    }
+
+    let extension = deck.extension().and_then(|s| s.to_str()).map(str::to_ascii_lowercase);
+    let pdf = match extension.as_deref() {
+        Some("pdf") => deck.to_path_buf(),
+        Some("pptx" | "ppt" | "odp" | "key") => {
+            let mut command = Command::new("soffice");
+            command.arg("--headless").arg("--convert-to").arg("pdf").arg("--outdir").arg(work_dir).arg(deck);
+            run_tool(command, "soffice", deck)?;
+            work_dir.join(deck.file_stem().unwrap_or_default()).with_extension("pdf")
+        }
+        _ => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{} is not a PDF, PowerPoint or OpenDocument deck, or a directory of page images", deck.display()),
+            ))
+        }
+    };
+
+    let pages_dir = work_dir.join("pages");
+    fs::create_dir_all(&pages_dir)?;
+    let mut command = Command::new("pdftoppm");
+    command.arg("-png").arg("-r").arg(RENDER_DPI.to_string()).arg(&pdf).arg(pages_dir.join("page"));
+    run_tool(command, "pdftoppm", &pdf)?;
+    page_images(&pages_dir)
+}
+
+/// Match the page hashes to the slides of `manifest`, counting pages within
+/// `max_distance` bits of a slide as shown by it
+pub fn match_pages(manifest: &Manifest, slide_hashes: &[u64], page_hashes: &[u64], max_distance: u32) -> Coverage {
+    let slides: Vec<SlideMatch> = manifest
+        .slides
+        .iter()
+        .zip(slide_hashes)
+        .map(|(slide, &hash)| {
+            let closest = page_hashes
+                .iter()
+                .enumerate()
+                .map(|(page, &page_hash)| (page + 1, (hash ^ page_hash).count_ones()))
+                .min_by_key(|&(_, distance)| distance);
+            SlideMatch {
+                slide: slide.index,
+                timestamp: slide.timestamp,
+                page: closest.filter(|&(_, distance)| distance <= max_distance).map(|(page, _)| page),
+                distance: closest.map_or(64, |(_, distance)| distance),
+                shown: match slide.shown.as_slice() {
+                    [] => vec![Interval { start: slide.timestamp, end: slide.timestamp }],
+                    shown => shown.to_vec(),
+                },
+            }
+        })
+        .collect();
+
+    let unshown = (1..=page_hashes.len()).filter(|&page| !slides.iter().any(|slide| slide.page == Some(page))).collect();
+
+    // Every time a page was brought up, including returns to it with --merge-revisits
+    let mut shown: Vec<(f64, usize)> = slides
+        .iter()
+        .filter_map(|slide| Some((slide, slide.page?)))
+        .flat_map(|(slide, page)| slide.shown.iter().map(move |interval| (interval.start, page)))
+        .collect();
+    shown.sort_by(|a, b| a.0.total_cmp(&b.0));
+    let mut out_of_order = Vec::new();
+    for pair in shown.windows(2) {
+        if pair[1].1 < pair[0].1 {
+            out_of_order.push(pair[1].1);
+        }
+    }
+
+    Coverage { pages: page_hashes.len(), slides, unshown, out_of_order }
+}
+
+/// Compare the slides in `slides_dir`, the output directory of a run, with the pages of `deck`
+pub fn coverage(slides_dir: &Path, deck: &Path, max_distance: u32) -> Result<Coverage, Error> {
+    let manifest = Manifest::read(slides_dir)?;
+    let open = |path: &Path| image::open(path).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", path.display(), e)));
+
+    let slide_hashes = manifest
+        .slides
+        .iter()
+        .map(|slide| open(&slides_dir.join(&slide.file)).map(|image| perceptual_hash(&image)))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let work_dir = tempfile::Builder::new().prefix("videoslides-deck-").tempdir()?;
+    // Keeps `gc` off it, as off a run's workspace
+    let _lock = try_lock_dir(work_dir.path())?;
+    let page_hashes = render_deck(deck, work_dir.path())?
+        .iter()
+        .map(|path| open(path).map(|image| perceptual_hash(&image)))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(match_pages(&manifest, &slide_hashes, &page_hashes, max_distance))
+}
@@ -0,0 +1,200 @@
+//! A long-running process controlled over a Unix socket, for scripts that
+//! manage many extractions without starting the extractor for each one.
+//!
+//! Each line sent to the socket is a JSON request and is answered with one
+//! line of JSON, a job status or `{"error": "..."}`:
+//!
+//! - `{"command": "submit", "input": "talk.mp4"}` queues a job. `output_dir`,
+//!   `fps`, `threshold` and `low_threshold` may be given as well.
+//! - `{"command": "status"}` lists every job, `{"command": "status", "id": 3}`
+//!   reports one.
+//! - `{"command": "cancel", "id": 3}` cancels a queued or running job.
+//!
+//! Jobs share the queue, workers and job directories of `serve`, so they
+//! survive a restart of the daemon the same way. Windows named pipes are not
+//! supported.
+
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::{BufRead, BufReader, Error, Write};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use crate::batch::JOB_LOG_FILE;
+use crate::config::Config;
+use crate::queue::{JobQueue, QueuedJob};
+use crate::server::{check_settings, open_jobs, run_job, Job, JobMap, JobStatus};
+
+/// How the daemon is set up
+#[derive(Debug, Clone)]
+pub struct DaemonOptions {
+    /// Path of the control socket
+    pub socket: PathBuf,
+    /// Directory holding job outputs and the job queue
+    pub data_dir: PathBuf,
+    /// Jobs processed at the same time
+    pub workers: usize,
+    /// Threads each job's ffmpeg may use, all cores when unset
+    pub ffmpeg_threads: Option<u32>,
+    /// Bytes each job should stay within, unlimited when unset
+    pub max_memory: Option<u64>,
+}
+
+/// A line sent to the control socket
+#[derive(Debug, Deserialize)]
+#[serde(tag = "command", rename_all = "lowercase")]
+enum ControlRequest {
+    Submit {
+        input: PathBuf,
+        output_dir: Option<PathBuf>,
+        fps: Option<u32>,
+        threshold: Option<f64>,
+        low_threshold: Option<f64>,
+    },
+    Status {
+        id: Option<u64>,
+    },
+    Cancel {
+        id: u64,
+    },
+}
+
+struct State {
+    options: DaemonOptions,
+    next_id: Mutex<u64>,
+    jobs: JobMap,
+    queue: JobQueue,
+}
+
+/// Listen on the control socket and run jobs until the process is stopped
+pub fn run_daemon(options: &DaemonOptions) -> Result<(), Error> {
+    fs::create_dir_all(&options.data_dir)?;
+    let (mut queue, jobs, last_id) = open_jobs(&options.data_dir)?;
+
+    let worker_jobs = Arc::clone(&jobs);
+    queue.start(options.workers, move |queued| {
+        run_job(&worker_jobs, queued);
+    });
+
+    // The queue lock is held, so a socket left behind is from a daemon that is gone
+    if options.socket.exists() {
+        fs::remove_file(&options.socket)?;
+    }
+    let listener = UnixListener::bind(&options.socket)?;
+    tracing::info!("Listening on {} with {} worker(s)", options.socket.display(), options.workers.max(1));
+
+    let state = Arc::new(State { options: options.clone(), next_id: Mutex::new(last_id + 1), jobs, queue });
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(e) => {
+                tracing::warn!("Failed to accept a control connection: {}", e);
+                continue;
+            }
+        };
+        let state = Arc::clone(&state);
+        thread::spawn(move || {
+            if let Err(e) = handle(&state, stream) {
+                tracing::warn!("Control connection failed: {}", e);
+            }
+        });
+    }
+    Ok(())
+}
+
+/// Answer each request on `stream` until the client hangs up
+fn handle(state: &State, stream: UnixStream) -> Result<(), Error> {
+    let mut writer = stream.try_clone()?;
+    for line in BufReader::new(stream).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let response = match serde_json::from_str::<ControlRequest>(&line) {
+            Ok(request) => respond(state, request),
+            Err(e) => error(&format!("Invalid request: {}", e)),
+        };
+        writeln!(writer, "{}", response)?;
+    }
+    Ok(())
+}
+
+fn respond(state: &State, request: ControlRequest) -> String {
+    match request {
+        ControlRequest::Submit { input, output_dir, fps, threshold, low_threshold } => {
+            let mut config = Config::new(input);
+            if let Some(fps) = fps {
+                config.fps = fps;
+            }
+            if let Some(threshold) = threshold {
+                config.threshold = threshold;
+            }
+            if low_threshold.is_some() {
+                config.low_threshold = low_threshold;
+            }
+            if let Err(message) = check_settings(&config) {
+                return error(&message);
+            }
+            match submit(state, config, output_dir) {
+                Ok(status) => json(&status),
+                Err(e) => error(&format!("Could not queue job: {}", e)),
+            }
+        }
+        ControlRequest::Status { id: None } => {
+            let mut statuses: Vec<JobStatus> =
+                state.jobs.lock().unwrap().values().map(|job| job.lock().unwrap().status.clone()).collect();
+            statuses.sort_by_key(|status| status.id);
+            json(&statuses)
+        }
+        ControlRequest::Status { id: Some(id) } => with_job(state, id, |job| json(&job.status)),
+        ControlRequest::Cancel { id } => with_job(state, id, |job| {
+            job.cancel();
+            json(&job.status)
+        }),
+    }
+}
+
+/// Queue a job in a directory of its own, writing the slides to `output_dir` if given
+fn submit(state: &State, mut config: Config, output_dir: Option<PathBuf>) -> Result<JobStatus, Error> {
+    let id = {
+        let mut next_id = state.next_id.lock().unwrap();
+        let id = *next_id;
+        *next_id += 1;
+        id
+    };
+
+    let job_dir = state.options.data_dir.join(format!("job-{}", id));
+    fs::create_dir_all(&job_dir)?;
+    config.output_dir = output_dir.unwrap_or_else(|| job_dir.join("slides"));
+    config.ffmpeg_threads = state.options.ffmpeg_threads;
+    config.max_memory = state.options.max_memory;
+    config.log_file = Some(job_dir.join(JOB_LOG_FILE));
+
+    let job = Arc::new(Mutex::new(Job::queued(id, &config)));
+    let status = job.lock().unwrap().status.clone();
+    state.jobs.lock().unwrap().insert(id, job);
+
+    if let Err(e) = state.queue.submit(QueuedJob { id, config }) {
+        state.jobs.lock().unwrap().remove(&id);
+        return Err(e);
+    }
+    tracing::info!("Queued job {}", id);
+    Ok(status)
+}
+
+fn with_job(state: &State, id: u64, f: impl FnOnce(&Job) -> String) -> String {
+    match state.jobs.lock().unwrap().get(&id).cloned() {
+        Some(job) => f(&job.lock().unwrap()),
+        None => error("No such job"),
+    }
+}
+
+fn json<T: Serialize>(body: &T) -> String {
+    serde_json::to_string(body).unwrap_or_else(|e| error(&e.to_string()))
+}
+
+fn error(message: &str) -> String {
+    serde_json::json!({ "error": message }).to_string()
+}
@@ -0,0 +1,448 @@
+use image::imageops::FilterType;
+use image::{DynamicImage, GenericImageView};
+use std::path::Path;
+
+use crate::compare::{match_histogram, perceptual_hash, prefilter, prefilter_kernel, Comparer};
+use crate::config::{Config, Ensemble, Mask, Metric, Prefilter};
+use crate::footer::FooterTracker;
+use crate::motion::MotionTracker;
+use crate::overlay;
+use crate::runlog::RunLog;
+use crate::scroll::{find_scroll, Scroll};
+use crate::zoom::find_zoom;
+use crate::text::TextWeighting;
+
+/// What became of a frame once it was compared with the one before it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verdict {
+    /// Nothing to compare against yet, so it is kept
+    First,
+    /// Differs from the previous frame and is kept
+    Unique,
+    /// Looks like the previous frame and is dropped
+    Similar,
+}
+
+impl Verdict {
+    pub fn is_kept(self) -> bool {
+        self != Verdict::Similar
+    }
+}
+
+/// A verdict together with the comparison it was based on
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Decision {
+    pub verdict: Verdict,
+    /// Share of pixels that differ from the previous frame, `None` for the first frame
+    pub difference: Option<f64>,
+    /// Largest difference that still counted as the same slide
+    pub threshold: f64,
+    /// Width and height of the frame if they differ from the previous frame's
+    pub resized: Option<(u32, u32)>,
+    /// How the frame scrolled the previous one, which makes it the same slide
+    pub scroll: Option<Scroll>,
+    /// How far the frame is zoomed into the slide, which makes it the same slide
+    pub zoom: Option<f64>,
+    /// The frame still differs by more than the low threshold from the one before it, so the
+    /// change that started the slide is not over
+    pub changing: bool,
+}
+
+impl Decision {
+    pub fn is_kept(self) -> bool {
+        self.verdict.is_kept()
+    }
+
+    /// Report the decision the way the command line tool always has, plus the numbers behind it
+    pub fn log(self, log: &RunLog, frame: &Path) {
+        if let Some((width, height)) = self.resized {
+            log.info(format_args!("Frame {:?} changes the resolution to {}x{}.", frame, width, height));
+        }
+        let difference = self.difference.unwrap_or(0.0);
+        if let Some(scroll) = self.scroll {
+            log.debug(format_args!("Frame {:?} scrolls the previous one by {} row(s), it is the same slide.", frame, scroll.rows));
+            return;
+        }
+        if let Some(zoom) = self.zoom {
+            log.debug(format_args!("Frame {:?} shows the slide zoomed in {:.1}x, it is the same slide.", frame, zoom));
+            return;
+        }
+        match self.verdict {
+            Verdict::First => log.debug(format_args!("First frame {:?} is considered unique.", frame)),
+            Verdict::Unique => log.debug(format_args!(
+                "Frame {:?} is unique (difference {:.4} > threshold {}).",
+                frame, difference, self.threshold
+            )),
+            Verdict::Similar if self.changing => log.debug(format_args!(
+                "Frame {:?} is still changing (difference {:.4} > low threshold {}), deleting it.",
+                frame, difference, self.threshold
+            )),
+            Verdict::Similar => log.debug(format_args!(
+                "Frame {:?} is similar to the previous one (difference {:.4} <= threshold {}), deleting it.",
+                frame, difference, self.threshold
+            )),
+        }
+    }
+}
+
+/// Compares each frame with the previous one to decide whether it starts a new slide.
+///
+/// With a low threshold below the regular one the decision has hysteresis: a
+/// frame has to differ by more than `threshold` to start a new slide, and the
+/// frames after it are part of the same change, never kept, until one differs
+/// by at most `low_threshold` from the one before it. Frames hovering right at
+/// a single threshold would flip between keep and delete instead, and every
+/// frame of a transition or animation would become a slide.
+///
+/// Frames are compared at the size of the first one, so a recording whose
+/// resolution changes midway (the screen was shared again) is still compared
+/// like for like.
+pub struct Deduplicator {
+    threshold: f64,
+    /// The threshold the run started with, before `set_threshold`
+    initial_threshold: f64,
+    low_threshold: f64,
+    /// A slide was started and the frames have not stopped changing since, so the low
+    /// threshold applies to the next one
+    changing: bool,
+    comparer: Comparer,
+    motion: Option<MotionTracker>,
+    /// Without motion tracking, changes to text count more than changes to the background
+    text: Option<TextWeighting>,
+    /// Blur every frame goes through before it is compared, with its kernel width
+    prefilter: Option<(Prefilter, u32)>,
+    /// Size every frame is scaled to before it is compared, that of the first frame
+    working_size: Option<(u32, u32)>,
+    last_image: Option<DynamicImage>,
+    /// `last_image` scaled to the working size and prefiltered, if either applies
+    last_prepared: Option<DynamicImage>,
+    /// Measures combined with the share of differing pixels, with `--metric ensemble:...`
+    ensemble: Option<Ensemble>,
+    /// Perceptual hash of the last frame, when the ensemble uses it
+    last_hash: Option<u64>,
+    /// Each frame's brightness is matched to the one it is compared against first
+    normalize_brightness: bool,
+    /// Areas left out of the comparison
+    masks: Vec<Mask>,
+    /// Finds clocks and counters along the edges to leave out as well
+    footer: Option<FooterTracker>,
+    /// Frames that scroll the previous one count as the same slide
+    stitch_scrolls: bool,
+    /// Close-ups of the slide count as the same slide
+    ignore_zoom: bool,
+    /// The frame the slide appeared at, prepared, when looking for close-ups of it
+    slide: Option<DynamicImage>,
+    /// How far the last frame was zoomed into the slide
+    zoom: Option<f64>,
+}
+
+impl Deduplicator {
+    pub fn new(config: &Config, log: &RunLog) -> Self {
+        // With `ink_pause` the frames only change where the page was snapshotted, and every change counts
+        let ensemble = match config.metric {
+            Metric::Ensemble(ensemble) if config.ink_pause.is_none() => Some(ensemble),
+            _ => None,
+        };
+        let threshold = match config.ink_pause {
+            Some(_) => 0.0,
+            None => ensemble.and_then(|ensemble| ensemble.pixel).unwrap_or(config.threshold),
+        };
+        Deduplicator {
+            threshold,
+            initial_threshold: threshold,
+            low_threshold: config.low_threshold.unwrap_or(threshold).min(threshold),
+            changing: false,
+            comparer: Comparer::new(config, log),
+            motion: config
+                .ignore_embedded_video
+                .then(|| MotionTracker::new(config.motion_streak, log.clone())),
+            text: config.text_weight.map(TextWeighting::new),
+            prefilter: prefilter_kernel(config),
+            working_size: None,
+            last_image: None,
+            last_prepared: None,
+            ensemble,
+            last_hash: None,
+            normalize_brightness: config.normalize_brightness,
+            masks: config.masks.clone(),
+            footer: config.ignore_live_footer.then(|| FooterTracker::new(log.clone())),
+            stitch_scrolls: config.stitch_scrolls,
+            ignore_zoom: config.ignore_zoom,
+            slide: None,
+            zoom: None,
+        }
+    }
+
+    /// Judge the next frame in sequence; it becomes the reference for the one after
+    pub fn observe(&mut self, current_image: DynamicImage) -> Decision {
+        let size = current_image.dimensions();
+        let resized = self.last_image.as_ref().filter(|last| last.dimensions() != size).map(|_| size);
+
+        let (width, height) = *self.working_size.get_or_insert(size);
+        let scaled = size != (width, height);
+        let current_scaled = scaled.then(|| current_image.resize_exact(width, height, FilterType::Triangle));
+        // The kept frame stays as it was, only what it is compared as is blurred
+        let current_prepared = match self.prefilter {
+            Some(kernel) => Some(prefilter(current_scaled.as_ref().unwrap_or(&current_image), kernel)),
+            None => current_scaled,
+        };
+
+        let reference = self.last_prepared.as_ref().or(self.last_image.as_ref());
+        let current = current_prepared.as_ref().unwrap_or(&current_image);
+        // Brightness is matched to the frame compared against for the comparison only
+        let normalized = reference.filter(|_| self.normalize_brightness).map(|reference| match_histogram(current, reference));
+        let compared = normalized.as_ref().unwrap_or(current);
+        let threshold = if self.changing { self.low_threshold } else { self.threshold };
+        let footer = match (self.footer.as_mut(), reference) {
+            (Some(footer), Some(reference)) => footer.observe(&mut self.comparer, reference, compared, threshold),
+            _ => &[],
+        };
+        let masks: Vec<Mask> = self.masks.iter().chain(footer).copied().collect();
+        let hidden = reference.filter(|_| !masks.is_empty()).map(|reference| overlay::hide(&masks, compared, reference));
+        let compared = hidden.as_ref().unwrap_or(compared);
+        let difference = reference.map(|reference| match (self.motion.as_mut(), self.text.as_mut()) {
+            (Some(tracker), _) => tracker.difference_ratio(&mut self.comparer, reference, compared),
+            (None, Some(text)) => text.difference_ratio(&mut self.comparer, reference, compared),
+            (None, None) => self.comparer.difference_ratio(reference, compared),
+        });
+        let hash = self.ensemble.and_then(|ensemble| ensemble.phash).map(|_| perceptual_hash(current));
+        let changed = difference.is_some_and(|difference| self.differs(difference, threshold, hash));
+        let verdict = match difference {
+            // While the frames are still changing none of them starts another slide
+            Some(_) if changed && !self.changing => Verdict::Unique,
+            Some(_) => Verdict::Similar,
+            None => Verdict::First,
+        };
+        self.last_hash = hash;
+        // A page scrolled on screen is still the same slide
+        let scroll = match (verdict, reference) {
+            (Verdict::Unique, Some(reference)) if self.stitch_scrolls && !scaled => {
+                find_scroll(&mut self.comparer, reference, current, threshold)
+            }
+            _ => None,
+        };
+        let verdict = if scroll.is_some() { Verdict::Similar } else { verdict };
+        // So is a close-up of the slide, and the slide again once zoomed back out of it
+        let zooming = verdict == Verdict::Unique || self.zoom.is_some();
+        let (verdict, zoom) = match self.slide.as_ref() {
+            Some(slide) if self.ignore_zoom && !scaled && scroll.is_none() && zooming => {
+                if self.zoom.is_some() && self.comparer.difference_ratio(slide, current) <= threshold {
+                    (Verdict::Similar, None)
+                } else if verdict == Verdict::Similar {
+                    (verdict, self.zoom)
+                } else {
+                    match find_zoom(slide, current) {
+                        Some(scale) => (Verdict::Similar, Some(scale)),
+                        None => (verdict, None),
+                    }
+                }
+            }
+            _ => (verdict, None),
+        };
+        self.zoom = zoom;
+        if self.ignore_zoom && verdict.is_kept() {
+            self.slide = Some(current.clone());
+        }
+
+        // The first frame is a fresh start, not a change; without a low threshold every change is a slide
+        self.changing = match self.changing {
+            true => changed,
+            false => verdict == Verdict::Unique && self.low_threshold < self.threshold,
+        };
+        self.last_image = Some(current_image);
+        self.last_prepared = current_prepared;
+        Decision { verdict, difference, threshold, resized, scroll, zoom, changing: self.changing }
+    }
+
+    /// Whether a frame `difference` away from the last one, hashed to `hash`, shows another slide
+    fn differs(&self, difference: f64, threshold: f64, hash: Option<u64>) -> bool {
+        let Some(ensemble) = self.ensemble else {
+            return difference > threshold;
+        };
+        let pixel = ensemble.pixel.map(|_| difference > threshold);
+        let phash = ensemble.phash.zip(self.last_hash.zip(hash)).map(|(bits, (last, hash))| (last ^ hash).count_ones() > bits);
+        let mut votes = [pixel, phash].into_iter().flatten();
+        if ensemble.any {
+            votes.any(|vote| vote)
+        } else {
+            votes.all(|vote| vote)
+        }
+    }
+
+    /// Largest share of differing pixels for a frame to count as the previous slide
+    pub fn threshold(&self) -> f64 {
+        self.threshold
+    }
+
+    /// The threshold the frames were compared with at first
+    pub fn initial_threshold(&self) -> f64 {
+        self.initial_threshold
+    }
+
+    /// Compare the frames from here on with a different threshold, scaling the low one along
+    pub fn set_threshold(&mut self, threshold: f64) {
+        if self.threshold > 0.0 {
+            self.low_threshold *= threshold / self.threshold;
+        } else {
+            self.low_threshold = threshold;
+        }
+        self.threshold = threshold;
+    }
+
+    /// The frame the next one will be compared against, i.e. the last one observed
+    pub fn reference(&self) -> Option<&DynamicImage> {
+        self.last_image.as_ref()
+    }
+}
+
+/// What becomes of a frame once the slide has to settle before it is committed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Settled {
+    /// Not a new slide
+    Drop,
+    /// Could become the slide if the next frames agree with it; a frame held before it is dropped
+    Hold,
+    /// Enough frames agreed: this one is kept for the slide that appeared at position `start`;
+    /// a frame held before it is dropped
+    Commit { start: usize },
+}
+
+/// Waits for a number of consecutive frames to be similar after a change
+/// before committing the slide, keeping the last of them. A slide that was
+/// still rendering when the change was detected is never committed that way.
+pub struct Stabilizer {
+    frames: u32,
+    /// Position the pending slide appeared at and how many frames agreed with it since
+    pending: Option<(usize, u32)>,
+}
+
+impl Stabilizer {
+    pub fn new(config: &Config) -> Self {
+        Stabilizer { frames: config.settle_frames.max(1), pending: None }
+    }
+
+    /// Take the decision on the frame at `position` into account
+    pub fn settle(&mut self, position: usize, decision: &Decision) -> Settled {
+        if decision.is_kept() {
+            self.pending = Some((position, 0));
+        }
+        let Some((start, agreed)) = self.pending.as_mut() else {
+            return Settled::Drop;
+        };
+        // Frames of the change that started the slide don't agree with it yet
+        if !decision.is_kept() && !decision.changing {
+            *agreed += 1;
+        }
+        if *agreed + 1 >= self.frames {
+            let start = *start;
+            self.pending = None;
+            Settled::Commit { start }
+        } else {
+            Settled::Hold
+        }
+    }
+
+    /// The frames ran out; a frame still held is kept anyway, for the slide that appeared at the returned position
+    pub fn finish(&mut self) -> Option<usize> {
+        self.pending.take().map(|(start, _)| start)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Rgb, RgbImage};
+
+    /// A white 100x100 frame with its first `black` pixels black, so two of them differ by
+    /// the difference of their counts in hundredths of a percent
+    fn frame(black: u32) -> DynamicImage {
+        let mut image = RgbImage::from_pixel(100, 100, Rgb([255, 255, 255]));
+        for i in 0..black {
+            image.put_pixel(i % 100, i / 100, Rgb([0, 0, 0]));
+        }
+        DynamicImage::ImageRgb8(image)
+    }
+
+    fn kept(config: &Config, frames: &[u32]) -> Vec<usize> {
+        let mut dedup = Deduplicator::new(config, &RunLog::open(None).unwrap());
+        let decisions: Vec<Decision> = frames.iter().map(|&black| dedup.observe(frame(black))).collect();
+        decisions.iter().enumerate().filter(|(_, decision)| decision.is_kept()).map(|(i, _)| i).collect()
+    }
+
+    /// Differences of 0.011, 0.0095, 0.0105, 0.0095, 0.01, 0 and 0.0125 in turn
+    const HOVERING: [u32; 8] = [0, 110, 15, 120, 25, 125, 125, 0];
+
+    #[test]
+    fn single_threshold_flips_around_it() {
+        assert_eq!(kept(&Config::new("talk.mp4"), &HOVERING), vec![0, 1, 3, 7]);
+    }
+
+    #[test]
+    fn low_threshold_holds_the_change_until_it_settles() {
+        let mut config = Config::new("talk.mp4");
+        config.low_threshold = Some(0.005);
+        assert_eq!(kept(&config, &HOVERING), vec![0, 1, 7]);
+    }
+
+    #[test]
+    fn changing_frames_do_not_settle_a_slide() {
+        let mut config = Config::new("talk.mp4");
+        config.low_threshold = Some(0.005);
+        config.settle_frames = 2;
+        let mut dedup = Deduplicator::new(&config, &RunLog::open(None).unwrap());
+        let mut stabilizer = Stabilizer::new(&config);
+        let settled: Vec<Settled> = [0, 200, 400, 400, 400]
+            .into_iter()
+            .enumerate()
+            .map(|(position, black)| stabilizer.settle(position, &dedup.observe(frame(black))))
+            .collect();
+        // The frame at 2 is still the change, the one at 3 is the first to agree with it
+        assert_eq!(settled, vec![Settled::Hold, Settled::Hold, Settled::Hold, Settled::Commit { start: 1 }, Settled::Drop]);
+    }
+
+    /// A decision on a frame that is kept or not, outside of any change
+    fn decision(kept: bool) -> Decision {
+        let verdict = if kept { Verdict::Unique } else { Verdict::Similar };
+        Decision { verdict, difference: Some(0.0), threshold: 0.01, resized: None, scroll: None, zoom: None, changing: false }
+    }
+
+    #[test]
+    fn stabilizer_commits_once_enough_frames_agree() {
+        let mut config = Config::new("talk.mp4");
+        config.settle_frames = 3;
+        let mut stabilizer = Stabilizer::new(&config);
+        let settled: Vec<Settled> = [true, false, false, false, true, false]
+            .into_iter()
+            .enumerate()
+            .map(|(position, kept)| stabilizer.settle(position, &decision(kept)))
+            .collect();
+        assert_eq!(
+            settled,
+            vec![Settled::Hold, Settled::Hold, Settled::Commit { start: 0 }, Settled::Drop, Settled::Hold, Settled::Hold]
+        );
+        // The video ended while the slide at 4 was settling
+        assert_eq!(stabilizer.finish(), Some(4));
+        assert_eq!(stabilizer.finish(), None);
+    }
+
+    #[test]
+    fn stabilizer_starts_over_on_a_slide_still_rendering() {
+        let mut config = Config::new("talk.mp4");
+        config.settle_frames = 2;
+        let mut stabilizer = Stabilizer::new(&config);
+        let settled: Vec<Settled> = [true, true, true, false]
+            .into_iter()
+            .enumerate()
+            .map(|(position, kept)| stabilizer.settle(position, &decision(kept)))
+            .collect();
+        assert_eq!(settled, vec![Settled::Hold, Settled::Hold, Settled::Hold, Settled::Commit { start: 2 }]);
+    }
+
+    #[test]
+    fn stabilizer_commits_at_once_by_default() {
+        let mut stabilizer = Stabilizer::new(&Config::new("talk.mp4"));
+        assert_eq!(stabilizer.settle(0, &decision(true)), Settled::Commit { start: 0 });
+        assert_eq!(stabilizer.settle(1, &decision(false)), Settled::Drop);
+        assert_eq!(stabilizer.finish(), None);
+    }
+}
@@ -0,0 +1,101 @@
+//! `--open` and `--notify`, for someone who starts a long run and turns to
+//! something else: the results open with the desktop's default application
+//! when it is done, and a desktop notification says it finished or failed.
+//!
+//! Both go through the platform's own tools: `open` and `osascript` on macOS,
+//! `explorer` and PowerShell on Windows, and `xdg-open` and `notify-send`
+//! elsewhere. Neither holds up the run; without a desktop they only warn.
+
+use clap::ValueEnum;
+use std::io::{self, ErrorKind};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use crate::error::Error;
+use crate::export::{export, ExportFormat};
+use crate::report::REPORT_FILE;
+
+/// Title of the notifications
+const APP_NAME: &str = "videoSlideExtractor";
+
+/// What `--open` opens when the run is done
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum OpenTarget {
+    /// The output directory, in the file manager
+    Dir,
+    /// report.html, written with --report
+    Report,
+    /// The slides exported to slides.html
+    Html,
+    /// The slides exported to slides.pdf
+    Pdf,
+}
+
+/// Open `target` of the run in `output_dir`, exporting the slides first for `Html` and `Pdf`,
+/// and return its path
+pub fn open_output(output_dir: &Path, target: OpenTarget) -> Result<PathBuf, Error> {
+    let path = match target {
+        OpenTarget::Dir => output_dir.to_path_buf(),
+        OpenTarget::Report => output_dir.join(REPORT_FILE),
+        OpenTarget::Html | OpenTarget::Pdf => {
+            let format = if target == OpenTarget::Html { ExportFormat::Html } else { ExportFormat::Pdf };
+            let path = format.default_path(output_dir);
+            export(output_dir, format, &path, &[])?;
+            path
+        }
+    };
+    open(&path)?;
+    Ok(path)
+}
+
+/// Open `path` with the application the desktop opens it with
+pub fn open(path: &Path) -> Result<(), io::Error> {
+    let mut command = if cfg!(target_os = "macos") {
+        Command::new("open")
+    } else if cfg!(windows) {
+        Command::new("explorer")
+    } else {
+        Command::new("xdg-open")
+    };
+    // Not waited for: the opener may stay until the application it started is closed
+    spawn(command.arg(path))
+}
+
+/// Show a desktop notification saying `message`
+pub fn notify(message: &str) -> Result<(), io::Error> {
+    let mut command = if cfg!(target_os = "macos") {
+        let mut command = Command::new("osascript");
+        command.arg("-e").arg(format!("display notification {} with title {}", apple_string(message), apple_string(APP_NAME)));
+        command
+    } else if cfg!(windows) {
+        let script = format!(
+            "Add-Type -AssemblyName System.Windows.Forms; $icon = New-Object System.Windows.Forms.NotifyIcon; \
+             $icon.Icon = [System.Drawing.SystemIcons]::Information; $icon.Visible = $true; \
+             $icon.ShowBalloonTip(10000, '{}', '{}', 'Info'); Start-Sleep -Seconds 10; $icon.Dispose()",
+            APP_NAME,
+            message.replace('\'', "''")
+        );
+        let mut command = Command::new("powershell");
+        command.args(["-NoProfile", "-Command", &script]);
+        command
+    } else {
+        let mut command = Command::new("notify-send");
+        command.arg("--app-name").arg(APP_NAME).arg(APP_NAME).arg(message);
+        command
+    };
+    spawn(&mut command)
+}
+
+/// `text` as an AppleScript string literal
+fn apple_string(text: &str) -> String {
+    format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+fn spawn(command: &mut Command) -> Result<(), io::Error> {
+    let program = command.get_program().to_string_lossy().into_owned();
+    command.stdin(Stdio::null()).stdout(Stdio::null()).stderr(Stdio::null()).spawn().map_err(|e| match e.kind() {
+        ErrorKind::NotFound => io::Error::new(ErrorKind::NotFound, format!("{} was not found", program)),
+        _ => e,
+    })?;
+    Ok(())
+}
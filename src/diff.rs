@@ -0,0 +1,242 @@
+//! `diff`: compare the slides of two runs, such as last year's lecture and
+//! this year's, and report which slides were added, removed or modified.
+//!
+//! Slides are paired in order by the perceptual hash `coverage` uses, which
+//! survives a different resolution or encoder. Paired slides are then compared
+//! tile by tile at a common size to tell an edited slide from the same one
+//! captured again; each modified pair is written out side by side with the
+//! changed area outlined.
+//!
+//! Either side may also be a video, which is extracted with the default
+//! settings first.
+
+use image::imageops::{self, FilterType};
+use image::{DynamicImage, GrayImage, Rgb, RgbImage};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::config::Config;
+use crate::coverage::perceptual_hash;
+use crate::error::Error;
+use crate::export::load_run;
+use crate::lock::try_lock_dir;
+use crate::manifest::Manifest;
+use crate::pipeline::run;
+use crate::source::open_frame;
+
+/// Width both slides of a pair are scaled to before they are compared
+const COMPARE_WIDTH: u32 = 320;
+/// Edge length in pixels of the tiles compared at that width
+const TILE_SIZE: u32 = 16;
+/// Difference in brightness (0 to 255) from which a pixel counts as changed, above compression noise
+const PIXEL_TOLERANCE: u8 = 48;
+/// Share of a tile's pixels that must change for the tile to count as changed
+const TILE_CHANGE_RATIO: f64 = 0.05;
+/// Gap between the two halves of a composite, in pixels
+const GAP: u32 = 16;
+/// Thickness of the outline around what changed, in pixels
+const OUTLINE: u32 = 3;
+
+/// How two runs are compared
+#[derive(Debug, Clone)]
+pub struct DiffOptions {
+    /// Bits of the 64-bit perceptual hashes two slides may differ in and still be paired
+    pub max_distance: u32,
+    /// Directory the side-by-side composites of modified slides are written to
+    pub output_dir: PathBuf,
+}
+
+/// A slide of one of the two runs
+#[derive(Debug, Clone, PartialEq)]
+pub struct SlideRef {
+    pub index: usize,
+    /// Seconds from the start of its recording
+    pub timestamp: f64,
+    pub file: PathBuf,
+}
+
+/// How a slide changed from the old run to the new one
+#[derive(Debug, Clone, PartialEq)]
+pub enum DiffEntry {
+    Unchanged { old: SlideRef, new: SlideRef },
+    /// The slide is still there, edited; `composite` shows both versions
+    Modified { old: SlideRef, new: SlideRef, composite: PathBuf },
+    Added { new: SlideRef },
+    Removed { old: SlideRef },
+}
+
+/// The slides of both runs, in the order they were shown
+#[derive(Debug, Clone, PartialEq)]
+pub struct SlideDiff {
+    pub entries: Vec<DiffEntry>,
+}
+
+impl SlideDiff {
+    /// Number of unchanged, modified, added and removed slides
+    pub fn counts(&self) -> (usize, usize, usize, usize) {
+        let count = |f: fn(&DiffEntry) -> bool| self.entries.iter().filter(|entry| f(entry)).count();
+        (
+            count(|entry| matches!(entry, DiffEntry::Unchanged { .. })),
+            count(|entry| matches!(entry, DiffEntry::Modified { .. })),
+            count(|entry| matches!(entry, DiffEntry::Added { .. })),
+            count(|entry| matches!(entry, DiffEntry::Removed { .. })),
+        )
+    }
+}
+
+/// The slides of a run with their images, from its output directory or by extracting them from a video into `work_dir`
+fn open_run(path: &Path, work_dir: &Path) -> Result<Vec<(SlideRef, DynamicImage)>, Error> {
+    let (dir, manifest): (PathBuf, Manifest) = if path.is_dir() {
+        (path.to_path_buf(), load_run(path)?)
+    } else {
+        let mut config = Config::new(path.to_path_buf());
+        config.output_dir = work_dir.to_path_buf();
+        (work_dir.to_path_buf(), run(&config)?)
+    };
+    manifest
+        .slides
+        .iter()
+        .map(|slide| {
+            let file = dir.join(&slide.file);
+            let image = open_frame(&file).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", file.display(), e)))?;
+            Ok((SlideRef { index: slide.index, timestamp: slide.timestamp, file }, image))
+        })
+        .collect()
+}
+
+/// Pair up the slides of both runs in order, pairing as many within
+/// `max_distance` bits as possible and preferring closer ones
+fn align(old: &[u64], new: &[u64], max_distance: u32) -> Vec<(Option<usize>, Option<usize>)> {
+    let score = |i: usize, j: usize| {
+        let distance = (old[i] ^ new[j]).count_ones();
+        (distance <= max_distance).then(|| 65 + max_distance - distance)
+    };
+
+    // best[i][j]: score of aligning old[i..] with new[j..]
+    let (n, m) = (old.len(), new.len());
+    let mut best = vec![vec![0u32; m + 1]; n + 1];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            let paired = score(i, j).map_or(0, |score| score + best[i + 1][j + 1]);
+            best[i][j] = paired.max(best[i + 1][j]).max(best[i][j + 1]);
+        }
+    }
+
+    let (mut i, mut j) = (0, 0);
+    let mut pairs = Vec::new();
+    while i < n || j < m {
+        if i < n && j < m && score(i, j).is_some_and(|score| score + best[i + 1][j + 1] == best[i][j]) {
+            pairs.push((Some(i), Some(j)));
+            (i, j) = (i + 1, j + 1);
+        } else if i < n && (j == m || best[i + 1][j] == best[i][j]) {
+            pairs.push((Some(i), None));
+            i += 1;
+        } else {
+            pairs.push((None, Some(j)));
+            j += 1;
+        }
+    }
+    pairs
+}
+
+/// `image` in greyscale at `COMPARE_WIDTH` wide and `height` high
+fn normalized(image: &DynamicImage, height: u32) -> GrayImage {
+    image.resize_exact(COMPARE_WIDTH, height, FilterType::Triangle).to_luma8()
+}
+
+/// Box around what differs between two versions of a slide, as shares of
+/// the slide's width and height (x, y, width, height); `None` if nothing does
+pub fn changed_area(old: &DynamicImage, new: &DynamicImage) -> Option<[f64; 4]> {
+    let height = ((COMPARE_WIDTH as f64 * new.height() as f64 / new.width().max(1) as f64).round() as u32).max(1);
+    let (old, new) = (normalized(old, height), normalized(new, height));
+
+    let columns = COMPARE_WIDTH.div_ceil(TILE_SIZE);
+    let mut diffs = vec![0u32; (columns * height.div_ceil(TILE_SIZE)) as usize];
+    for (x, y, pixel) in new.enumerate_pixels() {
+        if pixel[0].abs_diff(old.get_pixel(x, y)[0]) > PIXEL_TOLERANCE {
+            diffs[((y / TILE_SIZE) * columns + x / TILE_SIZE) as usize] += 1;
+        }
+    }
+
+    let (mut x0, mut y0, mut x1, mut y1) = (u32::MAX, u32::MAX, 0, 0);
+    for (index, &count) in diffs.iter().enumerate() {
+        let (tx, ty) = (index as u32 % columns, index as u32 / columns);
+        let (right, bottom) = (((tx + 1) * TILE_SIZE).min(COMPARE_WIDTH), ((ty + 1) * TILE_SIZE).min(height));
+        let pixels = (right - tx * TILE_SIZE) * (bottom - ty * TILE_SIZE);
+        if count as f64 / pixels as f64 > TILE_CHANGE_RATIO {
+            (x0, y0, x1, y1) = (x0.min(tx * TILE_SIZE), y0.min(ty * TILE_SIZE), x1.max(right), y1.max(bottom));
+        }
+    }
+    (x1 > 0).then(|| {
+        let (width, height) = (COMPARE_WIDTH as f64, height as f64);
+        [x0 as f64 / width, y0 as f64 / height, (x1 - x0) as f64 / width, (y1 - y0) as f64 / height]
+    })
+}
+
+/// Draw the outline of `area` (shares of the image's size) onto `image`
+fn outline(image: &mut RgbImage, area: [f64; 4]) {
+    let (width, height) = (image.width() as f64, image.height() as f64);
+    let x0 = (area[0] * width) as u32;
+    let y0 = (area[1] * height) as u32;
+    let x1 = (((area[0] + area[2]) * width) as u32).min(image.width());
+    let y1 = (((area[1] + area[3]) * height) as u32).min(image.height());
+    for y in y0..y1 {
+        for x in x0..x1 {
+            if x < x0 + OUTLINE || x + OUTLINE >= x1 || y < y0 + OUTLINE || y + OUTLINE >= y1 {
+                image.put_pixel(x, y, Rgb([255, 0, 0]));
+            }
+        }
+    }
+}
+
+/// The old version of a slide left of the new one, at the same height, with what changed outlined on both
+fn composite(old: &DynamicImage, new: &DynamicImage, area: [f64; 4]) -> RgbImage {
+    let height = old.height().min(new.height());
+    let scale = |image: &DynamicImage| {
+        let width = ((image.width() as f64 * height as f64 / image.height() as f64).round() as u32).max(1);
+        let mut scaled = image.resize_exact(width, height, FilterType::Triangle).to_rgb8();
+        outline(&mut scaled, area);
+        scaled
+    };
+    let (old, new) = (scale(old), scale(new));
+
+    let mut canvas = RgbImage::from_pixel(old.width() + GAP + new.width(), height, Rgb([255, 255, 255]));
+    imageops::replace(&mut canvas, &old, 0, 0);
+    imageops::replace(&mut canvas, &new, (old.width() + GAP) as i64, 0);
+    canvas
+}
+
+/// Compare the slides of the run in `old` with those of the run in `new`
+pub fn diff(old: &Path, new: &Path, options: &DiffOptions) -> Result<SlideDiff, Error> {
+    let work_dir = tempfile::Builder::new().prefix("videoslides-diff-").tempdir()?;
+    let _lock = try_lock_dir(work_dir.path())?;
+    let old_slides = open_run(old, &work_dir.path().join("old"))?;
+    let new_slides = open_run(new, &work_dir.path().join("new"))?;
+    let hashes = |slides: &[(SlideRef, DynamicImage)]| slides.iter().map(|(_, image)| perceptual_hash(image)).collect::<Vec<_>>();
+
+    let mut entries = Vec::new();
+    for pair in align(&hashes(&old_slides), &hashes(&new_slides), options.max_distance) {
+        let entry = match pair {
+            (Some(i), Some(j)) => {
+                let ((old, old_image), (new, new_image)) = (&old_slides[i], &new_slides[j]);
+                match changed_area(old_image, new_image) {
+                    None => DiffEntry::Unchanged { old: old.clone(), new: new.clone() },
+                    Some(area) => {
+                        fs::create_dir_all(&options.output_dir)?;
+                        let composite_path = options.output_dir.join(format!("modified_{:03}.png", new.index));
+                        composite(old_image, new_image, area)
+                            .save(&composite_path)
+                            .map_err(|e| io::Error::other(format!("Error saving image: {}", e)))?;
+                        DiffEntry::Modified { old: old.clone(), new: new.clone(), composite: composite_path }
+                    }
+                }
+            }
+            (Some(i), None) => DiffEntry::Removed { old: old_slides[i].0.clone() },
+            (None, Some(j)) => DiffEntry::Added { new: new_slides[j].0.clone() },
+            (None, None) => unreachable!("every step of the alignment takes a slide"),
+        };
+        entries.push(entry);
+    }
+    Ok(SlideDiff { entries })
+}
@@ -0,0 +1,142 @@
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::ExitStatus;
+
+/// Everything that can stop an extraction, with enough detail to act on
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("ffmpeg was not found; install it and make sure it is on the PATH")]
+    FfmpegNotFound,
+
+    /// Ends with the last lines ffmpeg wrote to stderr
+    #[error("ffmpeg failed on {input} ({status}):\n{stderr}")]
+    FfmpegFailed { input: String, status: String, stderr: String },
+
+    /// ffmpeg ran past `--ffmpeg-timeout` or `--stall-timeout` and was killed
+    #[error("ffmpeg {reason} on {input} and was killed:\n{stderr}")]
+    FfmpegStalled { input: String, reason: String, stderr: String },
+
+    #[error("Cannot read input {}: {source}", path.display())]
+    UnreadableInput {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+
+    /// A sampled frame could not be decoded; `--on-bad-frame` decides whether the run carries on
+    #[error("Error opening image {}: {source}", path.display())]
+    BadFrame {
+        path: PathBuf,
+        #[source]
+        source: image::ImageError,
+    },
+
+    /// The sampled frames would not fit on the disk they are written to
+    #[error(
+        "Extracting needs about {} in {} but only {} is free; free some space, pass --tmp-dir or --no-space-check",
+        format_size(*needed),
+        dir.display(),
+        format_size(*available)
+    )]
+    InsufficientSpace { dir: PathBuf, needed: u64, available: u64 },
+
+    /// More slides were kept than `--max-slides` or `--max-output-size` allow
+    #[error(
+        "Stopped after keeping {kept} slide(s) ({}) from {done} frame(s): {limit} exceeded; \
+         a threshold of {threshold} is probably too low for this video, raise --threshold or pass --on-limit raise",
+        format_size(*bytes)
+    )]
+    LimitExceeded { limit: String, kept: usize, bytes: u64, done: usize, threshold: f64 },
+
+    /// Another run is writing slides to the same output directory
+    #[error("Another run is writing to {}; wait for it to finish or pick another --output-dir", dir.display())]
+    OutputLocked { dir: PathBuf },
+
+    /// `evaluate` scored the detection below `--min-f1`
+    #[error("F1 score {f1:.3} is below the required {min_f1}")]
+    BelowTarget { f1: f64, min_f1: f64 },
+
+    /// The run finished without keeping a slide, with `--fail-on-zero-slides`
+    #[error("No slides were found in {input}")]
+    NoSlides { input: String },
+
+    /// Some videos of a batch failed, the others were processed
+    #[error("{failed} of {total} video(s) failed")]
+    BatchFailed { failed: usize, total: usize },
+
+    #[error("tesseract was not found; install it and make sure it is on the PATH, or leave out --ocr")]
+    TesseractNotFound,
+
+    /// The OCR engine could not read a slide; ends with the last lines tesseract wrote to
+    /// stderr, or what the endpoint answered
+    #[error("OCR failed on {}:\n{message}", path.display())]
+    OcrFailed { path: PathBuf, message: String },
+
+    #[error("Extraction cancelled")]
+    Cancelled,
+
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+
+impl Error {
+    /// Error for an ffmpeg run on `input` that exited with `status`
+    pub fn ffmpeg_failed(input: &Path, status: ExitStatus, stderr: impl Into<String>) -> Self {
+        Error::FfmpegFailed { input: input.display().to_string(), status: status.to_string(), stderr: stderr.into() }
+    }
+
+    /// Error for a failed attempt to start ffmpeg
+    pub fn ffmpeg_spawn(e: io::Error) -> Self {
+        match e.kind() {
+            io::ErrorKind::NotFound => Error::FfmpegNotFound,
+            _ => Error::Io(e),
+        }
+    }
+
+    /// Error for a failed attempt to start tesseract
+    pub fn tesseract_spawn(e: io::Error) -> Self {
+        match e.kind() {
+            io::ErrorKind::NotFound => Error::TesseractNotFound,
+            _ => Error::Io(e),
+        }
+    }
+
+    /// Process exit code the command line tool reports this error with
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            Error::Io(_) => 1,
+            // 2 is taken by clap for usage errors
+            Error::FfmpegNotFound => 3,
+            Error::FfmpegFailed { .. } => 4,
+            Error::UnreadableInput { .. } => 5,
+            Error::BadFrame { .. } => 6,
+            Error::InsufficientSpace { .. } => 7,
+            Error::LimitExceeded { .. } => 8,
+            Error::FfmpegStalled { .. } => 9,
+            Error::BelowTarget { .. } => 10,
+            Error::OutputLocked { .. } => 11,
+            Error::NoSlides { .. } => 12,
+            Error::BatchFailed { .. } => 13,
+            Error::TesseractNotFound => 14,
+            Error::OcrFailed { .. } => 15,
+            // Like a shell reports a process stopped by Ctrl-C
+            Error::Cancelled => 130,
+        }
+    }
+}
+
+/// Bytes in a human-friendly unit, for messages
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KB", "MB", "GB", "TB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
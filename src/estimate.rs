@@ -0,0 +1,117 @@
+//! `--estimate`: how long a run would take, how many slides it would find and
+//! how much disk it would use, from a few stretches of the video, to decide
+//! whether to run it here or hand it to a server before committing to it.
+//!
+//! A few evenly spaced stretches are sampled and compared with the chosen
+//! settings, timed, and the rest of the video is taken to go the same way.
+//! Sampling with `--adaptive` usually reads fewer frames than estimated, and
+//! the steps after the slides are kept, like `--ocr`, are left out.
+
+use serde::Serialize;
+use std::fs;
+use std::path::Path;
+use std::time::Instant;
+
+use crate::config::Config;
+use crate::dedup::Deduplicator;
+use crate::error::Error;
+use crate::extract::sample_window;
+use crate::probe::probe;
+use crate::rate;
+use crate::runlog::RunLog;
+use crate::source::{DirectorySource, FrameSource};
+use crate::workspace::Workspace;
+
+/// Stretches of the video sampled
+const WINDOWS: usize = 3;
+/// Seconds in each
+const WINDOW_SECONDS: f64 = 30.0;
+
+/// What a run of a video is expected to take
+#[derive(Debug, Clone, Serialize)]
+pub struct Estimate {
+    /// Seconds of video the run would go through
+    pub duration: f64,
+    /// Seconds of it sampled for the estimate
+    pub sampled: f64,
+    /// Frames the run would sample
+    pub frames: usize,
+    /// Seconds the run would take
+    pub runtime: f64,
+    /// Slides it would keep
+    pub slides: usize,
+    /// Bytes the kept slides would take up
+    pub output_size: u64,
+    /// Bytes the sampled frames take up in the working directory before the duplicates are removed
+    pub working_size: u64,
+}
+
+/// Sample a few stretches of `config.input_file` with `config`'s settings and extrapolate to the
+/// whole of it
+pub fn estimate(config: &Config) -> Result<Estimate, Error> {
+    let log = RunLog::open(config.log_file.as_deref())?;
+    let config = rate::resolve(config, &log)?;
+    let mut duration = probe(&config.input_file)?.duration;
+    if let Some(limit) = config.duration {
+        duration = duration.min(limit);
+    }
+    let windows: Vec<(f64, f64)> = if duration <= WINDOWS as f64 * WINDOW_SECONDS {
+        vec![(0.0, duration)]
+    } else {
+        let spacing = duration / WINDOWS as f64;
+        (0..WINDOWS).map(|i| ((i as f64 + 0.5) * spacing - WINDOW_SECONDS / 2.0, WINDOW_SECONDS)).collect()
+    };
+
+    let mut workspace = Workspace::create(&config)?;
+    let timer = Instant::now();
+    let (mut frames, mut frames_size, mut changes, mut kept, mut kept_size) = (0, 0, 0, 0, 0);
+    for (i, &(start, length)) in windows.iter().enumerate() {
+        let dir = workspace.path().join(format!("estimate_{}", i));
+        fs::create_dir(&dir)?;
+        sample_window(&config, &dir, start, Some(length), &config.fps.to_string())?;
+
+        let mut source = DirectorySource::open(&dir)?;
+        let mut dedup = Deduplicator::new(&config, &log);
+        let mut position = 0;
+        loop {
+            let frame = match source.next_frame() {
+                Ok(Some(frame)) => frame,
+                Ok(None) => break,
+                Err(Error::BadFrame { .. }) => continue,
+                Err(e) => return Err(e),
+            };
+            let size = frame.path.as_deref().map_or(0, file_size);
+            frames += 1;
+            frames_size += size;
+            if dedup.observe(frame.image).is_kept() {
+                kept += 1;
+                kept_size += size;
+                // The first frame of a stretch is only new to the stretch
+                changes += usize::from(position > 0);
+            }
+            position += 1;
+        }
+    }
+    let elapsed = timer.elapsed().as_secs_f64();
+    workspace.succeeded();
+
+    let sampled: f64 = windows.iter().map(|&(_, length)| length).sum();
+    let scale = duration / sampled.max(f64::EPSILON);
+    let total_frames = (duration * config.fps as f64).ceil() as usize;
+    let slides = 1 + (changes as f64 * scale).round() as usize;
+    let frame_size = frames_size / frames.max(1) as u64;
+    let slide_size = kept_size.checked_div(kept).unwrap_or(frame_size);
+    Ok(Estimate {
+        duration,
+        sampled,
+        frames: total_frames,
+        runtime: elapsed * scale,
+        slides,
+        output_size: slides as u64 * slide_size,
+        working_size: if config.in_memory { 0 } else { total_frames as u64 * frame_size },
+    })
+}
+
+fn file_size(path: &Path) -> u64 {
+    fs::metadata(path).map_or(0, |metadata| metadata.len())
+}
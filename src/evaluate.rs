@@ -0,0 +1,177 @@
+//! `evaluate`: score the slides found in a video against hand-made
+//! annotations, so detection settings can be tuned and regression-checked
+//! on a set of annotated recordings.
+//!
+//! The ground truth lists the time each slide appears, one per line, as
+//! seconds (`83.5`) or `mm:ss` / `hh:mm:ss`; blank lines and lines starting
+//! with `#` are ignored.
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::config::Config;
+use crate::error::Error;
+use crate::pipeline::run;
+
+/// How the detected slides compare with the expected ones
+#[derive(Debug, Clone, PartialEq)]
+pub struct Evaluation {
+    pub expected: usize,
+    pub detected: usize,
+    /// Detected slides that match an expected one
+    pub matched: usize,
+    /// Expected slides nothing was detected for, in seconds
+    pub missed: Vec<f64>,
+    /// Detected slides that match no expected one, in seconds
+    pub extra: Vec<f64>,
+}
+
+impl Evaluation {
+    /// Share of the detected slides that were expected, 1 if none were detected
+    pub fn precision(&self) -> f64 {
+        if self.detected == 0 {
+            return 1.0;
+        }
+        self.matched as f64 / self.detected as f64
+    }
+
+    /// Share of the expected slides that were detected, 1 if none were expected
+    pub fn recall(&self) -> f64 {
+        if self.expected == 0 {
+            return 1.0;
+        }
+        self.matched as f64 / self.expected as f64
+    }
+
+    pub fn f1(&self) -> f64 {
+        let (precision, recall) = (self.precision(), self.recall());
+        if precision + recall == 0.0 {
+            return 0.0;
+        }
+        2.0 * precision * recall / (precision + recall)
+    }
+}
+
+/// Seconds in `83.5`, `1:23.5` or `0:01:23.5`
+pub fn parse_timestamp(text: &str) -> Option<f64> {
+    let mut seconds = 0.0;
+    for part in text.trim().split(':') {
+        let value: f64 = part.trim().parse().ok()?;
+        if value < 0.0 {
+            return None;
+        }
+        seconds = seconds * 60.0 + value;
+    }
+    Some(seconds)
+}
+
+/// Read the expected slide times from a ground-truth file, sorted
+pub fn read_truth(path: &Path) -> Result<Vec<f64>, io::Error> {
+    let mut timestamps = Vec::new();
+    for (number, line) in fs::read_to_string(path)?.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let timestamp = parse_timestamp(line).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{}:{}: {:?} is not a timestamp", path.display(), number + 1, line),
+            )
+        })?;
+        timestamps.push(timestamp);
+    }
+    timestamps.sort_by(f64::total_cmp);
+    Ok(timestamps)
+}
+
+/// Pair up expected and detected slide times (both sorted) that lie within `tolerance` seconds of each other
+pub fn score(expected: &[f64], detected: &[f64], tolerance: f64) -> Evaluation {
+    let mut evaluation = Evaluation {
+        expected: expected.len(),
+        detected: detected.len(),
+        matched: 0,
+        missed: Vec::new(),
+        extra: Vec::new(),
+    };
+    let (mut e, mut d) = (0, 0);
+    while e < expected.len() && d < detected.len() {
+        if (detected[d] - expected[e]).abs() <= tolerance {
+            evaluation.matched += 1;
+            e += 1;
+            d += 1;
+        } else if detected[d] < expected[e] {
+            evaluation.extra.push(detected[d]);
+            d += 1;
+        } else {
+            evaluation.missed.push(expected[e]);
+            e += 1;
+        }
+    }
+    evaluation.missed.extend_from_slice(&expected[e..]);
+    evaluation.extra.extend_from_slice(&detected[d..]);
+    evaluation
+}
+
+/// Extract the slides of `config.input_file` and score them against the ground truth in `truth`
+pub fn evaluate(config: &Config, truth: &Path, tolerance: f64) -> Result<Evaluation, Error> {
+    let expected = read_truth(truth)?;
+
+    // The slides themselves are not wanted, only when they appear
+    let output_dir = tempfile::Builder::new().prefix("videoslides-evaluate-").tempdir()?;
+    let mut config = config.clone();
+    config.output_dir = output_dir.path().to_path_buf();
+    config.output = None;
+    config.archive = None;
+    let manifest = run(&config)?;
+
+    // A slide shown again counts as detected each time it appears
+    let mut detected: Vec<f64> = manifest
+        .slides
+        .iter()
+        .flat_map(|slide| match slide.shown.as_slice() {
+            [] => vec![slide.timestamp],
+            shown => shown.iter().map(|interval| interval.start).collect(),
+        })
+        .collect();
+    detected.sort_by(f64::total_cmp);
+    Ok(score(&expected, &detected, tolerance))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn score_matches_within_tolerance() {
+        let evaluation = score(&[0.0, 10.0, 20.0, 30.0], &[0.5, 12.0, 20.2, 25.0], 1.0);
+        assert_eq!(evaluation.matched, 2);
+        assert_eq!(evaluation.missed, vec![10.0, 30.0]);
+        assert_eq!(evaluation.extra, vec![12.0, 25.0]);
+        assert_eq!(evaluation.precision(), 0.5);
+        assert_eq!(evaluation.recall(), 0.5);
+        assert_eq!(evaluation.f1(), 0.5);
+    }
+
+    #[test]
+    fn precision_and_recall_of_nothing() {
+        let nothing_found = score(&[5.0, 15.0], &[], 1.0);
+        assert_eq!(nothing_found.precision(), 1.0);
+        assert_eq!(nothing_found.recall(), 0.0);
+        let nothing_expected = score(&[], &[5.0], 1.0);
+        assert_eq!(nothing_expected.precision(), 0.0);
+        assert_eq!(nothing_expected.recall(), 1.0);
+        assert_eq!(nothing_expected.f1(), 0.0);
+        assert_eq!(score(&[], &[], 1.0).f1(), 1.0);
+    }
+
+    #[test]
+    fn parses_timestamps() {
+        assert_eq!(parse_timestamp("83.5"), Some(83.5));
+        assert_eq!(parse_timestamp("1:23.5"), Some(83.5));
+        assert_eq!(parse_timestamp("0:01:23.5"), Some(83.5));
+        assert_eq!(parse_timestamp("-3"), None);
+        assert_eq!(parse_timestamp("1:xx"), None);
+    }
+}
@@ -0,0 +1,371 @@
+//! `export`: turn the slides of a finished run into one PDF, HTML page,
+//! Markdown document or NDJSON list, a transcript of their text, a chapter list, or long images of the pages scrolled through,
+//! straight from its manifest and images, so a run can be exported again without processing the video, also
+//! after slides were deleted by hand.
+//!
+//! Slides listed in the manifest whose image is gone are left out, and
+//! images the manifest doesn't list (added or renamed by hand) are not
+//! picked up; both are warned about.
+
+use clap::ValueEnum;
+use image::codecs::jpeg::JpegEncoder;
+use image::{DynamicImage, RgbImage};
+use std::fmt::Write as _;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::classify;
+use crate::compare::Comparer;
+use crate::config::Config;
+use crate::error::Error;
+use crate::links::clock;
+use crate::lock::write_atomic;
+use crate::manifest::{Manifest, SlideKind};
+use crate::ocr;
+use crate::runlog::RunLog;
+use crate::scroll::{find_scroll, Page};
+use crate::source::open_frame;
+
+/// JPEG quality the slides are stored in the PDF at
+const PDF_QUALITY: u8 = 90;
+
+/// What to export the slides of a run to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ExportFormat {
+    /// One page per slide, the size of the slide
+    Pdf,
+    /// A page showing the slides in order with their times
+    Html,
+    /// A document with each slide's image under a heading with its time
+    Markdown,
+    /// The manifest entry of each slide on a line of its own
+    Ndjson,
+    /// A Markdown document with the text of each slide under a heading with its time, for search
+    /// indexes and summarizing; slides read without --ocr are read with Tesseract
+    Transcript,
+    /// One `mm:ss Title` line per slide, as chapters in a video description; untitled slides are
+    /// named by their number
+    Chapters,
+    /// A directory with one long PNG per run of slides that scroll one another, such as a file
+    /// walked through in an editor, and one per slide on its own
+    Panorama,
+}
+
+impl ExportFormat {
+    /// Where the export goes unless told otherwise
+    pub fn default_path(self, slides_dir: &Path) -> PathBuf {
+        match self {
+            ExportFormat::Pdf => slides_dir.join("slides.pdf"),
+            ExportFormat::Html => slides_dir.join("slides.html"),
+            ExportFormat::Markdown => slides_dir.join("slides.md"),
+            ExportFormat::Ndjson => slides_dir.join("slides.ndjson"),
+            ExportFormat::Transcript => slides_dir.join("transcript.md"),
+            ExportFormat::Chapters => slides_dir.join("chapters.txt"),
+            ExportFormat::Panorama => slides_dir.join("panoramas"),
+        }
+    }
+}
+
+/// The manifest in `slides_dir` without the slides whose image is missing,
+/// warning about those and about images the manifest doesn't list
+pub fn load_run(slides_dir: &Path) -> Result<Manifest, Error> {
+    let mut manifest = Manifest::read(slides_dir)?;
+    manifest.slides.retain(|slide| {
+        let present = slides_dir.join(&slide.file).is_file();
+        if !present {
+            tracing::warn!("Slide {} ({}) is in the manifest but its image is missing, leaving it out", slide.index, slide.file);
+        }
+        present
+    });
+
+    for entry in fs::read_dir(slides_dir)? {
+        let path = entry?.path();
+        let is_image = matches!(
+            path.extension().and_then(|s| s.to_str()).map(str::to_ascii_lowercase).as_deref(),
+            Some("png" | "jpg" | "jpeg")
+        );
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        if is_image && !manifest.slides.iter().any(|slide| slide.file == name) {
+            tracing::warn!("{} is not in the manifest, leaving it out", name);
+        }
+    }
+    Ok(manifest)
+}
+
+/// Export the slides of the run in `slides_dir` as `format` to `path`, only those of `kinds`
+/// unless it is empty, returning how many were exported
+pub fn export(slides_dir: &Path, format: ExportFormat, path: &Path, kinds: &[SlideKind]) -> Result<usize, Error> {
+    let mut manifest = load_run(slides_dir)?;
+    if !kinds.is_empty() {
+        // Runs from before slides were classified
+        classify::tag_kinds(slides_dir, &mut manifest)?;
+        manifest.slides.retain(|slide| slide.kind.is_some_and(|kind| kinds.contains(&kind)));
+    }
+    let contents = match format {
+        ExportFormat::Pdf => pdf(slides_dir, &manifest)?,
+        ExportFormat::Html => html(slides_dir, &manifest, path)?.into_bytes(),
+        ExportFormat::Markdown => markdown(slides_dir, &manifest, path)?.into_bytes(),
+        ExportFormat::Ndjson => ndjson(&manifest)?.into_bytes(),
+        ExportFormat::Transcript => {
+            // Runs made without --ocr, and slides added since
+            ocr::read_slides(&ocr::Tesseract, slides_dir, &mut manifest, None, &RunLog::default())?;
+            transcript(&manifest).into_bytes()
+        }
+        ExportFormat::Chapters => chapters(&manifest).into_bytes(),
+        ExportFormat::Panorama => {
+            panoramas(slides_dir, &manifest, path)?;
+            return Ok(manifest.slides.len());
+        }
+    };
+    write_atomic(path, contents)?;
+    Ok(manifest.slides.len())
+}
+
+/// A PDF with each slide of `manifest` as a JPEG on a page of its own, one point per pixel
+pub fn pdf(slides_dir: &Path, manifest: &Manifest) -> Result<Vec<u8>, Error> {
+    let mut pdf = b"%PDF-1.4\n%\xe2\xe3\xcf\xd3\n".to_vec();
+    let mut offsets = Vec::new();
+    let mut object = |pdf: &mut Vec<u8>, header: String, stream: Option<&[u8]>| {
+        offsets.push(pdf.len());
+        pdf.extend_from_slice(format!("{} 0 obj\n{}\n", offsets.len(), header).as_bytes());
+        if let Some(stream) = stream {
+            pdf.extend_from_slice(b"stream\n");
+            pdf.extend_from_slice(stream);
+            pdf.extend_from_slice(b"\nendstream\n");
+        }
+        pdf.extend_from_slice(b"endobj\n");
+    };
+
+    // Objects 1 and 2 are the catalog and the page tree, then a page, its image and its contents per slide
+    let pages: Vec<String> = (0..manifest.slides.len()).map(|i| format!("{} 0 R", 3 + 3 * i)).collect();
+    object(&mut pdf, "<< /Type /Catalog /Pages 2 0 R >>".into(), None);
+    object(&mut pdf, format!("<< /Type /Pages /Kids [{}] /Count {} >>", pages.join(" "), pages.len()), None);
+
+    for (i, slide) in manifest.slides.iter().enumerate() {
+        let image = open_frame(&slides_dir.join(&slide.file)).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        let rgb = image.to_rgb8();
+        let (width, height) = rgb.dimensions();
+        let mut jpeg = Vec::new();
+        rgb.write_with_encoder(JpegEncoder::new_with_quality(&mut jpeg, PDF_QUALITY)).map_err(io::Error::other)?;
+        let contents = format!("q {} 0 0 {} 0 0 cm /Slide Do Q", width, height);
+
+        let first = 3 + 3 * i;
+        object(
+            &mut pdf,
+            format!(
+                "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {} {}] /Resources << /XObject << /Slide {} 0 R >> >> /Contents {} 0 R >>",
+                width,
+                height,
+                first + 1,
+                first + 2
+            ),
+            None,
+        );
+        object(
+            &mut pdf,
+            format!(
+                "<< /Type /XObject /Subtype /Image /Width {} /Height {} /ColorSpace /DeviceRGB /BitsPerComponent 8 /Filter /DCTDecode /Length {} >>",
+                width,
+                height,
+                jpeg.len()
+            ),
+            Some(&jpeg),
+        );
+        object(&mut pdf, format!("<< /Length {} >>", contents.len()), Some(contents.as_bytes()));
+    }
+
+    // Document properties, from the deck's catalog
+    let catalog = manifest.catalog.clone().unwrap_or_default();
+    let mut info = format!("<< /Producer {}", pdf_text(&format!("{} {}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"))));
+    for (key, value) in [("Title", &catalog.title), ("Author", &catalog.speaker), ("Subject", &catalog.course)] {
+        if let Some(value) = value {
+            let _ = write!(info, " /{} {}", key, pdf_text(value));
+        }
+    }
+    if let Some(date) = &catalog.date {
+        let _ = write!(info, " /CreationDate (D:{})", date.replace('-', ""));
+    }
+    info.push_str(" >>");
+    object(&mut pdf, info, None);
+
+    let xref = pdf.len();
+    let mut table = format!("xref\n0 {}\n0000000000 65535 f \n", offsets.len() + 1);
+    for offset in &offsets {
+        let _ = writeln!(table, "{:010} 00000 n ", offset);
+    }
+    let _ = write!(
+        table,
+        "trailer\n<< /Size {} /Root 1 0 R /Info {} 0 R >>\nstartxref\n{}\n%%EOF\n",
+        offsets.len() + 1,
+        offsets.len(),
+        xref
+    );
+    pdf.extend_from_slice(table.as_bytes());
+    Ok(pdf)
+}
+
+/// `text` as a PDF string, in UTF-16 so any script survives
+fn pdf_text(text: &str) -> String {
+    let mut hex = String::from("<FEFF");
+    for unit in text.encode_utf16() {
+        let _ = write!(hex, "{:04X}", unit);
+    }
+    hex.push('>');
+    hex
+}
+
+pub(crate) fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+/// Write the slides of `manifest` into `dir` as `panorama_001.png` and on, each
+/// run of slides that scroll one another stitched into one; returns how many were written
+pub fn panoramas(slides_dir: &Path, manifest: &Manifest, dir: &Path) -> Result<usize, Error> {
+    let config = Config::new(slides_dir);
+    let mut comparer = Comparer::new(&config, &RunLog::default());
+    fs::create_dir_all(dir)?;
+
+    let mut written = 0;
+    let mut write = |image: RgbImage| -> Result<(), Error> {
+        written += 1;
+        let path = dir.join(format!("panorama_{:03}.png", written));
+        image.save(&path).map_err(|e| io::Error::other(format!("Error saving {}: {}", path.display(), e)))?;
+        Ok(())
+    };
+    // The run so far: its last slide and the page stitched from it once it scrolled
+    let mut run: Option<(DynamicImage, Option<Page>)> = None;
+    for slide in &manifest.slides {
+        let image = open_frame(&slides_dir.join(&slide.file)).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        if let Some((last, page)) = run.as_mut() {
+            if let Some(scroll) = find_scroll(&mut comparer, last, &image, config.threshold) {
+                let page = page.get_or_insert_with(|| Page::new(&last.to_rgb8(), scroll));
+                page.extend(&image.to_rgb8(), scroll);
+                *last = image;
+                continue;
+            }
+        }
+        if let Some((last, page)) = run.replace((image, None)) {
+            write(page.map_or_else(|| last.to_rgb8(), |page| page.stitched()))?;
+        }
+    }
+    if let Some((last, page)) = run {
+        write(page.map_or_else(|| last.to_rgb8(), |page| page.stitched()))?;
+    }
+
+    // Those of an earlier export that are no more
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        let number = name.strip_prefix("panorama_").and_then(|rest| rest.strip_suffix(".png")).and_then(|n| n.parse::<usize>().ok());
+        if number.is_some_and(|number| number > written) {
+            fs::remove_file(&path)?;
+        }
+    }
+    Ok(written)
+}
+
+/// The deck's title, or the input it was extracted from
+fn document_title(manifest: &Manifest) -> &str {
+    let title = manifest.catalog.as_ref().and_then(|catalog| catalog.title.as_deref());
+    title.unwrap_or_else(|| manifest.sources.first().map_or("Slides", |source| source.path.as_str()))
+}
+
+/// What the images of `slides_dir` are linked relative to from a file at `path`: nothing
+/// when it sits next to them, their absolute path otherwise
+fn link_base(slides_dir: &Path, path: &Path) -> Result<PathBuf, Error> {
+    let next_to_slides = path.parent().is_some_and(|parent| parent.canonicalize().ok() == slides_dir.canonicalize().ok());
+    Ok(if next_to_slides { PathBuf::new() } else { slides_dir.canonicalize()? })
+}
+
+/// An HTML page showing the slides of `manifest` in order, to be written to `path`
+pub fn html(slides_dir: &Path, manifest: &Manifest, path: &Path) -> Result<String, Error> {
+    let base = link_base(slides_dir, path)?;
+    let title = document_title(manifest);
+
+    let mut html = String::new();
+    let _ = writeln!(html, "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>", escape(title));
+    if let Some(catalog) = &manifest.catalog {
+        for (name, value) in [("author", &catalog.speaker), ("course", &catalog.course), ("date", &catalog.date)] {
+            if let Some(value) = value {
+                let _ = writeln!(html, "<meta name=\"{}\" content=\"{}\">", name, escape(value));
+            }
+        }
+    }
+    html.push_str("<style>body{font-family:sans-serif;max-width:60rem;margin:auto}img{width:100%;border:1px solid #ccc}</style>\n");
+    let _ = writeln!(html, "</head>\n<body>\n<h1>{}</h1>", escape(title));
+    for slide in &manifest.slides {
+        let src = base.join(&slide.file);
+        let _ = writeln!(
+            html,
+            "<figure id=\"slide-{index}\">\n<img src=\"{src}\" alt=\"Slide {index}\">\n<figcaption>Slide {index} at {time}{title}</figcaption>\n</figure>",
+            index = slide.index,
+            src = escape(&src.to_string_lossy()),
+            time = clock(slide.timestamp),
+            title = slide.title.as_deref().map(|title| format!(": {}", escape(title))).unwrap_or_default()
+        );
+        if let Some(summary) = &slide.summary {
+            let _ = writeln!(html, "<p>{}</p>", escape(summary));
+        }
+    }
+    html.push_str("</body>\n</html>\n");
+    Ok(html)
+}
+
+/// A Markdown document showing the slides of `manifest` in order, to be written to `path`
+pub fn markdown(slides_dir: &Path, manifest: &Manifest, path: &Path) -> Result<String, Error> {
+    let base = link_base(slides_dir, path)?;
+    let title = document_title(manifest);
+    let mut markdown = format!("# {}\n", title);
+    for slide in &manifest.slides {
+        // Angle brackets keep spaces in the path from ending the link
+        let _ = write!(
+            markdown,
+            "\n## Slide {index} at {time}{title}\n\n![Slide {index}](<{src}>)\n",
+            index = slide.index,
+            time = clock(slide.timestamp),
+            title = slide.title.as_deref().map(|title| format!(": {}", title)).unwrap_or_default(),
+            src = base.join(&slide.file).to_string_lossy()
+        );
+        if let Some(summary) = &slide.summary {
+            let _ = writeln!(markdown, "\n{}", summary);
+        }
+    }
+    Ok(markdown)
+}
+
+/// A Markdown document with the text of each slide of `manifest` in order, under its time
+pub fn transcript(manifest: &Manifest) -> String {
+    let title = document_title(manifest);
+    let mut markdown = format!("# {}\n", title);
+    for slide in &manifest.slides {
+        let title = slide.title.as_deref().map(|title| format!(" {}", title)).unwrap_or_default();
+        let speaker = slide.speaker.as_deref().map(|speaker| format!(", {}", speaker)).unwrap_or_default();
+        let _ = writeln!(markdown, "\n## {}{} (slide {}{})", clock(slide.timestamp), title, slide.index, speaker);
+        if let Some(text) = slide.text.as_deref().map(str::trim).filter(|text| !text.is_empty()) {
+            let _ = writeln!(markdown, "\n{}", text);
+        }
+    }
+    markdown
+}
+
+/// A chapter list with a line per slide of `manifest`, starting at 00:00 as video sites require
+pub fn chapters(manifest: &Manifest) -> String {
+    let mut chapters = String::new();
+    for (i, slide) in manifest.slides.iter().enumerate() {
+        let time = if i == 0 { 0.0 } else { slide.timestamp };
+        let title = slide.title.clone().unwrap_or_else(|| format!("Slide {}", slide.index));
+        let _ = writeln!(chapters, "{} {}", clock(time), title);
+    }
+    chapters
+}
+
+/// The manifest entry of each slide of `manifest` as a line of JSON
+pub fn ndjson(manifest: &Manifest) -> Result<String, Error> {
+    let mut lines = String::new();
+    for slide in &manifest.slides {
+        lines.push_str(&serde_json::to_string(slide).map_err(io::Error::from)?);
+        lines.push('\n');
+    }
+    Ok(lines)
+}
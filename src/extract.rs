@@ -0,0 +1,624 @@
+use std::collections::VecDeque;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+
+use crate::config::{Config, Diarization, SyncMode};
+use crate::error::Error;
+use crate::metrics;
+use crate::pipeline::{is_frame_file, sort_frames};
+use crate::preflight;
+use crate::probe::probe;
+use crate::progress::{CancellationToken, Progress, Stage};
+use crate::runlog::RunLog;
+
+/// How often the child is checked for exit and the token for cancellation
+pub(crate) const POLL_INTERVAL: Duration = Duration::from_millis(100);
+/// Lines of ffmpeg's stderr quoted when it fails
+const STDERR_TAIL_LINES: usize = 20;
+/// Seconds before the first `--retries` attempt when `--retry-backoff` is not given
+const DEFAULT_RETRY_BACKOFF: f64 = 2.0;
+
+/// Input path that has ffmpeg read the video from stdin
+pub const STDIN_INPUT: &str = "-";
+
+/// Whether `input` is the video piped into stdin
+pub fn is_stdin(input: &Path) -> bool {
+    input == Path::new(STDIN_INPUT)
+}
+
+/// Whether `input` is a named pipe (FIFO), like one a recorder streams the session into
+pub fn is_fifo(input: &Path) -> bool {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::FileTypeExt;
+        fs::metadata(input).is_ok_and(|metadata| metadata.file_type().is_fifo())
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = input;
+        false
+    }
+}
+
+/// Whether `input` comes through stdin or a named pipe, to be read as it arrives until the writer closes it
+pub fn is_piped(input: &Path) -> bool {
+    is_stdin(input) || is_fifo(input)
+}
+
+/// Fail unless `config` reads its input just once from start to end, if it is piped:
+/// what was piped in cannot be probed, seeked or read again
+pub fn check_piped(config: &Config) -> Result<(), io::Error> {
+    if !is_piped(&config.input_file) {
+        return Ok(());
+    }
+    let conflicting = [
+        (config.auto_fps, "--auto-fps"),
+        (config.segments > 1, "--segments"),
+        (config.adaptive.is_some(), "--adaptive"),
+        (config.frame_cache.is_some(), "--frame-cache"),
+        (config.split_monitors.is_some(), "--split-monitors"),
+        (config.retries > 0, "--retries"),
+        (config.retry_ignore_errors, "--retry-ignore-errors"),
+        (config.snap_to_pauses.is_some(), "--snap-to-pauses"),
+        (config.split_decks && config.deck_audio, "--deck-audio"),
+        (matches!(config.diarize, Some(Diarization::Command { .. })), "--diarize with a command"),
+        (config.camera_file.is_some() && config.sync == SyncMode::Audio, "--sync audio"),
+    ];
+    match conflicting.iter().find(|&&(set, _)| set) {
+        Some((_, option)) => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{} needs to read the video more than once, which it cannot from a pipe", option),
+        )),
+        None => Ok(()),
+    }
+}
+
+/// Fail with `UnreadableInput` unless `input` is piped, a URL (left to ffmpeg) or a file that can be opened;
+/// opening a named pipe would take what the recorder wrote first
+pub fn check_input(input: &Path) -> Result<(), Error> {
+    if is_piped(input) || input.to_string_lossy().contains("://") {
+        return Ok(());
+    }
+    File::open(input)
+        .map(|_| ())
+        .map_err(|source| Error::UnreadableInput { path: input.to_path_buf(), source })
+}
+
+/// Move `from` to `to`, copying when they are on different filesystems
+pub fn move_file(from: &Path, to: &Path) -> Result<(), io::Error> {
+    if fs::rename(from, to).is_err() {
+        fs::copy(from, to)?;
+        fs::remove_file(from)?;
+    }
+    Ok(())
+}
+
+/// The last lines ffmpeg wrote to stderr; all of them also go to the log
+#[derive(Debug, Default)]
+pub struct StderrTail {
+    lines: VecDeque<String>,
+}
+
+impl StderrTail {
+    pub fn push(&mut self, log: &RunLog, line: String) {
+        log.debug(format_args!("ffmpeg: {}", line));
+        if self.lines.len() == STDERR_TAIL_LINES {
+            self.lines.pop_front();
+        }
+        self.lines.push_back(line);
+    }
+
+    pub fn into_string(self) -> String {
+        Vec::from(self.lines).join("\n")
+    }
+}
+
+/// Remove the frames a failed ffmpeg run left in `frames_dir` before trying again
+pub fn clear_frames(frames_dir: &Path) -> Result<(), io::Error> {
+    for entry in fs::read_dir(frames_dir)? {
+        fs::remove_file(entry?.path())?;
+    }
+    Ok(())
+}
+
+/// Frames a failed ffmpeg run wrote to `frames_dir` that the next one can carry
+/// on after; the last one is removed as it may have been cut off mid-write
+pub fn resume_point(frames_dir: &Path) -> Result<usize, io::Error> {
+    let mut written = 0;
+    while frames_dir.join(frame_name(written + 1)).exists() {
+        written += 1;
+    }
+    if written > 0 {
+        fs::remove_file(frames_dir.join(frame_name(written)))?;
+        written -= 1;
+    }
+    Ok(written)
+}
+
+/// File name ffmpeg gives the sampled frame `number`, counted from 1
+pub(crate) fn frame_name(number: usize) -> String {
+    format!("frame_{:06}.png", number)
+}
+
+/// Whether a failed extraction is worth resuming after `attempt` earlier retries
+pub fn should_resume(config: &Config, error: &Error, attempt: u32) -> bool {
+    attempt < config.retries && matches!(error, Error::FfmpegFailed { .. } | Error::FfmpegStalled { .. })
+}
+
+/// How long to wait before retry `attempt`, counted from 1; the wait doubles with each
+pub fn retry_delay(config: &Config, attempt: u32) -> Duration {
+    let backoff = config.retry_backoff.unwrap_or(DEFAULT_RETRY_BACKOFF);
+    Duration::from_secs_f64(backoff.max(0.0) * 2f64.powi(attempt.saturating_sub(1) as i32))
+}
+
+/// Watches a running ffmpeg for `--ffmpeg-timeout` and `--stall-timeout`
+pub struct Watchdog {
+    timeout: Option<Duration>,
+    stall_timeout: Option<Duration>,
+    started: Instant,
+    frames: usize,
+    last_frame: Instant,
+}
+
+impl Watchdog {
+    pub fn new(config: &Config) -> Self {
+        let now = Instant::now();
+        Watchdog {
+            timeout: config.ffmpeg_timeout.map(Duration::from_secs),
+            stall_timeout: config.stall_timeout.map(Duration::from_secs),
+            started: now,
+            frames: 0,
+            last_frame: now,
+        }
+    }
+
+    /// ffmpeg reported `frames` frames written so far
+    pub fn frames(&mut self, frames: usize) {
+        if frames > self.frames {
+            self.frames = frames;
+            self.last_frame = Instant::now();
+        }
+    }
+
+    /// Why ffmpeg should be killed now, if it should
+    pub fn expired(&self) -> Option<String> {
+        if let Some(timeout) = self.timeout.filter(|timeout| self.started.elapsed() > *timeout) {
+            return Some(format!("ran longer than {}s", timeout.as_secs()));
+        }
+        if let Some(stall_timeout) = self.stall_timeout.filter(|timeout| self.last_frame.elapsed() > *timeout) {
+            return Some(format!("produced no frame for {}s after frame {}", stall_timeout.as_secs(), self.frames));
+        }
+        None
+    }
+}
+
+/// Whether a failed extraction is worth repeating with `-err_detect ignore_err`
+pub fn should_retry(config: &Config, error: &Error) -> bool {
+    config.retry_ignore_errors && matches!(error, Error::FfmpegFailed { .. } | Error::FfmpegStalled { .. })
+}
+
+/// Build the ffmpeg invocation that samples frames into `frames_dir`,
+/// reporting machine-readable progress on stdout. `lenient` makes ffmpeg
+/// skip over damaged parts of the input instead of giving up on them;
+/// `resume_after` frames already sampled are skipped, by seeking past them.
+pub fn extract_command(config: &Config, frames_dir: &Path, lenient: bool, resume_after: usize) -> Command {
+    let mut command = sampling_command(config, lenient, resume_after);
+    command
+        .arg("-progress")
+        .arg("pipe:1")  // Machine-readable progress on stdout
+        .arg("-nostats")
+        .arg("-start_number")
+        .arg((resume_after + 1).to_string());
+    png_options(config, &mut command);
+    command
+        .arg(frames_dir.join("frame_%06d.png"))  // Output pattern for frame files, enough for days at 1 fps
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    command
+}
+
+/// Build the ffmpeg invocation that writes the sampled frames to stdout as
+/// one PPM image after another, for `--in-memory`
+pub fn pipe_command(config: &Config) -> Command {
+    let mut command = sampling_command(config, false, 0);
+    command
+        .arg("-nostats")
+        .arg("-f")
+        .arg("image2pipe")
+        .arg("-c:v")
+        .arg("ppm")
+        .arg("pipe:1")
+        .stdin(input_stdin(config))
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    command
+}
+
+/// What ffmpeg's stdin is: ours when the video is piped in, nothing otherwise
+fn input_stdin(config: &Config) -> Stdio {
+    if is_stdin(&config.input_file) {
+        Stdio::inherit()
+    } else {
+        Stdio::null()
+    }
+}
+
+/// ffmpeg with the input, its sampling rate and crop, but no output yet
+fn sampling_command(config: &Config, lenient: bool, resume_after: usize) -> Command {
+    let start = resume_after as f64 / config.fps as f64;
+    let length = config.duration.map(|duration| (duration - start).max(0.0));
+    window_command(config, lenient, start, length, &config.fps.to_string())
+}
+
+/// ffmpeg sampling `rate` frames a second (e.g. `1/5`) from `length` seconds
+/// of the input from `start` on, all of it when `None`, with no output yet
+fn window_command(config: &Config, lenient: bool, start: f64, length: Option<f64>, rate: &str) -> Command {
+    let mut command = Command::new("ffmpeg");
+    if lenient {
+        command.arg("-err_detect").arg("ignore_err");
+    }
+    if let Some(threads) = config.ffmpeg_threads {
+        // Cap decoding and filtering so parallel jobs share the machine
+        command
+            .arg("-threads")
+            .arg(threads.to_string())
+            .arg("-filter_threads")
+            .arg(threads.to_string());
+    }
+    if start > 0.0 {
+        command.arg("-ss").arg(start.to_string());
+    }
+    if let Some(length) = length {
+        command.arg("-t").arg(length.to_string());
+    }
+    command
+        .arg("-i")
+        .arg(if is_stdin(&config.input_file) { Path::new("pipe:0") } else { &config.input_file })
+        .arg("-vf")
+        .arg(match config.crop {
+            Some(crop) => format!("{},fps={}", crop.filter(), rate),
+            None => format!("fps={}", rate),  // Set the frame extraction rate
+        });
+    command
+}
+
+/// Add the PNG encoder's settings for the sampled frames to `command`
+fn png_options(config: &Config, command: &mut Command) {
+    if let Some(level) = config.png_compression {
+        command.arg("-compression_level").arg(level.to_string());
+    }
+    if let Some(prediction) = config.png_prediction {
+        command.arg("-pred").arg(prediction.ffmpeg_name());
+    }
+}
+
+/// Sample `rate` frames a second from `length` seconds of the input from
+/// `start` on into `frames_dir`, waiting for ffmpeg to finish
+pub fn sample_window(
+    config: &Config,
+    frames_dir: &Path,
+    start: f64,
+    length: Option<f64>,
+    rate: &str,
+) -> Result<(), Error> {
+    let mut command = window_command(config, false, start, length, rate);
+    png_options(config, &mut command);
+    command
+        .arg("-nostats")
+        .arg(frames_dir.join("frame_%06d.png"))
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped());
+    let output = command.output().map_err(Error::ffmpeg_spawn)?;
+    if !output.status.success() {
+        metrics::ffmpeg_failed();
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let lines: Vec<&str> = stderr.lines().collect();
+        let tail = lines[lines.len().saturating_sub(STDERR_TAIL_LINES)..].join("\n");
+        return Err(Error::ffmpeg_failed(&config.input_file, output.status, tail));
+    }
+    Ok(())
+}
+
+/// Parse the frame counter out of one line of ffmpeg's `-progress` output
+pub fn parse_progress_frames(line: &str) -> Option<usize> {
+    line.strip_prefix("frame=")?.trim().parse().ok()
+}
+
+/// Extract frames from the video into `frames_dir` using ffmpeg
+pub fn extract_frames(
+    config: &Config,
+    frames_dir: &Path,
+    log: &RunLog,
+    progress: &dyn Fn(Progress),
+    cancel: &CancellationToken,
+) -> Result<(), Error> {
+    check_input(&config.input_file)?;
+    if !config.skip_space_check {
+        preflight::check_space(config, frames_dir, log)?;
+    }
+    if config.segments > 1 {
+        return extract_segments(config, frames_dir, log, progress, cancel);
+    }
+
+    let (mut attempt, mut resume_after) = (0, 0);
+    loop {
+        match run_ffmpeg(config, frames_dir, false, resume_after, log, progress, cancel) {
+            Err(e) if should_resume(config, &e, attempt) => {
+                attempt += 1;
+                resume_after = resume_point(frames_dir)?;
+                let delay = retry_delay(config, attempt);
+                log.warn(format_args!("{}", e));
+                log.warn(format_args!(
+                    "Retrying in {:.0}s from {:.1}s ({} of {}).",
+                    delay.as_secs_f64(),
+                    resume_after as f64 / config.fps as f64,
+                    attempt,
+                    config.retries
+                ));
+                if sleep_unless_cancelled(delay, cancel) {
+                    return Err(Error::Cancelled);
+                }
+            }
+            Err(e) if should_retry(config, &e) => {
+                log.warn(format_args!("{}", e));
+                log.warn("Retrying with -err_detect ignore_err.");
+                clear_frames(frames_dir)?;
+                return run_ffmpeg(config, frames_dir, true, 0, log, progress, cancel);
+            }
+            outcome => return outcome,
+        }
+    }
+}
+
+/// Frame files ffmpeg wrote to `dir`, in order
+pub fn sampled_frames(dir: &Path) -> Result<Vec<PathBuf>, io::Error> {
+    let mut frames: Vec<PathBuf> =
+        fs::read_dir(dir)?.filter_map(Result::ok).map(|entry| entry.path()).filter(|path| is_frame_file(path)).collect();
+    sort_frames(&mut frames);
+    Ok(frames)
+}
+
+/// The last lines of the ffmpeg log at `path`, for the error message
+fn log_tail(path: &Path) -> String {
+    let log = fs::read_to_string(path).unwrap_or_default();
+    let lines: Vec<&str> = log.lines().collect();
+    lines[lines.len().saturating_sub(STDERR_TAIL_LINES)..].join("\n")
+}
+
+/// Sample `config.input_file` as `config.segments` stretches of equal length at
+/// once, one ffmpeg each, then number the frames in order as a single run would.
+/// Each ffmpeg is watched for `--ffmpeg-timeout` and `--stall-timeout` by the frames
+/// it has written, and all of them are killed as soon as one fails or stalls.
+fn extract_segments(
+    config: &Config,
+    frames_dir: &Path,
+    log: &RunLog,
+    progress: &dyn Fn(Progress),
+    cancel: &CancellationToken,
+) -> Result<(), Error> {
+    let info = probe(&config.input_file)?;
+    let fps = config.fps as f64;
+    let duration = config.duration.map_or(info.duration, |duration| duration.min(info.duration));
+    let total = (duration * fps).ceil() as usize;
+    // Stretches start on a sampled frame, so each frame keeps its time
+    let per_segment = total.div_ceil(config.segments as usize).max(1);
+
+    let mut segments = Vec::new();
+    for (index, first) in (0..total).step_by(per_segment).enumerate() {
+        let dir = frames_dir.join(format!("segment_{:03}", index));
+        fs::create_dir(&dir)?;
+        let length = per_segment.min(total - first) as f64 / fps;
+        let mut command = window_command(config, false, first as f64 / fps, Some(length), &config.fps.to_string());
+        // Written to a file rather than a pipe, which nothing would drain while the others run
+        let stderr = File::create(dir.join("ffmpeg.log"))?;
+        png_options(config, &mut command);
+        command.arg("-nostats").arg(dir.join("frame_%06d.png")).stdin(Stdio::null()).stdout(Stdio::null()).stderr(stderr);
+        segments.push((command.spawn().map_err(Error::ffmpeg_spawn)?, dir));
+    }
+    log.info(format_args!("Sampling {} stretch(es) of {:.0}s at once.", segments.len(), per_segment as f64 / fps));
+
+    let mut running: Vec<usize> = (0..segments.len()).collect();
+    let mut watchdogs: Vec<Watchdog> = segments.iter().map(|_| Watchdog::new(config)).collect();
+    let mut sampled = vec![0; segments.len()];
+    while !running.is_empty() {
+        if cancel.is_cancelled() {
+            for (child, _) in &mut segments {
+                let _ = child.kill();
+                let _ = child.wait();
+            }
+            return Err(Error::Cancelled);
+        }
+        let mut failed = None;
+        running.retain(|&index| {
+            let (child, dir) = &mut segments[index];
+            sampled[index] = sampled_frames(dir).map_or(sampled[index], |frames| frames.len());
+            match child.try_wait() {
+                Ok(Some(status)) if status.success() => false,
+                Ok(Some(status)) => {
+                    failed.get_or_insert_with(|| Error::ffmpeg_failed(&config.input_file, status, log_tail(&dir.join("ffmpeg.log"))));
+                    false
+                }
+                Ok(None) => {
+                    watchdogs[index].frames(sampled[index]);
+                    match watchdogs[index].expired() {
+                        Some(reason) => {
+                            let input = config.input_file.display().to_string();
+                            let stderr = log_tail(&dir.join("ffmpeg.log"));
+                            failed.get_or_insert(Error::FfmpegStalled { input, reason, stderr });
+                            false
+                        }
+                        None => true,
+                    }
+                }
+                Err(e) => {
+                    failed.get_or_insert(Error::Io(e));
+                    false
+                }
+            }
+        });
+        if let Some(e) = failed {
+            metrics::ffmpeg_failed();
+            for (child, _) in &mut segments {
+                let _ = child.kill();
+                let _ = child.wait();
+            }
+            return Err(e);
+        }
+        progress(Progress { stage: Stage::Extracting, done: sampled.iter().sum(), total: Some(total) });
+        thread::sleep(POLL_INTERVAL);
+    }
+
+    let mut number = 0;
+    for (_, dir) in &segments {
+        for frame in sampled_frames(dir)? {
+            number += 1;
+            move_file(&frame, &frames_dir.join(frame_name(number)))?;
+        }
+        fs::remove_dir_all(dir)?;
+    }
+    log.info("Frames extracted successfully.");
+    Ok(())
+}
+
+/// Sleep for `delay`, returning `true` as soon as `cancel` is cancelled
+fn sleep_unless_cancelled(delay: Duration, cancel: &CancellationToken) -> bool {
+    let until = Instant::now() + delay;
+    while !cancel.is_cancelled() {
+        let left = until.saturating_duration_since(Instant::now());
+        if left.is_zero() {
+            return false;
+        }
+        thread::sleep(left.min(POLL_INTERVAL));
+    }
+    true
+}
+
+/// One ffmpeg run of `extract_frames`
+fn run_ffmpeg(
+    config: &Config,
+    frames_dir: &Path,
+    lenient: bool,
+    resume_after: usize,
+    log: &RunLog,
+    progress: &dyn Fn(Progress),
+    cancel: &CancellationToken,
+) -> Result<(), Error> {
+    // Spawn ffmpeg process to extract frames
+    let mut child = extract_command(config, frames_dir, lenient, resume_after).spawn().map_err(Error::ffmpeg_spawn)?;
+
+    // Drain stderr so ffmpeg never blocks on it, keeping the end for the error message
+    let stderr = child.stderr.take().expect("stderr is piped");
+    let (stderr_log, span) = (log.clone(), tracing::Span::current());
+    let stderr_reader = thread::spawn(move || {
+        let _span = span.entered();
+        let mut tail = StderrTail::default();
+        for line in BufReader::new(stderr).lines().map_while(Result::ok) {
+            tail.push(&stderr_log, line);
+        }
+        tail
+    });
+
+    // Read progress on a separate thread so the main loop stays free to notice cancellation
+    let stdout = child.stdout.take().expect("stdout is piped");
+    let (frames_tx, frames_rx) = mpsc::channel();
+    let reader = thread::spawn(move || {
+        for line in BufReader::new(stdout).lines().map_while(Result::ok) {
+            if let Some(frames) = parse_progress_frames(&line) {
+                if frames_tx.send(frames).is_err() {
+                    break;
+                }
+            }
+        }
+    });
+
+    let mut watchdog = Watchdog::new(config);
+    let status = loop {
+        let expired = watchdog.expired();
+        if cancel.is_cancelled() || expired.is_some() {
+            // Kill rather than wait, a long video could otherwise run for hours
+            child.kill()?;
+            child.wait()?;
+            let _ = reader.join();
+            let stderr = stderr_reader.join().unwrap_or_default();
+            return Err(match expired {
+                Some(reason) => {
+                    metrics::ffmpeg_failed();
+                    Error::FfmpegStalled { input: config.input_file.display().to_string(), reason, stderr: stderr.into_string() }
+                }
+                None => Error::Cancelled,
+            });
+        }
+
+        match frames_rx.recv_timeout(POLL_INTERVAL) {
+            Ok(frames) => {
+                watchdog.frames(frames);
+                progress(Progress { stage: Stage::Extracting, done: resume_after + frames, total: None });
+            }
+            Err(RecvTimeoutError::Timeout) => {}
+            // ffmpeg closed stdout and is on its way out
+            Err(RecvTimeoutError::Disconnected) => thread::sleep(POLL_INTERVAL),
+        }
+
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+    };
+    let _ = reader.join();
+    let stderr = stderr_reader.join().unwrap_or_default();
+
+    if !status.success() {
+        metrics::ffmpeg_failed();
+        return Err(Error::ffmpeg_failed(&config.input_file, status, stderr.into_string()));
+    }
+    log.info("Frames extracted successfully.");
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_the_frame_counter_of_progress_lines() {
+        assert_eq!(parse_progress_frames("frame=42"), Some(42));
+        assert_eq!(parse_progress_frames("frame=  7 "), Some(7));
+        assert_eq!(parse_progress_frames("fps=25.0"), None);
+        assert_eq!(parse_progress_frames("frame=N/A"), None);
+        assert_eq!(parse_progress_frames("progress=end"), None);
+    }
+
+    #[test]
+    fn resumes_before_the_last_frame_written() {
+        let frames_dir = tempfile::tempdir().unwrap();
+        assert_eq!(resume_point(frames_dir.path()).unwrap(), 0);
+        for number in 1..=3 {
+            fs::write(frames_dir.path().join(frame_name(number)), b"png").unwrap();
+        }
+        // A gap ends the frames ffmpeg wrote in order
+        fs::write(frames_dir.path().join(frame_name(5)), b"png").unwrap();
+        assert_eq!(resume_point(frames_dir.path()).unwrap(), 2);
+        assert!(!frames_dir.path().join(frame_name(3)).exists());
+        assert!(frames_dir.path().join(frame_name(2)).exists());
+    }
+
+    #[test]
+    fn retries_back_off_and_run_out() {
+        let mut config = Config::new("talk.mp4");
+        config.retries = 2;
+        config.retry_backoff = Some(1.5);
+        let failed = Error::FfmpegStalled { input: "talk.mp4".to_string(), reason: "stalled".to_string(), stderr: String::new() };
+        assert_eq!(retry_delay(&config, 1), Duration::from_secs_f64(1.5));
+        assert_eq!(retry_delay(&config, 3), Duration::from_secs_f64(6.0));
+        assert!(should_resume(&config, &failed, 1));
+        assert!(!should_resume(&config, &failed, 2));
+        assert!(!should_resume(&config, &Error::Cancelled, 0));
+    }
+}
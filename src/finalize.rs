@@ -0,0 +1,186 @@
+//! `finalize`: bring a run's output directory back in order after slides
+//! were deleted or added by hand. The remaining images are numbered again
+//! without gaps, the manifest is rebuilt from them and whatever was written
+//! from the slides (sidecars, `links.md`, exports) is written again.
+//!
+//! A slide that stayed keeps its manifest entry. An image that is not in the
+//! manifest but is one of the run's sampled frames gets that frame's
+//! timestamp; any other image is marked as added by hand and takes the
+//! timestamp of the slide before it. Added slides are read with Tesseract if
+//! the run was made with `--ocr`.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::classify;
+use crate::config::SidecarFormat;
+use crate::error::Error;
+use crate::export::{export, ExportFormat};
+use crate::identity;
+use crate::links::{self, LINKS_FILE};
+use crate::lock::{lock_output, write_atomic};
+use crate::manifest::{Manifest, Slide};
+use crate::ocr;
+use crate::pipeline::sort_frames;
+use crate::runlog::RunLog;
+use crate::sidecar;
+
+/// What `finalize` did to a slides directory
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Finalized {
+    /// Manifest entries whose image was gone
+    pub removed: usize,
+    /// Images the manifest didn't list, now in it
+    pub added: usize,
+    pub slides: usize,
+}
+
+/// Image files directly in `dir`, in name order
+fn slide_images(dir: &Path) -> Result<Vec<PathBuf>, io::Error> {
+    let mut images: Vec<PathBuf> = fs::read_dir(dir)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<Vec<_>, _>>()?
+        .into_iter()
+        .filter(|path| {
+            let extension = path.extension().and_then(|s| s.to_str()).map(str::to_ascii_lowercase);
+            path.is_file() && matches!(extension.as_deref(), Some("png" | "jpg" | "jpeg"))
+        })
+        .collect();
+    sort_frames(&mut images);
+    Ok(images)
+}
+
+/// `slide_007.png` for the 7th of up to 999 slides
+fn numbered_name(index: usize, count: usize, file: &str) -> String {
+    let width = count.to_string().len().max(3);
+    let extension = Path::new(file).extension().and_then(|s| s.to_str()).unwrap_or("png");
+    let name = format!("slide_{:0width$}.{}", index, extension, width = width);
+    match Path::new(file).parent().filter(|parent| !parent.as_os_str().is_empty()) {
+        Some(parent) => format!("{}/{}", parent.to_string_lossy(), name),
+        None => name,
+    }
+}
+
+/// The format of the sidecar next to `file`, if it has one
+pub(crate) fn sidecar_format(slides_dir: &Path, file: &str) -> Option<SidecarFormat> {
+    [SidecarFormat::Json, SidecarFormat::Yaml].into_iter().find(|&format| slides_dir.join(sidecar::file_name(file, format)).is_file())
+}
+
+/// Rebuild the manifest of the run in `slides_dir` from the images in it, numbering them again
+pub fn finalize(slides_dir: &Path) -> Result<Finalized, Error> {
+    let _lock = lock_output(slides_dir)?;
+    let mut manifest = Manifest::read(slides_dir)?;
+    let listed = manifest.slides.len();
+    let sidecars = manifest.slides.iter().find_map(|slide| sidecar_format(slides_dir, &slide.file));
+    // Sidecars are written again under the new names below
+    if let Some(format) = sidecars {
+        for slide in &manifest.slides {
+            let path = slides_dir.join(sidecar::file_name(&slide.file, format));
+            if path.is_file() {
+                fs::remove_file(path)?;
+            }
+        }
+    }
+
+    // Slides in a deck-N directory stay in it; the top level is where images are added
+    let (mut slides, top_level): (Vec<Slide>, Vec<Slide>) =
+        manifest.slides.drain(..).filter(|slide| slides_dir.join(&slide.file).is_file()).partition(|slide| slide.file.contains('/'));
+    let removed = listed - slides.len() - top_level.len();
+
+    let mut added = 0;
+    let mut previous = 0.0;
+    for path in slide_images(slides_dir)? {
+        let file = path.file_name().unwrap_or_default().to_string_lossy().into_owned();
+        let slide = match top_level.iter().find(|slide| slide.file == file) {
+            Some(slide) => slide.clone(),
+            None => {
+                added += 1;
+                let frame = manifest.frames.iter().find(|frame| frame.file == file);
+                Slide {
+                    index: 0,
+                    file,
+                    id: None,
+                    timestamp: frame.map_or(previous, |frame| frame.timestamp),
+                    detected: None,
+                    camera_timestamp: None,
+                    monitor: None,
+                    shown: Vec::new(),
+                    qr_codes: Vec::new(),
+                    urls: Vec::new(),
+                    deck: None,
+                    confidence: None,
+                    change: None,
+                    kind: None,
+                    zooms: Vec::new(),
+                    diff: None,
+                    text: None,
+                    language: None,
+                    speaker: None,
+                    title: None,
+                    summary: None,
+                    manual: frame.is_none(),
+                }
+            }
+        };
+        previous = slide.timestamp;
+        slides.push(slide);
+    }
+    slides.sort_by(|a, b| a.timestamp.total_cmp(&b.timestamp));
+
+    for frame in &mut manifest.frames {
+        frame.kept = slides.iter().any(|slide| slide.file.rsplit('/').next() == Some(frame.file.as_str()));
+    }
+
+    // Through temporary names first, so no slide is renamed over one not moved yet
+    let count = slides.len();
+    let mut staged = Vec::new();
+    for (i, slide) in slides.iter_mut().enumerate() {
+        slide.index = i + 1;
+        let name = numbered_name(slide.index, count, &slide.file);
+        if name != slide.file {
+            let temporary = format!("{}.finalizing", name);
+            fs::rename(slides_dir.join(&slide.file), slides_dir.join(&temporary))?;
+            staged.push((temporary, name.clone()));
+            slide.file = name;
+        }
+    }
+    for (temporary, name) in &staged {
+        fs::rename(slides_dir.join(temporary), slides_dir.join(name))?;
+    }
+    manifest.slides = slides;
+    identity::assign_ids(slides_dir, &mut manifest)?;
+    // A run with --ocr left its cache behind; read the slides added since, with Tesseract
+    // as the engine the run used isn't recorded
+    if slides_dir.join(ocr::CACHE_DIR).is_dir() {
+        ocr::read_slides(&ocr::Tesseract, slides_dir, &mut manifest, None, &RunLog::default())?;
+    }
+    classify::tag_kinds(slides_dir, &mut manifest)?;
+
+    rewrite(slides_dir, &manifest, sidecars)?;
+    Ok(Finalized { removed, added, slides: manifest.slides.len() })
+}
+
+/// Write `manifest` into `slides_dir` with the sidecars, `links.md` and
+/// existing exports that were made from it
+pub(crate) fn rewrite(slides_dir: &Path, manifest: &Manifest, sidecars: Option<SidecarFormat>) -> Result<(), Error> {
+    if let Some(format) = sidecars {
+        for slide in &manifest.slides {
+            write_atomic(&slides_dir.join(sidecar::file_name(&slide.file, format)), sidecar::contents(slide, format)?)?;
+        }
+    }
+    match links::markdown(manifest) {
+        Some(markdown) => write_atomic(&slides_dir.join(LINKS_FILE), markdown)?,
+        None if slides_dir.join(LINKS_FILE).is_file() => fs::remove_file(slides_dir.join(LINKS_FILE))?,
+        None => {}
+    }
+    manifest.write(slides_dir)?;
+
+    for format in [ExportFormat::Pdf, ExportFormat::Html, ExportFormat::Markdown, ExportFormat::Ndjson, ExportFormat::Transcript, ExportFormat::Chapters, ExportFormat::Panorama] {
+        let path = format.default_path(slides_dir);
+        if path.exists() {
+            export(slides_dir, format, &path, &[])?;
+        }
+    }
+    Ok(())
+}
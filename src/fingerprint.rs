@@ -0,0 +1,167 @@
+//! Recognising a video that was already processed with the same settings, so
+//! reruns (e.g. a nightly batch) only spend time on new recordings.
+
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::borrow::Cow;
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::Path;
+
+use crate::config::{AspectRatio, BadFramePolicy, Color, Config, CropArea, Diarization, LimitPolicy, LiveFormat, Mask, Metric, OcrBackend, Prefilter, SidecarFormat, SyncMode};
+use crate::extract::is_piped;
+use crate::manifest::{Fingerprint, Manifest};
+use crate::s3::hex;
+
+/// Bytes hashed from each end of the input; the whole file would take as long as reading the video
+const SAMPLE_SIZE: u64 = 1 << 20;
+
+/// The settings that change which slides come out of a video
+#[derive(Serialize)]
+struct Settings<'a> {
+    fps: u32,
+    duration: Option<f64>,
+    crop: Option<CropArea>,
+    threshold: f64,
+    metric: Metric,
+    low_threshold: Option<f64>,
+    camera_file: Option<Cow<'a, str>>,
+    sync: SyncMode,
+    camera_offset: f64,
+    max_sync_offset: f64,
+    compare_stride: u32,
+    prefilter: Option<Prefilter>,
+    prefilter_size: Option<u32>,
+    text_weight: Option<f64>,
+    normalize_brightness: bool,
+    masks: Vec<Mask>,
+    ignore_embedded_video: bool,
+    ignore_live_footer: bool,
+    motion_streak: u32,
+    whiteboard: bool,
+    ink_pause: Option<f64>,
+    stitch_scrolls: bool,
+    ignore_zoom: bool,
+    save_zooms: bool,
+    save_diffs: bool,
+    settle_frames: u32,
+    name_template: Option<&'a str>,
+    min_confidence: Option<f64>,
+    merge_revisits: bool,
+    revisit_threshold: Option<f64>,
+    find_links: bool,
+    ocr: Option<(&'a OcrBackend, bool)>,
+    summarize: Option<(&'a str, Option<&'a str>, bool)>,
+    diarize: Option<&'a Diarization>,
+    split_decks: bool,
+    deck_gap: Option<f64>,
+    deck_audio: bool,
+    trim_idle: Option<f64>,
+    idle_keywords: Option<&'a [String]>,
+    snap_to_pauses: Option<f64>,
+    sidecars: Option<SidecarFormat>,
+    report: bool,
+    live: &'a [LiveFormat],
+    canvas: Option<AspectRatio>,
+    canvas_background: Color,
+    adaptive: Option<f64>,
+    coarse_threshold: Option<f64>,
+    on_bad_frame: BadFramePolicy,
+    max_slides: Option<usize>,
+    max_output_size: Option<u64>,
+    on_limit: LimitPolicy,
+}
+
+/// Hash of the size and the first and last megabyte of a file
+pub fn hash_input(path: &Path) -> Result<String, io::Error> {
+    let mut file = File::open(path)?;
+    let size = file.metadata()?.len();
+    let mut hasher = Sha256::new();
+    hasher.update(size.to_le_bytes());
+
+    let mut buffer = Vec::new();
+    (&mut file).take(SAMPLE_SIZE).read_to_end(&mut buffer)?;
+    if size > SAMPLE_SIZE {
+        file.seek(SeekFrom::Start(size.saturating_sub(SAMPLE_SIZE).max(SAMPLE_SIZE)))?;
+        file.take(SAMPLE_SIZE).read_to_end(&mut buffer)?;
+    }
+    hasher.update(&buffer);
+    Ok(hex(&hasher.finalize()))
+}
+
+/// Hash of the settings in `config` that affect the result
+pub fn hash_settings(config: &Config) -> String {
+    let settings = Settings {
+        fps: config.fps,
+        duration: config.duration,
+        crop: config.crop,
+        threshold: config.threshold,
+        metric: config.metric,
+        low_threshold: config.low_threshold,
+        camera_file: config.camera_file.as_deref().map(Path::to_string_lossy),
+        sync: config.sync,
+        camera_offset: config.camera_offset,
+        max_sync_offset: config.max_sync_offset,
+        compare_stride: config.compare_stride.max(1),
+        prefilter: config.prefilter,
+        prefilter_size: config.prefilter.and(config.prefilter_size),
+        text_weight: config.text_weight,
+        normalize_brightness: config.normalize_brightness,
+        masks: config.masks.clone(),
+        ignore_embedded_video: config.ignore_embedded_video,
+        ignore_live_footer: config.ignore_live_footer,
+        motion_streak: config.motion_streak,
+        whiteboard: config.whiteboard,
+        ink_pause: config.ink_pause,
+        stitch_scrolls: config.stitch_scrolls,
+        ignore_zoom: config.ignore_zoom,
+        save_zooms: config.ignore_zoom && config.save_zooms,
+        save_diffs: config.save_diffs,
+        settle_frames: config.settle_frames.max(1),
+        name_template: config.name_template.as_deref(),
+        min_confidence: config.min_confidence,
+        merge_revisits: config.merge_revisits,
+        revisit_threshold: config.revisit_threshold,
+        find_links: config.find_links,
+        ocr: config.ocr.then_some((&config.ocr_backend, config.ocr_by_language)),
+        summarize: config.summarize.as_deref().map(|url| (url, config.summarize_model.as_deref(), config.summarize_images)),
+        diarize: config.diarize.as_ref(),
+        split_decks: config.split_decks,
+        deck_gap: config.deck_gap,
+        deck_audio: config.deck_audio,
+        trim_idle: config.trim_idle,
+        idle_keywords: config.idle_keywords.as_deref(),
+        snap_to_pauses: config.snap_to_pauses,
+        sidecars: config.sidecars,
+        report: config.report,
+        live: &config.live,
+        canvas: config.canvas,
+        canvas_background: config.canvas_background,
+        adaptive: config.adaptive,
+        coarse_threshold: config.coarse_threshold,
+        on_bad_frame: config.on_bad_frame,
+        max_slides: config.max_slides,
+        max_output_size: config.max_output_size,
+        on_limit: config.on_limit,
+    };
+    let json = serde_json::to_vec(&settings).expect("settings serialize");
+    hex(&Sha256::digest(json))
+}
+
+/// Fingerprint of this run, if the input is a local file that can be hashed
+pub fn fingerprint(config: &Config) -> Option<Fingerprint> {
+    if is_piped(&config.input_file) || config.input_file.to_string_lossy().contains("://") {
+        return None;
+    }
+    let input = hash_input(&config.input_file).ok()?;
+    Some(Fingerprint { input, settings: hash_settings(config) })
+}
+
+/// The manifest a previous run left in the output directory, if it came from
+/// the same input and settings and all of its slides are still there
+pub fn previous_run(config: &Config, fingerprint: &Fingerprint) -> Option<Manifest> {
+    let output_dir = &config.output_dir;
+    let manifest = Manifest::read(output_dir).ok()?;
+    let complete = manifest.slides.iter().all(|slide| output_dir.join(&slide.file).is_file());
+    (manifest.fingerprint.as_ref() == Some(fingerprint) && complete).then_some(manifest)
+}
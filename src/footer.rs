@@ -0,0 +1,133 @@
+//! `--ignore-live-footer`: a clock, date or counter in the bar along the top
+//! or bottom of the screen ticking over is not a slide change, without a mask
+//! having to be drawn around it.
+//!
+//! The strips along the top and bottom edges are watched in tiles. A tile
+//! that changes while the rest of the frame stays the same, twice, is
+//! something that updates on its own, and is masked from then on like the
+//! areas of `--platform`. If they add up to more than a sliver of the frame
+//! they are taken for content and nothing is masked. The first tick of a
+//! clock still counts as a change; a counter that only changes with the
+//! slides is never masked, as it never changes on its own.
+
+use image::{DynamicImage, GenericImageView};
+
+use crate::compare::{sampled, Comparer};
+use crate::config::Mask;
+use crate::runlog::RunLog;
+
+/// Edge length in pixels of the square tiles watched
+const TILE_SIZE: u32 = 32;
+/// Share of the frame's height along the top and bottom edges watched
+const STRIP: f64 = 0.12;
+/// Share of a tile's pixels that must differ for the tile to count as changed
+const TILE_CHANGE_RATIO: f64 = 0.02;
+/// Times a tile must change on its own before it is masked
+const CHANGES_NEEDED: u32 = 2;
+/// Masks larger than this share of the frame are content, not a footer
+const MAX_MASK_RATIO: f64 = 0.05;
+
+/// Finds the tiles along the top and bottom edges that change on their own
+pub struct FooterTracker {
+    dimensions: (u32, u32),
+    columns: u32,
+    rows: u32,
+    /// Times each tile changed while nothing outside the strips did
+    changes: Vec<u32>,
+    masks: Vec<Mask>,
+    log: RunLog,
+}
+
+impl FooterTracker {
+    pub fn new(log: RunLog) -> Self {
+        FooterTracker { dimensions: (0, 0), columns: 0, rows: 0, changes: Vec::new(), masks: Vec::new(), log }
+    }
+
+    /// Forget what was seen, e.g. after the frame size changed
+    fn reset(&mut self, dimensions: (u32, u32)) {
+        self.dimensions = dimensions;
+        self.columns = dimensions.0.div_ceil(TILE_SIZE);
+        self.rows = dimensions.1.div_ceil(TILE_SIZE);
+        self.changes = vec![0; (self.columns * self.rows) as usize];
+        self.masks.clear();
+    }
+
+    /// Take the change from `reference` to `image` into account, judging the rest of the frame
+    /// unchanged up to `threshold`, and return the areas to leave out of comparing them
+    pub fn observe(&mut self, comparer: &mut Comparer, reference: &DynamicImage, image: &DynamicImage, threshold: f64) -> &[Mask] {
+        if reference.dimensions() != image.dimensions() {
+            return &[];
+        }
+        if image.dimensions() != self.dimensions {
+            self.reset(image.dimensions());
+        }
+        let stride = comparer.stride();
+        let tile_diffs = comparer.tile_diffs(reference, image, TILE_SIZE);
+
+        let (mut inside, mut outside, mut outside_pixels) = (Vec::new(), 0, 0);
+        for ty in 0..self.rows {
+            for tx in 0..self.columns {
+                let index = (ty * self.columns + tx) as usize;
+                let pixels = self.tile_pixels(tx, ty, stride);
+                if self.in_strip(ty) {
+                    if tile_diffs[index] as f64 / pixels.max(1) as f64 > TILE_CHANGE_RATIO {
+                        inside.push(index);
+                    }
+                } else {
+                    outside += tile_diffs[index];
+                    outside_pixels += pixels;
+                }
+            }
+        }
+        if inside.is_empty() || outside as f64 > threshold * outside_pixels.max(1) as f64 {
+            return &self.masks;
+        }
+        for &index in &inside {
+            self.changes[index] += 1;
+        }
+
+        let changing: Vec<usize> = (0..self.changes.len()).filter(|&index| self.changes[index] >= CHANGES_NEEDED).collect();
+        let (width, height) = self.dimensions;
+        let masked: u64 = changing.iter().map(|&index| self.tile_pixels(index as u32 % self.columns, index as u32 / self.columns, 1)).sum();
+        let masks: Vec<Mask> = if masked as f64 > MAX_MASK_RATIO * (width as u64 * height as u64) as f64 {
+            Vec::new()
+        } else {
+            changing
+                .iter()
+                .map(|&index| {
+                    let (tx, ty) = (index as u32 % self.columns, index as u32 / self.columns);
+                    let (x1, y1) = (((tx + 1) * TILE_SIZE).min(width), ((ty + 1) * TILE_SIZE).min(height));
+                    Mask {
+                        x: (tx * TILE_SIZE) as f64 / width as f64,
+                        y: (ty * TILE_SIZE) as f64 / height as f64,
+                        width: (x1 - tx * TILE_SIZE) as f64 / width as f64,
+                        height: (y1 - ty * TILE_SIZE) as f64 / height as f64,
+                    }
+                })
+                .collect()
+        };
+        if masks.len() > self.masks.len() {
+            self.log.info(format_args!(
+                "Ignoring {} tile(s) of {}x{} pixels along the edges that change on their own (clock or counter?).",
+                masks.len(),
+                TILE_SIZE,
+                TILE_SIZE
+            ));
+        }
+        self.masks = masks;
+        &self.masks
+    }
+
+    /// Whether the tiles of row `ty` are in the strip along the top or bottom edge
+    fn in_strip(&self, ty: u32) -> bool {
+        let strip = (STRIP * self.dimensions.1 as f64).ceil() as u32;
+        ty * TILE_SIZE < strip || ((ty + 1) * TILE_SIZE).min(self.dimensions.1) > self.dimensions.1.saturating_sub(strip)
+    }
+
+    /// Number of compared pixels in a tile, accounting for the cut-off tiles at the edges
+    fn tile_pixels(&self, tx: u32, ty: u32, stride: u32) -> u64 {
+        let x_end = ((tx + 1) * TILE_SIZE).min(self.dimensions.0);
+        let y_end = ((ty + 1) * TILE_SIZE).min(self.dimensions.1);
+        sampled(tx * TILE_SIZE, x_end, stride) * sampled(ty * TILE_SIZE, y_end, stride)
+    }
+}
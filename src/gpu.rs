@@ -0,0 +1,184 @@
+use image::{DynamicImage, GenericImageView};
+use wgpu::util::DeviceExt;
+
+/// Pixels are compared as packed RGBA words, one invocation per sampled pixel,
+/// and every differing one is counted towards the tile it lies in
+const SHADER: &str = r#"
+struct Params {
+    width: u32,
+    height: u32,
+    stride: u32,
+    tile_size: u32,
+    columns: u32,
+}
+
+@group(0) @binding(0) var<uniform> params: Params;
+@group(0) @binding(1) var<storage, read> frame1: array<u32>;
+@group(0) @binding(2) var<storage, read> frame2: array<u32>;
+@group(0) @binding(3) var<storage, read_write> tiles: array<atomic<u32>>;
+
+@compute @workgroup_size(16, 16)
+fn main(@builtin(global_invocation_id) id: vec3<u32>) {
+    let x = id.x * params.stride;
+    let y = id.y * params.stride;
+    if (x >= params.width || y >= params.height) {
+        return;
+    }
+    let index = y * params.width + x;
+    if (frame1[index] != frame2[index]) {
+        atomicAdd(&tiles[(y / params.tile_size) * params.columns + x / params.tile_size], 1u);
+    }
+}
+"#;
+
+/// Edge length of a workgroup in sampled pixels, as declared in the shader
+const WORKGROUP_SIZE: u32 = 16;
+
+/// Buffers sized for one frame size, reused until the size changes
+struct Buffers {
+    dimensions: (u32, u32),
+    tile_count: u64,
+    frame1: wgpu::Buffer,
+    frame2: wgpu::Buffer,
+    tiles: wgpu::Buffer,
+    readback: wgpu::Buffer,
+}
+
+/// Counts differing pixels per tile in a compute shader
+pub struct GpuDiff {
+    /// Name of the adapter in use, for the log
+    pub adapter: String,
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    pipeline: wgpu::ComputePipeline,
+    buffers: Option<Buffers>,
+}
+
+impl GpuDiff {
+    /// Set up the first adapter found; the error says why there is none to use
+    pub fn new() -> Result<Self, String> {
+        let instance = wgpu::Instance::default();
+        let adapter = pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
+            power_preference: wgpu::PowerPreference::HighPerformance,
+            ..Default::default()
+        }))
+        .map_err(|e| e.to_string())?;
+        let (device, queue) = pollster::block_on(adapter.request_device(&wgpu::DeviceDescriptor {
+            label: Some("frame comparison"),
+            // Large frames need the biggest storage buffers the adapter allows
+            required_limits: adapter.limits(),
+            ..Default::default()
+        }))
+        .map_err(|e| e.to_string())?;
+
+        let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("frame comparison"),
+            source: wgpu::ShaderSource::Wgsl(SHADER.into()),
+        });
+        let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("frame comparison"),
+            layout: None,
+            module: &module,
+            entry_point: Some("main"),
+            compilation_options: Default::default(),
+            cache: None,
+        });
+
+        Ok(GpuDiff { adapter: adapter.get_info().name, device, queue, pipeline, buffers: None })
+    }
+
+    /// Differing pixels per `tile_size` square, row by row, looking at every `stride`th pixel
+    /// in each direction; the frames must have the same size
+    pub fn tile_diffs(
+        &mut self,
+        img1: &DynamicImage,
+        img2: &DynamicImage,
+        stride: u32,
+        tile_size: u32,
+    ) -> Result<Vec<u64>, String> {
+        let (width, height) = img1.dimensions();
+        let columns = width.div_ceil(tile_size);
+        let tile_count = (columns * height.div_ceil(tile_size)) as u64;
+        let groups_x = width.div_ceil(stride).div_ceil(WORKGROUP_SIZE);
+        let groups_y = height.div_ceil(stride).div_ceil(WORKGROUP_SIZE);
+
+        let limits = self.device.limits();
+        let frame_bytes = width as u64 * height as u64 * 4;
+        if frame_bytes > limits.max_storage_buffer_binding_size || frame_bytes > limits.max_buffer_size {
+            return Err(format!("{}x{} frames do not fit in the GPU's buffers", width, height));
+        }
+        if groups_x.max(groups_y) > limits.max_compute_workgroups_per_dimension {
+            return Err(format!("{}x{} frames need more workgroups than the GPU allows", width, height));
+        }
+
+        if self.buffers.as_ref().is_none_or(|b| b.dimensions != (width, height) || b.tile_count != tile_count) {
+            self.buffers = Some(self.create_buffers((width, height), frame_bytes, tile_count));
+        }
+        let buffers = self.buffers.as_ref().expect("buffers were just created");
+
+        self.queue.write_buffer(&buffers.frame1, 0, img1.to_rgba8().as_raw());
+        self.queue.write_buffer(&buffers.frame2, 0, img2.to_rgba8().as_raw());
+        self.queue.write_buffer(&buffers.tiles, 0, &vec![0; tile_count as usize * 4]);
+
+        let params: Vec<u8> = [width, height, stride, tile_size, columns]
+            .iter()
+            .flat_map(|value| value.to_le_bytes())
+            .collect();
+        let params = self.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("comparison parameters"),
+            contents: &params,
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+        let bind_group = self.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: None,
+            layout: &self.pipeline.get_bind_group_layout(0),
+            entries: &[
+                wgpu::BindGroupEntry { binding: 0, resource: params.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 1, resource: buffers.frame1.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 2, resource: buffers.frame2.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 3, resource: buffers.tiles.as_entire_binding() },
+            ],
+        });
+
+        let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+        {
+            let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor::default());
+            pass.set_pipeline(&self.pipeline);
+            pass.set_bind_group(0, &bind_group, &[]);
+            pass.dispatch_workgroups(groups_x, groups_y, 1);
+        }
+        encoder.copy_buffer_to_buffer(&buffers.tiles, 0, &buffers.readback, 0, tile_count * 4);
+        self.queue.submit([encoder.finish()]);
+
+        let slice = buffers.readback.slice(..);
+        slice.map_async(wgpu::MapMode::Read, |_| {});
+        self.device.poll(wgpu::PollType::wait_indefinitely()).map_err(|e| e.to_string())?;
+        let counts = slice
+            .get_mapped_range()
+            .map_err(|e| e.to_string())?
+            .chunks_exact(4)
+            .map(|word| u32::from_le_bytes(word.try_into().unwrap()) as u64)
+            .collect();
+        buffers.readback.unmap();
+        Ok(counts)
+    }
+
+    fn create_buffers(&self, dimensions: (u32, u32), frame_bytes: u64, tile_count: u64) -> Buffers {
+        let buffer = |label, size, usage| {
+            self.device.create_buffer(&wgpu::BufferDescriptor { label: Some(label), size, usage, mapped_at_creation: false })
+        };
+        let frame = wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST;
+        Buffers {
+            dimensions,
+            tile_count,
+            frame1: buffer("previous frame", frame_bytes, frame),
+            frame2: buffer("current frame", frame_bytes, frame),
+            tiles: buffer(
+                "tile counts",
+                tile_count * 4,
+                wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC | wgpu::BufferUsages::COPY_DST,
+            ),
+            readback: buffer("tile readback", tile_count * 4, wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST),
+        }
+    }
+}
@@ -0,0 +1,46 @@
+//! Content-derived slide IDs, so a slide gets the same ID when the video is
+//! processed again with other settings and databases can upsert slides
+//! instead of adding them twice.
+//!
+//! The ID is a 128-bit difference hash of the slide: whether each cell of a
+//! 17x8 greyscale thumbnail is brighter than its right neighbour, like the
+//! hash `coverage` matches deck pages with. Neighbours within a few levels of
+//! each other count as equal, so compression noise in a flat background
+//! doesn't flip bits between two frames of the same slide. The slide's mean
+//! colour, to 16 levels per channel, follows it so plain slides in different
+//! colours get different IDs. A slide shown twice gets the same ID both times.
+
+use image::imageops::FilterType;
+use image::DynamicImage;
+use std::io;
+use std::path::Path;
+
+use crate::manifest::Manifest;
+use crate::source::open_frame;
+
+/// Difference in brightness (0 to 255) between neighbouring cells below which they count as equal
+const MARGIN: u8 = 2;
+
+/// The stable ID of `image`, 35 hex digits
+pub fn slide_id(image: &DynamicImage) -> String {
+    let thumbnail = image.resize_exact(17, 8, FilterType::Triangle).to_luma8();
+    let mut hash: u128 = 0;
+    for y in 0..8 {
+        for x in 0..16 {
+            let (left, right) = (thumbnail.get_pixel(x, y)[0], thumbnail.get_pixel(x + 1, y)[0]);
+            hash = hash << 1 | u128::from(left > right.saturating_add(MARGIN));
+        }
+    }
+    let mean = image.resize_exact(1, 1, FilterType::Triangle).to_rgb8().get_pixel(0, 0).0;
+    format!("{:032x}{:x}{:x}{:x}", hash, mean[0] >> 4, mean[1] >> 4, mean[2] >> 4)
+}
+
+/// Give every slide of `manifest` without an ID one from its image in `output_dir`
+pub fn assign_ids(output_dir: &Path, manifest: &mut Manifest) -> Result<(), io::Error> {
+    for slide in manifest.slides.iter_mut().filter(|slide| slide.id.is_none()) {
+        let path = output_dir.join(&slide.file);
+        let image = open_frame(&path).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", path.display(), e)))?;
+        slide.id = Some(slide_id(&image));
+    }
+    Ok(())
+}
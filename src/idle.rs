@@ -0,0 +1,130 @@
+//! `--trim-idle`: leave out the static screens a recording opens and closes
+//! with ("Starting soon", a waiting room, "Thanks for watching"), so they
+//! don't appear as slides and the manifest says where the content starts.
+//!
+//! A screen counts as idle when it is the first or last slide, was shown
+//! once and stayed up at least the given number of seconds. With `--ocr` a
+//! screen anywhere in the recording also counts when its text is short and
+//! says one of the idle keywords, like "be right back" or "starting soon"
+//! (`--idle-keywords` replaces the list), so a break mid-talk is left out too.
+
+use std::fs;
+use std::io;
+
+use crate::config::Config;
+use crate::manifest::{Interval, Manifest, Slide};
+use crate::runlog::RunLog;
+
+/// Phrases an idle screen says, unless `--idle-keywords` gives others
+const DEFAULT_KEYWORDS: [&str; 12] = [
+    "starting soon",
+    "will begin shortly",
+    "will start shortly",
+    "be right back",
+    "brb",
+    "back in a few",
+    "waiting for the host",
+    "waiting room",
+    "please stand by",
+    "technical difficulties",
+    "thanks for watching",
+    "thank you for watching",
+];
+/// Words a screen's text may have at most to be taken for an idle card, not a slide about one
+const MAX_CARD_WORDS: usize = 20;
+
+/// Whether `slide` stayed on screen, in one visit, for at least `min_duration` seconds
+fn is_static(slide: &Slide, min_duration: f64) -> bool {
+    match slide.shown.as_slice() {
+        [interval] => interval.end - interval.start >= min_duration,
+        _ => false,
+    }
+}
+
+/// The words of `text` in lower case, without punctuation
+fn words(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric()).filter(|word| !word.is_empty()).map(str::to_lowercase).collect()
+}
+
+/// The idle keyword `slide`'s text says, if it is short enough to be an idle card
+fn idle_keyword<'a>(slide: &Slide, keywords: &'a [String]) -> Option<&'a str> {
+    let words_said = words(slide.text.as_deref()?);
+    if words_said.is_empty() || words_said.len() > MAX_CARD_WORDS {
+        return None;
+    }
+    // Whole words, so "brb" doesn't match inside a longer one
+    let said = format!(" {} ", words_said.join(" "));
+    keywords.iter().map(String::as_str).find(|keyword| {
+        let keyword = words(keyword);
+        !keyword.is_empty() && said.contains(&format!(" {} ", keyword.join(" ")))
+    })
+}
+
+/// Where `slide` was first and last on screen
+fn span(slide: &Slide) -> Interval {
+    match (slide.shown.first(), slide.shown.last()) {
+        (Some(first), Some(last)) => Interval { start: first.start, end: last.end },
+        _ => Interval { start: slide.timestamp, end: slide.timestamp },
+    }
+}
+
+/// Apply `--trim-idle`: delete idle screens from both ends of `manifest`, and
+/// those its text gives away from anywhere in it, number the remaining slides
+/// again and record the stretch in between
+pub fn trim(config: &Config, manifest: &mut Manifest, log: &RunLog) -> Result<(), io::Error> {
+    let Some(min_duration) = config.trim_idle else {
+        return Ok(());
+    };
+    let keywords: Vec<String> = match &config.idle_keywords {
+        Some(keywords) => keywords.clone(),
+        None => DEFAULT_KEYWORDS.iter().map(|keyword| keyword.to_string()).collect(),
+    };
+    let is_idle = |slide: &Slide| is_static(slide, min_duration) || idle_keyword(slide, &keywords).is_some();
+
+    let (mut intro, mut outro) = (Vec::new(), Vec::new());
+    // One slide is always kept, however long it stayed up
+    while manifest.slides.len() > 1 && is_idle(&manifest.slides[0]) {
+        intro.push(manifest.slides.remove(0));
+    }
+    while manifest.slides.len() > 1 && manifest.slides.last().is_some_and(is_idle) {
+        outro.extend(manifest.slides.pop());
+    }
+    let mut breaks = Vec::new();
+    let mut i = 0;
+    while manifest.slides.len() > 1 && i < manifest.slides.len() {
+        if idle_keyword(&manifest.slides[i], &keywords).is_some() {
+            breaks.push(manifest.slides.remove(i));
+        } else {
+            i += 1;
+        }
+    }
+    if intro.is_empty() && outro.is_empty() && breaks.is_empty() {
+        return Ok(());
+    }
+
+    for slide in intro.iter().chain(&outro).chain(&breaks) {
+        match idle_keyword(slide, &keywords) {
+            Some(keyword) => log.info(format_args!("Trimming idle screen {} at {:.1}s, it says \"{}\".", slide.file, slide.timestamp, keyword)),
+            None => {
+                let shown = span(slide);
+                log.info(format_args!("Trimming idle screen {} at {:.1}s, static for {:.0}s.", slide.file, slide.timestamp, shown.end - shown.start));
+            }
+        }
+        fs::remove_file(config.output_dir.join(&slide.file))?;
+    }
+    for (index, slide) in manifest.slides.iter_mut().enumerate() {
+        slide.index = index + 1;
+    }
+
+    // Content runs from the end of the last intro screen to the start of the first outro screen
+    let start = match intro.last() {
+        Some(slide) => span(slide).end,
+        None => manifest.slides.first().map_or(0.0, |slide| slide.timestamp),
+    };
+    let end = match outro.last() {
+        Some(slide) => span(slide).start,
+        None => manifest.slides.iter().flat_map(|slide| &slide.shown).map(|interval| interval.end).fold(start, f64::max),
+    };
+    manifest.content = Some(Interval { start, end });
+    Ok(())
+}
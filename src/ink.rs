@@ -0,0 +1,182 @@
+//! `--ink-pause`: slides from a lecture handwritten on a tablet, where ink
+//! piles up on one page for minutes and there are no slide changes to find.
+//!
+//! The page is snapshotted once no ink was added to it for `ink_pause`
+//! seconds, and right before writing disappears from it because it scrolled,
+//! was cleared, turned or erased. The pipeline is shown the last snapshot for
+//! every frame, so each one becomes a slide and what happens between them
+//! doesn't; the last state of the page is kept when the video ends. Writing is
+//! anything that stands out from the page's colour in the first frame.
+//! `run_stream` rejects `--ink-pause`.
+
+use image::{DynamicImage, GenericImageView, Pixel};
+use std::collections::VecDeque;
+
+use crate::config::Config;
+use crate::error::Error;
+use crate::progress::{CancellationToken, Progress};
+use crate::runlog::RunLog;
+use crate::source::{Frame, FrameSource};
+
+/// Difference in any channel from which a pixel changed between two frames
+const CHANGE_STEP: u8 = 24;
+/// Share of the pixels that must change for ink to have been added
+const MIN_CHANGE: f64 = 0.0002;
+/// Difference in brightness (0 to 255) from the page from which a pixel is writing
+const INK_STEP: i32 = 60;
+/// Share of the pixels that must go from writing to page for writing to have disappeared
+const MIN_REMOVED: f64 = 0.001;
+
+/// The frames of another source, each showing the page as it was last snapshotted
+pub struct InkSource<'a> {
+    inner: &'a mut dyn FrameSource,
+    log: RunLog,
+    /// Consecutive frames without new ink before the page is snapshotted
+    pause_frames: u32,
+    /// Brightness of the page in the first frame
+    page: Option<i32>,
+    /// The frame read last, handed out once the frame after it showed whether it was snapshotted
+    previous: Option<Frame>,
+    snapshot: Option<DynamicImage>,
+    /// Ink was added since the last snapshot
+    unsaved: bool,
+    /// Frames since ink was last added
+    still: u32,
+    /// Frames that could not be decoded, handed out after `previous`
+    bad_frames: VecDeque<Error>,
+    finished: bool,
+}
+
+impl<'a> InkSource<'a> {
+    pub fn new(config: &Config, inner: &'a mut dyn FrameSource, log: &RunLog) -> Self {
+        let pause = config.ink_pause.unwrap_or(0.0);
+        InkSource {
+            inner,
+            log: log.clone(),
+            pause_frames: ((pause * config.fps as f64).round() as u32).max(1),
+            page: None,
+            previous: None,
+            snapshot: None,
+            unsaved: false,
+            still: 0,
+            bad_frames: VecDeque::new(),
+            finished: false,
+        }
+    }
+
+    /// `previous` showing the last snapshot
+    fn hand_out(&self, previous: Frame) -> Frame {
+        let image = self.snapshot.clone().unwrap_or(previous.image);
+        Frame { image, name: previous.name, path: None }
+    }
+}
+
+impl FrameSource for InkSource<'_> {
+    fn prepare(&mut self, progress: &dyn Fn(Progress), cancel: &CancellationToken) -> Result<(), Error> {
+        self.inner.prepare(progress, cancel)
+    }
+
+    fn remaining(&self) -> Option<usize> {
+        let read = self.bad_frames.len() + usize::from(self.previous.is_some());
+        self.inner.remaining().map(|remaining| remaining + read)
+    }
+
+    fn next_frame(&mut self) -> Result<Option<Frame>, Error> {
+        loop {
+            if let Some(e) = self.bad_frames.pop_front() {
+                return Err(e);
+            }
+            if self.finished {
+                return Ok(None);
+            }
+            let frame = match self.inner.next_frame() {
+                Ok(Some(frame)) => frame,
+                Ok(None) => {
+                    self.finished = true;
+                    let Some(previous) = self.previous.take() else {
+                        continue;
+                    };
+                    // The page as the video leaves it
+                    if self.unsaved {
+                        self.snapshot = Some(previous.image.clone());
+                        self.unsaved = false;
+                    }
+                    return Ok(Some(self.hand_out(previous)));
+                }
+                Err(e @ Error::BadFrame { .. }) if self.previous.is_some() => {
+                    self.bad_frames.push_back(e);
+                    continue;
+                }
+                Err(e) => return Err(e),
+            };
+            // Only the snapshots are ever kept
+            self.inner.discard(frame.path.as_deref())?;
+
+            let Some(previous) = self.previous.take() else {
+                let page = frame.image.to_luma8();
+                self.page = Some(most_common(page.as_raw()) as i32);
+                self.snapshot = Some(frame.image.clone());
+                self.previous = Some(frame);
+                continue;
+            };
+            let page = self.page.expect("set with the first frame");
+            let (changed, removed) = compare(&previous.image, &frame.image, page);
+            let pixels = (frame.image.width() as u64 * frame.image.height() as u64).max(1) as f64;
+            let handed = if removed as f64 >= MIN_REMOVED * pixels {
+                self.log.debug(format_args!("Writing disappeared at frame {}, snapshotting the page before it.", frame.name));
+                self.snapshot = Some(previous.image.clone());
+                (self.unsaved, self.still) = (true, 0);
+                self.hand_out(previous)
+            } else {
+                let handed = self.hand_out(previous);
+                if changed as f64 >= MIN_CHANGE * pixels {
+                    (self.unsaved, self.still) = (true, 0);
+                } else {
+                    self.still += 1;
+                    if self.unsaved && self.still >= self.pause_frames {
+                        self.log.debug(format_args!("No ink added until frame {}, snapshotting the page.", frame.name));
+                        self.snapshot = Some(frame.image.clone());
+                        self.unsaved = false;
+                    }
+                }
+                handed
+            };
+            self.previous = Some(frame);
+            return Ok(Some(handed));
+        }
+    }
+
+    fn succeeded(&mut self) {
+        self.inner.succeeded();
+    }
+}
+
+/// Pixels that changed from `before` to `after`, and how many of them went from writing to page
+fn compare(before: &DynamicImage, after: &DynamicImage, page: i32) -> (u64, u64) {
+    if before.dimensions() != after.dimensions() {
+        let pixels = after.width() as u64 * after.height() as u64;
+        return (pixels, pixels);
+    }
+    let (before, after) = (before.to_rgb8(), after.to_rgb8());
+    let (mut changed, mut removed) = (0, 0);
+    for (a, b) in before.pixels().zip(after.pixels()) {
+        if a.0.iter().zip(b.0).all(|(&a, b)| a.abs_diff(b) <= CHANGE_STEP) {
+            continue;
+        }
+        changed += 1;
+        let ink = |pixel: &image::Rgb<u8>| (pixel.to_luma().0[0] as i32 - page).abs() > INK_STEP;
+        if ink(a) && !ink(b) {
+            removed += 1;
+        }
+    }
+    (changed, removed)
+}
+
+/// The most common value of `values`
+fn most_common(values: &[u8]) -> u8 {
+    let mut histogram = [0usize; 256];
+    for &value in values {
+        histogram[value as usize] += 1;
+    }
+    (0..=255).max_by_key(|&value| histogram[value as usize]).unwrap_or(0)
+}
@@ -0,0 +1,95 @@
+//! The language of each slide's OCR text, for the manifest and for
+//! `--ocr-by-language`, which reads a slide again with the Tesseract model of
+//! its language: courses that mix languages read poorly with one model.
+//!
+//! The language is told by the common short words of each language the text
+//! uses, like "the" and "und". That needs a few of them, so titles and code
+//! are left without a language rather than guessed at.
+
+use crate::manifest::Manifest;
+
+/// Common words of a language found in a text before it is taken to be in it
+const MIN_WORDS: usize = 3;
+
+/// A language slides are told apart in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Language {
+    /// ISO 639-1 code, as in the manifest
+    pub code: &'static str,
+    /// Name of Tesseract's model for it
+    pub tesseract: &'static str,
+    /// Its most common short words, which other languages rarely use
+    words: &'static [&'static str],
+}
+
+/// The languages told apart
+const LANGUAGES: [Language; 7] = [
+    Language {
+        code: "en",
+        tesseract: "eng",
+        words: &["the", "and", "of", "to", "is", "in", "that", "for", "with", "are", "this", "you", "it", "on", "be", "we", "not", "can", "how", "what"],
+    },
+    Language {
+        code: "de",
+        tesseract: "deu",
+        words: &["der", "die", "das", "und", "ist", "nicht", "mit", "den", "ein", "eine", "für", "auf", "sich", "von", "zu", "wir", "auch", "werden", "oder", "sind", "dem", "des", "im"],
+    },
+    Language {
+        code: "fr",
+        tesseract: "fra",
+        words: &["le", "les", "et", "est", "une", "du", "pour", "dans", "qui", "pas", "sur", "avec", "sont", "nous", "ce", "au", "des", "la"],
+    },
+    Language {
+        code: "es",
+        tesseract: "spa",
+        words: &["el", "los", "las", "y", "que", "es", "por", "con", "para", "una", "del", "se", "como", "más", "son", "la", "en"],
+    },
+    Language {
+        code: "it",
+        tesseract: "ita",
+        words: &["il", "gli", "di", "che", "è", "per", "non", "della", "sono", "più", "nel", "una", "con", "come"],
+    },
+    Language {
+        code: "pt",
+        tesseract: "por",
+        words: &["os", "em", "é", "não", "uma", "um", "do", "da", "dos", "são", "mais", "com", "para"],
+    },
+    Language {
+        code: "nl",
+        tesseract: "nld",
+        words: &["het", "een", "van", "dat", "niet", "voor", "op", "zijn", "ook", "wij", "aan", "maar", "bij", "er"],
+    },
+];
+
+/// The language `text` is in, `None` if too few of its words tell
+pub fn detect(text: &str) -> Option<&'static Language> {
+    let words: Vec<String> = text
+        .split(|c: char| !c.is_alphabetic())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect();
+    let mut scores: Vec<(usize, &'static Language)> = LANGUAGES
+        .iter()
+        .map(|language| (words.iter().filter(|word| language.words.contains(&word.as_str())).count(), language))
+        .collect();
+    scores.sort_by_key(|&(score, _)| std::cmp::Reverse(score));
+    match scores.as_slice() {
+        // A tie doesn't tell
+        [(best, language), (second, _), ..] if *best >= MIN_WORDS && best > second => Some(language),
+        _ => None,
+    }
+}
+
+/// The language with the ISO 639-1 `code`
+pub fn by_code(code: &str) -> Option<&'static Language> {
+    LANGUAGES.iter().find(|language| language.code == code)
+}
+
+/// Tag every slide of `manifest` with text but no language yet with the language of its text
+pub fn tag_languages(manifest: &mut Manifest) {
+    for slide in &mut manifest.slides {
+        if slide.language.is_none() {
+            slide.language = slide.text.as_deref().and_then(detect).map(|language| language.code.to_string());
+        }
+    }
+}
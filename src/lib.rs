@@ -0,0 +1,160 @@
+//! Extract the unique slides from a recorded presentation.
+//!
+//! Frames are sampled from the video with ffmpeg, consecutive look-alikes are
+//! deleted, and the survivors are described in a JSON manifest.
+//!
+//! On `wasm32` only the comparison and dedup core is built, fed with frames
+//! the caller decoded itself (see the `wasm` module).
+
+// Helpers only the native pipeline calls
+#![cfg_attr(target_arch = "wasm32", allow(dead_code))]
+
+#[cfg(not(target_arch = "wasm32"))]
+mod adaptive;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod archive;
+#[cfg(not(target_arch = "wasm32"))]
+mod audio;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod batch;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod bench;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod calibrate;
+#[cfg(not(target_arch = "wasm32"))]
+mod cache;
+#[cfg(not(target_arch = "wasm32"))]
+mod classify;
+#[cfg(not(target_arch = "wasm32"))]
+mod canvas;
+#[cfg(not(target_arch = "wasm32"))]
+mod catalog;
+#[cfg(not(target_arch = "wasm32"))]
+mod board;
+#[cfg(not(target_arch = "wasm32"))]
+mod boundaries;
+#[cfg(not(target_arch = "wasm32"))]
+mod changes;
+mod compare;
+#[cfg(not(target_arch = "wasm32"))]
+mod confidence;
+mod config;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod coverage;
+#[cfg(unix)]
+pub mod daemon;
+mod dedup;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod desktop;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod diff;
+mod error;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod estimate;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod evaluate;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod export;
+#[cfg(not(target_arch = "wasm32"))]
+mod extract;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod finalize;
+#[cfg(not(target_arch = "wasm32"))]
+mod fingerprint;
+mod footer;
+#[cfg(feature = "gpu")]
+mod gpu;
+#[cfg(not(target_arch = "wasm32"))]
+mod identity;
+#[cfg(not(target_arch = "wasm32"))]
+mod idle;
+#[cfg(not(target_arch = "wasm32"))]
+mod ink;
+#[cfg(not(target_arch = "wasm32"))]
+mod language;
+#[cfg(not(target_arch = "wasm32"))]
+mod limits;
+pub mod manifest;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod links;
+#[cfg(not(target_arch = "wasm32"))]
+mod live;
+#[cfg(not(target_arch = "wasm32"))]
+mod lock;
+#[cfg(not(target_arch = "wasm32"))]
+mod memory;
+#[cfg(not(target_arch = "wasm32"))]
+mod metadata;
+pub mod metrics;
+#[cfg(not(target_arch = "wasm32"))]
+mod monitors;
+mod motion;
+mod overlay;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod naming;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod ocr;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod output;
+#[cfg(not(target_arch = "wasm32"))]
+mod pauses;
+#[cfg(not(target_arch = "wasm32"))]
+mod pipeline;
+#[cfg(not(target_arch = "wasm32"))]
+mod preflight;
+#[cfg(not(target_arch = "wasm32"))]
+mod probe;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod profile;
+mod progress;
+#[cfg(feature = "python")]
+mod python;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod queue;
+mod runlog;
+#[cfg(not(target_arch = "wasm32"))]
+mod rate;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod reprocess;
+#[cfg(not(target_arch = "wasm32"))]
+mod report;
+#[cfg(not(target_arch = "wasm32"))]
+mod revisits;
+#[cfg(not(target_arch = "wasm32"))]
+mod s3;
+mod scroll;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod server;
+#[cfg(not(target_arch = "wasm32"))]
+mod sidecar;
+#[cfg(not(target_arch = "wasm32"))]
+mod speakers;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod source;
+#[cfg(feature = "async")]
+pub mod stream;
+#[cfg(not(target_arch = "wasm32"))]
+mod summarize;
+#[cfg(not(target_arch = "wasm32"))]
+mod sync;
+mod text;
+#[cfg(target_arch = "wasm32")]
+pub mod wasm;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod webhook;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod workspace;
+mod zoom;
+
+pub use config::{AspectRatio, BadFramePolicy, Color, Config, Crop, CropArea, Diarization, Ensemble, Length, LimitPolicy, LiveFormat, Mask, Metric, MonitorSplit, OcrBackend, PngPrediction, Prefilter, SidecarFormat, SyncMode, WorkspacePolicy};
+pub use error::{format_size, Error};
+pub use manifest::Manifest;
+#[cfg(not(target_arch = "wasm32"))]
+pub use pipeline::{run, run_with, run_with_source};
+#[cfg(not(target_arch = "wasm32"))]
+pub use adaptive::AdaptiveSource;
+#[cfg(not(target_arch = "wasm32"))]
+pub use source::{DirectorySource, FfmpegSource, Frame, FrameSource, MemorySource, PipeSource};
+pub use progress::{CancellationToken, Progress, Stage};
+#[cfg(feature = "async")]
+pub use stream::{run_stream, SlideEvent};
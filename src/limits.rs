@@ -0,0 +1,98 @@
+//! `--max-slides` and `--max-output-size`, the guards against a threshold
+//! that is far too low for the video and keeps nearly every frame.
+
+use std::fs;
+use std::path::Path;
+
+use crate::config::{Config, LimitPolicy};
+use crate::dedup::Deduplicator;
+use crate::error::{format_size, Error};
+use crate::runlog::RunLog;
+
+/// Share of a budget that has to be used up before the threshold is raised (again)
+const RAISE_STEP: f64 = 0.1;
+
+/// Tracks the kept slides against the limits of a run
+pub struct OutputBudget {
+    max_slides: Option<usize>,
+    max_output_size: Option<u64>,
+    policy: LimitPolicy,
+    kept: usize,
+    bytes: u64,
+    /// Budget share used when the threshold was last raised
+    raised_at: f64,
+}
+
+impl OutputBudget {
+    pub fn new(config: &Config) -> Self {
+        OutputBudget {
+            max_slides: config.max_slides,
+            max_output_size: config.max_output_size,
+            policy: config.on_limit,
+            kept: 0,
+            bytes: 0,
+            raised_at: 0.0,
+        }
+    }
+
+    /// Share of the tighter limit the kept slides use up, 0 without limits
+    fn used(&self) -> f64 {
+        let slides = self.max_slides.map_or(0.0, |max| self.kept as f64 / max.max(1) as f64);
+        let bytes = self.max_output_size.map_or(0.0, |max| self.bytes as f64 / max.max(1) as f64);
+        slides.max(bytes)
+    }
+
+    /// Account for the slide just stored at `path`, the `done`th of `total` frames.
+    ///
+    /// Fails once a limit is exceeded. Under `LimitPolicy::Raise` the threshold
+    /// of `dedup` is doubled whenever the slides use up the budget faster than
+    /// the frames go by, so the rest of the video is judged more leniently.
+    pub fn record(
+        &mut self,
+        path: &Path,
+        done: usize,
+        total: Option<usize>,
+        dedup: &mut Deduplicator,
+        log: &RunLog,
+    ) -> Result<(), Error> {
+        if self.max_slides.is_none() && self.max_output_size.is_none() {
+            return Ok(());
+        }
+        self.kept += 1;
+        self.bytes += fs::metadata(path)?.len();
+
+        let exceeded = match (self.max_slides, self.max_output_size) {
+            (Some(max), _) if self.kept > max => Some(format!("--max-slides {}", max)),
+            (_, Some(max)) if self.bytes > max => Some(format!("--max-output-size {}", format_size(max))),
+            _ => None,
+        };
+        if let Some(limit) = exceeded {
+            return Err(Error::LimitExceeded {
+                limit,
+                kept: self.kept,
+                bytes: self.bytes,
+                done,
+                threshold: dedup.threshold(),
+            });
+        }
+
+        if self.policy == LimitPolicy::Raise {
+            let used = self.used();
+            // Without a frame count every step of the budget counts as too fast
+            let seen = total.map_or(0.0, |total| done as f64 / total.max(1) as f64);
+            if used > seen && used >= self.raised_at + RAISE_STEP && dedup.threshold() < 1.0 {
+                let threshold = (dedup.threshold() * 2.0).clamp(0.01, 1.0);
+                log.warn(format_args!(
+                    "{} slide(s) use {:.0}% of the limit after {} frame(s); raising the threshold to {}.",
+                    self.kept,
+                    used * 100.0,
+                    done,
+                    threshold
+                ));
+                dedup.set_threshold(threshold);
+                self.raised_at = used;
+            }
+        }
+        Ok(())
+    }
+}
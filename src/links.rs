@@ -0,0 +1,127 @@
+//! `--find-links`: decode the QR codes on the kept slides, and with `--ocr`
+//! pick the web addresses out of their text, so the resources a talk points
+//! to can be followed without transcribing them by hand.
+//!
+//! What is found goes into the manifest and into `links.md` next to it. A
+//! printed address is recognised by its `http://` or `https://`, a leading
+//! `www.`, or a domain followed by a path like `github.com/org/repo`.
+
+use image::DynamicImage;
+use std::io;
+use std::path::Path;
+
+use crate::config::Config;
+use crate::lock::write_atomic;
+use crate::manifest::Manifest;
+use crate::runlog::RunLog;
+use crate::source::open_frame;
+
+/// File name of the Markdown list of links written next to the manifest
+pub const LINKS_FILE: &str = "links.md";
+
+/// The contents of every readable QR code in `image`
+pub fn qr_codes(image: &DynamicImage) -> Vec<String> {
+    let luma = image.to_luma8();
+    let mut prepared = rqrr::PreparedImage::prepare_from_greyscale(luma.width() as usize, luma.height() as usize, |x, y| {
+        luma.get_pixel(x as u32, y as u32)[0]
+    });
+    prepared.detect_grids().into_iter().filter_map(|grid| grid.decode().ok()).map(|(_, content)| content).collect()
+}
+
+/// The web addresses in `text`, in order and without repeats
+pub fn urls(text: &str) -> Vec<String> {
+    let mut found: Vec<String> = Vec::new();
+    for word in text.split_whitespace() {
+        let word = word.trim_start_matches(['(', '<', '[', '"', '\'']).trim_end_matches(['.', ',', ';', ':', '!', '?', ')', '>', ']', '"', '\'']);
+        if is_url(word) && !found.iter().any(|url| url == word) {
+            found.push(word.to_string());
+        }
+    }
+    found
+}
+
+fn is_url(word: &str) -> bool {
+    let lower = word.to_ascii_lowercase();
+    if let Some(rest) = lower.strip_prefix("http://").or_else(|| lower.strip_prefix("https://")) {
+        return rest.contains('.');
+    }
+    if let Some(rest) = lower.strip_prefix("www.") {
+        return rest.contains('.');
+    }
+    // A bare domain only counts with a path, "e.g." or "v1.2" aren't addresses
+    let Some((host, _)) = lower.split_once('/') else {
+        return false;
+    };
+    let Some((name, tld)) = host.rsplit_once('.') else {
+        return false;
+    };
+    !name.is_empty()
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '.')
+        && (2..=6).contains(&tld.len())
+        && tld.chars().all(|c| c.is_ascii_alphabetic())
+}
+
+/// Scan the kept slides of `manifest` for QR codes, and their text for web addresses, if
+/// `config.find_links` asks for it
+pub fn find_links(config: &Config, manifest: &mut Manifest, log: &RunLog) -> Result<(), io::Error> {
+    if !config.find_links {
+        return Ok(());
+    }
+    for slide in &mut manifest.slides {
+        let path = config.output_dir.join(&slide.file);
+        let image = open_frame(&path).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        slide.qr_codes = qr_codes(&image);
+        for content in &slide.qr_codes {
+            log.info(format_args!("Slide {} has a QR code for {}", slide.index, content));
+        }
+        // A QR code usually spells out the address printed next to it
+        let printed = slide.text.as_deref().map(urls).unwrap_or_default();
+        slide.urls = printed.into_iter().filter(|url| !slide.qr_codes.contains(url)).collect();
+        for url in &slide.urls {
+            log.info(format_args!("Slide {} links to {}", slide.index, url));
+        }
+    }
+    Ok(())
+}
+
+/// `mm:ss`, or `h:mm:ss` from an hour on
+pub(crate) fn clock(seconds: f64) -> String {
+    let seconds = seconds as u64;
+    match seconds / 3600 {
+        0 => format!("{:02}:{:02}", seconds / 60, seconds % 60),
+        hours => format!("{}:{:02}:{:02}", hours, seconds / 60 % 60, seconds % 60),
+    }
+}
+
+/// The links found on the slides as a Markdown list per slide, `None` if there are none
+pub fn markdown(manifest: &Manifest) -> Option<String> {
+    let mut markdown = String::from("# Links\n");
+    let mut found = false;
+    for slide in manifest.slides.iter().filter(|slide| !slide.qr_codes.is_empty() || !slide.urls.is_empty()) {
+        found = true;
+        markdown.push_str(&format!("\n## Slide {} ({})\n\n", slide.index, clock(slide.timestamp)));
+        for content in &slide.qr_codes {
+            if content.starts_with("http://") || content.starts_with("https://") {
+                markdown.push_str(&format!("- <{}>\n", content));
+            } else {
+                markdown.push_str(&format!("- {}\n", content));
+            }
+        }
+        for url in &slide.urls {
+            if url.contains("://") {
+                markdown.push_str(&format!("- <{}>\n", url));
+            } else {
+                markdown.push_str(&format!("- <https://{}>\n", url));
+            }
+        }
+    }
+    found.then_some(markdown)
+}
+
+/// Write `links.md` into the output directory if any links were found
+pub fn write_links(output_dir: &Path, manifest: &Manifest) -> Result<(), io::Error> {
+    match markdown(manifest) {
+        Some(markdown) => write_atomic(&output_dir.join(LINKS_FILE), markdown),
+        None => Ok(()),
+    }
+}
@@ -0,0 +1,57 @@
+//! `--live`: the HTML, Markdown and NDJSON exports written into the output
+//! directory each time a slide is kept, so the first hour of a four-hour
+//! video can be reviewed while the rest is still being processed.
+//!
+//! While the run goes on, the exports list the slides as they were kept,
+//! and the HTML page reloads itself every few seconds. Once it is done they
+//! are written again from the final manifest, without the slides dropped or
+//! moved afterwards (e.g. by `--min-confidence` or `--split-decks`).
+
+use std::path::{Path, PathBuf};
+
+use crate::config::{Config, LiveFormat};
+use crate::error::Error;
+use crate::export::{html, markdown, ndjson, ExportFormat};
+use crate::lock::write_atomic;
+use crate::manifest::Manifest;
+use crate::pipeline::build_manifest;
+
+/// Seconds between reloads of the HTML page while the run goes on
+const RELOAD_SECONDS: u32 = 10;
+
+fn export_format(format: LiveFormat) -> ExportFormat {
+    match format {
+        LiveFormat::Html => ExportFormat::Html,
+        LiveFormat::Markdown => ExportFormat::Markdown,
+        LiveFormat::Ndjson => ExportFormat::Ndjson,
+    }
+}
+
+/// Rewrite the exports of `config.live` with the slides kept so far
+pub fn update(config: &Config, kept: &[(usize, PathBuf)]) -> Result<(), Error> {
+    if config.live.is_empty() {
+        return Ok(());
+    }
+    let manifest = build_manifest(config, config.camera_offset, kept.to_vec());
+    write(&config.output_dir, &config.live, &manifest, true)
+}
+
+/// Write the exports of `config.live` from the final `manifest`
+pub fn finish(config: &Config, manifest: &Manifest) -> Result<(), Error> {
+    write(&config.output_dir, &config.live, manifest, false)
+}
+
+fn write(output_dir: &Path, formats: &[LiveFormat], manifest: &Manifest, in_progress: bool) -> Result<(), Error> {
+    for &format in formats {
+        let path = export_format(format).default_path(output_dir);
+        let contents = match format {
+            LiveFormat::Html if in_progress => html(output_dir, manifest, &path)?
+                .replacen("<head>\n", &format!("<head>\n<meta http-equiv=\"refresh\" content=\"{}\">\n", RELOAD_SECONDS), 1),
+            LiveFormat::Html => html(output_dir, manifest, &path)?,
+            LiveFormat::Markdown => markdown(output_dir, manifest, &path)?,
+            LiveFormat::Ndjson => ndjson(manifest)?,
+        };
+        write_atomic(&path, contents)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,46 @@
+//! Keeps runs that share an output directory from overwriting and deleting
+//! each other's slides. Each run holds an exclusive lock on a file in its
+//! output directory until it finishes, as does a job queue on its spool
+//! directory, and final artifacts are written under a temporary name and
+//! renamed into place, so a reader never sees half of one.
+//!
+//! The sampled frames need neither: every run gets a workspace of its own for
+//! them, locked only to keep `gc` from removing it mid-run.
+
+use fs4::fs_std::FileExt;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::Path;
+
+use crate::error::Error;
+
+/// File in a directory its user holds locked; left behind empty when it is done
+pub const LOCK_FILE: &str = ".videoslides.lock";
+
+/// The claim of one process on a directory, released when dropped
+#[derive(Debug)]
+pub struct DirLock {
+    _file: File,
+}
+
+/// Claim `dir`, creating it if needed; `None` rather than waiting if someone else holds it
+pub fn try_lock_dir(dir: &Path) -> Result<Option<DirLock>, io::Error> {
+    fs::create_dir_all(dir)?;
+    let file = OpenOptions::new().create(true).truncate(false).write(true).open(dir.join(LOCK_FILE))?;
+    Ok(file.try_lock_exclusive()?.then_some(DirLock { _file: file }))
+}
+
+/// Claim `output_dir` for this run; fails with `OutputLocked` if another run holds it
+pub fn lock_output(output_dir: &Path) -> Result<DirLock, Error> {
+    try_lock_dir(output_dir)?.ok_or_else(|| Error::OutputLocked { dir: output_dir.to_path_buf() })
+}
+
+/// Write `contents` to `path` by way of a temporary file beside it, so that
+/// `path` holds either its old contents or all of the new ones
+pub fn write_atomic(path: &Path, contents: impl AsRef<[u8]>) -> Result<(), io::Error> {
+    let dir = path.parent().filter(|dir| !dir.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    let mut file = tempfile::Builder::new().prefix(".videoslides-").tempfile_in(dir)?;
+    file.write_all(contents.as_ref())?;
+    file.persist(path).map_err(|e| e.error)?;
+    Ok(())
+}
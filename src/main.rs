@@ -1,123 +1,1337 @@
-use std::env;
-use std::io::Error;
-use std::io::ErrorKind;
-use image::{DynamicImage, GenericImageView};
-use std::process::Command;
-use std::fs;
+use clap::{ArgAction, Args, CommandFactory, Parser, Subcommand, ValueEnum};
+use clap_complete::Shell;
+use std::fs::OpenOptions;
+use std::io::{IsTerminal, Write};
+use std::sync::Mutex;
+use tracing_subscriber::filter::{EnvFilter, LevelFilter};
 use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::time::Duration;
+use video_slide_extractor::batch::{run_batch, BatchOptions};
+use video_slide_extractor::bench::{run_bench, BenchOptions};
+use video_slide_extractor::calibrate::{calibrate, CalibrateOptions};
+use video_slide_extractor::coverage::{coverage, write_page_map};
+use video_slide_extractor::desktop::{self, OpenTarget};
+use video_slide_extractor::diff::{diff, DiffEntry, DiffOptions, SlideRef};
+use video_slide_extractor::export::{export, ExportFormat};
+use video_slide_extractor::manifest::{self, SlideKind};
+use video_slide_extractor::finalize::finalize;
+use video_slide_extractor::estimate::estimate;
+use video_slide_extractor::evaluate::{evaluate, parse_timestamp};
+use video_slide_extractor::naming::NameTemplate;
+use video_slide_extractor::profile::{self, Profile};
+use video_slide_extractor::reprocess::reprocess;
+#[cfg(unix)]
+use video_slide_extractor::daemon::{run_daemon, DaemonOptions};
+use video_slide_extractor::server::{serve, ServeOptions};
+use video_slide_extractor::workspace::{gc, GcOptions};
+use video_slide_extractor::{format_size, AspectRatio, Manifest, BadFramePolicy, Color, Config, CropArea, Diarization, Error, LimitPolicy, LiveFormat, Metric, MonitorSplit, OcrBackend, PngPrediction, Prefilter, SidecarFormat, SyncMode, WorkspacePolicy};
 
-/// Extract frames from the video using ffmpeg
-fn extract_frames(input_file: &str, output_dir: &str, fps: u32) -> Result<(), Error> {
-    // Ensure output directory exists
-    if !Path::new(output_dir).exists() {
-        fs::create_dir(output_dir)?;
+/// What the exit status means, for scripts
+const EXIT_CODES: &str = "\
+Exit status:
+  0    success
+  1    I/O error
+  2    invalid arguments
+  3    ffmpeg not found
+  4    ffmpeg failed
+  5    input unreadable
+  6    a sampled frame could not be decoded
+  7    not enough disk space
+  8    --max-slides or --max-output-size exceeded
+  9    ffmpeg timed out or stalled
+  10   evaluate: F1 score below --min-f1
+  11   output directory in use by another run
+  12   no slides found, with --fail-on-zero-slides
+  13   batch: some videos failed
+  14   tesseract not found, with --ocr
+  15   OCR failed
+  130  cancelled";
+
+/// Extract unique slides from a screen recording
+#[derive(Debug, Parser)]
+#[command(version, about, after_long_help = EXIT_CODES, args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    #[command(flatten)]
+    extract: ExtractArgs,
+
+    #[command(flatten)]
+    logging: LoggingArgs,
+}
+
+/// How much gets logged and where to
+#[derive(Debug, Args)]
+struct LoggingArgs {
+    /// Log more: -v adds every frame decision, -vv everything
+    #[arg(short, long, action = ArgAction::Count, global = true)]
+    verbose: u8,
+
+    /// Only log warnings and errors
+    #[arg(short, long, global = true, conflicts_with = "verbose")]
+    quiet: bool,
+
+    /// Format of log lines
+    #[arg(long, value_enum, default_value_t = LogFormat::Text, global = true)]
+    log_format: LogFormat,
+
+    /// Append log lines to this file instead of writing them to stderr
+    #[arg(long, global = true)]
+    log_file: Option<PathBuf>,
+
+    /// Never color the output, as when NO_COLOR is set or it isn't going to a terminal
+    #[arg(long, global = true)]
+    no_color: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum OutputFormat {
+    /// A table of the kept slides, left out with --quiet
+    Table,
+    /// The manifest, as the only thing on stdout, for piping into jq and the like
+    Json,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum LogFormat {
+    Text,
+    /// One JSON object per line, for log collectors
+    Json,
+}
+
+impl LoggingArgs {
+    /// Whether to color what goes to a stream, given whether it is a terminal
+    fn color(&self, terminal: bool) -> bool {
+        terminal && !self.no_color && std::env::var_os("NO_COLOR").is_none()
     }
 
-    // Spawn ffmpeg process to extract frames
-    let status = Command::new("ffmpeg")
-        .arg("-i")
-        .arg(input_file)
-        .arg("-vf")
-        .arg(format!("fps={}", fps))  // Set the frame extraction rate
-        .arg(format!("{}/frame_%04d.png", output_dir))  // Output pattern for frame files
-        .status()?;
+    /// Install the global subscriber; `RUST_LOG` overrides the level flags
+    fn init(&self) -> Result<(), Error> {
+        let level = match (self.quiet, self.verbose) {
+            (true, _) => LevelFilter::WARN,
+            (false, 0) => LevelFilter::INFO,
+            (false, 1) => LevelFilter::DEBUG,
+            (false, _) => LevelFilter::TRACE,
+        };
+        let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(level.to_string()));
+        let builder = tracing_subscriber::fmt().with_env_filter(filter).with_target(false);
 
-    if !status.success() {
-        eprintln!("ffmpeg process failed");
-    } else {
-        println!("Frames extracted successfully.");
+        match (&self.log_file, self.log_format) {
+            (Some(path), format) => {
+                let file = Mutex::new(OpenOptions::new().create(true).append(true).open(path)?);
+                let builder = builder.with_ansi(false).with_writer(file);
+                match format {
+                    LogFormat::Text => builder.init(),
+                    LogFormat::Json => builder.json().init(),
+                }
+            }
+            (None, LogFormat::Text) => builder.with_ansi(self.color(std::io::stderr().is_terminal())).with_writer(std::io::stderr).init(),
+            (None, LogFormat::Json) => builder.json().with_writer(std::io::stderr).init(),
+        }
+        Ok(())
     }
+}
 
-    Ok(())
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Run as a shared service with a REST API for submitting videos
+    Serve(ServeArgs),
+    /// Run in the background taking submit, status and cancel commands on a Unix socket
+    #[cfg(unix)]
+    Daemon(DaemonArgs),
+    /// Extract slides from many videos, a bounded number at a time
+    Batch(Box<BatchArgs>),
+    /// Time the comparison metrics and thresholds on a sample of a video and count the slides each finds
+    Bench(Box<BenchArgs>),
+    /// Score same-slide and different-slide frame pairs of a video with each metric and recommend one with a threshold
+    Calibrate(Box<CalibrateArgs>),
+    /// Score the detected slides against ground-truth slide times
+    Evaluate(Box<EvaluateArgs>),
+    /// Match the slides of a run to the speaker's deck and report pages not shown or shown out of order
+    Coverage(CoverageArgs),
+    /// Export the slides of a finished run as one PDF or HTML page, or long images of the pages scrolled through, from its manifest
+    Export(ExportArgs),
+    /// Number the slides of a run again and rebuild its manifest after slides were deleted or added by hand
+    Finalize(FinalizeArgs),
+    /// Remove the working directories runs left behind in the temp directory
+    Gc(GcArgs),
+    /// Work with the manifest of a finished run
+    #[command(subcommand)]
+    Manifest(ManifestCommand),
+    /// Compare the slides of two runs or videos and report added, removed and modified slides
+    Diff(DiffArgs),
+    /// Extract one stretch of a processed video again, e.g. with another threshold, and merge it into the run
+    Reprocess(Box<ReprocessArgs>),
+    /// Print the completion script for a shell, e.g. `completions bash > /etc/bash_completion.d/videoSlideExtractor`
+    Completions {
+        #[arg(value_enum)]
+        shell: Shell,
+    },
+    /// Print the man page, e.g. `manpage > videoSlideExtractor.1`
+    Manpage,
+}
+
+#[derive(Debug, Args)]
+struct ServeArgs {
+    /// Address to listen on
+    #[arg(long, default_value = "127.0.0.1:8080")]
+    listen: String,
+
+    /// Directory holding uploaded videos, job outputs and the job queue
+    #[arg(long, default_value = "jobs")]
+    data_dir: PathBuf,
+
+    /// URL that receives a JSON notification as each job completes
+    #[arg(long)]
+    webhook: Option<String>,
+
+    /// Base URL clients reach this server on, used for links in notifications
+    #[arg(long)]
+    public_url: Option<String>,
+
+    /// Let jobs name a local file or any protocol ffmpeg knows instead of only http(s) URLs;
+    /// only for a server no untrusted client can reach
+    #[arg(long)]
+    allow_local_inputs: bool,
+
+    #[command(flatten)]
+    limits: JobLimits,
+}
+
+#[cfg(unix)]
+#[derive(Debug, Args)]
+struct DaemonArgs {
+    /// Path of the control socket
+    #[arg(long, default_value = "videoslides.sock")]
+    socket: PathBuf,
+
+    /// Directory holding job outputs and the job queue
+    #[arg(long, default_value = "jobs")]
+    data_dir: PathBuf,
+
+    #[command(flatten)]
+    limits: JobLimits,
+}
+
+#[derive(Debug, Args)]
+struct BatchArgs {
+    /// Video files to extract slides from
+    #[arg(required = true)]
+    files: Vec<PathBuf>,
+
+    /// Directory that receives one subdirectory of slides per video
+    #[arg(long, default_value = "slides")]
+    output_dir: PathBuf,
+
+    /// Where queued jobs are kept so an interrupted batch can resume
+    #[arg(long, default_value = ".videoslides-queue")]
+    spool_dir: PathBuf,
+
+    /// URL that receives a JSON notification as each video completes
+    #[arg(long)]
+    webhook: Option<String>,
+
+    /// Slack incoming webhook URL that receives a summary of the batch, failures included
+    #[arg(long)]
+    slack_webhook: Option<String>,
+
+    /// Mail a summary of the batch, failures included, to this address with the system's sendmail
+    #[arg(long, value_delimiter = ',')]
+    email: Vec<String>,
+
+    #[command(flatten)]
+    limits: JobLimits,
+
+    #[command(flatten)]
+    options: ExtractOptions,
+}
+
+#[derive(Debug, Args)]
+struct BenchArgs {
+    /// Video file to sample
+    file_path: PathBuf,
+
+    /// Seconds from the start of the video to sample
+    #[arg(long, default_value_t = 120.0)]
+    sample: f64,
+
+    /// Thresholds to run each metric with
+    #[arg(long, value_delimiter = ',', default_values_t = [0.005, 0.01, 0.02, 0.05])]
+    thresholds: Vec<f64>,
+
+    /// Values of --compare-stride to try
+    #[arg(long, value_delimiter = ',', default_values_t = [1, 2, 4])]
+    strides: Vec<u32>,
+
+    #[command(flatten)]
+    options: ExtractOptions,
+}
+
+#[derive(Debug, Args)]
+struct CalibrateArgs {
+    /// Video file to sample
+    file_path: PathBuf,
+
+    /// Seconds from the start of the video to sample
+    #[arg(long, default_value_t = 120.0)]
+    sample: f64,
+
+    /// Values of --compare-stride to try
+    #[arg(long, value_delimiter = ',', default_values_t = [1, 2, 4])]
+    strides: Vec<u32>,
+
+    /// File with the time each slide appears, one per line in seconds or [hh:]mm:ss, to label
+    /// the frame pairs by; without it they are split by how much they differ
+    #[arg(long)]
+    truth: Option<PathBuf>,
+
+    /// With --truth, seconds around each slide change in which pairs are left out as transitions
+    #[arg(long, default_value_t = 1.0, requires = "truth")]
+    tolerance: f64,
+
+    #[command(flatten)]
+    options: ExtractOptions,
+}
+
+#[derive(Debug, Args)]
+struct EvaluateArgs {
+    /// Video file to extract slides from
+    file_path: PathBuf,
+
+    /// File with the time each slide appears, one per line in seconds or [hh:]mm:ss
+    #[arg(long)]
+    truth: PathBuf,
+
+    /// Seconds a detected slide may be off from the expected time and still count
+    #[arg(long, default_value_t = 2.0)]
+    tolerance: f64,
+
+    /// Fail if the F1 score is below this, for regression checks
+    #[arg(long)]
+    min_f1: Option<f64>,
+
+    #[command(flatten)]
+    options: ExtractOptions,
+}
+
+#[derive(Debug, Args)]
+struct ExportArgs {
+    /// What to export to
+    #[arg(value_enum)]
+    format: ExportFormat,
+
+    /// Output directory of a run, holding its slides and manifest.json
+    slides_dir: PathBuf,
+
+    /// Where to write the export [default: slides.pdf, slides.html, slides.md, slides.ndjson, transcript.md,
+    /// chapters.txt or the panoramas directory in SLIDES_DIR]
+    #[arg(long)]
+    output: Option<PathBuf>,
+
+    /// Only export slides of these kinds, e.g. code
+    #[arg(long, value_enum, value_delimiter = ',')]
+    kind: Vec<SlideKind>,
+}
+
+#[derive(Debug, Args)]
+struct FinalizeArgs {
+    /// Output directory of a run, holding its slides and manifest.json
+    slides_dir: PathBuf,
+}
+
+#[derive(Debug, Subcommand)]
+enum ManifestCommand {
+    /// Rewrite the manifest of a run written by an older version in the current schema
+    Migrate {
+        /// Output directory of a run, holding its slides and manifest.json
+        slides_dir: PathBuf,
+    },
 }
 
-/// Compare two images and determine if they are visually similar
-fn are_images_similar(img1: &DynamicImage, img2: &DynamicImage, threshold: f64) -> bool {
-    if img1.dimensions() != img2.dimensions() {
-        return false;
+#[derive(Debug, Args)]
+struct GcArgs {
+    /// Directory the runs kept their sampled frames in, their --tmp-dir [default: system temp directory]
+    #[arg(long)]
+    tmp_dir: Option<PathBuf>,
+
+    /// Remove working directories untouched for this many hours; 0 removes all no run holds
+    #[arg(long, default_value_t = 24.0)]
+    older_than: f64,
+
+    /// Then remove the oldest of the rest until they take at most this much, e.g. 5G
+    #[arg(long, value_parser = parse_size)]
+    max_size: Option<u64>,
+
+    /// Only list what would be removed
+    #[arg(long)]
+    dry_run: bool,
+}
+
+#[derive(Debug, Args)]
+struct DiffArgs {
+    /// Output directory of the earlier run, or a video to extract with the default settings
+    old: PathBuf,
+
+    /// Output directory of the later run, or a video to extract with the default settings
+    new: PathBuf,
+
+    /// Directory for the side-by-side images of modified slides
+    #[arg(long, default_value = "slide-diff")]
+    output: PathBuf,
+
+    /// Bits of the 64-bit perceptual hashes two slides may differ in and still be the same slide
+    #[arg(long, default_value_t = 10, value_parser = clap::value_parser!(u32).range(0..=64))]
+    max_distance: u32,
+}
+
+#[derive(Debug, Args)]
+struct ReprocessArgs {
+    /// Output directory of a run, holding its slides and manifest.json
+    slides_dir: PathBuf,
+
+    /// Start of the stretch, in seconds or [hh:]mm:ss
+    #[arg(long, value_parser = parse_time)]
+    from: f64,
+
+    /// End of the stretch, in seconds or [hh:]mm:ss
+    #[arg(long, value_parser = parse_time)]
+    to: f64,
+
+    /// The video the run was made from [default: the screen recording named in the manifest]
+    #[arg(long)]
+    video: Option<PathBuf>,
+
+    #[command(flatten)]
+    options: ExtractOptions,
+}
+
+#[derive(Debug, Args)]
+struct CoverageArgs {
+    /// Output directory of a run, holding its slides and manifest.json
+    slides_dir: PathBuf,
+
+    /// The speaker's deck: a PDF (needs pdftoppm), a PPTX, PPT, ODP or Keynote file
+    /// (also needs LibreOffice) or a directory of page images
+    #[arg(long)]
+    deck: PathBuf,
+
+    /// Bits of the 64-bit perceptual hashes a slide and a page may differ in and still match
+    #[arg(long, default_value_t = 10, value_parser = clap::value_parser!(u32).range(0..=64))]
+    max_distance: u32,
+
+    /// Also write the times each page was on screen to this JSON file, for players that
+    /// jump the video to a page picked in the PDF
+    #[arg(long)]
+    page_map: Option<PathBuf>,
+}
+
+/// Resource limits for modes that run several jobs
+#[derive(Debug, Args)]
+struct JobLimits {
+    /// Jobs processed at the same time
+    #[arg(long, default_value_t = 1)]
+    workers: usize,
+
+    /// Threads each job's ffmpeg may use (all cores by default)
+    #[arg(long)]
+    job_threads: Option<u32>,
+
+    /// Memory all jobs together should stay within, e.g. 2G; split evenly between the workers
+    #[arg(long, value_parser = parse_size)]
+    max_memory: Option<u64>,
+}
+
+impl JobLimits {
+    /// Share of --max-memory each job gets
+    fn job_memory(&self) -> Option<u64> {
+        self.max_memory.map(|total| total / self.workers.max(1) as u64)
     }
+}
 
-    let (width, height) = img1.dimensions();
-    let mut diff_count = 0;
+#[derive(Debug, Args)]
+struct ExtractArgs {
+    /// Video file to extract slides from (the screen capture), or - to read it from stdin,
+    /// e.g. `curl -s URL | videoSlideExtractor - --archive out.zip`; from stdin or a named pipe
+    /// the frames are compared as they arrive until the writer closes it
+    #[arg(required = true)]
+    file_path: Option<PathBuf>,
 
-    for x in 0..width {
-        for y in 0..height {
-            let p1 = img1.get_pixel(x, y);
-            let p2 = img2.get_pixel(x, y);
+    /// Memory the run should stay within, e.g. 2G; ffmpeg gets fewer threads to fit
+    #[arg(long, value_parser = parse_size)]
+    max_memory: Option<u64>,
 
-            if p1 != p2 {
-                diff_count += 1;
-            }
+    /// What to print once the slides are extracted; logs always go to stderr or --log-file
+    #[arg(long, value_enum, default_value_t = OutputFormat::Table)]
+    output_format: OutputFormat,
+
+    /// Exit with status 12 when no slide was found, to tell a video without slides from a failed run
+    #[arg(long)]
+    fail_on_zero_slides: bool,
+
+    /// When done, open the output directory with the desktop's file manager, or the report
+    /// (turning on --report) or the slides exported as html or pdf
+    #[arg(long, value_enum, value_name = "WHAT", num_args = 0..=1, default_missing_value = "dir")]
+    open: Option<OpenTarget>,
+
+    /// Show a desktop notification when the run finishes or fails
+    #[arg(long)]
+    notify: bool,
+
+    /// Only sample a few stretches of the video and print how long the run would take, how many
+    /// slides it would keep and how much disk it would use, without running it
+    #[arg(long, conflicts_with_all = ["open", "notify", "fail_on_zero_slides"])]
+    estimate: bool,
+
+    #[command(flatten)]
+    options: ExtractOptions,
+}
+
+/// Settings shared by every way of running an extraction
+#[derive(Debug, Args)]
+struct ExtractOptions {
+    /// Room camera recording of the same session; its timeline is added to the manifest
+    #[arg(long)]
+    camera: Option<PathBuf>,
+
+    /// How to align the camera recording with the screen recording
+    #[arg(long, value_enum, default_value_t = SyncMode::Offset, requires = "camera")]
+    sync: SyncMode,
+
+    /// Seconds the camera clock runs ahead of the screen clock (negative if it started later)
+    #[arg(long, default_value_t = 0.0, allow_negative_numbers = true)]
+    camera_offset: f64,
+
+    /// Largest offset in seconds considered when syncing by audio
+    #[arg(long, default_value_t = 120.0)]
+    max_sync_offset: f64,
+
+    /// Compare only every Nth pixel in each direction, much faster on 4K recordings
+    #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..))]
+    compare_stride: u32,
+
+    /// Blur both frames of each comparison first, to suppress dithering and compression noise;
+    /// the kept slides are not blurred
+    #[arg(long, value_enum)]
+    prefilter: Option<Prefilter>,
+
+    /// Width of the --prefilter kernel in pixels, made odd
+    #[arg(long, default_value_t = 3, requires = "prefilter", value_parser = clap::value_parser!(u32).range(1..=15))]
+    prefilter_size: u32,
+
+    /// Count changed pixels in areas that look like text this many times, so a one-word edit
+    /// on a mostly empty slide still clears the threshold
+    #[arg(long, conflicts_with = "ignore_embedded_video")]
+    text_weight: Option<f64>,
+
+    /// Compare frames in a compute shader on the GPU, falling back to the CPU if there is none
+    #[arg(long)]
+    gpu: bool,
+
+    /// Only look at this area of the video, and keep only it in the slides: e.g. 1920x1080+1920+0 for the
+    /// right one of two monitors, or 0,0,75%,100% (X,Y,W,H) to leave out a chat panel on the right
+    #[arg(long, value_name = "WxH+X+Y|X,Y,W,H")]
+    crop: Option<CropArea>,
+
+    /// The recording spans monitors side by side: "auto" guesses how many from the aspect ratio;
+    /// each becomes a deck of its own in a monitor-N subdirectory unless --monitor picks one
+    #[arg(long, value_name = "auto|N", conflicts_with = "crop")]
+    split_monitors: Option<MonitorSplit>,
+
+    /// With --split-monitors, only keep the monitor the slides are on, counted from 1 at the left
+    #[arg(long, requires = "split_monitors", value_parser = clap::value_parser!(u32).range(1..))]
+    monitor: Option<u32>,
+
+    /// Match each frame's brightness to the one it is compared against, for a screen filmed with a camera
+    /// whose exposure flickers or drifts; best together with --prefilter
+    #[arg(long)]
+    normalize_brightness: bool,
+
+    /// Settings for the tool the video was recorded with: obs, zoom, teams, meet, panopto, echo360,
+    /// projector (a camera filming a projected screen), or a JSON file of one's own; options given here
+    /// take precedence over the profile's where they differ from their defaults
+    #[arg(long, value_name = "NAME|FILE", value_parser = profile::load)]
+    platform: Option<Profile>,
+
+    /// Leave regions that change in every frame (embedded videos) out of the comparison
+    #[arg(long)]
+    ignore_embedded_video: bool,
+
+    /// Consecutive frames a region must keep changing in before it is ignored
+    #[arg(long, default_value_t = 3, requires = "ignore_embedded_video")]
+    motion_streak: u32,
+
+    /// Leave a clock, date or counter in a bar along the top or bottom edge out of the comparison
+    /// once it is seen changing on its own, so it ticking over is not a new slide
+    #[arg(long)]
+    ignore_live_footer: bool,
+
+    /// The video films a whiteboard or blackboard lecture: ignore the lecturer walking past the board
+    /// and keep the board as it was right before each time it is erased
+    #[arg(long)]
+    whiteboard: bool,
+
+    /// The video is handwritten on a tablet: keep the page once no ink was added for this many seconds
+    /// and right before writing disappears from it (scrolled, cleared or erased)
+    #[arg(long, value_name = "SECONDS", conflicts_with_all = ["whiteboard", "auto_fps"])]
+    ink_pause: Option<f64>,
+
+    /// A document or editor scrolled on screen stays one slide, extended with the lines scrolled into view
+    #[arg(long)]
+    stitch_scrolls: bool,
+
+    /// Zooming into part of a slide, or panning around it zoomed in, stays on that slide
+    #[arg(long)]
+    ignore_zoom: bool,
+
+    /// Keep the closest view of each zoom as <slide>_zoom.png in a zooms directory next to the slides
+    #[arg(long, requires = "ignore_zoom")]
+    save_zooms: bool,
+
+    /// Largest share of differing pixels for two frames to count as the same slide
+    #[arg(long, default_value_t = 0.01)]
+    threshold: f64,
+
+    /// Once a frame starts a slide, the frames after it are the same change until one differs from the one
+    /// before it by at most this share; below --threshold transitions and frames hovering at it make one slide
+    #[arg(long)]
+    low_threshold: Option<f64>,
+
+    /// How frames are told apart: pixel (--threshold), or measures that must all (&) or any (|)
+    /// say frames differ, e.g. ensemble:pixel>0.01&phash>6 (phash: bits of a 64-bit perceptual hash)
+    #[arg(long, default_value_t = Metric::Pixel)]
+    metric: Metric,
+
+    /// Wait for this many consecutive similar frames after a change and keep the last of them,
+    /// so slides caught mid-render are skipped
+    #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..))]
+    settle_frames: u32,
+
+    /// File names for the kept slides, from {video}, {index}, {frame}, {seconds}, {timestamp} and,
+    /// with --summarize, {title}; numbers take a zero-padded width like {index:03}
+    #[arg(long, value_parser = parse_name_template)]
+    name_template: Option<String>,
+
+    /// Delete kept slides scoring below this confidence (0 to 1) from how long they
+    /// persisted, how distinct they were and how sharp
+    #[arg(long, value_parser = parse_fraction)]
+    min_confidence: Option<f64>,
+
+    /// Keep a slide the presenter comes back to only once, listing every interval it was shown
+    #[arg(long)]
+    merge_revisits: bool,
+
+    /// With --merge-revisits, largest difference to an earlier slide that still counts as
+    /// showing it again [default: --threshold]
+    #[arg(long, requires = "merge_revisits")]
+    revisit_threshold: Option<f64>,
+
+    /// Decode QR codes on the kept slides, and with --ocr pick web addresses out of their text, into
+    /// the manifest and a links.md next to it
+    #[arg(long)]
+    find_links: bool,
+
+    /// Read the text off the kept slides with Tesseract into the manifest; what was read is
+    /// cached in ocr/ so running again only reads new slides
+    #[arg(long)]
+    ocr: bool,
+
+    /// With --ocr, slides read at once [default: one per CPU]
+    #[arg(long, value_name = "N", requires = "ocr")]
+    ocr_threads: Option<usize>,
+
+    /// With --ocr, what reads the text: tesseract, or the URL of an endpoint taking Google Cloud
+    /// Vision requests for handwriting and scripts Tesseract reads poorly; the API key is taken
+    /// from OCR_API_KEY
+    #[arg(long, value_name = "ENGINE", requires = "ocr", default_value = "tesseract")]
+    ocr_engine: OcrBackend,
+
+    /// With --ocr, read slides not in English again with Tesseract's model for the language
+    /// detected in them (German, French, Spanish, Italian, Portuguese or Dutch), if installed
+    #[arg(long, requires = "ocr")]
+    ocr_by_language: bool,
+
+    /// With --ocr, ask the OpenAI-compatible API at this base URL (e.g. https://api.openai.com/v1) for a
+    /// short title and summary of each slide, for the manifest, exports and {title} in --name-template;
+    /// the API key is taken from OPENAI_API_KEY
+    #[arg(long, value_name = "URL", requires = "ocr")]
+    summarize: Option<String>,
+
+    /// With --summarize, the model to ask [default: gpt-4o-mini]
+    #[arg(long, value_name = "MODEL", requires = "summarize")]
+    summarize_model: Option<String>,
+
+    /// With --summarize, also send a small copy of each slide, for models that take images
+    #[arg(long, requires = "summarize")]
+    summarize_images: bool,
+
+    /// Tag each slide with who spoke longest while it was up, from an .rttm file a diarization tool
+    /// wrote, or a command (e.g. a pyannote script) given the audio as a WAV file that prints RTTM
+    #[arg(long, value_name = "RTTM|COMMAND")]
+    diarize: Option<Diarization>,
+
+    /// Give each deck of a multi-speaker recording its own deck-N directory and manifest,
+    /// starting a new one where the slides' theme colour changes
+    #[arg(long)]
+    split_decks: bool,
+
+    /// With --split-decks, also start a new deck after this many seconds without a still slide
+    #[arg(long, requires = "split_decks")]
+    deck_gap: Option<f64>,
+
+    /// With --split-decks, also start a new deck after applause or a long silence, between
+    /// talks of a conference recording whose speakers share a template
+    #[arg(long, requires = "split_decks")]
+    deck_audio: bool,
+
+    /// Leave out a "Starting soon" or closing screen that opens or ends the recording
+    /// unchanged for at least this many seconds, and record where the content runs; with --ocr,
+    /// also screens anywhere whose short text says an idle keyword like "be right back"
+    #[arg(long)]
+    trim_idle: Option<f64>,
+
+    /// With --trim-idle and --ocr, the phrases that mark a screen as idle, replacing the built-in
+    /// list ("starting soon", "be right back", "brb", "waiting room", ...)
+    #[arg(long, value_name = "PHRASE,...", value_delimiter = ',', requires = "trim_idle")]
+    idle_keywords: Option<Vec<String>>,
+
+    /// Move each slide change to the nearest pause in the speech up to this many seconds away,
+    /// so chapters and the transcript split between sentences [default: 2]
+    #[arg(long, value_name = "SECS", num_args = 0..=1, default_missing_value = "2")]
+    snap_to_pauses: Option<f64>,
+
+    /// Also write each slide's timestamp, duration and scores to a file next to it
+    #[arg(long, value_name = "FORMAT", num_args = 0..=1, default_missing_value = "json")]
+    sidecars: Option<SidecarFormat>,
+
+    /// Also write report.html, a timeline of every sampled frame as a thumbnail marked kept or dropped,
+    /// with its difference on hover, to see why a slide was missed
+    #[arg(long)]
+    report: bool,
+
+    /// Also save each slide next to the one before it with what changed highlighted in red, as
+    /// <slide>_diff.png in a diffs directory, to check that every kept slide is new
+    #[arg(long)]
+    save_diffs: bool,
+
+    /// Keep these exports in the output directory up to date as slides are kept, to review the first
+    /// slides of a long video while the rest is processed: html, markdown, ndjson
+    #[arg(long, value_enum, value_delimiter = ',')]
+    live: Vec<LiveFormat>,
+
+    /// Pad every slide onto a canvas of this aspect ratio, e.g. 16:9, so slides from
+    /// mixed sources share one shape
+    #[arg(long)]
+    canvas: Option<AspectRatio>,
+
+    /// Colour of the padding added by --canvas, as #RRGGBB
+    #[arg(long, default_value_t = Color::default(), requires = "canvas")]
+    canvas_background: Color,
+
+    /// Deliver slides and manifest to this directory or s3://bucket/prefix when done
+    #[arg(long)]
+    output: Option<String>,
+
+    /// Also write a zip of the slides and manifest with this file name
+    #[arg(long)]
+    archive: Option<String>,
+
+    /// What to do with a frame that cannot be decoded
+    #[arg(long, value_enum, default_value_t = BadFramePolicy::Stop)]
+    on_bad_frame: BadFramePolicy,
+
+    /// Same as --on-bad-frame skip
+    #[arg(long, conflicts_with = "on_bad_frame")]
+    skip_bad_frames: bool,
+
+    /// Stop once more than this many slides have been kept
+    #[arg(long)]
+    max_slides: Option<usize>,
+
+    /// Stop once the kept slides take up more than this, e.g. 500MB
+    #[arg(long, value_parser = parse_size)]
+    max_output_size: Option<u64>,
+
+    /// What to do when the slides approach --max-slides or --max-output-size
+    #[arg(long, value_enum, default_value_t = LimitPolicy::Stop)]
+    on_limit: LimitPolicy,
+
+    /// Kill ffmpeg if sampling the video takes longer than this many seconds
+    #[arg(long, value_name = "SECS")]
+    ffmpeg_timeout: Option<u64>,
+
+    /// Kill ffmpeg if it produces no new frame for this many seconds
+    #[arg(long, value_name = "SECS")]
+    stall_timeout: Option<u64>,
+
+    /// If ffmpeg fails or is killed, try once more with -err_detect ignore_err
+    #[arg(long)]
+    retry_ignore_errors: bool,
+
+    /// If ffmpeg fails or is killed, e.g. on a flaky network share or URL, run it again up to
+    /// this many times, carrying on after the last frame it wrote (before --retry-ignore-errors)
+    #[arg(long, default_value_t = 0)]
+    retries: u32,
+
+    /// Seconds to wait before the first of --retries, doubling with each
+    #[arg(long, value_name = "SECS", requires = "retries")]
+    retry_backoff: Option<f64>,
+
+    /// Process the video even if its output directory already holds slides from the same input and settings
+    #[arg(long)]
+    force: bool,
+
+    /// Directory for the sampled frames while they are compared [default: system temp directory]
+    #[arg(long)]
+    tmp_dir: Option<PathBuf>,
+
+    /// Keep the sampled frames in this directory and reuse them when the same video is run
+    /// again, e.g. with another --threshold, without decoding it
+    #[arg(long, value_name = "DIR", conflicts_with_all = ["in_memory", "adaptive"])]
+    frame_cache: Option<PathBuf>,
+
+    /// Extract even if the sampled frames look like they won't fit on the disk
+    #[arg(long)]
+    no_space_check: bool,
+
+    /// Whether the working directory of sampled frames is left behind when the run ends
+    #[arg(long, value_enum, default_value_t = WorkspacePolicy::Delete)]
+    workspace: WorkspacePolicy,
+
+    /// Size the working directories left behind in the temp directory may take together,
+    /// e.g. 5G; the oldest are removed to make room
+    #[arg(long, value_parser = parse_size)]
+    workspace_cap: Option<u64>,
+
+    /// Choose the sampling rate from how fast a few stretches of the video change: up to 4 fps
+    /// for quick demos, sparse sampling as with --adaptive for slow decks
+    #[arg(long)]
+    auto_fps: bool,
+
+    /// Sample one frame every this many seconds, and at the full sampling rate (1 fps, or the
+    /// one --auto-fps picks) only where two of those differ; much less decoding for static lectures
+    #[arg(long, value_name = "SECS", conflicts_with = "in_memory")]
+    adaptive: Option<f64>,
+
+    /// With --adaptive, largest difference between two sparse samples that skips sampling
+    /// the stretch between them at the full sampling rate [default: --threshold]
+    #[arg(long, requires = "adaptive")]
+    coarse_threshold: Option<f64>,
+
+    /// Don't write the source video, timestamp and slide index into each kept PNG
+    #[arg(long)]
+    no_metadata: bool,
+
+    /// Sample the video as this many stretches at once, one ffmpeg each, for long videos
+    /// whose decoding keeps one core busy
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..), conflicts_with_all = ["in_memory", "adaptive", "retries"])]
+    segments: Option<u32>,
+
+    /// Decode the sampled frames into memory instead of writing them to disk, for short clips
+    #[arg(long, conflicts_with_all = ["tmp_dir", "retries"])]
+    in_memory: bool,
+
+    /// How hard ffmpeg compresses the sampled frames, the kept slides among them: 0 (fastest,
+    /// largest) to 9 (smallest); 0 or 1 speeds up long videos at the cost of disk [default: ffmpeg's]
+    #[arg(long, value_name = "LEVEL", value_parser = clap::value_parser!(u32).range(0..=9), conflicts_with = "in_memory")]
+    png_compression: Option<u32>,
+
+    /// Filter ffmpeg runs the sampled frames through before compressing them: none is the
+    /// fastest, mixed the smallest [default: ffmpeg's]
+    #[arg(long, value_enum, value_name = "FILTER", conflicts_with = "in_memory")]
+    png_prediction: Option<PngPrediction>,
+}
+
+impl ExtractOptions {
+    /// Settings for extracting slides from `input_file`
+    fn to_config(&self, input_file: &Path) -> Config {
+        let mut config = Config::new(input_file);
+        config.threshold = self.threshold;
+        config.low_threshold = self.low_threshold;
+        config.metric = self.metric;
+        config.settle_frames = self.settle_frames;
+        config.camera_file = self.camera.clone();
+        config.sync = self.sync;
+        config.camera_offset = self.camera_offset;
+        config.max_sync_offset = self.max_sync_offset;
+        config.compare_stride = self.compare_stride;
+        config.prefilter = self.prefilter;
+        config.prefilter_size = Some(self.prefilter_size);
+        config.text_weight = self.text_weight;
+        config.gpu = self.gpu;
+        config.crop = self.crop;
+        config.split_monitors = self.split_monitors;
+        config.monitor = self.monitor;
+        config.normalize_brightness = self.normalize_brightness;
+        config.ignore_embedded_video = self.ignore_embedded_video;
+        config.ignore_live_footer = self.ignore_live_footer;
+        config.whiteboard = self.whiteboard;
+        config.ink_pause = self.ink_pause;
+        config.stitch_scrolls = self.stitch_scrolls;
+        config.ignore_zoom = self.ignore_zoom;
+        config.save_zooms = self.save_zooms;
+        config.save_diffs = self.save_diffs;
+        config.motion_streak = self.motion_streak;
+        config.name_template = self.name_template.clone();
+        config.min_confidence = self.min_confidence;
+        config.merge_revisits = self.merge_revisits;
+        config.revisit_threshold = self.revisit_threshold;
+        config.find_links = self.find_links;
+        config.ocr = self.ocr;
+        config.ocr_threads = self.ocr_threads;
+        config.ocr_backend = self.ocr_engine.clone();
+        config.ocr_by_language = self.ocr_by_language;
+        config.summarize = self.summarize.clone();
+        config.diarize = self.diarize.clone();
+        config.summarize_model = self.summarize_model.clone();
+        config.summarize_images = self.summarize_images;
+        config.split_decks = self.split_decks;
+        config.deck_gap = self.deck_gap;
+        config.deck_audio = self.deck_audio;
+        config.trim_idle = self.trim_idle;
+        config.idle_keywords = self.idle_keywords.clone();
+        config.snap_to_pauses = self.snap_to_pauses;
+        config.sidecars = self.sidecars;
+        config.report = self.report;
+        config.live = self.live.clone();
+        config.canvas = self.canvas;
+        config.canvas_background = self.canvas_background;
+        config.output = self.output.clone();
+        config.archive = self.archive.clone();
+        config.on_bad_frame = if self.skip_bad_frames { BadFramePolicy::Skip } else { self.on_bad_frame };
+        config.max_slides = self.max_slides;
+        config.max_output_size = self.max_output_size;
+        config.on_limit = self.on_limit;
+        config.ffmpeg_timeout = self.ffmpeg_timeout;
+        config.stall_timeout = self.stall_timeout;
+        config.retry_ignore_errors = self.retry_ignore_errors;
+        config.retries = self.retries;
+        config.retry_backoff = self.retry_backoff;
+        config.force = self.force;
+        config.tmp_dir = self.tmp_dir.clone();
+        config.frame_cache = self.frame_cache.clone();
+        config.skip_space_check = self.no_space_check;
+        config.workspace = self.workspace;
+        config.workspace_cap = self.workspace_cap;
+        config.skip_metadata = self.no_metadata;
+        config.in_memory = self.in_memory;
+        config.png_compression = self.png_compression;
+        config.png_prediction = self.png_prediction;
+        config.segments = self.segments.unwrap_or(1);
+        config.auto_fps = self.auto_fps;
+        config.adaptive = self.adaptive;
+        config.coarse_threshold = self.coarse_threshold;
+        if let Some(profile) = &self.platform {
+            profile.apply(&mut config);
         }
+        config
     }
+}
 
-    let total_pixels = width * height;
-    let difference_ratio = (diff_count as f64) / (total_pixels as f64);
-    
-    difference_ratio <= threshold
+/// Parse a size like `500MB`, `1.5G` or `2048` (bytes); units are powers of 1024
+fn parse_size(arg: &str) -> Result<u64, String> {
+    let arg = arg.trim();
+    let split = arg.find(|c: char| !c.is_ascii_digit() && c != '.').unwrap_or(arg.len());
+    let (number, unit) = arg.split_at(split);
+    let number: f64 = number.parse().map_err(|_| format!("{:?} is not a size", arg))?;
+    let multiplier: u64 = match unit.trim().to_ascii_uppercase().trim_end_matches("IB").trim_end_matches('B') {
+        "" => 1,
+        "K" => 1 << 10,
+        "M" => 1 << 20,
+        "G" => 1 << 30,
+        "T" => 1 << 40,
+        _ => return Err(format!("unknown unit in {:?}, use B, KB, MB, GB or TB", arg)),
+    };
+    Ok((number * multiplier as f64) as u64)
 }
 
-/// Process extracted frames and filter out non-unique frames
-fn process_frames(output_dir: &str, threshold: f64) -> Result<(), Error> {
-    let mut frame_files: Vec<PathBuf> = fs::read_dir(output_dir)?
-        .filter_map(Result::ok)
-        .filter(|entry| entry.path().extension().and_then(|s| s.to_str()) == Some("png"))
-        .map(|entry| entry.path())
-        .collect();
+/// A number from 0 to 1
+fn parse_fraction(arg: &str) -> Result<f64, String> {
+    match arg.parse::<f64>() {
+        Ok(value) if (0.0..=1.0).contains(&value) => Ok(value),
+        _ => Err(format!("{:?} is not a number from 0 to 1", arg)),
+    }
+}
 
-    frame_files.sort(); // Ensure files are sorted in correct order
+/// A time in seconds or [hh:]mm:ss
+fn parse_time(arg: &str) -> Result<f64, String> {
+    parse_timestamp(arg).ok_or_else(|| format!("{:?} is not a time in seconds or [hh:]mm:ss", arg))
+}
 
-    let mut last_image: Option<DynamicImage> = None;
+/// Check a --name-template up front rather than after extracting the frames
+fn parse_name_template(arg: &str) -> Result<String, String> {
+    NameTemplate::parse(arg).map(|_| arg.to_string())
+}
 
-    for frame in frame_files {
-        // Here, we map the image error to an io::Error
-        let current_image = image::open(&frame).map_err(|e| {
-            Error::new(ErrorKind::Other, format!("Error opening image: {}", e))
-        })?;
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    if let Err(e) = cli.logging.init() {
+        eprintln!("Cannot open log file: {}", e);
+        return ExitCode::FAILURE;
+    }
+    match run(cli) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            tracing::error!("{}", e);
+            ExitCode::from(e.exit_code())
+        }
+    }
+}
+
+fn run(cli: Cli) -> Result<(), Error> {
+    match cli.command {
+        Some(Command::Serve(args)) => serve(&ServeOptions {
+            listen: args.listen,
+            data_dir: args.data_dir,
+            workers: args.limits.workers,
+            ffmpeg_threads: args.limits.job_threads,
+            max_memory: args.limits.job_memory(),
+            webhook: args.webhook,
+            public_url: args.public_url,
+            allow_local_inputs: args.allow_local_inputs,
+        })
+        .map_err(Error::from),
+        #[cfg(unix)]
+        Some(Command::Daemon(args)) => run_daemon(&DaemonOptions {
+            socket: args.socket,
+            data_dir: args.data_dir,
+            workers: args.limits.workers,
+            ffmpeg_threads: args.limits.job_threads,
+            max_memory: args.limits.job_memory(),
+        })
+        .map_err(Error::from),
+        Some(Command::Batch(args)) => batch(*args),
+        Some(Command::Bench(args)) => bench(*args),
+        Some(Command::Calibrate(args)) => calibrate_metrics(*args),
+        Some(Command::Evaluate(args)) => evaluate_detection(*args),
+        Some(Command::Coverage(args)) => deck_coverage(args),
+        Some(Command::Export(args)) => export_slides(args),
+        Some(Command::Finalize(args)) => finalize_slides(args),
+        Some(Command::Gc(args)) => collect_workspaces(args),
+        Some(Command::Manifest(ManifestCommand::Migrate { slides_dir })) => migrate_manifest(&slides_dir),
+        Some(Command::Diff(args)) => diff_runs(args),
+        Some(Command::Reprocess(args)) => reprocess_window(*args),
+        Some(Command::Completions { shell }) => {
+            let mut command = Cli::command();
+            let name = command.get_name().to_string();
+            // clap_complete panics on a closed stdout, such as piping into head
+            let mut script = Vec::new();
+            clap_complete::generate(shell, &mut command, name, &mut script);
+            std::io::stdout().write_all(&script).map_err(Error::from)
+        }
+        Some(Command::Manpage) => clap_mangen::Man::new(Cli::command()).render(&mut std::io::stdout()).map_err(Error::from),
+        None => extract(cli.extract, &cli.logging),
+    }
+}
 
-        if let Some(ref last_image) = last_image {
-            if are_images_similar(last_image, &current_image, threshold) {
-                println!("Frame {:?} is similar to the previous one, deleting it.", frame);
-                fs::remove_file(&frame)?; // Remove non-unique frame
-            } else {
-                println!("Frame {:?} is unique.", frame);
+fn extract(args: ExtractArgs, logging: &LoggingArgs) -> Result<(), Error> {
+    let file_path = args.file_path.as_deref().expect("clap enforces the file path");
+    let mut config = args.options.to_config(file_path);
+    config.max_memory = args.max_memory;
+    config.report |= args.open == Some(OpenTarget::Report);
+    if args.estimate {
+        return print_estimate(&config, args.output_format);
+    }
+
+    let manifest = match video_slide_extractor::run(&config) {
+        Ok(manifest) => manifest,
+        Err(e) => {
+            if args.notify {
+                notify(&format!("Extracting slides from {} failed: {}", file_path.display(), e));
             }
-        } else {
-            println!("First frame {:?} is considered unique.", frame);
+            return Err(e);
+        }
+    };
+    if args.notify {
+        notify(&format!("Extracted {} slide(s) from {} into {}", manifest.slides.len(), file_path.display(), config.output_dir.display()));
+    }
+    if let Some(target) = args.open {
+        if let Err(e) = desktop::open_output(&config.output_dir, target) {
+            tracing::warn!("Could not open the output: {}", e);
         }
+    }
+    if args.fail_on_zero_slides && manifest.slides.is_empty() {
+        return Err(Error::NoSlides { input: file_path.display().to_string() });
+    }
 
-        last_image = Some(current_image);
+    match args.output_format {
+        OutputFormat::Json => println!("{}", manifest.to_json()?),
+        OutputFormat::Table if !logging.quiet => print_slides(&manifest, logging.color(std::io::stdout().is_terminal())),
+        OutputFormat::Table => {}
     }
+    Ok(())
+}
 
+fn print_estimate(config: &Config, output_format: OutputFormat) -> Result<(), Error> {
+    let estimate = estimate(config)?;
+    if output_format == OutputFormat::Json {
+        println!("{}", serde_json::to_string_pretty(&estimate).map_err(std::io::Error::other)?);
+        return Ok(());
+    }
+    println!("{:.0}s of video, {} frame(s) from {:.0}s sampled", estimate.duration, estimate.frames, estimate.sampled);
+    let minutes = (estimate.runtime / 60.0).round() as u64;
+    let runtime = match minutes {
+        0 => format!("{:.0}s", estimate.runtime.ceil()),
+        1..=59 => format!("{}m", minutes),
+        _ => format!("{}h {:02}m", minutes / 60, minutes % 60),
+    };
+    println!("runtime:      about {}", runtime);
+    println!("slides:       about {}", estimate.slides);
+    println!("output:       about {}", format_size(estimate.output_size));
+    println!("working dir:  about {}", format_size(estimate.working_size));
     Ok(())
 }
 
-fn main() -> Result<(), Error> {
-    let args: Vec<String> = env::args().collect();
-    if args.len() != 2 {
-        eprintln!("Usage: {} <file_path>", args[0]);
-        std::process::exit(1);
+/// Show a desktop notification saying `message`, warning if there is no desktop to show it on
+fn notify(message: &str) {
+    if let Err(e) = desktop::notify(message) {
+        tracing::warn!("Could not show a desktop notification: {}", e);
     }
-    let file_path = Path::new(&args[1]);
+}
 
-    // Convert the file_path (Path) to a &str
-    let input_file = match file_path.to_str() {
-        Some(path_str) => path_str,  // Valid string path
-        None => {
-            eprintln!("Invalid file path.");
-            std::process::exit(1);
-        }
+/// Confidence below which a slide is highlighted as doubtful
+const LOW_CONFIDENCE: f64 = 0.5;
+
+/// Table of the kept slides, with doubtful ones and frames that could not be read highlighted
+fn print_slides(manifest: &Manifest, color: bool) {
+    let paint = |code: &str, text: String| if color { format!("\x1b[{}m{}\x1b[0m", code, text) } else { text };
+    let clock = |seconds: f64| {
+        let seconds = seconds.max(0.0);
+        format!("{}:{:02}:{:04.1}", (seconds / 3600.0) as u64, (seconds / 60.0) as u64 % 60, seconds % 60.0)
     };
 
-    let output_dir = "frames";     // Directory to store extracted frames
-    let fps = 1;                   // Set extraction to 1 frame per second (or as desired)
-    let similarity_threshold = 0.01; // Threshold for image similarity (adjust as needed)
+    println!("{}", paint("1", format!("{:>5}  {:>10}  {:>8}  {:>5}  {}", "slide", "time", "shown", "score", "file")));
+    for (i, slide) in manifest.slides.iter().enumerate() {
+        // Until the next slide if the confidence wasn't worked out
+        let shown = slide.confidence.as_ref().map(|confidence| confidence.persisted).or_else(|| {
+            manifest.slides.get(i + 1).map(|next| next.timestamp - slide.timestamp)
+        });
+        let shown = shown.map_or("-".to_string(), |shown| format!("{:.1}s", shown));
+        let score = slide.confidence.as_ref().map(|confidence| confidence.score);
+        let score_text = format!("{:>5}", score.map_or("-".to_string(), |score| format!("{:.2}", score)));
+        let score_text = match score {
+            Some(score) if score < LOW_CONFIDENCE => paint("33", score_text),
+            _ => score_text,
+        };
+        println!("{:>5}  {:>10}  {:>8}  {}  {}", slide.index, clock(slide.timestamp), shown, score_text, slide.file);
+    }
+
+    println!("{}", paint("32", format!("{} slide(s)", manifest.slides.len())));
+    let doubtful = manifest
+        .slides
+        .iter()
+        .filter(|slide| slide.confidence.as_ref().is_some_and(|confidence| confidence.score < LOW_CONFIDENCE))
+        .count();
+    if doubtful > 0 {
+        println!("{}", paint("33", format!("{} slide(s) with a confidence below {}", doubtful, LOW_CONFIDENCE)));
+    }
+    if !manifest.bad_frames.is_empty() {
+        println!("{}", paint("31", format!("{} frame(s) could not be read and were left out", manifest.bad_frames.len())));
+    }
+}
+
+fn batch(args: BatchArgs) -> Result<(), Error> {
+    // The input is replaced per video, the rest applies to all of them
+    let mut template = args.options.to_config(Path::new(""));
+    template.ffmpeg_threads = args.limits.job_threads;
+    template.max_memory = args.limits.job_memory();
+
+    let options = BatchOptions {
+        output_dir: args.output_dir,
+        workers: args.limits.workers,
+        spool_dir: args.spool_dir,
+        webhook: args.webhook,
+        slack_webhook: args.slack_webhook,
+        email: args.email,
+    };
+    let results = run_batch(&args.files, &template, &options)?;
+
+    let failed = results.iter().filter(|result| result.error.is_some()).count();
+    tracing::info!("Processed {} video(s), {} failed.", results.len(), failed);
+
+    if failed > 0 {
+        return Err(Error::BatchFailed { failed, total: results.len() });
+    }
+    Ok(())
+}
+
+fn bench(args: BenchArgs) -> Result<(), Error> {
+    let config = args.options.to_config(&args.file_path);
+    let options = BenchOptions { sample: args.sample, thresholds: args.thresholds, strides: args.strides };
+    let results = run_bench(&config, &options)?;
 
-    // Step 1: Extract frames from the video
-    extract_frames(input_file, output_dir, fps)?;
+    println!("{:<45} {:>9} {:>10} {:>7}", "metric", "threshold", "frames/s", "slides");
+    for result in &results {
+        println!(
+            "{:<45} {:>9} {:>10.1} {:>7}",
+            result.metric,
+            result.threshold,
+            result.frames_per_second(),
+            result.slides
+        );
+    }
+    Ok(())
+}
 
-    // Step 2: Process the extracted frames and remove duplicates
-    process_frames(output_dir, similarity_threshold)?;
+fn calibrate_metrics(args: CalibrateArgs) -> Result<(), Error> {
+    let config = args.options.to_config(&args.file_path);
+    let options = CalibrateOptions { sample: args.sample, strides: args.strides, truth: args.truth, tolerance: args.tolerance };
+    let report = calibrate(&config, &options)?;
 
+    println!(
+        "{} same-slide and {} different-slide pairs, labelled {}",
+        report.same_pairs,
+        report.different_pairs,
+        if report.from_truth { "from the ground truth" } else { "by how much they differ" }
+    );
+    println!(
+        "{:<45} {:>11} {:>11} {:>11} {:>11} {:>10} {:>9} {:>6}",
+        "metric", "same med", "same max", "diff min", "diff med", "separation", "threshold", "errors"
+    );
+    for metric in &report.metrics {
+        println!(
+            "{:<45} {:>11.6} {:>11.6} {:>11.6} {:>11.6} {:>10.2} {:>9.5} {:>6}",
+            metric.metric,
+            metric.same_median,
+            metric.same_max,
+            metric.different_min,
+            metric.different_median,
+            metric.separation,
+            metric.threshold,
+            metric.errors
+        );
+    }
+    if let Some(best) = report.best() {
+        println!("recommended: {} --threshold {:.5}", best.metric, best.threshold);
+    }
+    Ok(())
+}
+
+fn evaluate_detection(args: EvaluateArgs) -> Result<(), Error> {
+    let config = args.options.to_config(&args.file_path);
+    let evaluation = evaluate(&config, &args.truth, args.tolerance)?;
+
+    println!("expected:  {}", evaluation.expected);
+    println!("detected:  {}", evaluation.detected);
+    println!("matched:   {}", evaluation.matched);
+    println!("precision: {:.3}", evaluation.precision());
+    println!("recall:    {:.3}", evaluation.recall());
+    println!("f1:        {:.3}", evaluation.f1());
+    for timestamp in &evaluation.missed {
+        println!("missed slide at {:.1}s", timestamp);
+    }
+    for timestamp in &evaluation.extra {
+        println!("extra slide at {:.1}s", timestamp);
+    }
+
+    match args.min_f1 {
+        Some(min_f1) if evaluation.f1() < min_f1 => Err(Error::BelowTarget { f1: evaluation.f1(), min_f1 }),
+        _ => Ok(()),
+    }
+}
+
+fn export_slides(args: ExportArgs) -> Result<(), Error> {
+    let path = args.output.unwrap_or_else(|| args.format.default_path(&args.slides_dir));
+    let slides = export(&args.slides_dir, args.format, &path, &args.kind)?;
+    println!("Exported {} slide(s) to {}", slides, path.display());
+    Ok(())
+}
+
+fn finalize_slides(args: FinalizeArgs) -> Result<(), Error> {
+    let finalized = finalize(&args.slides_dir)?;
+    println!(
+        "{} slide(s) in {}: {} removed, {} added",
+        finalized.slides,
+        args.slides_dir.display(),
+        finalized.removed,
+        finalized.added
+    );
+    Ok(())
+}
+
+fn migrate_manifest(slides_dir: &Path) -> Result<(), Error> {
+    let version = manifest::migrate_file(slides_dir)?;
+    if version == manifest::SCHEMA_VERSION {
+        println!("{} is already version {}", slides_dir.join(manifest::MANIFEST_FILE).display(), version);
+    } else {
+        println!("{} migrated from version {} to {}", slides_dir.join(manifest::MANIFEST_FILE).display(), version, manifest::SCHEMA_VERSION);
+    }
+    Ok(())
+}
+
+fn collect_workspaces(args: GcArgs) -> Result<(), Error> {
+    let removed = gc(&GcOptions {
+        dir: args.tmp_dir.unwrap_or_else(std::env::temp_dir),
+        older_than: Some(Duration::from_secs_f64(args.older_than.max(0.0) * 3600.0)),
+        max_size: args.max_size,
+        dry_run: args.dry_run,
+    })?;
+    for workspace in &removed {
+        let action = if args.dry_run { "would remove" } else { "removed" };
+        println!("{} {} ({})", action, workspace.path.display(), format_size(workspace.size));
+    }
+    let freed: u64 = removed.iter().map(|workspace| workspace.size).sum();
+    println!("{} working director(ies), {}", removed.len(), format_size(freed));
+    Ok(())
+}
+
+fn diff_runs(args: DiffArgs) -> Result<(), Error> {
+    let slide_diff = diff(&args.old, &args.new, &DiffOptions { max_distance: args.max_distance, output_dir: args.output })?;
+
+    let at = |slide: &SlideRef| format!("slide {} at {:.1}s", slide.index, slide.timestamp);
+    for entry in &slide_diff.entries {
+        match entry {
+            DiffEntry::Unchanged { old, new } => println!("  {} is {}", at(old), at(new)),
+            DiffEntry::Modified { old, new, composite } => {
+                println!("~ {} is modified as {} ({})", at(old), at(new), composite.display())
+            }
+            DiffEntry::Added { new } => println!("+ {} was added", at(new)),
+            DiffEntry::Removed { old } => println!("- {} was removed", at(old)),
+        }
+    }
+    let (unchanged, modified, added, removed) = slide_diff.counts();
+    println!("unchanged: {}, modified: {}, added: {}, removed: {}", unchanged, modified, added, removed);
+    Ok(())
+}
+
+fn reprocess_window(args: ReprocessArgs) -> Result<(), Error> {
+    let template = args.options.to_config(args.video.as_deref().unwrap_or(Path::new("")));
+    let reprocessed = reprocess(&args.slides_dir, &template, args.from, args.to)?;
+    println!(
+        "{} slide(s) in {}: {} replaced by {} found in {:.1}s to {:.1}s",
+        reprocessed.slides,
+        args.slides_dir.display(),
+        reprocessed.removed,
+        reprocessed.added,
+        args.from,
+        args.to
+    );
+    Ok(())
+}
+
+fn deck_coverage(args: CoverageArgs) -> Result<(), Error> {
+    let coverage = coverage(&args.slides_dir, &args.deck, args.max_distance)?;
+
+    println!("pages:     {}", coverage.pages);
+    println!("shown:     {}", coverage.pages - coverage.unshown.len());
+    println!("coverage:  {:.1}%", coverage.ratio() * 100.0);
+    for slide in &coverage.slides {
+        if let Some(page) = slide.page {
+            println!("slide {} at {:.1}s is page {}", slide.slide, slide.timestamp, page);
+        }
+    }
+    for page in &coverage.unshown {
+        println!("page {} was never shown", page);
+    }
+    for page in &coverage.out_of_order {
+        println!("page {} was shown out of order", page);
+    }
+    for slide in coverage.not_in_deck() {
+        println!("slide {} at {:.1}s is not in the deck", slide.slide, slide.timestamp);
+    }
+    if let Some(page_map) = &args.page_map {
+        write_page_map(page_map, &coverage)?;
+    }
     Ok(())
 }
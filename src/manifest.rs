@@ -0,0 +1,354 @@
+//! The `manifest.json` a run leaves next to its slides, for tools built on
+//! top of it.
+//!
+//! The manifest carries the version of its schema in `version`. Within a
+//! version fields are only ever added, and only ones a reader can do without:
+//! they are left out when empty, and readers should ignore fields they don't
+//! know. Renaming or removing a field, or changing what one means, takes a new
+//! version and a step in [`MIGRATIONS`] upgrading a manifest of the version
+//! before. Manifests are upgraded as they are read, `manifest migrate`
+//! rewrites one in the current version, and a manifest of a version newer than
+//! this build is refused rather than misread.
+//!
+//! Manifests written before the schema was versioned have no `version` and
+//! are taken for version 0, which has the same fields as version 1.
+
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::io::{Error, ErrorKind};
+use std::path::Path;
+
+/// File name of the manifest written next to the kept slides
+pub const MANIFEST_FILE: &str = "manifest.json";
+/// Version of the manifest's schema written by this build
+pub const SCHEMA_VERSION: u32 = 1;
+/// Steps upgrading a manifest, as JSON, from the version of its index to the next
+const MIGRATIONS: [fn(&mut Value); SCHEMA_VERSION as usize] = [
+    // 0 to 1: only the version was added
+    |_| {},
+];
+
+/// Role a recording plays in the session
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SourceRole {
+    Screen,
+    Camera,
+}
+
+/// A recording that contributed to the manifest
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Source {
+    pub role: SourceRole,
+    pub path: String,
+    /// Seconds to add to a screen timestamp to land on the same moment in this source
+    pub offset: f64,
+}
+
+/// A kept slide and where it sits on the session timeline
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Slide {
+    pub index: usize,
+    pub file: String,
+    /// Derived from what the slide shows, the same for the same slide in every run of the video
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    /// Seconds from the start of the screen recording
+    pub timestamp: f64,
+    /// Where the slide was first seen in the sampled frames, when `--snap-to-pauses` moved `timestamp`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub detected: Option<f64>,
+    /// Same moment expressed on the camera recording's clock
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub camera_timestamp: Option<f64>,
+    /// Monitor the slide was shown on, counted from the left, when the recording was split
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub monitor: Option<u32>,
+    /// Every time the slide was on screen, in order; more than one if the presenter came back to it
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub shown: Vec<Interval>,
+    /// Contents of the QR codes on the slide, with `--find-links`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub qr_codes: Vec<String>,
+    /// Web addresses printed on the slide, from its text, with `--find-links` and `--ocr`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub urls: Vec<String>,
+    /// Deck the slide belongs to, counted from 1, with `--split-decks`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deck: Option<u32>,
+    /// How much to trust the detection
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub confidence: Option<Confidence>,
+    /// What changed since the slide before it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub change: Option<Change>,
+    /// What kind of content the slide shows, as far as its colours and edges tell
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub kind: Option<SlideKind>,
+    /// Close-ups of the slide the presenter zoomed into, with `--save-zooms`, relative to the output directory
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub zooms: Vec<String>,
+    /// The slide next to the one before it with what changed highlighted, with `--save-diffs`,
+    /// relative to the output directory
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub diff: Option<String>,
+    /// Text read off the slide, with `--ocr`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+    /// Language of `text` as an ISO 639-1 code like `en`, when enough of it tells
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub language: Option<String>,
+    /// Who spoke longest while the slide was up, labelled as the diarization does, with `--diarize`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub speaker: Option<String>,
+    /// Short title of the slide, with `--summarize`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    /// A sentence or two on what the slide says, with `--summarize`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub summary: Option<String>,
+    /// Added to the output directory by hand and picked up by `finalize`; its timestamp is the slide's before it
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub manual: bool,
+}
+
+/// What kind of content a slide shows
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SlideKind {
+    /// Source code, in a dark editor theme
+    Code,
+    /// A chart or diagram
+    #[value(alias = "chart")]
+    Diagram,
+    /// A photo or screenshot of one
+    Photo,
+    /// Mostly text
+    Text,
+    /// Little on it, such as a title slide
+    Plain,
+}
+
+/// How much of the slide changed at a transition
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChangeKind {
+    /// Most of the slide, a new slide
+    Full,
+    /// A region of it, like an animation step or an edit
+    Partial,
+}
+
+/// The box around what changed at a transition, in pixels of the kept frame
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Change {
+    pub kind: ChangeKind,
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+    /// Share of the frame the box covers, 0 to 1
+    pub area: f64,
+}
+
+/// A stretch of the screen recording, in seconds from its start
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Interval {
+    pub start: f64,
+    pub end: f64,
+}
+
+/// Why a kept slide is likely a real one
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Confidence {
+    /// 0 to 1, the mean of how long the slide persisted (full from 10 seconds), its distinctness and its sharpness
+    pub score: f64,
+    /// Seconds the slide stayed on screen, all visits together
+    pub persisted: f64,
+    /// How far the change that brought the slide up cleared the threshold, 0 (just) to 1
+    pub distinctness: f64,
+    /// Edge contrast of the kept frame, 0 (flat or blurred) to 1 (crisp)
+    pub sharpness: f64,
+}
+
+/// How one sampled frame compared with the one before it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FrameScore {
+    pub file: String,
+    /// Seconds from the start of the screen recording
+    pub timestamp: f64,
+    /// Share of pixels that differ from the previous frame; absent for the first frame
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub difference: Option<f64>,
+    /// Largest difference that still counted as the same slide
+    pub threshold: f64,
+    /// Whether the frame was kept as a slide
+    pub kept: bool,
+    /// For a frame not kept, the kept frame of the slide it was judged the same as
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub same_as: Option<String>,
+}
+
+/// A frame whose resolution differs from the one before it, e.g. because the screen was shared again
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResolutionChange {
+    pub file: String,
+    /// Seconds from the start of the screen recording
+    pub timestamp: f64,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// What a run was made from, to tell whether a rerun would produce the same slides
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Fingerprint {
+    /// SHA-256 of the input's size and first and last megabyte
+    pub input: String,
+    /// SHA-256 of the settings that affect which slides are kept
+    pub settings: String,
+}
+
+/// What the recorded deck as a whole is, as far as its slides and file name tell
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Catalog {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub speaker: Option<String>,
+    /// Course code, like `CS101`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub course: Option<String>,
+    /// Date of the talk, `YYYY-MM-DD`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub date: Option<String>,
+}
+
+/// Everything a downstream tool needs to find the slides of one run
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Manifest {
+    /// Version of the schema, [`SCHEMA_VERSION`] when written by this build
+    #[serde(default)]
+    pub version: u32,
+    pub sources: Vec<Source>,
+    pub slides: Vec<Slide>,
+    /// Frames that could not be decoded and were left out
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub bad_frames: Vec<String>,
+    /// Every decoded frame with the comparison that decided its fate, in order
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub frames: Vec<FrameScore>,
+    /// Points where the recording's resolution changed
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub resolution_changes: Vec<ResolutionChange>,
+    /// Where the content runs when idle screens were trimmed from the start or end with `--trim-idle`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content: Option<Interval>,
+    /// Title, speaker, course and date of the deck
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub catalog: Option<Catalog>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fingerprint: Option<Fingerprint>,
+}
+
+impl Manifest {
+    /// The manifest as pretty-printed JSON
+    pub fn to_json(&self) -> Result<String, Error> {
+        serde_json::to_string_pretty(self).map_err(Error::other)
+    }
+
+    /// Write the manifest as pretty-printed JSON into the output directory,
+    /// replacing any earlier one in a single step
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn write(&self, output_dir: &Path) -> Result<(), Error> {
+        crate::lock::write_atomic(&output_dir.join(MANIFEST_FILE), self.to_json()?)
+    }
+
+    /// The manifest in `json`, upgraded to the current version
+    pub fn from_json(json: &[u8]) -> Result<Manifest, Error> {
+        let mut value: Value = serde_json::from_slice(json)?;
+        migrate(&mut value)?;
+        serde_json::from_value(value).map_err(Error::from)
+    }
+
+    /// Read the manifest a run left in `output_dir`
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn read(output_dir: &Path) -> Result<Manifest, Error> {
+        Manifest::from_json(&std::fs::read(output_dir.join(MANIFEST_FILE))?)
+    }
+}
+
+/// Upgrade the manifest in `value` to the current version, returning the version it had
+pub fn migrate(value: &mut Value) -> Result<u32, Error> {
+    let version = match value.get("version") {
+        None => 0,
+        Some(version) => version
+            .as_u64()
+            .and_then(|version| u32::try_from(version).ok())
+            .ok_or_else(|| Error::new(ErrorKind::InvalidData, format!("The manifest's version is not a number: {}", version)))?,
+    };
+    if version > SCHEMA_VERSION {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            format!("The manifest is version {}, newer than the {} this build reads; upgrade videoSlideExtractor to read it", version, SCHEMA_VERSION),
+        ));
+    }
+    for step in &MIGRATIONS[version as usize..] {
+        step(value);
+    }
+    if let Some(object) = value.as_object_mut() {
+        object.insert("version".to_string(), Value::from(SCHEMA_VERSION));
+    }
+    Ok(version)
+}
+
+/// Rewrite the manifest in `output_dir` in the current version, returning the version it had
+#[cfg(not(target_arch = "wasm32"))]
+pub fn migrate_file(output_dir: &Path) -> Result<u32, Error> {
+    let mut value: Value = serde_json::from_slice(&std::fs::read(output_dir.join(MANIFEST_FILE))?)?;
+    let version = migrate(&mut value)?;
+    if version < SCHEMA_VERSION {
+        let manifest: Manifest = serde_json::from_value(value)?;
+        manifest.write(output_dir)?;
+    }
+    Ok(version)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A manifest as written before the schema was versioned
+    const UNVERSIONED: &str = r#"{
+        "sources": [{ "role": "screen", "path": "talk.mp4", "offset": 0.0 }],
+        "slides": [{ "index": 1, "file": "frame_000001.png", "timestamp": 0.0 }]
+    }"#;
+
+    #[test]
+    fn reads_unversioned_manifests_as_the_current_version() {
+        let manifest = Manifest::from_json(UNVERSIONED.as_bytes()).unwrap();
+        assert_eq!(manifest.version, SCHEMA_VERSION);
+        assert_eq!(manifest.slides[0].file, "frame_000001.png");
+        let mut value: Value = serde_json::from_str(UNVERSIONED).unwrap();
+        assert_eq!(migrate(&mut value).unwrap(), 0);
+        assert_eq!(value["version"], SCHEMA_VERSION);
+    }
+
+    #[test]
+    fn rejects_newer_and_malformed_versions() {
+        let newer = serde_json::json!({ "version": SCHEMA_VERSION + 1, "sources": [], "slides": [] });
+        assert!(Manifest::from_json(newer.to_string().as_bytes()).is_err());
+        let mut malformed = serde_json::json!({ "version": "one" });
+        assert!(migrate(&mut malformed).is_err());
+    }
+
+    #[test]
+    fn migrate_file_rewrites_only_older_manifests() {
+        let output_dir = tempfile::tempdir().unwrap();
+        std::fs::write(output_dir.path().join(MANIFEST_FILE), UNVERSIONED).unwrap();
+        assert_eq!(migrate_file(output_dir.path()).unwrap(), 0);
+        let written: Value = serde_json::from_slice(&std::fs::read(output_dir.path().join(MANIFEST_FILE)).unwrap()).unwrap();
+        assert_eq!(written["version"], SCHEMA_VERSION);
+        assert_eq!(migrate_file(output_dir.path()).unwrap(), SCHEMA_VERSION);
+    }
+}
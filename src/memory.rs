@@ -0,0 +1,73 @@
+//! `--max-memory`, fitting a run into a memory budget on small machines
+//! where going over it gets the process killed.
+//!
+//! What a run needs grows with the frame size: ffmpeg keeps a few decoded
+//! frames in flight per thread, and the comparison holds the previous and the
+//! current frame. The budget is met by giving ffmpeg fewer threads.
+
+use crate::config::Config;
+use crate::error::format_size;
+use crate::probe::probe;
+use crate::runlog::RunLog;
+
+/// ffmpeg's and our own memory use that does not depend on the frame size
+const BASE_BYTES: u64 = 96 << 20;
+/// Bytes per pixel each ffmpeg thread holds: a few YUV frames being decoded and the RGB one being encoded
+const THREAD_BYTES_PER_PIXEL: u64 = 12;
+/// Bytes per pixel the comparison holds: previous and current frame, plus a converted copy
+const COMPARE_BYTES_PER_PIXEL: u64 = 12;
+
+/// Estimated peak memory of a run on `width`x`height` frames with ffmpeg on `threads` threads
+pub fn estimate(width: u32, height: u32, threads: u32) -> u64 {
+    let pixels = width as u64 * height as u64;
+    BASE_BYTES + pixels * COMPARE_BYTES_PER_PIXEL + pixels * THREAD_BYTES_PER_PIXEL * threads as u64
+}
+
+/// Most ffmpeg threads that keep a run on `width`x`height` frames within `budget`, at least 1
+pub fn threads_within(budget: u64, width: u32, height: u32) -> u32 {
+    let pixels = (width as u64 * height as u64).max(1);
+    let spare = budget.saturating_sub(estimate(width, height, 0));
+    (spare / (pixels * THREAD_BYTES_PER_PIXEL)).clamp(1, u32::MAX as u64) as u32
+}
+
+/// Lower `config.ffmpeg_threads` so the run stays within `config.max_memory`.
+///
+/// The frame size is probed from the input; if that fails, or the budget is
+/// too small even for a single thread, the run goes ahead with a warning.
+pub fn fit_budget(config: &mut Config, log: &RunLog) {
+    let Some(budget) = config.max_memory else {
+        return;
+    };
+    let info = match probe(&config.input_file) {
+        Ok(info) => info,
+        Err(e) => {
+            log.warn(format_args!("Cannot size the run for --max-memory: {}", e));
+            return;
+        }
+    };
+
+    let cores = std::thread::available_parallelism().map_or(1, |n| n.get() as u32);
+    let wanted = config.ffmpeg_threads.unwrap_or(cores);
+    let threads = threads_within(budget, info.width, info.height).min(wanted);
+    if threads < wanted {
+        log.info(format_args!(
+            "Limiting ffmpeg to {} thread(s) to stay within {} for {}x{} frames.",
+            threads,
+            format_size(budget),
+            info.width,
+            info.height
+        ));
+        config.ffmpeg_threads = Some(threads);
+    }
+
+    let needed = estimate(info.width, info.height, threads);
+    if needed > budget {
+        log.warn(format_args!(
+            "{}x{} frames need about {}, more than the {} allowed; lower the resolution or raise --max-memory.",
+            info.width,
+            info.height,
+            format_size(needed),
+            format_size(budget)
+        ));
+    }
+}
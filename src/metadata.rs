@@ -0,0 +1,92 @@
+//! Where each kept slide came from, written into the PNG itself as `iTXt`
+//! chunks, so a slide image found on its own can be traced back to its
+//! moment in the recording: `Source` (the input), `Timestamp` (seconds),
+//! `Slide` (its index) and `Software` (this tool and its version).
+//!
+//! The chunks are spliced in after the image header; the pixels are not
+//! encoded again.
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::config::Config;
+use crate::manifest::{Manifest, Slide};
+
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+/// Signature, then the `IHDR` chunk: length, type, 13 bytes of data and CRC
+const HEADER_END: usize = 8 + 4 + 4 + 13 + 4;
+
+/// A PNG `iTXt` chunk holding `text` under `keyword`, uncompressed and without a language
+fn itxt_chunk(keyword: &str, text: &str) -> Vec<u8> {
+    let mut data = Vec::with_capacity(keyword.len() + text.len() + 5);
+    data.extend_from_slice(keyword.as_bytes());
+    // Keyword terminator, no compression (flag and method), empty language tag and translated keyword
+    data.extend_from_slice(&[0, 0, 0, 0, 0]);
+    data.extend_from_slice(text.as_bytes());
+
+    let mut chunk = Vec::with_capacity(data.len() + 12);
+    chunk.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    let mut crc = crc32fast::Hasher::new();
+    crc.update(b"iTXt");
+    crc.update(&data);
+    chunk.extend_from_slice(b"iTXt");
+    chunk.extend_from_slice(&data);
+    chunk.extend_from_slice(&crc.finalize().to_be_bytes());
+    chunk
+}
+
+/// The text fields written into the image of `slide`
+pub fn fields(config: &Config, slide: &Slide) -> Vec<(&'static str, String)> {
+    let mut fields = vec![
+        ("Source", config.input_file.to_string_lossy().into_owned()),
+        ("Timestamp", format!("{:.3}", slide.timestamp)),
+        ("Slide", slide.index.to_string()),
+        ("Software", format!("{} {}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"))),
+    ];
+    if let Some(camera_timestamp) = slide.camera_timestamp {
+        fields.push(("Camera Timestamp", format!("{:.3}", camera_timestamp)));
+    }
+    fields
+}
+
+/// Add `fields` to the PNG at `path`; files that are not PNGs are left alone
+pub fn embed(path: &Path, fields: &[(&str, String)]) -> Result<(), io::Error> {
+    let png = fs::read(path)?;
+    if png.len() < HEADER_END || !png.starts_with(PNG_SIGNATURE) || &png[12..16] != b"IHDR" {
+        return Ok(());
+    }
+    let mut tagged = Vec::with_capacity(png.len() + 256);
+    tagged.extend_from_slice(&png[..HEADER_END]);
+    for (keyword, text) in fields {
+        tagged.extend(itxt_chunk(keyword, text));
+    }
+    tagged.extend_from_slice(&png[HEADER_END..]);
+    fs::write(path, tagged)
+}
+
+/// Tag every kept slide of `manifest` in the output directory, unless `config.skip_metadata`
+pub fn tag_slides(config: &Config, manifest: &Manifest) -> Result<(), io::Error> {
+    if config.skip_metadata {
+        return Ok(());
+    }
+    for slide in &manifest.slides {
+        embed(&config.output_dir.join(&slide.file), &fields(config, slide))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn itxt_chunk_layout_and_crc() {
+        let chunk = itxt_chunk("Source", "talk.mp4");
+        let data = b"Source\0\0\0\0\0talk.mp4";
+        assert_eq!(chunk[..4], (data.len() as u32).to_be_bytes());
+        assert_eq!(&chunk[4..8], b"iTXt");
+        assert_eq!(&chunk[8..8 + data.len()], data);
+        assert_eq!(chunk[8 + data.len()..], 0x2faa9f01u32.to_be_bytes());
+    }
+}
@@ -0,0 +1,144 @@
+//! Process-wide counters for the server's `/metrics` endpoint, rendered in
+//! the Prometheus text exposition format.
+
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::progress::Stage;
+
+/// Upper bounds in seconds of the stage duration histogram buckets
+const DURATION_BUCKETS: [f64; 10] = [1.0, 5.0, 15.0, 30.0, 60.0, 120.0, 300.0, 600.0, 1800.0, 3600.0];
+
+struct Histogram {
+    counts: [u64; DURATION_BUCKETS.len()],
+    count: u64,
+    sum: f64,
+}
+
+impl Histogram {
+    const fn new() -> Self {
+        Histogram { counts: [0; DURATION_BUCKETS.len()], count: 0, sum: 0.0 }
+    }
+
+    fn observe(&mut self, value: f64) {
+        for (bound, count) in DURATION_BUCKETS.iter().zip(self.counts.iter_mut()) {
+            if value <= *bound {
+                *count += 1;
+            }
+        }
+        self.count += 1;
+        self.sum += value;
+    }
+}
+
+static JOBS_FINISHED: AtomicU64 = AtomicU64::new(0);
+static JOBS_FAILED: AtomicU64 = AtomicU64::new(0);
+static JOBS_CANCELLED: AtomicU64 = AtomicU64::new(0);
+static FRAMES_EXAMINED: AtomicU64 = AtomicU64::new(0);
+static SLIDES_KEPT: AtomicU64 = AtomicU64::new(0);
+static FFMPEG_FAILURES: AtomicU64 = AtomicU64::new(0);
+static STAGE_DURATIONS: Mutex<[Histogram; 3]> = Mutex::new([Histogram::new(), Histogram::new(), Histogram::new()]);
+
+const STAGES: [Stage; 3] = [Stage::Syncing, Stage::Extracting, Stage::Comparing];
+
+/// How a job ended, for the jobs counter
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JobOutcome {
+    Finished,
+    Failed,
+    Cancelled,
+}
+
+pub fn job_completed(outcome: JobOutcome) {
+    let counter = match outcome {
+        JobOutcome::Finished => &JOBS_FINISHED,
+        JobOutcome::Failed => &JOBS_FAILED,
+        JobOutcome::Cancelled => &JOBS_CANCELLED,
+    };
+    counter.fetch_add(1, Ordering::Relaxed);
+}
+
+pub fn frame_examined(kept: bool) {
+    FRAMES_EXAMINED.fetch_add(1, Ordering::Relaxed);
+    if kept {
+        SLIDES_KEPT.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+pub fn ffmpeg_failed() {
+    FFMPEG_FAILURES.fetch_add(1, Ordering::Relaxed);
+}
+
+pub fn stage_finished(stage: Stage, duration: Duration) {
+    let index = STAGES.iter().position(|s| *s == stage).expect("every stage is listed");
+    STAGE_DURATIONS.lock().unwrap()[index].observe(duration.as_secs_f64());
+}
+
+/// Run `f` and record how long it took as a duration of `stage`
+pub fn timed<T>(stage: Stage, f: impl FnOnce() -> T) -> T {
+    let start = Instant::now();
+    let result = f();
+    stage_finished(stage, start.elapsed());
+    result
+}
+
+fn stage_label(stage: Stage) -> &'static str {
+    match stage {
+        Stage::Syncing => "syncing",
+        Stage::Extracting => "extracting",
+        Stage::Comparing => "comparing",
+    }
+}
+
+/// Render every metric, plus the current number of jobs in each of `job_states`
+pub fn render(job_states: &[(&str, usize)]) -> String {
+    let mut out = String::new();
+    let load = |counter: &AtomicU64| counter.load(Ordering::Relaxed);
+
+    let _ = writeln!(out, "# HELP videoslides_jobs_total Jobs that reached a final state.");
+    let _ = writeln!(out, "# TYPE videoslides_jobs_total counter");
+    for (outcome, counter) in [("finished", &JOBS_FINISHED), ("failed", &JOBS_FAILED), ("cancelled", &JOBS_CANCELLED)] {
+        let _ = writeln!(out, "videoslides_jobs_total{{outcome=\"{}\"}} {}", outcome, load(counter));
+    }
+
+    let _ = writeln!(out, "# HELP videoslides_jobs Jobs currently known to the server by state.");
+    let _ = writeln!(out, "# TYPE videoslides_jobs gauge");
+    for (state, count) in job_states {
+        let _ = writeln!(out, "videoslides_jobs{{state=\"{}\"}} {}", state, count);
+    }
+
+    for (name, help, counter) in [
+        ("videoslides_frames_examined_total", "Sampled frames compared for uniqueness.", &FRAMES_EXAMINED),
+        ("videoslides_slides_kept_total", "Frames kept as slides.", &SLIDES_KEPT),
+        ("videoslides_ffmpeg_failures_total", "ffmpeg runs that exited unsuccessfully.", &FFMPEG_FAILURES),
+    ] {
+        let _ = writeln!(out, "# HELP {} {}", name, help);
+        let _ = writeln!(out, "# TYPE {} counter", name);
+        let _ = writeln!(out, "{} {}", name, load(counter));
+    }
+
+    let _ = writeln!(out, "# HELP videoslides_stage_duration_seconds Time spent in each pipeline stage.");
+    let _ = writeln!(out, "# TYPE videoslides_stage_duration_seconds histogram");
+    let histograms = STAGE_DURATIONS.lock().unwrap();
+    for (stage, histogram) in STAGES.iter().zip(histograms.iter()) {
+        let label = stage_label(*stage);
+        for (bound, count) in DURATION_BUCKETS.iter().zip(histogram.counts.iter()) {
+            let _ = writeln!(
+                out,
+                "videoslides_stage_duration_seconds_bucket{{stage=\"{}\",le=\"{}\"}} {}",
+                label, bound, count
+            );
+        }
+        let _ = writeln!(
+            out,
+            "videoslides_stage_duration_seconds_bucket{{stage=\"{}\",le=\"+Inf\"}} {}",
+            label, histogram.count
+        );
+        let _ = writeln!(out, "videoslides_stage_duration_seconds_sum{{stage=\"{}\"}} {}", label, histogram.sum);
+        let _ = writeln!(out, "videoslides_stage_duration_seconds_count{{stage=\"{}\"}} {}", label, histogram.count);
+    }
+
+    out
+}
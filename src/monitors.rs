@@ -0,0 +1,103 @@
+//! Recordings of a desktop spanning several monitors side by side (e.g.
+//! 3840x1080 for two 1920x1080 screens), where the slides are on only one of
+//! them or each monitor shows a deck of its own.
+
+use crate::config::{Config, Crop, MonitorSplit};
+use crate::error::Error;
+use crate::links;
+use crate::manifest::{Manifest, Slide};
+use crate::probe::probe;
+use crate::runlog::RunLog;
+
+/// Aspect ratio of a single monitor, used to guess how many sit side by side
+const MONITOR_ASPECT: f64 = 16.0 / 9.0;
+
+/// Number of 16:9 monitors side by side that best explains a `width`x`height` recording
+pub fn guess_monitors(width: u32, height: u32) -> u32 {
+    let aspect = width as f64 / height.max(1) as f64;
+    ((aspect / MONITOR_ASPECT).round() as u32).max(1)
+}
+
+/// The area of each of `count` monitors side by side, left to right
+pub fn monitor_crops(width: u32, height: u32, count: u32) -> Vec<Crop> {
+    let monitor_width = width / count.max(1);
+    (0..count.max(1)).map(|i| Crop { width: monitor_width, height, x: i * monitor_width, y: 0 }).collect()
+}
+
+/// One config per deck to extract from `config.input_file`: a single one
+/// unless the recording is split into monitors processed separately, in
+/// which case each gets its crop and a `monitor-N` subdirectory
+pub fn decks(config: &Config, log: &RunLog) -> Result<Vec<(Option<u32>, Config)>, Error> {
+    let Some(split) = config.split_monitors.filter(|_| config.crop.is_none()) else {
+        return Ok(vec![(None, config.clone())]);
+    };
+
+    let info = probe(&config.input_file)?;
+    let count = match split {
+        MonitorSplit::Auto => guess_monitors(info.width, info.height),
+        MonitorSplit::Count(count) => count,
+    };
+    if count <= 1 {
+        log.info(format_args!("{}x{} looks like a single monitor, not splitting it.", info.width, info.height));
+        return Ok(vec![(None, config.clone())]);
+    }
+    let crops = monitor_crops(info.width, info.height, count);
+
+    if let Some(monitor) = config.monitor {
+        let crop = *crops.get(monitor.saturating_sub(1) as usize).ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("--monitor {} but the recording shows {} monitor(s)", monitor, count),
+            )
+        })?;
+        log.info(format_args!("Keeping monitor {} of {} ({}).", monitor, count, crop));
+        let mut deck = config.clone();
+        deck.crop = Some(crop.into());
+        return Ok(vec![(None, deck)]);
+    }
+
+    log.info(format_args!("Splitting the recording into {} monitors.", count));
+    Ok(crops
+        .into_iter()
+        .zip(1..)
+        .map(|(crop, monitor)| {
+            let name = format!("monitor-{}", monitor);
+            let mut deck = config.clone();
+            deck.crop = Some(crop.into());
+            deck.output_dir = config.output_dir.join(&name);
+            deck.output = config.output.as_ref().map(|output| format!("{}/{}", output.trim_end_matches('/'), name));
+            (Some(monitor), deck)
+        })
+        .collect())
+}
+
+/// Point a slide of a monitor's deck at its file relative to the whole recording's output directory
+pub fn on_monitor(slide: &mut Slide, monitor: u32) {
+    slide.file = format!("monitor-{}/{}", monitor, slide.file);
+    slide.monitor = Some(monitor);
+}
+
+/// Merge the manifests of the monitors into one for the whole recording,
+/// with slide files relative to `config.output_dir`, and write it there
+pub fn combine_decks(config: &Config, decks: Vec<(u32, Manifest)>) -> Result<Manifest, Error> {
+    let mut combined: Option<Manifest> = None;
+    for (monitor, deck) in decks {
+        let mut slides = deck.slides;
+        for slide in &mut slides {
+            on_monitor(slide, monitor);
+        }
+        match combined.as_mut() {
+            Some(combined) => combined.slides.extend(slides),
+            None => combined = Some(Manifest { slides, frames: Vec::new(), fingerprint: None, ..deck }),
+        }
+    }
+
+    let mut combined = combined.expect("a split recording has at least two monitors");
+    combined.slides.sort_by(|a, b| a.timestamp.total_cmp(&b.timestamp));
+    for (index, slide) in combined.slides.iter_mut().enumerate() {
+        slide.index = index + 1;
+    }
+    combined.write(&config.output_dir)?;
+    links::write_links(&config.output_dir, &combined)?;
+    Ok(combined)
+}
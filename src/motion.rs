@@ -0,0 +1,248 @@
+use image::{DynamicImage, GenericImageView};
+
+use crate::compare::{sampled, Comparer};
+use crate::runlog::RunLog;
+
+/// Edge length in pixels of the square tiles motion is tracked on
+const TILE_SIZE: u32 = 32;
+/// Share of a tile's pixels that must differ for the tile to count as changed
+const TILE_CHANGE_RATIO: f64 = 0.1;
+/// Masks larger than this share of the frame are not an embedded video but a real change
+const MAX_MASK_RATIO: f64 = 0.5;
+
+/// A rectangle in tile coordinates, inclusive on both ends
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct TileRect {
+    x0: u32,
+    y0: u32,
+    x1: u32,
+    y1: u32,
+}
+
+/// Finds regions that keep changing from frame to frame (an embedded video
+/// playing inside a slide) and leaves them out of the difference ratio.
+pub struct MotionTracker {
+    /// Consecutive comparisons a tile must change in before it is excluded
+    streak_needed: u32,
+    dimensions: (u32, u32),
+    columns: u32,
+    rows: u32,
+    streaks: Vec<u32>,
+    excluded: Vec<TileRect>,
+    log: RunLog,
+}
+
+impl MotionTracker {
+    pub fn new(streak_needed: u32, log: RunLog) -> Self {
+        MotionTracker {
+            streak_needed: streak_needed.max(1),
+            dimensions: (0, 0),
+            columns: 0,
+            rows: 0,
+            streaks: Vec::new(),
+            excluded: Vec::new(),
+            log,
+        }
+    }
+
+    /// Forget all motion history, e.g. after the frame size changed
+    fn reset(&mut self, dimensions: (u32, u32)) {
+        self.dimensions = dimensions;
+        self.columns = dimensions.0.div_ceil(TILE_SIZE);
+        self.rows = dimensions.1.div_ceil(TILE_SIZE);
+        self.streaks = vec![0; (self.columns * self.rows) as usize];
+        self.excluded.clear();
+    }
+
+    /// Compare two frames and return the share of differing pixels outside
+    /// any region that has been in constant motion
+    pub fn difference_ratio(&mut self, comparer: &mut Comparer, img1: &DynamicImage, img2: &DynamicImage) -> f64 {
+        if img1.dimensions() != img2.dimensions() {
+            return 1.0;
+        }
+        if img1.dimensions() != self.dimensions {
+            self.reset(img1.dimensions());
+        }
+
+        let (width, height) = img1.dimensions();
+        let stride = comparer.stride();
+        let tile_diffs = comparer.tile_diffs(img1, img2, TILE_SIZE);
+
+        // Update how long each tile has been changing without a break
+        for ty in 0..self.rows {
+            for tx in 0..self.columns {
+                let index = self.tile_index(tx, ty);
+                let tile_pixels = self.tile_pixels(tx, ty, stride);
+                if tile_diffs[index] as f64 / tile_pixels as f64 > TILE_CHANGE_RATIO {
+                    self.streaks[index] += 1;
+                } else {
+                    self.streaks[index] = 0;
+                }
+            }
+        }
+
+        let excluded = self.find_motion_rects();
+        if excluded != self.excluded {
+            for rect in &excluded {
+                self.log.info(format_args!(
+                    "Ignoring moving region at x={} y={} {}x{} (embedded video?).",
+                    rect.x0 * TILE_SIZE,
+                    rect.y0 * TILE_SIZE,
+                    ((rect.x1 + 1) * TILE_SIZE).min(width) - rect.x0 * TILE_SIZE,
+                    ((rect.y1 + 1) * TILE_SIZE).min(height) - rect.y0 * TILE_SIZE
+                ));
+            }
+            self.excluded = excluded;
+        }
+
+        let mut diff_count = 0;
+        let mut total_pixels = 0;
+        for ty in 0..self.rows {
+            for tx in 0..self.columns {
+                if self.is_excluded(tx, ty) {
+                    continue;
+                }
+                diff_count += tile_diffs[self.tile_index(tx, ty)];
+                total_pixels += self.tile_pixels(tx, ty, stride);
+            }
+        }
+
+        if total_pixels == 0 {
+            return 0.0;
+        }
+        (diff_count as f64) / (total_pixels as f64)
+    }
+
+    fn tile_index(&self, tx: u32, ty: u32) -> usize {
+        (ty * self.columns + tx) as usize
+    }
+
+    /// Number of compared pixels in a tile, accounting for the cut-off tiles at the edges
+    fn tile_pixels(&self, tx: u32, ty: u32, stride: u32) -> u64 {
+        let x_end = ((tx + 1) * TILE_SIZE).min(self.dimensions.0);
+        let y_end = ((ty + 1) * TILE_SIZE).min(self.dimensions.1);
+        sampled(tx * TILE_SIZE, x_end, stride) * sampled(ty * TILE_SIZE, y_end, stride)
+    }
+
+    fn is_excluded(&self, tx: u32, ty: u32) -> bool {
+        self.excluded
+            .iter()
+            .any(|r| tx >= r.x0 && tx <= r.x1 && ty >= r.y0 && ty <= r.y1)
+    }
+
+    /// Group persistently changing tiles into bounding rectangles
+    fn find_motion_rects(&self) -> Vec<TileRect> {
+        let mut visited = vec![false; self.streaks.len()];
+        let mut rects = Vec::new();
+
+        for start in 0..self.streaks.len() {
+            if visited[start] || self.streaks[start] < self.streak_needed {
+                continue;
+            }
+
+            // Flood fill the connected group of moving tiles
+            let mut rect = TileRect {
+                x0: u32::MAX,
+                y0: u32::MAX,
+                x1: 0,
+                y1: 0,
+            };
+            let mut stack = vec![start];
+            visited[start] = true;
+            while let Some(index) = stack.pop() {
+                let tx = index as u32 % self.columns;
+                let ty = index as u32 / self.columns;
+                rect.x0 = rect.x0.min(tx);
+                rect.y0 = rect.y0.min(ty);
+                rect.x1 = rect.x1.max(tx);
+                rect.y1 = rect.y1.max(ty);
+
+                let neighbours = [
+                    (tx.wrapping_sub(1), ty),
+                    (tx + 1, ty),
+                    (tx, ty.wrapping_sub(1)),
+                    (tx, ty + 1),
+                ];
+                for (nx, ny) in neighbours {
+                    if nx >= self.columns || ny >= self.rows {
+                        continue;
+                    }
+                    let neighbour = self.tile_index(nx, ny);
+                    if !visited[neighbour] && self.streaks[neighbour] >= self.streak_needed {
+                        visited[neighbour] = true;
+                        stack.push(neighbour);
+                    }
+                }
+            }
+            rects.push(rect);
+        }
+
+        let masked_tiles: u32 = rects
+            .iter()
+            .map(|r| (r.x1 - r.x0 + 1) * (r.y1 - r.y0 + 1))
+            .sum();
+        if masked_tiles as f64 > MAX_MASK_RATIO * self.streaks.len() as f64 {
+            return Vec::new();
+        }
+
+        rects
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use image::{Rgb, RgbImage};
+
+    /// A white 128x128 frame with its top left tile filled with `video` and the tile below
+    /// it black if `changed`
+    fn frame(video: u8, changed: bool) -> DynamicImage {
+        let mut image = RgbImage::from_pixel(128, 128, Rgb([255, 255, 255]));
+        for y in 0..TILE_SIZE {
+            for x in 0..TILE_SIZE {
+                image.put_pixel(x, y, Rgb([video, 0, 0]));
+                if changed {
+                    image.put_pixel(x, y + TILE_SIZE, Rgb([0, 0, 0]));
+                }
+            }
+        }
+        DynamicImage::ImageRgb8(image)
+    }
+
+    #[test]
+    fn leaves_out_a_region_that_keeps_changing() {
+        let log = RunLog::open(None).unwrap();
+        let mut comparer = Comparer::new(&Config::new("talk.mp4"), &log);
+        let mut tracker = MotionTracker::new(3, log);
+        let ratios: Vec<f64> = (0..4u8)
+            .map(|i| tracker.difference_ratio(&mut comparer, &frame(i * 50, false), &frame(i * 50 + 25, false)))
+            .collect();
+        // One tile of 16 changes until it has done so three times in a row
+        assert_eq!(ratios, vec![1.0 / 16.0, 1.0 / 16.0, 0.0, 0.0]);
+        // A change elsewhere still counts, out of the 15 tiles left
+        let ratio = tracker.difference_ratio(&mut comparer, &frame(200, false), &frame(225, true));
+        assert_eq!(ratio, 1.0 / 15.0);
+    }
+
+    #[test]
+    fn a_pause_resets_the_streak() {
+        let log = RunLog::open(None).unwrap();
+        let mut comparer = Comparer::new(&Config::new("talk.mp4"), &log);
+        let mut tracker = MotionTracker::new(2, log);
+        tracker.difference_ratio(&mut comparer, &frame(0, false), &frame(25, false));
+        assert_eq!(tracker.difference_ratio(&mut comparer, &frame(25, false), &frame(25, false)), 0.0);
+        assert_eq!(tracker.difference_ratio(&mut comparer, &frame(25, false), &frame(50, false)), 1.0 / 16.0);
+    }
+
+    #[test]
+    fn a_change_over_most_of_the_frame_is_not_masked() {
+        let log = RunLog::open(None).unwrap();
+        let mut comparer = Comparer::new(&Config::new("talk.mp4"), &log);
+        let mut tracker = MotionTracker::new(1, log);
+        let (black, white) = (DynamicImage::new_rgb8(128, 128), frame(255, false));
+        for _ in 0..3 {
+            assert_eq!(tracker.difference_ratio(&mut comparer, &black, &white), 1.0);
+        }
+    }
+}
@@ -0,0 +1,189 @@
+//! `--name-template`: file names for the kept slides in whatever convention
+//! downstream tooling expects, e.g. `{video}_{index:03}_{timestamp}`.
+//!
+//! Placeholders are `{video}` (file stem of the input), `{index}` (slide
+//! number from 1), `{frame}` (sampled frame number from 1), `{seconds}` and
+//! `{timestamp}` (`hh-mm-ss`) of when the slide appears, and `{title}`, the
+//! slide's title from `--summarize`. Numbers take a zero-padded width like
+//! `{index:03}`; `.png` is appended.
+//!
+//! The title is only known once the slides are kept, so `{title}` stays in
+//! the name as it is until the slides are renamed with their titles.
+
+use std::path::Path;
+
+/// Characters of a title kept in a file name
+const SLUG_LENGTH: usize = 60;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Field {
+    Video,
+    Index,
+    Frame,
+    Seconds,
+    Timestamp,
+    Title,
+}
+
+/// What `{title}` is rendered as until the slide has a title
+pub const TITLE_PLACEHOLDER: &str = "{title}";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Part {
+    Literal(String),
+    Field { field: Field, width: usize },
+}
+
+/// A parsed `--name-template`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NameTemplate {
+    parts: Vec<Part>,
+}
+
+/// What a slide's name can be made of
+pub struct SlideName<'a> {
+    pub input_file: &'a Path,
+    pub index: usize,
+    pub frame: usize,
+    pub seconds: f64,
+}
+
+impl NameTemplate {
+    pub fn parse(template: &str) -> Result<Self, String> {
+        let mut parts = Vec::new();
+        let mut rest = template;
+        while let Some(open) = rest.find('{') {
+            if open > 0 {
+                parts.push(Part::Literal(rest[..open].to_string()));
+            }
+            let close = rest[open..]
+                .find('}')
+                .map(|close| open + close)
+                .ok_or_else(|| format!("unclosed {{ in name template {:?}", template))?;
+            let placeholder = &rest[open + 1..close];
+            let (name, width) = placeholder.split_once(':').unwrap_or((placeholder, ""));
+            let field = match name {
+                "video" => Field::Video,
+                "index" => Field::Index,
+                "frame" => Field::Frame,
+                "seconds" => Field::Seconds,
+                "timestamp" => Field::Timestamp,
+                "title" => Field::Title,
+                _ => {
+                    return Err(format!(
+                        "unknown placeholder {{{}}}, use {{video}}, {{index}}, {{frame}}, {{seconds}}, {{timestamp}} or {{title}}",
+                        name
+                    ))
+                }
+            };
+            let width = match width {
+                "" => 0,
+                width => width.parse().map_err(|_| format!("{:?} is not a width in {{{}}}", width, placeholder))?,
+            };
+            parts.push(Part::Field { field, width });
+            rest = &rest[close + 1..];
+        }
+        if !rest.is_empty() {
+            parts.push(Part::Literal(rest.to_string()));
+        }
+        if parts.iter().any(|part| matches!(part, Part::Literal(text) if text.contains(['/', '\\']))) {
+            return Err(format!("name template {:?} must not contain path separators", template));
+        }
+        Ok(NameTemplate { parts })
+    }
+
+    /// Whether the names have the slides' titles in them
+    pub fn has_title(&self) -> bool {
+        self.parts.iter().any(|part| matches!(part, Part::Field { field: Field::Title, .. }))
+    }
+
+    /// Whether every slide gets a name of its own before it has a title
+    pub fn numbers_slides(&self) -> bool {
+        self.parts.iter().any(|part| matches!(part, Part::Field { field: Field::Index | Field::Frame, .. }))
+    }
+
+    /// File name of a slide, `.png` included
+    pub fn render(&self, slide: &SlideName) -> String {
+        let mut name = String::new();
+        for part in &self.parts {
+            match part {
+                Part::Literal(text) => name.push_str(text),
+                Part::Field { field, width } => {
+                    let value = match field {
+                        Field::Video => video_stem(slide.input_file),
+                        Field::Index => slide.index.to_string(),
+                        Field::Frame => slide.frame.to_string(),
+                        Field::Seconds => (slide.seconds as u64).to_string(),
+                        Field::Timestamp => {
+                            let seconds = slide.seconds as u64;
+                            format!("{:02}-{:02}-{:02}", seconds / 3600, seconds / 60 % 60, seconds % 60)
+                        }
+                        Field::Title => TITLE_PLACEHOLDER.to_string(),
+                    };
+                    name.push_str(&format!("{:0>width$}", value, width = *width));
+                }
+            }
+        }
+        name.push_str(".png");
+        name
+    }
+}
+
+/// `title` as it goes into a file name: lowercase letters and digits, the rest turned into dashes
+pub fn slug(title: &str) -> String {
+    let mut slug = String::new();
+    for c in title.chars().flat_map(char::to_lowercase) {
+        if c.is_alphanumeric() {
+            slug.push(c);
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+    let slug: String = slug.trim_end_matches('-').chars().take(SLUG_LENGTH).collect();
+    slug.trim_end_matches('-').to_string()
+}
+
+/// File stem of the input, also for URLs
+fn video_stem(input_file: &Path) -> String {
+    let input_file = input_file.to_string_lossy();
+    let last = input_file.rsplit(['/', '\\']).next().unwrap_or(&input_file);
+    let last = last.split(['?', '#']).next().unwrap_or(last);
+    Path::new(last).file_stem().map(|stem| stem.to_string_lossy().into_owned()).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn name(template: &str, input_file: &str) -> String {
+        let slide = SlideName { input_file: Path::new(input_file), index: 7, frame: 3725, seconds: 3725.6 };
+        NameTemplate::parse(template).unwrap().render(&slide)
+    }
+
+    #[test]
+    fn renders_placeholders() {
+        assert_eq!(name("{video}_{index:03}_{timestamp}", "talks/intro.mp4"), "intro_007_01-02-05.png");
+        assert_eq!(name("slide-{frame:6}-{seconds}", "intro.mp4"), "slide-003725-3725.png");
+        assert_eq!(name("{index:1}", "intro.mp4"), "7.png");
+    }
+
+    #[test]
+    fn takes_the_stem_of_urls() {
+        assert_eq!(name("{video}", "https://example.com/media/talk.mp4?token=abc#t=5"), "talk.png");
+        assert_eq!(name("{video}", "C:\\talks\\talk.mkv"), "talk.png");
+    }
+
+    #[test]
+    fn rejects_bad_templates() {
+        for template in ["{index", "{slide}", "{index:wide}", "talks/{index}", "a\\{index}"] {
+            assert!(NameTemplate::parse(template).is_err(), "{} parsed", template);
+        }
+    }
+
+    #[test]
+    fn tells_whether_slides_are_numbered() {
+        assert!(NameTemplate::parse("{video}_{index}").unwrap().numbers_slides());
+        assert!(NameTemplate::parse("{frame}").unwrap().numbers_slides());
+        assert!(!NameTemplate::parse("{video}_{timestamp}").unwrap().numbers_slides());
+    }
+}
@@ -0,0 +1,290 @@
+//! `--ocr`: read the text off the kept slides, so a talk can be searched and
+//! quoted without typing its slides out.
+//!
+//! The text is read by an [`OcrEngine`]: `tesseract` on the PATH, or with
+//! `--ocr-engine URL` an endpoint taking Google Cloud Vision `images:annotate`
+//! requests, for handwriting and scripts Tesseract reads poorly. The endpoint
+//! is sent the slide as a document text detection request, with the API key
+//! from `OCR_API_KEY` as the `key` parameter if set, and its full text
+//! annotation is the slide's text.
+//!
+//! The language of each slide's text is detected once it is read, and with
+//! `--ocr-by-language` slides found not to be in English are read again with
+//! Tesseract's model for their language, which must be installed; without it
+//! the first reading is kept.
+//!
+//! Slides are read on `--ocr-threads` threads at once. What is read is cached
+//! in `ocr/` in the output directory, keyed by the hash of the slide's image
+//! and the engine, so running the same video into the same directory again,
+//! or `finalize` after slides were added by hand, only reads the slides it
+//! hasn't seen yet. Slides that already have text in the manifest are left
+//! alone. Delete the directory to read everything again.
+
+use base64::Engine as _;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+use crate::config::{Config, OcrBackend};
+use crate::error::Error;
+use crate::language::{self, Language};
+use crate::lock::write_atomic;
+use crate::manifest::Manifest;
+use crate::runlog::RunLog;
+use crate::s3::hex;
+
+/// Subdirectory of the output directory the text read is cached in
+pub const CACHE_DIR: &str = "ocr";
+/// Lines of tesseract's stderr kept for an error
+const STDERR_LINES: usize = 10;
+/// Environment variable holding the API key for an HTTP engine
+const API_KEY_VAR: &str = "OCR_API_KEY";
+
+/// Something that reads the text in an image
+pub trait OcrEngine: Send + Sync {
+    /// The text in the image at `path`, empty if there is none
+    fn recognize(&self, path: &Path) -> Result<String, Error>;
+
+    /// Tells the engine's results apart in the cache
+    fn id(&self) -> String;
+}
+
+/// `tesseract` on the PATH
+pub struct Tesseract;
+
+impl OcrEngine for Tesseract {
+    fn recognize(&self, path: &Path) -> Result<String, Error> {
+        tesseract(path, None)
+    }
+
+    fn id(&self) -> String {
+        "tesseract".to_string()
+    }
+}
+
+/// `tesseract` on the PATH with the model of one language
+pub struct TesseractModel {
+    /// Name of the model, like `deu`
+    pub language: String,
+}
+
+impl OcrEngine for TesseractModel {
+    fn recognize(&self, path: &Path) -> Result<String, Error> {
+        tesseract(path, Some(&self.language))
+    }
+
+    fn id(&self) -> String {
+        format!("tesseract:{}", self.language)
+    }
+}
+
+/// What tesseract reads in the image at `path`, with its default model unless `language` is given
+fn tesseract(path: &Path, language: Option<&str>) -> Result<String, Error> {
+    let mut command = Command::new("tesseract");
+    command.arg(path).arg("stdout");
+    if let Some(language) = language {
+        command.arg("-l").arg(language);
+    }
+    let output = command.stdin(Stdio::null()).output().map_err(Error::tesseract_spawn)?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let lines: Vec<&str> = stderr.lines().collect();
+        let message = lines[lines.len().saturating_sub(STDERR_LINES)..].join("\n");
+        return Err(Error::OcrFailed { path: path.to_path_buf(), message });
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// An endpoint taking Google Cloud Vision `images:annotate` requests
+pub struct VisionApi {
+    url: String,
+    key: Option<String>,
+}
+
+impl VisionApi {
+    /// The endpoint at `url`, with the API key from `OCR_API_KEY` if set
+    pub fn new(url: impl Into<String>) -> Self {
+        VisionApi { url: url.into(), key: env::var(API_KEY_VAR).ok().filter(|key| !key.is_empty()) }
+    }
+}
+
+impl OcrEngine for VisionApi {
+    fn recognize(&self, path: &Path) -> Result<String, Error> {
+        let failed = |message: String| Error::OcrFailed { path: path.to_path_buf(), message };
+        let content = base64::engine::general_purpose::STANDARD.encode(fs::read(path)?);
+        let body = json!({
+            "requests": [{
+                "image": { "content": content },
+                "features": [{ "type": "DOCUMENT_TEXT_DETECTION" }],
+            }],
+        });
+        let mut request = ureq::post(&self.url);
+        if let Some(key) = &self.key {
+            request = request.query("key", key);
+        }
+        let response: Value = match request.send_json(body) {
+            Ok(response) => response.into_json()?,
+            Err(ureq::Error::Status(code, response)) => {
+                return Err(failed(format!("{} answered {}: {}", self.url, code, response.into_string().unwrap_or_default())));
+            }
+            Err(e) => return Err(failed(e.to_string())),
+        };
+        let response = &response["responses"][0];
+        if let Some(message) = response["error"]["message"].as_str() {
+            return Err(failed(format!("{} answered: {}", self.url, message)));
+        }
+        Ok(response["fullTextAnnotation"]["text"].as_str().unwrap_or_default().trim().to_string())
+    }
+
+    fn id(&self) -> String {
+        self.url.clone()
+    }
+}
+
+/// The engine `backend` picks
+pub fn engine(backend: &OcrBackend) -> Box<dyn OcrEngine> {
+    match backend {
+        OcrBackend::Tesseract => Box::new(Tesseract),
+        OcrBackend::Http { url } => Box::new(VisionApi::new(url)),
+    }
+}
+
+/// The text `engine` reads in the image at `path` from `cache_dir`, read and cached first if
+/// it isn't there, and whether it came from the cache
+fn read_cached(engine: &dyn OcrEngine, path: &Path, cache_dir: &Path) -> Result<(String, bool), Error> {
+    let mut hasher = Sha256::new();
+    hasher.update(engine.id());
+    hasher.update([0]);
+    hasher.update(fs::read(path)?);
+    let entry = cache_dir.join(format!("{}.txt", hex(&hasher.finalize())));
+    if let Ok(text) = fs::read_to_string(&entry) {
+        return Ok((text, true));
+    }
+    let text = engine.recognize(path)?;
+    write_atomic(&entry, &text)?;
+    Ok((text, false))
+}
+
+/// Read the text of the slides of `manifest` as `config` asks: with `config.ocr`, with the engine
+/// it picks, and with `config.ocr_by_language` again in the language detected
+pub fn read_text(config: &Config, manifest: &mut Manifest, log: &RunLog) -> Result<(), Error> {
+    if !config.ocr {
+        return Ok(());
+    }
+    read_slides(&*engine(&config.ocr_backend), &config.output_dir, manifest, config.ocr_threads, log)?;
+    if config.ocr_by_language {
+        match config.ocr_backend {
+            OcrBackend::Tesseract => read_by_language(&config.output_dir, manifest, config.ocr_threads, log)?,
+            OcrBackend::Http { .. } => log.warn("--ocr-by-language picks Tesseract's models, the OCR endpoint keeps its reading"),
+        }
+    }
+    Ok(())
+}
+
+/// Read the text of every slide of `manifest` in `output_dir` that has none yet with `engine`,
+/// on `threads` threads or one per CPU, and tag the slides with the language of their text
+pub fn read_slides(
+    engine: &dyn OcrEngine,
+    output_dir: &Path,
+    manifest: &mut Manifest,
+    threads: Option<usize>,
+    log: &RunLog,
+) -> Result<(), Error> {
+    let pending: Vec<(usize, PathBuf)> = manifest
+        .slides
+        .iter()
+        .enumerate()
+        .filter(|(_, slide)| slide.text.is_none())
+        .map(|(i, slide)| (i, output_dir.join(&slide.file)))
+        .collect();
+    if !pending.is_empty() {
+        let (mut read, mut cached) = (0, 0);
+        for (slide, text, from_cache) in read_all(engine, &pending, output_dir, threads)? {
+            manifest.slides[slide].text = Some(text);
+            read += 1;
+            cached += usize::from(from_cache);
+        }
+        log.info(format_args!("Read the text of {} slide(s), {} from the cache.", read, cached));
+    }
+    language::tag_languages(manifest);
+    Ok(())
+}
+
+/// Read the slides of `manifest` whose text isn't English again with Tesseract's model for its
+/// language, keeping the first reading where the model isn't installed
+fn read_by_language(output_dir: &Path, manifest: &mut Manifest, threads: Option<usize>, log: &RunLog) -> Result<(), Error> {
+    let mut languages: Vec<&'static Language> = Vec::new();
+    for slide in &manifest.slides {
+        if let Some(language) = slide.language.as_deref().and_then(language::by_code) {
+            if language.code != "en" && !languages.contains(&language) {
+                languages.push(language);
+            }
+        }
+    }
+    for language in languages {
+        let pending: Vec<(usize, PathBuf)> = manifest
+            .slides
+            .iter()
+            .enumerate()
+            .filter(|(_, slide)| slide.language.as_deref() == Some(language.code))
+            .map(|(i, slide)| (i, output_dir.join(&slide.file)))
+            .collect();
+        let engine = TesseractModel { language: language.tesseract.to_string() };
+        match read_all(&engine, &pending, output_dir, threads) {
+            Ok(results) => {
+                for (slide, text, _) in results {
+                    manifest.slides[slide].text = Some(text);
+                }
+                log.info(format_args!("Read {} slide(s) again with the {} model.", pending.len(), language.tesseract));
+            }
+            Err(Error::OcrFailed { message, .. }) => {
+                log.warn(format_args!("Keeping the first reading of the {} slide(s) in {}, tesseract could not use its {} model: {}", pending.len(), language.code, language.tesseract, message));
+            }
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(())
+}
+
+/// The text `engine` reads in each of the `pending` slides, by their index, on `threads` threads
+/// or one per CPU, and whether it came from the cache in `output_dir`
+fn read_all(
+    engine: &dyn OcrEngine,
+    pending: &[(usize, PathBuf)],
+    output_dir: &Path,
+    threads: Option<usize>,
+) -> Result<Vec<(usize, String, bool)>, Error> {
+    let cache_dir = output_dir.join(CACHE_DIR);
+    fs::create_dir_all(&cache_dir)?;
+
+    let threads = threads
+        .unwrap_or_else(|| thread::available_parallelism().map_or(1, |n| n.get()))
+        .clamp(1, pending.len());
+    let next = AtomicUsize::new(0);
+    let results = Mutex::new(Vec::with_capacity(pending.len()));
+    thread::scope(|scope| {
+        for _ in 0..threads {
+            scope.spawn(|| {
+                while let Some((slide, path)) = pending.get(next.fetch_add(1, Ordering::Relaxed)) {
+                    let result = read_cached(engine, path, &cache_dir);
+                    let failed = result.is_err();
+                    results.lock().unwrap().push((*slide, result));
+                    if failed {
+                        // Stop handing out slides to every thread
+                        next.store(pending.len(), Ordering::Relaxed);
+                    }
+                }
+            });
+        }
+    });
+
+    let mut results = results.into_inner().unwrap();
+    results.sort_by_key(|(slide, _)| *slide);
+    results.into_iter().map(|(slide, result)| result.map(|(text, from_cache)| (slide, text, from_cache))).collect()
+}
@@ -0,0 +1,105 @@
+use std::fs;
+use std::io::{Cursor, Error};
+use std::path::PathBuf;
+
+use crate::archive::write_archive;
+use crate::config::Config;
+use crate::links::{self, LINKS_FILE};
+use crate::lock::write_atomic;
+use crate::manifest::{Manifest, MANIFEST_FILE};
+use crate::runlog::RunLog;
+use crate::s3::S3Bucket;
+use crate::sidecar;
+
+/// Somewhere the final artifacts of a run are delivered to
+pub trait OutputBackend {
+    /// Store `data` under `name`, a file name or a relative path like `deck-2/frame_000042.png`
+    fn put(&self, name: &str, data: &[u8], content_type: &str) -> Result<(), Error>;
+
+    /// Human-readable destination, for messages
+    fn describe(&self) -> String;
+}
+
+/// A directory on the local filesystem
+pub struct LocalDir {
+    dir: PathBuf,
+}
+
+impl LocalDir {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        LocalDir { dir: dir.into() }
+    }
+}
+
+impl OutputBackend for LocalDir {
+    fn put(&self, name: &str, data: &[u8], _content_type: &str) -> Result<(), Error> {
+        let path = self.dir.join(name);
+        fs::create_dir_all(path.parent().unwrap_or(&self.dir))?;
+        write_atomic(&path, data)
+    }
+
+    fn describe(&self) -> String {
+        self.dir.display().to_string()
+    }
+}
+
+impl OutputBackend for S3Bucket {
+    fn put(&self, name: &str, data: &[u8], content_type: &str) -> Result<(), Error> {
+        S3Bucket::put(self, name, data, content_type)
+    }
+
+    fn describe(&self) -> String {
+        self.url()
+    }
+}
+
+/// Pick the backend for a `--output` destination: `s3://bucket/prefix` or a local directory
+pub fn backend_for(destination: &str) -> Result<Box<dyn OutputBackend>, Error> {
+    if destination.starts_with("s3://") {
+        Ok(Box::new(S3Bucket::from_url(destination)?))
+    } else {
+        Ok(Box::new(LocalDir::new(destination)))
+    }
+}
+
+/// Fail early on a destination that could never be written to (e.g. missing credentials)
+pub fn check_destination(config: &Config) -> Result<(), Error> {
+    if let Some(destination) = &config.output {
+        backend_for(destination)?;
+    }
+    Ok(())
+}
+
+/// Deliver the kept slides, the manifest and the optional archive of a finished run
+pub fn publish(config: &Config, manifest: &Manifest, log: &RunLog) -> Result<(), Error> {
+    let backend: Box<dyn OutputBackend> = match &config.output {
+        Some(destination) => backend_for(destination)?,
+        // Slides and manifest are already in the working directory
+        None if config.archive.is_some() => Box::new(LocalDir::new(&config.output_dir)),
+        None => return Ok(()),
+    };
+    let working_dir = &config.output_dir;
+
+    if config.output.is_some() {
+        for slide in &manifest.slides {
+            let data = fs::read(working_dir.join(&slide.file))?;
+            backend.put(&slide.file, &data, "image/png")?;
+            if let Some(format) = config.sidecars {
+                let contents = sidecar::contents(slide, format)?;
+                backend.put(&sidecar::file_name(&slide.file, format), contents.as_bytes(), format.content_type())?;
+            }
+        }
+        backend.put(MANIFEST_FILE, manifest.to_json()?.as_bytes(), "application/json")?;
+        if let Some(markdown) = links::markdown(manifest) {
+            backend.put(LINKS_FILE, markdown.as_bytes(), "text/markdown")?;
+        }
+    }
+
+    if let Some(archive_name) = &config.archive {
+        let zip = write_archive(&config.output_dir, manifest, config.sidecars, Cursor::new(Vec::new()))?;
+        backend.put(archive_name, &zip.into_inner(), "application/zip")?;
+    }
+
+    log.info(format_args!("Results written to {}", backend.describe()));
+    Ok(())
+}
@@ -0,0 +1,34 @@
+//! Masks: areas of the frame left out of the comparison, like the places a
+//! video call app draws chat popups, join notifications and "this meeting is
+//! being recorded" banners over the shared screen. The profiles of `--platform`
+//! bring the masks for each app.
+//!
+//! Before two frames are compared, the masked areas of the newer frame are
+//! painted over with the older one's, so an overlay coming or going is not a
+//! slide change while a change anywhere else still is. The kept slides are
+//! left as they were captured.
+
+use image::{DynamicImage, GenericImageView};
+
+use crate::config::Mask;
+
+/// `image` with the areas of `masks` taken from `reference`
+pub fn hide(masks: &[Mask], image: &DynamicImage, reference: &DynamicImage) -> DynamicImage {
+    if image.dimensions() != reference.dimensions() {
+        return image.clone();
+    }
+    let (width, height) = image.dimensions();
+    let (mut hidden, reference) = (image.to_rgb8(), reference.to_rgb8());
+    for mask in masks {
+        let x0 = ((mask.x * width as f64).round() as u32).min(width);
+        let y0 = ((mask.y * height as f64).round() as u32).min(height);
+        let x1 = (((mask.x + mask.width) * width as f64).round() as u32).min(width);
+        let y1 = (((mask.y + mask.height) * height as f64).round() as u32).min(height);
+        for y in y0..y1 {
+            for x in x0..x1 {
+                hidden.put_pixel(x, y, *reference.get_pixel(x, y));
+            }
+        }
+    }
+    DynamicImage::ImageRgb8(hidden)
+}
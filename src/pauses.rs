@@ -0,0 +1,83 @@
+//! `--snap-to-pauses`: move each slide change to the pause in speech it
+//! almost always falls in, so chapters start, and a transcript splits,
+//! between sentences rather than up to a sampling interval off.
+//!
+//! A pause is a stretch of at least 0.3s more than 30dB quieter than the
+//! loudest speech in the recording's audio. A slide's timestamp moves to the
+//! middle of the nearest pause up to the given number of seconds away, never
+//! past the slide before or after it, and where it was seen is kept in
+//! `detected`. Without an audio track nothing moves.
+
+use crate::audio;
+use crate::config::Config;
+use crate::error::Error;
+use crate::manifest::{Interval, Manifest};
+use crate::runlog::RunLog;
+
+/// Decibels below speech a block must be to be quiet
+const PAUSE_DB: f64 = 30.0;
+/// Seconds a quiet stretch must last to be a pause
+const MIN_PAUSE: f64 = 0.3;
+
+/// Move the slides of `manifest` to the pauses in `config.input_file`'s audio, if
+/// `config.snap_to_pauses` asks for it
+pub fn snap(config: &Config, manifest: &mut Manifest, log: &RunLog) -> Result<(), Error> {
+    let Some(max_shift) = config.snap_to_pauses else {
+        return Ok(());
+    };
+    let blocks = match audio::read_blocks(config) {
+        Ok(blocks) => blocks,
+        Err(e) => {
+            log.warn(format_args!("Not snapping slide changes to pauses, the audio could not be read: {}", e));
+            return Ok(());
+        }
+    };
+    let pauses = audio::quiet_stretches(&blocks, PAUSE_DB, MIN_PAUSE);
+
+    let mut moved = 0;
+    for i in 0..manifest.slides.len() {
+        let timestamp = manifest.slides[i].timestamp;
+        // The first slide is there from the start
+        if timestamp <= 0.0 {
+            continue;
+        }
+        let after = if i > 0 { manifest.slides[i - 1].timestamp } else { 0.0 };
+        let before = manifest.slides.get(i + 1).map_or(f64::INFINITY, |next| next.timestamp);
+        let Some(snapped) = nearest(&pauses, timestamp, max_shift).filter(|&snapped| snapped > after && snapped < before) else {
+            continue;
+        };
+        if (snapped - timestamp).abs() < 1e-3 {
+            continue;
+        }
+        let shift = snapped - timestamp;
+        let slide = &mut manifest.slides[i];
+        slide.detected = Some(timestamp);
+        slide.timestamp = snapped;
+        if let Some(camera_timestamp) = &mut slide.camera_timestamp {
+            *camera_timestamp += shift;
+        }
+        // Keep the stretches the slides were on screen joined up
+        for interval in manifest.slides.iter_mut().flat_map(|slide| &mut slide.shown) {
+            if interval.start == timestamp {
+                interval.start = snapped;
+            }
+            if interval.end == timestamp {
+                interval.end = snapped;
+            }
+        }
+        moved += 1;
+    }
+    log.info(format_args!("Snapped {} of {} slide change(s) to a pause in {} found.", moved, manifest.slides.len().saturating_sub(1), pauses.len()));
+    Ok(())
+}
+
+/// Where in the pause nearest to `timestamp` a slide change there goes: the middle, or as
+/// close to it as `max_shift` allows; `None` if no pause is that close
+fn nearest(pauses: &[Interval], timestamp: f64, max_shift: f64) -> Option<f64> {
+    let distance = |pause: &Interval| (pause.start - timestamp).max(timestamp - pause.end).max(0.0);
+    let pause = pauses.iter().filter(|pause| distance(pause) <= max_shift).min_by(|a, b| distance(a).total_cmp(&distance(b)))?;
+    let middle = (pause.start + pause.end) / 2.0;
+    let snapped = middle.clamp(timestamp - max_shift, timestamp + max_shift).clamp(pause.start, pause.end);
+    // To the millisecond, past what the audio's 20ms blocks tell anyway
+    Some((snapped * 1000.0).round() / 1000.0)
+}
@@ -0,0 +1,539 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::confidence::{self, Evidence};
+use crate::adaptive::AdaptiveSource;
+use crate::board::BoardSource;
+use crate::boundaries;
+use crate::cache::CachedSource;
+use crate::canvas;
+use crate::catalog;
+use crate::changes;
+use crate::classify;
+use crate::config::{BadFramePolicy, Config, SyncMode};
+use crate::dedup::{Decision, Deduplicator, Settled, Stabilizer};
+use crate::error::Error;
+use crate::extract::{check_piped, is_piped};
+use crate::fingerprint::{fingerprint, previous_run};
+use crate::identity;
+use crate::idle;
+use crate::ink::InkSource;
+use crate::limits::OutputBudget;
+use crate::links;
+use crate::live;
+use crate::lock::lock_output;
+use crate::manifest::{FrameScore, Manifest, ResolutionChange, Slide, Source, SourceRole, SCHEMA_VERSION};
+use crate::metadata;
+use crate::metrics;
+use crate::monitors;
+use crate::naming::{NameTemplate, SlideName};
+use crate::ocr;
+use crate::output;
+use crate::pauses;
+use crate::rate;
+use crate::report;
+use crate::revisits::{self, Revisits};
+use crate::progress::{CancellationToken, Progress, Stage};
+use crate::runlog::RunLog;
+use crate::scroll::Stitcher;
+use crate::zoom::{self, Zooms};
+use crate::sidecar;
+use crate::speakers;
+use crate::summarize;
+use crate::source::{FfmpegSource, FrameSource, PipeSource};
+use crate::sync;
+
+/// Is this directory entry one of the frames ffmpeg wrote?
+pub fn is_frame_file(path: &Path) -> bool {
+    path.extension().and_then(|s| s.to_str()) == Some("png")
+}
+
+/// Name of a frame split around the number at the end of it (`frame_`, 10000),
+/// so `frame_10000.png` sorts after `frame_9999.png` however the numbers are padded
+fn frame_key(path: &Path) -> (String, Option<u64>, String) {
+    let stem = path.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default();
+    let prefix = stem.trim_end_matches(|c: char| c.is_ascii_digit());
+    let index = stem[prefix.len()..].parse().ok();
+    (prefix.to_string(), index, path.to_string_lossy().into_owned())
+}
+
+/// Put frames in the order they were sampled in
+pub fn sort_frames(frames: &mut [PathBuf]) {
+    frames.sort_by_cached_key(|path| frame_key(path));
+}
+
+/// Apply `config.on_bad_frame` to a frame that failed to decode.
+/// Returns the frame's file name if the run carries on without it.
+pub fn handle_bad_frame(config: &Config, log: &RunLog, path: PathBuf, error: image::ImageError) -> Result<String, Error> {
+    let name = path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+    match config.on_bad_frame {
+        BadFramePolicy::Stop => return Err(Error::BadFrame { path, source: error }),
+        BadFramePolicy::Skip => {
+            log.warn(format_args!("Skipping frame {:?} that could not be decoded: {}", path, error));
+        }
+        BadFramePolicy::Delete => {
+            log.warn(format_args!("Deleting frame {:?} that could not be decoded: {}", path, error));
+            fs::remove_file(&path)?;
+        }
+    }
+    Ok(name)
+}
+
+/// What deduplicating the frames of a run left behind
+pub struct Processed {
+    /// Position in the sampled sequence and path of every kept frame
+    pub kept: Vec<(usize, PathBuf)>,
+    /// Names of the frames that could not be decoded
+    pub bad_frames: Vec<String>,
+    /// The decision made on every decoded frame
+    pub scores: Vec<FrameScore>,
+    /// Frames at which the resolution changed
+    pub resolution_changes: Vec<ResolutionChange>,
+    /// What each kept frame's confidence is scored from, in the same order
+    pub evidence: Vec<Evidence>,
+    /// Position each slide appeared at and its position in `kept`, in order; slides shown again appear more than once
+    pub appearances: Vec<(usize, usize)>,
+    /// Close-ups saved of each kept frame, in the same order
+    pub zooms: Vec<Vec<String>>,
+}
+
+/// Pull every frame from `source` and filter out non-unique frames
+fn process_frames(
+    config: &Config,
+    source: &mut dyn FrameSource,
+    log: &RunLog,
+    progress: &dyn Fn(Progress),
+    cancel: &CancellationToken,
+) -> Result<Processed, Error> {
+    let output_dir = &config.output_dir;
+    let total = source.remaining();
+    let mut dedup = Deduplicator::new(config, log);
+    let mut budget = OutputBudget::new(config);
+    let mut stabilizer = Stabilizer::new(config);
+    let mut revisits = Revisits::new(config, log);
+    let mut stitcher = Stitcher::default();
+    let mut zooms = Zooms::default();
+    let template = name_template(config)?;
+    let mut kept = Vec::new();
+    let mut bad_frames = Vec::new();
+    let mut scores = Vec::new();
+    let mut resolution_changes = Vec::new();
+    let mut evidence = Vec::new();
+    let mut appearances = Vec::new();
+    // The decision on the frame the pending slide appeared at
+    let mut change = None;
+    // Name, path, score and position of the frame waiting for the slide to settle
+    let mut held: Option<(String, Option<PathBuf>, usize, usize)> = None;
+    let mut position = 0;
+
+    loop {
+        if cancel.is_cancelled() {
+            return Err(Error::Cancelled);
+        }
+        let frame = match source.next_frame() {
+            Ok(Some(frame)) => frame,
+            Ok(None) => break,
+            Err(Error::BadFrame { path, source: e }) => {
+                bad_frames.push(handle_bad_frame(config, log, path, e)?);
+                position += 1;
+                progress(Progress { stage: Stage::Comparing, done: position, total });
+                continue;
+            }
+            Err(e) => return Err(e),
+        };
+
+        let shown_path = frame.path.clone().unwrap_or_else(|| output_dir.join(&frame.name));
+        let decision = dedup.observe(frame.image);
+        decision.log(log, &shown_path);
+        if config.report {
+            report::save_thumbnail(output_dir, &frame.name, dedup.reference().expect("the frame was just observed"))?;
+        }
+        if config.stitch_scrolls {
+            stitcher.observe(position, &decision, dedup.reference().expect("the frame was just observed"));
+        }
+        if config.save_zooms {
+            zooms.observe(position, &decision, dedup.reference().expect("the frame was just observed"));
+        }
+        scores.push(frame_score(config, position, &frame.name, decision));
+        resolution_changes.extend(resolution_change(config, position, &frame.name, decision));
+        if decision.is_kept() {
+            change = Some(decision);
+        }
+
+        let settled = stabilizer.settle(position, &decision);
+        if settled != Settled::Drop {
+            if let Some((name, path, _, _)) = held.take() {
+                // Otherwise the held frame just makes way for a later one of the same slide
+                if decision.is_kept() {
+                    log.debug(format_args!("Dropping frame {}, the slide was still changing.", name));
+                }
+                metrics::frame_examined(false);
+                source.discard(path.as_deref())?;
+            }
+        }
+        match settled {
+            Settled::Drop => {
+                metrics::frame_examined(false);
+                source.discard(frame.path.as_deref())?;
+            }
+            Settled::Hold => held = Some((frame.name, frame.path, scores.len() - 1, position)),
+            Settled::Commit { start } => {
+                let image = dedup.reference().expect("the observed frame is the reference");
+                match revisits.as_mut().and_then(|revisits| revisits.recognize(image)) {
+                    Some(earlier) => {
+                        log.debug(format_args!("Frame {} shows slide {} again.", frame.name, earlier + 1));
+                        metrics::frame_examined(false);
+                        source.discard(frame.path.as_deref())?;
+                        appearances.push((start, earlier));
+                    }
+                    None => {
+                        metrics::frame_examined(true);
+                        scores.last_mut().expect("just pushed").kept = true;
+                        appearances.push((start, kept.len()));
+                        let name =
+                            slide_file_name(config, template.as_ref(), kept.len() + 1, start, position, &frame.name);
+                        evidence.push(Evidence::new(change, image));
+                        let slide = source.keep(&name, frame.path.as_deref(), image, output_dir)?;
+                        budget.record(&slide, position + 1, total, &mut dedup, log)?;
+                        kept.push((start, slide));
+                        live::update(config, &kept)?;
+                    }
+                }
+            }
+        }
+
+        position += 1;
+        progress(Progress { stage: Stage::Comparing, done: position, total });
+    }
+
+    // The video ended before the last slide settled, it is the best there is
+    if let (Some(start), Some((name, path, score, held_position))) = (stabilizer.finish(), held.take()) {
+        let image = dedup.reference().expect("the held frame was observed last");
+        match revisits.as_mut().and_then(|revisits| revisits.recognize(image)) {
+            Some(earlier) => {
+                log.debug(format_args!("Frame {} shows slide {} again.", name, earlier + 1));
+                metrics::frame_examined(false);
+                source.discard(path.as_deref())?;
+                appearances.push((start, earlier));
+            }
+            None => {
+                metrics::frame_examined(true);
+                scores[score].kept = true;
+                appearances.push((start, kept.len()));
+                let name = slide_file_name(config, template.as_ref(), kept.len() + 1, start, held_position, &name);
+                evidence.push(Evidence::new(change, image));
+                let slide = source.keep(&name, path.as_deref(), image, output_dir)?;
+                budget.record(&slide, position, total, &mut dedup, log)?;
+                kept.push((start, slide));
+                live::update(config, &kept)?;
+            }
+        }
+    }
+
+    stitcher.write(&kept)?;
+    let zooms = zooms.write(&kept, &config.output_dir)?;
+    report_threshold(&dedup, log);
+    record_provenance(config, &mut scores, &appearances);
+    Ok(Processed { kept, bad_frames, scores, resolution_changes, evidence, appearances, zooms })
+}
+
+/// The run's `--name-template`, if it has one
+pub fn name_template(config: &Config) -> Result<Option<NameTemplate>, Error> {
+    let template = config.name_template.as_deref().map(NameTemplate::parse).transpose();
+    let template = template.map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    if let Some(template) = template.as_ref().filter(|template| template.has_title()) {
+        if config.summarize.is_none() {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "{title} in the name template needs --summarize").into());
+        }
+        // The slides are all named `{title}` until they are titled
+        if !template.numbers_slides() {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "{title} in the name template needs {index} or {frame} with it").into());
+        }
+    }
+    Ok(template)
+}
+
+/// File name for the `index`th slide, which appeared at `start` and is kept as the frame at `position`
+pub fn slide_file_name(
+    config: &Config,
+    template: Option<&NameTemplate>,
+    index: usize,
+    start: usize,
+    position: usize,
+    frame_name: &str,
+) -> String {
+    match template {
+        Some(template) => template.render(&SlideName {
+            input_file: &config.input_file,
+            index,
+            frame: position + 1,
+            seconds: start as f64 / config.fps as f64,
+        }),
+        None => frame_name.to_string(),
+    }
+}
+
+/// Manifest record of the comparison of the frame at `position` in the sampled sequence; not yet kept
+pub fn frame_score(config: &Config, position: usize, file: &str, decision: Decision) -> FrameScore {
+    FrameScore {
+        file: file.to_string(),
+        timestamp: position as f64 / config.fps as f64,
+        difference: decision.difference,
+        threshold: decision.threshold,
+        kept: false,
+        same_as: None,
+    }
+}
+
+/// Point every frame in `scores` that was not kept at the kept frame of the
+/// slide on screen at the time, from the `appearances` of the slides
+pub fn record_provenance(config: &Config, scores: &mut [FrameScore], appearances: &[(usize, usize)]) {
+    let kept: Vec<String> = scores.iter().filter(|score| score.kept).map(|score| score.file.clone()).collect();
+    for score in scores.iter_mut().filter(|score| !score.kept) {
+        let slide = appearances.iter().rev().find(|&&(start, _)| start as f64 / config.fps as f64 <= score.timestamp);
+        score.same_as = slide.and_then(|&(_, slide)| kept.get(slide).cloned());
+    }
+}
+
+/// Manifest record of the frame at `position` if it changed the resolution
+pub fn resolution_change(config: &Config, position: usize, file: &str, decision: Decision) -> Option<ResolutionChange> {
+    decision.resized.map(|(width, height)| ResolutionChange {
+        file: file.to_string(),
+        timestamp: position as f64 / config.fps as f64,
+        width,
+        height,
+    })
+}
+
+/// Say why a run did nothing
+pub fn report_skipped(config: &Config, previous: &Manifest, log: &RunLog) {
+    log.info(format_args!(
+        "{} slide(s) from the same input and settings are already in {}, skipping (pass --force to redo it).",
+        previous.slides.len(),
+        config.output_dir.display()
+    ));
+}
+
+/// Say so if `--on-limit raise` had to give up on the configured threshold
+pub fn report_threshold(dedup: &Deduplicator, log: &RunLog) {
+    if dedup.threshold() != dedup.initial_threshold() {
+        log.warn(format_args!(
+            "Raised the threshold from {} to {} to stay within the limits.",
+            dedup.initial_threshold(),
+            dedup.threshold()
+        ));
+    }
+}
+
+/// Manifest entry for the kept frame at `position` in the sampled sequence
+pub fn slide_entry(config: &Config, camera_offset: f64, index: usize, position: usize, frame: &Path) -> Slide {
+    let timestamp = position as f64 / config.fps as f64;
+    Slide {
+        index,
+        file: frame.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default(),
+        id: None,
+        timestamp,
+        detected: None,
+        camera_timestamp: config.camera_file.as_ref().map(|_| timestamp + camera_offset),
+        monitor: None,
+        shown: Vec::new(),
+        qr_codes: Vec::new(),
+        urls: Vec::new(),
+        deck: None,
+        confidence: None,
+        change: None,
+        kind: None,
+        zooms: Vec::new(),
+        diff: None,
+        text: None,
+        language: None,
+        speaker: None,
+        title: None,
+        summary: None,
+        manual: false,
+    }
+}
+
+/// Describe the kept frames on the shared session timeline
+pub fn build_manifest(config: &Config, camera_offset: f64, kept: Vec<(usize, PathBuf)>) -> Manifest {
+    let camera_file = config.camera_file.as_deref();
+
+    let mut sources = vec![Source { role: SourceRole::Screen, path: config.input_file.to_string_lossy().into_owned(), offset: 0.0 }];
+    if let Some(camera_file) = camera_file {
+        sources.push(Source { role: SourceRole::Camera, path: camera_file.to_string_lossy().into_owned(), offset: camera_offset });
+    }
+
+    let slides = kept
+        .iter()
+        .enumerate()
+        .map(|(index, (position, frame))| slide_entry(config, camera_offset, index + 1, *position, frame))
+        .collect();
+
+    Manifest {
+        version: SCHEMA_VERSION,
+        sources,
+        slides,
+        bad_frames: Vec::new(),
+        frames: Vec::new(),
+        resolution_changes: Vec::new(),
+        content: None,
+        catalog: None,
+        fingerprint: None,
+    }
+}
+
+/// ffmpeg's frames of `config.input_file`, through the disk, with `in_memory`
+/// or a piped input without it, with `adaptive` densely only around changes or
+/// with `frame_cache` from an earlier run
+fn ffmpeg_source(config: &Config) -> Result<Box<dyn FrameSource>, io::Error> {
+    if config.frame_cache.is_some() {
+        Ok(Box::new(CachedSource::new(config)?))
+    } else if config.adaptive.is_some() {
+        Ok(Box::new(AdaptiveSource::new(config)?))
+    } else if config.in_memory || is_piped(&config.input_file) {
+        // Frames of a piped video are compared as they come in, so slides appear while it is being recorded
+        Ok(Box::new(PipeSource::new(config)?))
+    } else {
+        Ok(Box::new(FfmpegSource::new(config)?))
+    }
+}
+
+/// Run the whole pipeline, blocking until it finishes
+pub fn run(config: &Config) -> Result<Manifest, Error> {
+    run_with(config, |_| {}, &CancellationToken::new())
+}
+
+/// Run the whole pipeline, reporting progress as it goes and stopping early
+/// (killing ffmpeg if it is running) once `cancel` is cancelled
+pub fn run_with(
+    config: &Config,
+    progress: impl Fn(Progress),
+    cancel: &CancellationToken,
+) -> Result<Manifest, Error> {
+    let log = RunLog::open(config.log_file.as_deref())?;
+    check_piped(config)?;
+    let config = &rate::resolve(config, &log)?;
+    let decks = monitors::decks(config, &log)?;
+    if let [(None, deck)] = decks.as_slice() {
+        return run_logged(deck, &mut *ffmpeg_source(deck)?, progress, cancel, log);
+    }
+
+    // The combined manifest goes here, each monitor's run locks its own subdirectory
+    let _lock = lock_output(&config.output_dir)?;
+    let mut manifests = Vec::new();
+    for (monitor, deck) in decks {
+        let manifest = run_logged(&deck, &mut *ffmpeg_source(&deck)?, &progress, cancel, log.clone())?;
+        manifests.push((monitor.expect("split decks are numbered"), manifest));
+    }
+    monitors::combine_decks(config, manifests)
+}
+
+/// Like `run_with`, but deduplicate the frames of `source` instead of sampling
+/// `config.input_file` with ffmpeg
+pub fn run_with_source(
+    config: &Config,
+    source: &mut dyn FrameSource,
+    progress: impl Fn(Progress),
+    cancel: &CancellationToken,
+) -> Result<Manifest, Error> {
+    run_logged(config, source, progress, cancel, RunLog::open(config.log_file.as_deref())?)
+}
+
+/// `run_with_source` logging to the already opened `log`
+fn run_logged(
+    config: &Config,
+    source: &mut dyn FrameSource,
+    progress: impl Fn(Progress),
+    cancel: &CancellationToken,
+    log: RunLog,
+) -> Result<Manifest, Error> {
+    let _span = tracing::info_span!("extract", input = %config.input_file.display()).entered();
+    output::check_destination(config)?;
+    let _lock = lock_output(&config.output_dir)?;
+
+    let fingerprint = fingerprint(config);
+    if let Some(previous) = fingerprint.as_ref().filter(|_| !config.force).and_then(|f| previous_run(config, f)) {
+        report_skipped(config, &previous, &log);
+        return Ok(previous);
+    }
+
+    // Work out the camera offset up front so a sync failure doesn't waste an extraction
+    let camera_offset = match (config.camera_file.as_deref(), config.sync) {
+        (Some(camera_file), SyncMode::Audio) => {
+            progress(Progress { stage: Stage::Syncing, done: 0, total: None });
+            let offset = metrics::timed(Stage::Syncing, || {
+                tracing::info_span!("syncing")
+                    .in_scope(|| sync::estimate_offset(&config.input_file, camera_file, config.max_sync_offset))
+            })?;
+            log.info(format_args!("Camera recording is offset by {:.2}s from the screen recording.", offset));
+            offset
+        }
+        _ => config.camera_offset,
+    };
+
+    let (mut board, mut ink);
+    let source: &mut dyn FrameSource = if config.whiteboard {
+        board = BoardSource::new(config, source, &log);
+        &mut board
+    } else if config.ink_pause.is_some() {
+        ink = InkSource::new(config, source, &log);
+        &mut ink
+    } else {
+        source
+    };
+
+    // Step 1: Extract frames from the video
+    fs::create_dir_all(&config.output_dir)?;
+    metrics::timed(Stage::Extracting, || {
+        tracing::info_span!("extracting").in_scope(|| source.prepare(&progress, cancel))
+    })?;
+
+    // Step 2: Process the extracted frames and remove duplicates
+    let processed = metrics::timed(Stage::Comparing, || {
+        tracing::info_span!("comparing").in_scope(|| process_frames(config, source, &log, &progress, cancel))
+    })?;
+
+    // Step 3: Record the kept slides on the shared session timeline
+    let mut manifest = build_manifest(config, camera_offset, processed.kept);
+    zoom::attach(&mut manifest, processed.zooms);
+    manifest.bad_frames = processed.bad_frames;
+    manifest.frames = processed.scores;
+    manifest.resolution_changes = processed.resolution_changes;
+    manifest.fingerprint = fingerprint;
+    revisits::record_intervals(config, &mut manifest, &processed.appearances);
+    confidence::score(&mut manifest, &processed.evidence);
+    boundaries::assign_decks(config, &mut manifest, &processed.evidence, &log);
+    // Before anything that goes by what a slide says
+    ocr::read_text(config, &mut manifest, &log)?;
+    idle::trim(config, &mut manifest, &log)?;
+    confidence::filter(config, &mut manifest, &log)?;
+    pauses::snap(config, &mut manifest, &log)?;
+    speakers::tag_speakers(config, &mut manifest, &log)?;
+    changes::record_changes(config, &mut manifest)?;
+    identity::assign_ids(&config.output_dir, &mut manifest)?;
+    classify::tag_kinds(&config.output_dir, &mut manifest)?;
+    summarize::summarize_slides(config, &config.output_dir, &mut manifest, &log)?;
+    catalog::describe(&config.input_file, &mut manifest);
+    canvas::pad_slides(config, &manifest, &log)?;
+    metadata::tag_slides(config, &manifest)?;
+    links::find_links(config, &mut manifest, &log)?;
+    boundaries::split_output(config, &mut manifest)?;
+    sidecar::write_sidecars(config, &manifest)?;
+    manifest.write(&config.output_dir)?;
+    links::write_links(&config.output_dir, &manifest)?;
+    if config.report {
+        report::write(&config.output_dir, &manifest)?;
+    }
+    live::finish(config, &manifest)?;
+    log.info(format_args!("Kept {} slide(s) in {}", manifest.slides.len(), config.output_dir.display()));
+    if !manifest.bad_frames.is_empty() {
+        log.warn(format_args!("Left out {} frame(s) that could not be decoded.", manifest.bad_frames.len()));
+    }
+
+    // Step 4: Deliver the results if they are wanted somewhere else
+    output::publish(config, &manifest, &log)?;
+
+    source.succeeded();
+    Ok(manifest)
+}
@@ -0,0 +1,57 @@
+use std::io;
+use std::path::Path;
+
+use crate::config::Config;
+use crate::error::{format_size, Error};
+use crate::extract::is_piped;
+use crate::probe::probe;
+use crate::runlog::RunLog;
+
+/// Rough size of a sampled PNG frame relative to its raw RGB pixels; slides
+/// compress far better than this, camera footage about as well
+const PNG_SIZE_RATIO: f64 = 0.25;
+/// Warn when the estimate would use up more than this share of the free space
+const WARN_RATIO: f64 = 0.8;
+
+/// Space all sampled frames of `config.input_file` take up before deduplication, if it can be probed
+pub fn estimate_frames_size(config: &Config) -> Result<u64, io::Error> {
+    let info = probe(&config.input_file)?;
+    let duration = config.duration.map_or(info.duration, |duration| duration.min(info.duration));
+    let frames = (duration * config.fps as f64).ceil();
+    // Uncompressed, the frames take up their raw pixels
+    let ratio = if config.png_compression == Some(0) { 1.0 } else { PNG_SIZE_RATIO };
+    let frame_size = info.width as f64 * info.height as f64 * 3.0 * ratio;
+    Ok((frames * frame_size) as u64)
+}
+
+/// Abort before extracting if the filesystem holding `frames_dir` clearly cannot take all
+/// sampled frames; an estimate that cannot be made is only worth a warning
+pub fn check_space(config: &Config, frames_dir: &Path, log: &RunLog) -> Result<(), Error> {
+    // Nothing to measure before the video is piped in
+    if is_piped(&config.input_file) {
+        return Ok(());
+    }
+    let needed = match estimate_frames_size(config) {
+        Ok(needed) => needed,
+        Err(e) => {
+            log.warn(format_args!("Cannot estimate the disk space needed: {}", e));
+            return Ok(());
+        }
+    };
+    let available = fs4::available_space(frames_dir)?;
+    // The run's own directory is an implementation detail, name the one the user chose
+    let shown_dir = frames_dir.parent().unwrap_or(frames_dir);
+
+    if needed > available {
+        return Err(Error::InsufficientSpace { dir: shown_dir.to_path_buf(), needed, available });
+    }
+    if needed as f64 > available as f64 * WARN_RATIO {
+        log.warn(format_args!(
+            "Extracting needs about {} of the {} free in {}.",
+            format_size(needed),
+            format_size(available),
+            shown_dir.display()
+        ));
+    }
+    Ok(())
+}
@@ -0,0 +1,60 @@
+use serde_json::Value;
+use std::io;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+/// What ffprobe reports about the first video stream of a file
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct VideoInfo {
+    pub width: u32,
+    pub height: u32,
+    /// Seconds
+    pub duration: f64,
+}
+
+/// Build the ffprobe invocation that prints the size and duration as JSON
+pub fn probe_command(input_file: &Path) -> Command {
+    let mut command = Command::new("ffprobe");
+    command
+        .arg("-v")
+        .arg("error")
+        .arg("-select_streams")
+        .arg("v:0")
+        .arg("-show_entries")
+        .arg("stream=width,height:format=duration")
+        .arg("-of")
+        .arg("json")
+        .arg(input_file)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    command
+}
+
+/// Read the output of `probe_command`
+pub fn parse_probe(output: &[u8]) -> Option<VideoInfo> {
+    let json: Value = serde_json::from_slice(output).ok()?;
+    let stream = json.get("streams")?.get(0)?;
+    Some(VideoInfo {
+        width: stream.get("width")?.as_u64()? as u32,
+        height: stream.get("height")?.as_u64()? as u32,
+        // ffprobe prints the duration as a string
+        duration: json.get("format")?.get("duration")?.as_str()?.parse().ok()?,
+    })
+}
+
+/// Ask ffprobe for the size and duration of `input_file`
+pub fn probe(input_file: &Path) -> Result<VideoInfo, io::Error> {
+    let output = probe_command(input_file).output().map_err(|e| match e.kind() {
+        io::ErrorKind::NotFound => io::Error::new(io::ErrorKind::NotFound, "ffprobe was not found"),
+        _ => e,
+    })?;
+    if !output.status.success() {
+        return Err(io::Error::other(format!(
+            "ffprobe failed on {} ({}): {}",
+            input_file.display(),
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    parse_probe(&output.stdout).ok_or_else(|| io::Error::other(format!("ffprobe found no video stream in {}", input_file.display())))
+}
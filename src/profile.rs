@@ -0,0 +1,79 @@
+//! `--platform`: settings tuned for the layout of a recording tool, so a
+//! Zoom call or a camera pointed at a projector works without finding the
+//! right crop, masks and threshold first.
+//!
+//! A profile is a JSON file with any of the settings below, named like the
+//! config's fields. The built-in ones are in the `profiles` directory of the
+//! repository; to add one, put a file there and list it in `BUILT_IN`.
+//! `--platform` also takes the path of a profile of one's own.
+
+use serde::Deserialize;
+use std::fs;
+use std::path::Path;
+
+use crate::config::{Config, CropArea, Mask, Metric, Prefilter};
+
+/// The profiles shipped with the extractor, by name
+const BUILT_IN: &[(&str, &str)] = &[
+    ("obs", include_str!("../profiles/obs.json")),
+    ("zoom", include_str!("../profiles/zoom.json")),
+    ("teams", include_str!("../profiles/teams.json")),
+    ("meet", include_str!("../profiles/meet.json")),
+    ("panopto", include_str!("../profiles/panopto.json")),
+    ("echo360", include_str!("../profiles/echo360.json")),
+    ("projector", include_str!("../profiles/projector.json")),
+];
+
+/// Settings a profile brings along
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Profile {
+    pub crop: Option<CropArea>,
+    pub masks: Vec<Mask>,
+    pub threshold: Option<f64>,
+    pub metric: Option<Metric>,
+    pub prefilter: Option<Prefilter>,
+    pub normalize_brightness: bool,
+    pub ignore_embedded_video: bool,
+}
+
+/// Names of the built-in profiles
+pub fn names() -> impl Iterator<Item = &'static str> {
+    BUILT_IN.iter().map(|&(name, _)| name)
+}
+
+/// The built-in profile `name`, or the profile in the file at that path
+pub fn load(name: &str) -> Result<Profile, String> {
+    let (json, source) = match BUILT_IN.iter().find(|&&(built_in, _)| built_in == name.to_ascii_lowercase()) {
+        Some(&(_, json)) => (json.to_string(), format!("built-in profile {}", name)),
+        None if Path::new(name).is_file() => {
+            (fs::read_to_string(name).map_err(|e| format!("{}: {}", name, e))?, name.to_string())
+        }
+        None => {
+            return Err(format!(
+                "{:?} is neither a profile ({}) nor a profile file",
+                name,
+                names().collect::<Vec<_>>().join(", ")
+            ))
+        }
+    };
+    serde_json::from_str(&json).map_err(|e| format!("{}: {}", source, e))
+}
+
+impl Profile {
+    /// Fill in the settings of `config` left at their defaults; masks are added to those it has
+    pub fn apply(&self, config: &mut Config) {
+        let defaults = Config::new(&config.input_file);
+        config.crop = config.crop.or(self.crop);
+        config.masks.extend_from_slice(&self.masks);
+        if let Some(threshold) = self.threshold.filter(|_| config.threshold == defaults.threshold) {
+            config.threshold = threshold;
+        }
+        if let Some(metric) = self.metric.filter(|_| config.metric == defaults.metric) {
+            config.metric = metric;
+        }
+        config.prefilter = config.prefilter.or(self.prefilter);
+        config.normalize_brightness |= self.normalize_brightness;
+        config.ignore_embedded_video |= self.ignore_embedded_video;
+    }
+}
@@ -0,0 +1,48 @@
+use serde::Serialize;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// Pipeline stage a progress report refers to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Stage {
+    /// Aligning the camera recording with the screen recording
+    Syncing,
+    /// ffmpeg is writing frames to disk
+    Extracting,
+    /// Extracted frames are being compared and deduplicated
+    Comparing,
+}
+
+/// A snapshot of how far a run has come
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Progress {
+    pub stage: Stage,
+    /// Frames handled so far in this stage
+    pub done: usize,
+    /// Frames this stage will handle, when known up front
+    pub total: Option<usize>,
+}
+
+/// Lets another thread ask a running extraction to stop.
+///
+/// Clones share the same flag, so hand one to the pipeline and keep one to cancel with.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Request cancellation; the pipeline stops at its next check
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+}
@@ -0,0 +1,296 @@
+//! Python bindings, built as the `video_slide_extractor` extension module.
+//!
+//! ```python
+//! from video_slide_extractor import extract_slides
+//! slides = extract_slides("talk.mp4", output_dir="slides", progress=print)
+//! ```
+
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use pyo3::prelude::*;
+
+use crate::config::{Config, SyncMode};
+use crate::error::Error;
+use crate::manifest::{ChangeKind, SlideKind};
+use crate::pipeline::run_with;
+use crate::profile;
+use crate::progress::{CancellationToken, Progress, Stage};
+
+/// One kept slide
+#[pyclass(frozen, get_all, module = "video_slide_extractor")]
+pub struct Slide {
+    /// Position of the frame among all sampled frames, starting at 1
+    index: usize,
+    /// Path of the slide image
+    path: String,
+    /// Derived from what the slide shows, the same for the same slide in every run
+    id: Option<String>,
+    /// Seconds into the screen recording
+    timestamp: f64,
+    /// Seconds into the camera recording, if one was given
+    camera_timestamp: Option<f64>,
+    /// How much to trust the detection, 0 to 1
+    confidence: Option<f64>,
+    /// Every `(start, end)` in seconds the slide was on screen
+    shown: Vec<(f64, f64)>,
+    /// Contents of the QR codes on the slide, with `find_links`
+    qr_codes: Vec<String>,
+    /// Web addresses printed on the slide, with `find_links` and `ocr`
+    urls: Vec<String>,
+    /// How much changed since the slide before: "full" for a new slide, "partial" for an animation step
+    change: Option<String>,
+    /// What the slide shows: "code", "diagram", "photo", "text" or "plain"
+    kind: Option<String>,
+    /// Text read off the slide, with `ocr`
+    text: Option<String>,
+    /// Language of `text` as an ISO 639-1 code, with `ocr`
+    language: Option<String>,
+    /// Who spoke longest while the slide was up, with `diarize`
+    speaker: Option<String>,
+    /// Short title of the slide, with `summarize`
+    title: Option<String>,
+    /// What the slide says in a sentence or two, with `summarize`
+    summary: Option<String>,
+}
+
+#[pymethods]
+impl Slide {
+    fn __repr__(&self) -> String {
+        format!("Slide(index={}, path={:?}, timestamp={:?})", self.index, self.path, self.timestamp)
+    }
+}
+
+impl From<Error> for PyErr {
+    fn from(e: Error) -> Self {
+        use pyo3::exceptions::{PyFileNotFoundError, PyInterruptedError, PyOSError, PyRuntimeError, PyTimeoutError};
+        match e {
+            Error::Io(e) => e.into(),
+            Error::Cancelled => PyInterruptedError::new_err(e.to_string()),
+            Error::FfmpegNotFound | Error::TesseractNotFound => PyFileNotFoundError::new_err(e.to_string()),
+            Error::UnreadableInput { .. } | Error::InsufficientSpace { .. } | Error::OutputLocked { .. } => PyOSError::new_err(e.to_string()),
+            Error::FfmpegStalled { .. } => PyTimeoutError::new_err(e.to_string()),
+            Error::FfmpegFailed { .. }
+            | Error::BadFrame { .. }
+            | Error::LimitExceeded { .. }
+            | Error::BelowTarget { .. }
+            | Error::NoSlides { .. }
+            | Error::BatchFailed { .. }
+            | Error::OcrFailed { .. } => {
+                PyRuntimeError::new_err(e.to_string())
+            }
+        }
+    }
+}
+
+fn stage_name(stage: Stage) -> &'static str {
+    match stage {
+        Stage::Syncing => "syncing",
+        Stage::Extracting => "extracting",
+        Stage::Comparing => "comparing",
+    }
+}
+
+/// Extract the unique slides from a video.
+///
+/// `progress`, if given, is called as `progress(stage, done, total)` while the
+/// video is processed; raising from it (or pressing Ctrl-C) stops the run.
+#[pyfunction]
+#[pyo3(signature = (
+    path,
+    *,
+    output_dir = None,
+    fps = None,
+    threshold = None,
+    low_threshold = None,
+    metric = None,
+    camera = None,
+    sync = None,
+    camera_offset = None,
+    compare_stride = None,
+    gpu = false,
+    normalize_brightness = false,
+    platform = None,
+    ignore_embedded_video = false,
+    ignore_live_footer = false,
+    motion_streak = None,
+    whiteboard = false,
+    ink_pause = None,
+    stitch_scrolls = false,
+    ignore_zoom = false,
+    settle_frames = None,
+    min_confidence = None,
+    merge_revisits = false,
+    find_links = false,
+    ocr = false,
+    ocr_by_language = false,
+    ocr_engine = None,
+    summarize = None,
+    trim_idle = None,
+    snap_to_pauses = None,
+    diarize = None,
+    progress = None,
+))]
+#[allow(clippy::too_many_arguments)]
+fn extract_slides(
+    py: Python<'_>,
+    path: PathBuf,
+    output_dir: Option<PathBuf>,
+    fps: Option<u32>,
+    threshold: Option<f64>,
+    low_threshold: Option<f64>,
+    metric: Option<String>,
+    camera: Option<PathBuf>,
+    sync: Option<String>,
+    camera_offset: Option<f64>,
+    compare_stride: Option<u32>,
+    gpu: bool,
+    normalize_brightness: bool,
+    platform: Option<String>,
+    ignore_embedded_video: bool,
+    ignore_live_footer: bool,
+    motion_streak: Option<u32>,
+    whiteboard: bool,
+    ink_pause: Option<f64>,
+    stitch_scrolls: bool,
+    ignore_zoom: bool,
+    settle_frames: Option<u32>,
+    min_confidence: Option<f64>,
+    merge_revisits: bool,
+    find_links: bool,
+    ocr: bool,
+    ocr_by_language: bool,
+    ocr_engine: Option<String>,
+    summarize: Option<String>,
+    trim_idle: Option<f64>,
+    snap_to_pauses: Option<f64>,
+    diarize: Option<String>,
+    progress: Option<PyObject>,
+) -> PyResult<Vec<Slide>> {
+    let mut config = Config::new(path);
+    if let Some(output_dir) = output_dir {
+        config.output_dir = output_dir;
+    }
+    if let Some(fps) = fps {
+        config.fps = fps;
+    }
+    if let Some(threshold) = threshold {
+        config.threshold = threshold;
+    }
+    config.low_threshold = low_threshold;
+    if let Some(metric) = metric {
+        config.metric = metric.parse().map_err(pyo3::exceptions::PyValueError::new_err)?;
+    }
+    config.camera_file = camera;
+    config.sync = match sync.as_deref() {
+        None | Some("offset") => SyncMode::Offset,
+        Some("audio") => SyncMode::Audio,
+        Some(other) => {
+            return Err(pyo3::exceptions::PyValueError::new_err(format!(
+                "sync must be \"offset\" or \"audio\", not {:?}",
+                other
+            )))
+        }
+    };
+    config.camera_offset = camera_offset.unwrap_or(0.0);
+    if let Some(compare_stride) = compare_stride {
+        config.compare_stride = compare_stride;
+    }
+    config.gpu = gpu;
+    config.normalize_brightness = normalize_brightness;
+    config.ignore_embedded_video = ignore_embedded_video;
+    config.ignore_live_footer = ignore_live_footer;
+    if let Some(motion_streak) = motion_streak {
+        config.motion_streak = motion_streak;
+    }
+    config.whiteboard = whiteboard;
+    config.ink_pause = ink_pause;
+    config.stitch_scrolls = stitch_scrolls;
+    config.ignore_zoom = ignore_zoom;
+    if let Some(settle_frames) = settle_frames {
+        config.settle_frames = settle_frames;
+    }
+    config.min_confidence = min_confidence;
+    config.merge_revisits = merge_revisits;
+    config.find_links = find_links;
+    config.ocr = ocr;
+    config.ocr_by_language = ocr_by_language;
+    if let Some(ocr_engine) = ocr_engine {
+        config.ocr_backend = ocr_engine.parse().map_err(pyo3::exceptions::PyValueError::new_err)?;
+    }
+    config.summarize = summarize;
+    config.trim_idle = trim_idle;
+    config.snap_to_pauses = snap_to_pauses;
+    if let Some(diarize) = diarize {
+        config.diarize = Some(diarize.parse().map_err(pyo3::exceptions::PyValueError::new_err)?);
+    }
+    if let Some(platform) = platform {
+        profile::load(&platform).map_err(pyo3::exceptions::PyValueError::new_err)?.apply(&mut config);
+    }
+    std::fs::create_dir_all(&config.output_dir)?;
+
+    let cancel = CancellationToken::new();
+    let callback_error: Mutex<Option<PyErr>> = Mutex::new(None);
+
+    let outcome = py.allow_threads(|| {
+        let report = |p: Progress| {
+            Python::with_gil(|py| {
+                let result = match &progress {
+                    Some(progress) => progress.call1(py, (stage_name(p.stage), p.done, p.total)).map(|_| ()),
+                    None => Ok(()),
+                };
+                if let Err(e) = result.and_then(|_| py.check_signals()) {
+                    callback_error.lock().unwrap().get_or_insert(e);
+                    cancel.cancel();
+                }
+            })
+        };
+        run_with(&config, report, &cancel)
+    });
+
+    if let Some(e) = callback_error.into_inner().unwrap() {
+        return Err(e);
+    }
+    let manifest = outcome?;
+    Ok(manifest
+        .slides
+        .into_iter()
+        .map(|slide| Slide {
+            index: slide.index,
+            path: config.output_dir.join(&slide.file).to_string_lossy().into_owned(),
+            id: slide.id,
+            timestamp: slide.timestamp,
+            camera_timestamp: slide.camera_timestamp,
+            confidence: slide.confidence.map(|confidence| confidence.score),
+            shown: slide.shown.iter().map(|interval| (interval.start, interval.end)).collect(),
+            qr_codes: slide.qr_codes,
+            urls: slide.urls,
+            change: slide.change.map(|change| match change.kind {
+                ChangeKind::Full => "full".to_string(),
+                ChangeKind::Partial => "partial".to_string(),
+            }),
+            kind: slide.kind.map(|kind| {
+                match kind {
+                    SlideKind::Code => "code",
+                    SlideKind::Diagram => "diagram",
+                    SlideKind::Photo => "photo",
+                    SlideKind::Text => "text",
+                    SlideKind::Plain => "plain",
+                }
+                .to_string()
+            }),
+            text: slide.text,
+            language: slide.language,
+            speaker: slide.speaker,
+            title: slide.title,
+            summary: slide.summary,
+        })
+        .collect())
+}
+
+#[pymodule]
+fn video_slide_extractor(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<Slide>()?;
+    m.add_function(wrap_pyfunction!(extract_slides, m)?)?;
+    Ok(())
+}
@@ -0,0 +1,239 @@
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fs;
+use std::io::{Error, ErrorKind};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::{self, JoinHandle};
+
+use crate::config::Config;
+use crate::lock::{try_lock_dir, write_atomic, DirLock};
+
+/// A job waiting for (or holding) a worker
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QueuedJob {
+    pub id: u64,
+    pub config: Config,
+}
+
+struct QueueState {
+    pending: VecDeque<QueuedJob>,
+    running: usize,
+    closed: bool,
+}
+
+struct Shared {
+    spool_dir: PathBuf,
+    /// Keeps a second process from running the same jobs
+    _lock: DirLock,
+    state: Mutex<QueueState>,
+    changed: Condvar,
+}
+
+/// Runs jobs on a fixed number of worker threads so a pile of long videos
+/// doesn't turn into a pile of ffmpeg processes fighting over the disk.
+///
+/// Every job is written to the spool directory until it has run, so jobs
+/// that were queued or running when the process died are picked up again by
+/// the next `open` on the same directory. A job that panics is logged and
+/// forgotten; `run` should turn a panic into a failure it reports itself.
+pub struct JobQueue {
+    shared: Arc<Shared>,
+    workers: Vec<JoinHandle<()>>,
+}
+
+impl JobQueue {
+    /// Open the spool directory, restoring whatever an earlier process left unfinished.
+    /// Nothing runs until `start` is called.
+    pub fn open(spool_dir: &Path) -> Result<Self, Error> {
+        let lock = try_lock_dir(spool_dir)?.ok_or_else(|| {
+            Error::new(
+                ErrorKind::WouldBlock,
+                format!("another process is using the job spool directory {}", spool_dir.display()),
+            )
+        })?;
+
+        let mut restored = Vec::new();
+        for entry in fs::read_dir(spool_dir)?.filter_map(Result::ok) {
+            let path = entry.path();
+            if path.extension().and_then(|s| s.to_str()) != Some("json") {
+                continue;
+            }
+            match fs::read(&path).map(|data| serde_json::from_slice::<QueuedJob>(&data)) {
+                Ok(Ok(job)) => restored.push(job),
+                _ => tracing::warn!("Skipping unreadable queued job {:?}", path),
+            }
+        }
+        restored.sort_by_key(|job| job.id);
+
+        Ok(JobQueue {
+            shared: Arc::new(Shared {
+                spool_dir: spool_dir.to_path_buf(),
+                _lock: lock,
+                state: Mutex::new(QueueState { pending: restored.into(), running: 0, closed: false }),
+                changed: Condvar::new(),
+            }),
+            workers: Vec::new(),
+        })
+    }
+
+    /// Jobs restored from the spool directory that have not been picked up yet
+    pub fn pending(&self) -> Vec<QueuedJob> {
+        self.shared.state.lock().unwrap().pending.iter().cloned().collect()
+    }
+
+    /// Highest job id known to the queue, so callers can keep ids unique across restarts
+    pub fn last_id(&self) -> u64 {
+        self.shared.state.lock().unwrap().pending.iter().map(|job| job.id).max().unwrap_or(0)
+    }
+
+    /// Spawn `workers` threads that each run one job at a time with `run`
+    pub fn start<F>(&mut self, workers: usize, run: F)
+    where
+        F: Fn(QueuedJob) + Send + Sync + 'static,
+    {
+        let run = Arc::new(run);
+        for _ in 0..workers.max(1) {
+            let shared = Arc::clone(&self.shared);
+            let run = Arc::clone(&run);
+            self.workers.push(thread::spawn(move || worker(&shared, &*run)));
+        }
+    }
+
+    /// Persist a job and queue it behind everything already waiting
+    pub fn submit(&self, job: QueuedJob) -> Result<(), Error> {
+        let json = serde_json::to_vec_pretty(&job).map_err(Error::other)?;
+        write_atomic(&self.shared.spool_dir.join(format!("{}.json", job.id)), json)?;
+
+        self.shared.state.lock().unwrap().pending.push_back(job);
+        self.shared.changed.notify_all();
+        Ok(())
+    }
+
+    /// Block until every queued job has run, then stop the workers
+    pub fn finish(mut self) {
+        {
+            let mut state = self.shared.state.lock().unwrap();
+            while !state.pending.is_empty() || state.running > 0 {
+                state = self.shared.changed.wait(state).unwrap();
+            }
+            state.closed = true;
+        }
+        self.shared.changed.notify_all();
+
+        for handle in self.workers.drain(..) {
+            let _ = handle.join();
+        }
+    }
+}
+
+fn worker(shared: &Shared, run: &dyn Fn(QueuedJob)) {
+    loop {
+        let job = {
+            let mut state = shared.state.lock().unwrap();
+            loop {
+                if let Some(job) = state.pending.pop_front() {
+                    state.running += 1;
+                    break job;
+                }
+                if state.closed {
+                    return;
+                }
+                state = shared.changed.wait(state).unwrap();
+            }
+        };
+
+        let id = job.id;
+        let _span = tracing::info_span!("job", id).entered();
+        // A panicking job must not take the worker (and the running count) down with it
+        if panic::catch_unwind(AssertUnwindSafe(|| run(job))).is_err() {
+            tracing::error!("Job {} panicked", id);
+        }
+
+        // Forget the job once it ran, also if it panicked as it would only panic again;
+        // the process dying mid-run requeues it
+        let _ = fs::remove_file(shared.spool_dir.join(format!("{}.json", id)));
+        shared.state.lock().unwrap().running -= 1;
+        shared.changed.notify_all();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    fn job(id: u64) -> QueuedJob {
+        QueuedJob { id, config: Config::new(format!("talk-{}.mp4", id)) }
+    }
+
+    fn spooled(spool_dir: &Path) -> usize {
+        fs::read_dir(spool_dir).unwrap().filter_map(Result::ok).filter(|entry| entry.path().extension().is_some_and(|ext| ext == "json")).count()
+    }
+
+    #[test]
+    fn runs_every_job_on_at_most_its_workers() {
+        let spool_dir = tempfile::tempdir().unwrap();
+        let mut queue = JobQueue::open(spool_dir.path()).unwrap();
+        let (running, most, ran) = (Arc::new(AtomicUsize::new(0)), Arc::new(AtomicUsize::new(0)), Arc::new(Mutex::new(Vec::new())));
+        let (worker_running, worker_most, worker_ran) = (Arc::clone(&running), Arc::clone(&most), Arc::clone(&ran));
+        queue.start(2, move |job| {
+            let now = worker_running.fetch_add(1, Ordering::SeqCst) + 1;
+            worker_most.fetch_max(now, Ordering::SeqCst);
+            thread::sleep(Duration::from_millis(20));
+            worker_ran.lock().unwrap().push(job.id);
+            worker_running.fetch_sub(1, Ordering::SeqCst);
+        });
+        for id in 1..=6 {
+            queue.submit(job(id)).unwrap();
+        }
+        queue.finish();
+
+        let mut ran = ran.lock().unwrap().clone();
+        ran.sort();
+        assert_eq!(ran, vec![1, 2, 3, 4, 5, 6]);
+        assert!(most.load(Ordering::SeqCst) <= 2);
+        assert_eq!(spooled(spool_dir.path()), 0);
+    }
+
+    #[test]
+    fn restores_jobs_that_did_not_run() {
+        let spool_dir = tempfile::tempdir().unwrap();
+        {
+            let queue = JobQueue::open(spool_dir.path()).unwrap();
+            queue.submit(job(3)).unwrap();
+            queue.submit(job(1)).unwrap();
+            // Another process can't take the queue over meanwhile
+            let taken = JobQueue::open(spool_dir.path()).err().unwrap();
+            assert_eq!(taken.kind(), ErrorKind::WouldBlock);
+        }
+        fs::write(spool_dir.path().join("2.json"), b"not a job").unwrap();
+
+        let queue = JobQueue::open(spool_dir.path()).unwrap();
+        let pending: Vec<u64> = queue.pending().iter().map(|job| job.id).collect();
+        assert_eq!(pending, vec![1, 3]);
+        assert_eq!(queue.last_id(), 3);
+        assert_eq!(queue.pending()[0].config.input_file, PathBuf::from("talk-1.mp4"));
+    }
+
+    #[test]
+    fn a_panicking_job_does_not_stop_its_worker() {
+        let spool_dir = tempfile::tempdir().unwrap();
+        let mut queue = JobQueue::open(spool_dir.path()).unwrap();
+        let ran = Arc::new(Mutex::new(Vec::new()));
+        let worker_ran = Arc::clone(&ran);
+        queue.start(1, move |job| {
+            if job.id == 1 {
+                panic!("job 1 fails");
+            }
+            worker_ran.lock().unwrap().push(job.id);
+        });
+        queue.submit(job(1)).unwrap();
+        queue.submit(job(2)).unwrap();
+        queue.finish();
+        assert_eq!(*ran.lock().unwrap(), vec![2]);
+        assert_eq!(spooled(spool_dir.path()), 0);
+    }
+}
@@ -0,0 +1,125 @@
+//! `--auto-fps`: pick the sampling rate from how often the picture changes,
+//! densely for a fast-moving screenshare demo and sparsely for a slow deck.
+//!
+//! A few evenly spaced stretches of the video are sampled at the highest
+//! rate considered, and the shortest time a slide stayed up in them decides
+//! the rate: enough to see every slide twice. When even the shortest one
+//! stayed up for half a minute, the video is sampled as with `--adaptive`,
+//! one frame every few seconds and at the full rate only around changes.
+//! Stretches that were not probed may of course move faster.
+
+use std::fs;
+
+use crate::config::Config;
+use crate::dedup::Deduplicator;
+use crate::error::Error;
+use crate::extract::sample_window;
+use crate::probe::probe;
+use crate::runlog::RunLog;
+use crate::source::{DirectorySource, FrameSource};
+use crate::workspace::Workspace;
+
+/// Stretches of the video probed
+const PROBE_WINDOWS: usize = 5;
+/// Seconds in each
+const PROBE_SECONDS: f64 = 20.0;
+/// Rate the stretches are sampled at, the highest chosen
+const PROBE_FPS: u32 = 4;
+/// Frames every slide should be seen in
+const FRAMES_PER_SLIDE: f64 = 2.0;
+/// Seconds the shortest slide must stay up for the video to be sampled sparsely
+const SPARSE_HOLD: f64 = 30.0;
+/// Most seconds between two sparse samples
+const MAX_SPARSE_STEP: f64 = 10.0;
+
+/// The sampling rate chosen for a video and what it was chosen from
+#[derive(Debug, Clone, PartialEq)]
+pub struct SamplingRate {
+    pub fps: u32,
+    /// Seconds between sparse samples, `None` to sample at `fps` throughout
+    pub adaptive: Option<f64>,
+    /// Seconds of video probed
+    pub probed: f64,
+    /// Slide changes seen in them
+    pub changes: usize,
+    /// Seconds the shortest slide wholly inside a probed stretch stayed up, `None` if none was
+    pub shortest: Option<f64>,
+}
+
+/// Sample a few stretches of `config.input_file` and choose the rate to sample all of it at
+pub fn measure(config: &Config, log: &RunLog) -> Result<SamplingRate, Error> {
+    let mut duration = probe(&config.input_file)?.duration;
+    if let Some(limit) = config.duration {
+        duration = duration.min(limit);
+    }
+    let windows: Vec<(f64, f64)> = if duration <= PROBE_WINDOWS as f64 * PROBE_SECONDS {
+        vec![(0.0, duration)]
+    } else {
+        let spacing = duration / PROBE_WINDOWS as f64;
+        (0..PROBE_WINDOWS).map(|i| ((i as f64 + 0.5) * spacing - PROBE_SECONDS / 2.0, PROBE_SECONDS)).collect()
+    };
+
+    let workspace = Workspace::create(config)?;
+    let (mut changes, mut shortest) = (0, None::<f64>);
+    for (i, &(start, length)) in windows.iter().enumerate() {
+        let dir = workspace.path().join(format!("probe_{}", i));
+        fs::create_dir(&dir)?;
+        sample_window(config, &dir, start, Some(length), &PROBE_FPS.to_string())?;
+
+        let mut source = DirectorySource::open(&dir)?;
+        let mut dedup = Deduplicator::new(config, log);
+        let (mut position, mut last_change) = (0, None);
+        loop {
+            let frame = match source.next_frame() {
+                Ok(Some(frame)) => frame,
+                Ok(None) => break,
+                Err(Error::BadFrame { .. }) => continue,
+                Err(e) => return Err(e),
+            };
+            if dedup.observe(frame.image).is_kept() && position > 0 {
+                changes += 1;
+                if let Some(last) = last_change {
+                    let hold = (position - last) as f64 / PROBE_FPS as f64;
+                    shortest = Some(shortest.map_or(hold, |shortest| shortest.min(hold)));
+                }
+                last_change = Some(position);
+            }
+            position += 1;
+        }
+    }
+
+    let probed: f64 = windows.iter().map(|&(_, length)| length).sum();
+    // Without a slide wholly inside a stretch, no two changes were closer than a stretch is long
+    let hold = shortest.unwrap_or(windows[0].1);
+    let fps = ((FRAMES_PER_SLIDE / hold).ceil() as u32).clamp(1, PROBE_FPS);
+    let adaptive = (hold >= SPARSE_HOLD).then(|| (hold / 3.0).min(MAX_SPARSE_STEP).floor());
+    Ok(SamplingRate { fps, adaptive, probed, changes, shortest })
+}
+
+/// `config` with the rate chosen by `measure` if `config.auto_fps` is set, unchanged otherwise
+pub fn resolve(config: &Config, log: &RunLog) -> Result<Config, Error> {
+    let mut config = config.clone();
+    if !config.auto_fps {
+        return Ok(config);
+    }
+    let rate = measure(&config, log)?;
+    config.fps = rate.fps;
+    // Sparse sampling reads the frames from disk in one piece, and a frame cache holds every frame
+    if config.adaptive.is_none() && !config.in_memory && config.segments <= 1 && config.frame_cache.is_none() {
+        config.adaptive = rate.adaptive;
+    }
+    let shortest = rate.shortest.map_or("no slide wholly inside them".to_string(), |shortest| {
+        format!("the shortest slide up for {:.1}s", shortest)
+    });
+    match config.adaptive {
+        Some(step) => log.info(format_args!(
+            "Sampling one frame every {}s and {} fps around changes: {} change(s) in {:.0}s probed, {}.",
+            step, config.fps, rate.changes, rate.probed, shortest
+        )),
+        None => log.info(format_args!(
+            "Sampling at {} fps: {} change(s) in {:.0}s probed, {}.",
+            config.fps, rate.changes, rate.probed, shortest
+        )),
+    }
+    Ok(config)
+}
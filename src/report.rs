@@ -0,0 +1,109 @@
+//! `--report`: an HTML page in the output directory showing the decision on
+//! every sampled frame, to find out why a slide was missed or kept twice
+//! without digging through the log.
+//!
+//! Each frame is a small thumbnail on a timeline, framed green where it was
+//! kept, orange where it differed enough but was not kept (still settling, or
+//! a slide shown again) and grey where it was dropped as the same slide.
+//! Hovering a frame shows its time, difference and threshold; clicking it
+//! opens the slide that was on screen at the time. The thumbnails are saved
+//! in `report/` as the frames are compared.
+
+use image::imageops::FilterType;
+use image::{DynamicImage, GenericImageView};
+use std::fmt::Write as _;
+use std::io;
+use std::path::Path;
+
+use crate::error::Error;
+use crate::export::escape;
+use crate::links::clock;
+use crate::lock::write_atomic;
+use crate::manifest::{FrameScore, Manifest, Slide};
+
+/// The page, in the output directory
+pub const REPORT_FILE: &str = "report.html";
+/// Subdirectory of the output directory the thumbnails are saved in
+pub const THUMBNAILS_DIR: &str = "report";
+/// Width of the thumbnails in pixels
+const THUMBNAIL_WIDTH: u32 = 96;
+
+/// Name of the thumbnail of the frame `file`
+fn thumbnail_name(file: &str) -> String {
+    let stem = Path::new(file).file_stem().unwrap_or_default().to_string_lossy();
+    format!("{}/{}.jpg", THUMBNAILS_DIR, stem)
+}
+
+/// Save a thumbnail of `image`, the frame `file`, for the report in `output_dir`
+pub fn save_thumbnail(output_dir: &Path, file: &str, image: &DynamicImage) -> Result<(), Error> {
+    let (width, height) = image.dimensions();
+    let height = ((height as f64 * THUMBNAIL_WIDTH as f64 / width.max(1) as f64).round() as u32).max(1);
+    let thumbnail = image.resize_exact(THUMBNAIL_WIDTH, height, FilterType::Triangle).to_rgb8();
+    let path = output_dir.join(thumbnail_name(file));
+    std::fs::create_dir_all(output_dir.join(THUMBNAILS_DIR))?;
+    thumbnail.save(&path).map_err(|e| io::Error::other(format!("Error saving {}: {}", path.display(), e)))?;
+    Ok(())
+}
+
+/// The slide of `manifest` on screen at `timestamp`
+fn on_screen(manifest: &Manifest, timestamp: f64) -> Option<&Slide> {
+    let shown = manifest.slides.iter().find(|slide| slide.shown.iter().any(|shown| (shown.start..shown.end).contains(&timestamp)));
+    shown.or_else(|| manifest.slides.iter().rev().find(|slide| slide.timestamp <= timestamp))
+}
+
+/// Why `frame` was kept or dropped, as its colour and in words
+fn verdict(frame: &FrameScore) -> (&'static str, String) {
+    let compared = match frame.difference {
+        Some(difference) => format!("difference {:.4}, threshold {}", difference, frame.threshold),
+        None => "first frame".to_string(),
+    };
+    match frame.difference {
+        _ if frame.kept => ("kept", format!("kept: {}", compared)),
+        Some(difference) if difference > frame.threshold => ("changed", format!("changed, not kept: {}", compared)),
+        _ => match &frame.same_as {
+            Some(same_as) => ("dropped", format!("dropped as the same slide as {}: {}", same_as, compared)),
+            None => ("dropped", format!("dropped: {}", compared)),
+        },
+    }
+}
+
+/// Write the report of the run in `output_dir` that `manifest` describes
+pub fn write(output_dir: &Path, manifest: &Manifest) -> Result<(), Error> {
+    let title = manifest.sources.first().map_or("Slides", |source| source.path.as_str());
+    let kept = manifest.frames.iter().filter(|frame| frame.kept).count();
+
+    let mut html = String::new();
+    let _ = writeln!(html, "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>Report: {}</title>", escape(title));
+    html.push_str(
+        "<style>body{font-family:sans-serif}main{display:flex;flex-wrap:wrap;gap:4px}\
+         a{display:block;border:3px solid #ccc}a.kept{border-color:#2a2}a.changed{border-color:#e80}\
+         img{display:block;width:96px}</style>\n",
+    );
+    let _ = writeln!(html, "</head>\n<body>\n<h1>{}</h1>", escape(title));
+    let _ = writeln!(
+        html,
+        "<p>{} frame(s) compared, {} kept and {} slide(s) in the end. \
+         Green frames were kept, orange ones differed but were not kept, grey ones were the same slide.</p>\n<main>",
+        manifest.frames.len(),
+        kept,
+        manifest.slides.len()
+    );
+    for frame in &manifest.frames {
+        let (class, why) = verdict(frame);
+        let href = on_screen(manifest, frame.timestamp).map_or_else(|| thumbnail_name(&frame.file), |slide| slide.file.clone());
+        let _ = writeln!(
+            html,
+            "<a class=\"{}\" href=\"{}\" title=\"{} at {}: {}\"><img src=\"{}\" alt=\"{}\" loading=\"lazy\"></a>",
+            class,
+            escape(&href),
+            escape(&frame.file),
+            clock(frame.timestamp),
+            why,
+            escape(&thumbnail_name(&frame.file)),
+            escape(&frame.file)
+        );
+    }
+    html.push_str("</main>\n</body>\n</html>\n");
+    write_atomic(&output_dir.join(REPORT_FILE), html)?;
+    Ok(())
+}
@@ -0,0 +1,150 @@
+//! `reprocess`: sample and deduplicate one stretch of an already processed
+//! video again, with other settings, and merge the result into the run's
+//! output directory, to fix a stretch where the threshold misbehaved without
+//! redoing the whole video.
+//!
+//! The slides that appeared within the window are replaced by the ones found
+//! now, named after their frame in the whole video. A slide found at either
+//! end of the window with the same ID as its neighbour outside it is that
+//! slide carried over, and is not added twice. Sidecars, `links.md` and
+//! existing exports are written again; the manifest loses its fingerprint, as
+//! no single set of settings produced it any more.
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::config::Config;
+use crate::error::Error;
+use crate::extract::{frame_name, sample_window};
+use crate::finalize::{rewrite, sidecar_format};
+use crate::identity;
+use crate::lock::lock_output;
+use crate::manifest::{Manifest, SourceRole};
+use crate::metadata;
+use crate::pipeline::run_with_source;
+use crate::progress::CancellationToken;
+use crate::sidecar;
+use crate::source::DirectorySource;
+use crate::workspace::Workspace;
+
+/// What `reprocess` changed in a slides directory
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Reprocessed {
+    /// Slides of the window that were replaced
+    pub removed: usize,
+    /// Slides found in the window now
+    pub added: usize,
+    pub slides: usize,
+}
+
+/// Process seconds `from` to `to` of the video the run in `slides_dir` was
+/// made from again with `template`'s settings and merge the slides found
+/// into it. The video is the one in the manifest unless `template` names one.
+pub fn reprocess(slides_dir: &Path, template: &Config, from: f64, to: f64) -> Result<Reprocessed, Error> {
+    if to <= from {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("The window ends ({}s) before it starts ({}s)", to, from)).into());
+    }
+    let _lock = lock_output(slides_dir)?;
+    let mut manifest = Manifest::read(slides_dir)?;
+    // Runs from before slides had IDs
+    identity::assign_ids(slides_dir, &mut manifest)?;
+
+    let mut config = template.clone();
+    if config.input_file.as_os_str().is_empty() {
+        let screen = manifest.sources.iter().find(|source| source.role == SourceRole::Screen);
+        config.input_file = screen.ok_or_else(|| io::Error::other("The manifest names no screen recording"))?.path.clone().into();
+    }
+    let workspace = Workspace::create(&config)?;
+    let window_dir = workspace.path().join("window");
+    fs::create_dir(&window_dir)?;
+    sample_window(&config, workspace.path(), from, Some(to - from), &config.fps.to_string())?;
+
+    // Everything is written into the slides directory below, once the window's slides are merged
+    config.output_dir = window_dir;
+    config.force = true;
+    config.output = None;
+    config.archive = None;
+    config.sidecars = None;
+    config.save_zooms = false;
+    config.skip_metadata = true;
+    config.name_template = None;
+    config.trim_idle = None;
+    config.split_decks = false;
+    let mut window = run_with_source(&config, &mut DirectorySource::open(workspace.path())?, |_| {}, &CancellationToken::new())?;
+
+    let sidecars = manifest.slides.iter().find_map(|slide| sidecar_format(slides_dir, &slide.file));
+    let inside = |timestamp: f64| timestamp >= from && timestamp < to;
+    let before = manifest.slides.iter().rfind(|slide| slide.timestamp < from).and_then(|slide| slide.id.clone());
+    let after = manifest.slides.iter().find(|slide| slide.timestamp >= to).and_then(|slide| slide.id.clone());
+
+    let mut removed = 0;
+    for slide in manifest.slides.iter().filter(|slide| inside(slide.timestamp)) {
+        removed += 1;
+        fs::remove_file(slides_dir.join(&slide.file))?;
+        if let Some(format) = sidecars {
+            let path = slides_dir.join(sidecar::file_name(&slide.file, format));
+            if path.is_file() {
+                fs::remove_file(path)?;
+            }
+        }
+    }
+    manifest.slides.retain(|slide| !inside(slide.timestamp));
+
+    if window.slides.first().is_some_and(|slide| slide.id.is_some() && slide.id == before) {
+        window.slides.remove(0);
+    }
+    if window.slides.last().is_some_and(|slide| slide.id.is_some() && slide.id == after) {
+        window.slides.pop();
+    }
+
+    // The name ffmpeg gives the frame `timestamp` seconds into the window when sampling the whole video
+    let first_frame = (from * config.fps as f64).round() as usize;
+    let global_name = |timestamp: f64| frame_name(first_frame + (timestamp * config.fps as f64).round() as usize + 1);
+    for slide in &mut window.slides {
+        let mut name = global_name(slide.timestamp);
+        // Only taken if the run sampled at another rate
+        for copy in 2.. {
+            if !slides_dir.join(&name).exists() {
+                break;
+            }
+            name = format!("{}_{}.png", global_name(slide.timestamp).trim_end_matches(".png"), copy);
+        }
+        fs::rename(config.output_dir.join(&slide.file), slides_dir.join(&name))?;
+        slide.file = name;
+        slide.timestamp += from;
+        slide.camera_timestamp = slide.camera_timestamp.map(|timestamp| timestamp + from);
+        for interval in &mut slide.shown {
+            interval.start += from;
+            interval.end += from;
+        }
+    }
+    let added = window.slides.len();
+
+    let tag_config = Config { output_dir: slides_dir.to_path_buf(), skip_metadata: template.skip_metadata, ..config.clone() };
+    metadata::tag_slides(&tag_config, &window)?;
+
+    manifest.slides.append(&mut window.slides);
+    manifest.slides.sort_by(|a, b| a.timestamp.total_cmp(&b.timestamp));
+    for (index, slide) in manifest.slides.iter_mut().enumerate() {
+        slide.index = index + 1;
+    }
+    manifest.frames.retain(|frame| !inside(frame.timestamp));
+    manifest.frames.extend(window.frames.into_iter().map(|mut frame| {
+        frame.file = global_name(frame.timestamp);
+        frame.timestamp += from;
+        frame
+    }));
+    manifest.frames.sort_by(|a, b| a.timestamp.total_cmp(&b.timestamp));
+    manifest.resolution_changes.retain(|change| !inside(change.timestamp));
+    manifest.resolution_changes.extend(window.resolution_changes.into_iter().map(|mut change| {
+        change.file = global_name(change.timestamp);
+        change.timestamp += from;
+        change
+    }));
+    manifest.resolution_changes.sort_by(|a, b| a.timestamp.total_cmp(&b.timestamp));
+    manifest.fingerprint = None;
+
+    rewrite(slides_dir, &manifest, sidecars)?;
+    Ok(Reprocessed { removed, added, slides: manifest.slides.len() })
+}
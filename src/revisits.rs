@@ -0,0 +1,79 @@
+//! `--merge-revisits`: recognise the presenter going back to a slide shown
+//! earlier, so it is kept once with every interval it was on screen instead
+//! of once per visit.
+
+use image::imageops::FilterType;
+use image::DynamicImage;
+
+use crate::compare::Comparer;
+use crate::config::Config;
+use crate::manifest::{Interval, Manifest};
+use crate::runlog::RunLog;
+
+/// Size the kept slides are remembered at; small enough to compare against all of them
+const THUMBNAIL_SIZE: (u32, u32) = (320, 180);
+
+/// Remembers every kept slide to tell whether a new one was shown before
+pub struct Revisits {
+    threshold: f64,
+    comparer: Comparer,
+    thumbnails: Vec<DynamicImage>,
+    /// Slide of the latest appearance, which a new one cannot be a revisit of
+    current: Option<usize>,
+}
+
+impl Revisits {
+    /// A tracker if `config.merge_revisits` asks for one
+    pub fn new(config: &Config, log: &RunLog) -> Option<Self> {
+        if !config.merge_revisits {
+            return None;
+        }
+        // Thumbnails are too small to be worth a trip to the GPU
+        let mut thumbnail_config = config.clone();
+        thumbnail_config.gpu = false;
+        thumbnail_config.compare_stride = 1;
+        Some(Revisits {
+            threshold: config.revisit_threshold.unwrap_or(config.threshold),
+            comparer: Comparer::new(&thumbnail_config, log),
+            thumbnails: Vec::new(),
+            current: None,
+        })
+    }
+
+    /// The earlier slide `image` shows again, by its position among the kept
+    /// slides; `None` if it is new, in which case it is remembered as the next one
+    pub fn recognize(&mut self, image: &DynamicImage) -> Option<usize> {
+        let (width, height) = THUMBNAIL_SIZE;
+        let thumbnail = DynamicImage::ImageLuma8(image.resize_exact(width, height, FilterType::Triangle).to_luma8());
+
+        // The most recent visit is the likeliest one to be returned to
+        let earlier = (0..self.thumbnails.len())
+            .rev()
+            .filter(|&slide| Some(slide) != self.current)
+            .find(|&slide| self.comparer.difference_ratio(&self.thumbnails[slide], &thumbnail) <= self.threshold);
+
+        match earlier {
+            Some(slide) => self.current = Some(slide),
+            None => {
+                self.current = Some(self.thumbnails.len());
+                self.thumbnails.push(thumbnail);
+            }
+        }
+        earlier
+    }
+}
+
+/// Fill in when each slide of `manifest` was on screen from its appearances,
+/// given as the position each appeared at with its position among the slides;
+/// an appearance lasts until the next one or the end of the video
+pub fn record_intervals(config: &Config, manifest: &mut Manifest, appearances: &[(usize, usize)]) {
+    let fps = config.fps as f64;
+    let end = manifest.frames.last().map_or(0.0, |frame| frame.timestamp + 1.0 / fps);
+    for (i, &(start, slide)) in appearances.iter().enumerate() {
+        let Some(slide) = manifest.slides.get_mut(slide) else {
+            continue;
+        };
+        let next = appearances.get(i + 1).map_or(end, |&(next, _)| next as f64 / fps);
+        slide.shown.push(Interval { start: start as f64 / fps, end: next.max(start as f64 / fps) });
+    }
+}
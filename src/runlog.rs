@@ -0,0 +1,46 @@
+use std::fmt::Display;
+use std::fs::{File, OpenOptions};
+use std::io::{Error, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+/// Where the messages of one run go: always to `tracing`, and additionally
+/// to a per-job log file when several runs share a process (batch and server
+/// modes), so each job's log can be read on its own.
+#[derive(Debug, Clone, Default)]
+pub struct RunLog {
+    file: Option<Arc<Mutex<File>>>,
+}
+
+impl RunLog {
+    /// Log to `path` if given, appending to what earlier attempts wrote
+    pub fn open(path: Option<&Path>) -> Result<Self, Error> {
+        let file = match path {
+            Some(path) => Some(Arc::new(Mutex::new(OpenOptions::new().create(true).append(true).open(path)?))),
+            None => None,
+        };
+        Ok(RunLog { file })
+    }
+
+    /// Per-frame detail, only shown with `-v`
+    pub fn debug(&self, message: impl Display) {
+        tracing::debug!("{}", message);
+        self.write(message);
+    }
+
+    pub fn info(&self, message: impl Display) {
+        tracing::info!("{}", message);
+        self.write(message);
+    }
+
+    pub fn warn(&self, message: impl Display) {
+        tracing::warn!("{}", message);
+        self.write(message);
+    }
+
+    fn write(&self, message: impl Display) {
+        if let Some(file) = &self.file {
+            let _ = writeln!(file.lock().unwrap(), "{}", message);
+        }
+    }
+}
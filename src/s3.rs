@@ -0,0 +1,206 @@
+//! Minimal S3 `PutObject` client signed with AWS Signature Version 4.
+//!
+//! Credentials come from `AWS_ACCESS_KEY_ID`, `AWS_SECRET_ACCESS_KEY` and the
+//! optional `AWS_SESSION_TOKEN`; the region from `AWS_REGION` (or
+//! `AWS_DEFAULT_REGION`, falling back to `us-east-1`). Setting
+//! `AWS_ENDPOINT_URL` targets an S3-compatible store such as MinIO, which is
+//! then addressed path-style.
+
+use hmac::{Hmac, Mac};
+use sha2::{Digest, Sha256};
+use std::env;
+use std::io::Error;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Where objects get uploaded to and who signs for them
+pub struct S3Bucket {
+    bucket: String,
+    prefix: String,
+    region: String,
+    /// Scheme and host of a custom endpoint, `None` for AWS itself
+    endpoint: Option<String>,
+    access_key: String,
+    secret_key: String,
+    session_token: Option<String>,
+}
+
+impl S3Bucket {
+    /// Parse an `s3://bucket/prefix` URL, taking credentials from the environment
+    pub fn from_url(url: &str) -> Result<Self, Error> {
+        let rest = url
+            .strip_prefix("s3://")
+            .ok_or_else(|| Error::other(format!("Not an s3:// URL: {}", url)))?;
+        let (bucket, prefix) = rest.split_once('/').unwrap_or((rest, ""));
+        if bucket.is_empty() {
+            return Err(Error::other(format!("No bucket in {}", url)));
+        }
+
+        let var = |name: &str| env::var(name).ok().filter(|v| !v.is_empty());
+        let missing = |name: &str| Error::other(format!("{} must be set to upload to S3", name));
+
+        Ok(S3Bucket {
+            bucket: bucket.to_string(),
+            prefix: prefix.trim_matches('/').to_string(),
+            region: var("AWS_REGION")
+                .or_else(|| var("AWS_DEFAULT_REGION"))
+                .unwrap_or_else(|| "us-east-1".to_string()),
+            endpoint: var("AWS_ENDPOINT_URL").map(|e| e.trim_end_matches('/').to_string()),
+            access_key: var("AWS_ACCESS_KEY_ID").ok_or_else(|| missing("AWS_ACCESS_KEY_ID"))?,
+            secret_key: var("AWS_SECRET_ACCESS_KEY").ok_or_else(|| missing("AWS_SECRET_ACCESS_KEY"))?,
+            session_token: var("AWS_SESSION_TOKEN"),
+        })
+    }
+
+    /// `s3://bucket/prefix` form, for messages
+    pub fn url(&self) -> String {
+        format!("s3://{}/{}", self.bucket, self.prefix)
+    }
+
+    /// Upload `data` as the object `name` under the prefix
+    pub fn put(&self, name: &str, data: &[u8], content_type: &str) -> Result<(), Error> {
+        let key = if self.prefix.is_empty() {
+            name.to_string()
+        } else {
+            format!("{}/{}", self.prefix, name)
+        };
+
+        let (scheme, host, path) = match &self.endpoint {
+            Some(endpoint) => {
+                let (scheme, host) = endpoint.split_once("://").unwrap_or(("https", endpoint));
+                (scheme.to_string(), host.to_string(), format!("/{}/{}", self.bucket, uri_encode(&key)))
+            }
+            None => (
+                "https".to_string(),
+                format!("{}.s3.{}.amazonaws.com", self.bucket, self.region),
+                format!("/{}", uri_encode(&key)),
+            ),
+        };
+
+        let (amz_date, date) = timestamps(SystemTime::now());
+        let payload_hash = hex(&Sha256::digest(data));
+
+        let mut headers = vec![
+            ("host", host.clone()),
+            ("x-amz-content-sha256", payload_hash.clone()),
+            ("x-amz-date", amz_date.clone()),
+        ];
+        if let Some(token) = &self.session_token {
+            headers.push(("x-amz-security-token", token.clone()));
+        }
+
+        let canonical_headers: String = headers.iter().map(|(k, v)| format!("{}:{}\n", k, v.trim())).collect();
+        let signed_headers = headers.iter().map(|(k, _)| *k).collect::<Vec<_>>().join(";");
+        let canonical_request = format!(
+            "PUT\n{}\n\n{}\n{}\n{}",
+            path, canonical_headers, signed_headers, payload_hash
+        );
+
+        let scope = format!("{}/{}/s3/aws4_request", date, self.region);
+        let string_to_sign = format!(
+            "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+            amz_date,
+            scope,
+            hex(&Sha256::digest(canonical_request.as_bytes()))
+        );
+
+        let mut key = hmac(format!("AWS4{}", self.secret_key).as_bytes(), date.as_bytes());
+        for part in [self.region.as_str(), "s3", "aws4_request"] {
+            key = hmac(&key, part.as_bytes());
+        }
+        let signature = hex(&hmac(&key, string_to_sign.as_bytes()));
+
+        let authorization = format!(
+            "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+            self.access_key, scope, signed_headers, signature
+        );
+
+        let mut request = ureq::put(&format!("{}://{}{}", scheme, host, path))
+            .set("Authorization", &authorization)
+            .set("Content-Type", content_type);
+        for (name, value) in &headers {
+            if *name != "host" {
+                request = request.set(name, value);
+            }
+        }
+
+        match request.send_bytes(data) {
+            Ok(_) => Ok(()),
+            Err(ureq::Error::Status(code, response)) => Err(Error::other(format!(
+                "Uploading {} to {} failed with HTTP {}: {}",
+                name,
+                self.url(),
+                code,
+                response.into_string().unwrap_or_default()
+            ))),
+            Err(e) => Err(Error::other(format!("Uploading {} to {} failed: {}", name, self.url(), e))),
+        }
+    }
+}
+
+fn hmac(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut mac = HmacSha256::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(data);
+    mac.finalize().into_bytes().to_vec()
+}
+
+pub(crate) fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Percent-encode an object key as SigV4 wants it, keeping the slashes
+fn uri_encode(key: &str) -> String {
+    let mut encoded = String::new();
+    for byte in key.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'/' => encoded.push(byte as char),
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+/// `YYYYMMDD'T'HHMMSS'Z'` and `YYYYMMDD` in UTC
+fn timestamps(now: SystemTime) -> (String, String) {
+    let secs = now.duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+    let (days, rem) = ((secs / 86400) as i64, secs % 86400);
+
+    // Civil date from days since the epoch (Howard Hinnant's algorithm)
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    let date = format!("{:04}{:02}{:02}", year, month, day);
+    let time = format!("{:02}{:02}{:02}", rem / 3600, rem % 3600 / 60, rem % 60);
+    (format!("{}T{}Z", date, time), date)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn timestamps_are_utc() {
+        let (stamp, date) = timestamps(UNIX_EPOCH + Duration::from_secs(1_700_000_000));
+        assert_eq!(stamp, "20231114T221320Z");
+        assert_eq!(date, "20231114");
+        // A leap day, and the epoch itself
+        assert_eq!(timestamps(UNIX_EPOCH + Duration::from_secs(951_782_400)).1, "20000229");
+        assert_eq!(timestamps(UNIX_EPOCH).0, "19700101T000000Z");
+    }
+
+    #[test]
+    fn uri_encode_keeps_slashes_and_unreserved() {
+        assert_eq!(uri_encode("talks/slide_001.png"), "talks/slide_001.png");
+        assert_eq!(uri_encode("a b+c~d"), "a%20b%2Bc~d");
+        assert_eq!(uri_encode("é"), "%C3%A9");
+    }
+}
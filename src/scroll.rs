@@ -0,0 +1,209 @@
+//! `--stitch-scrolls`: a document or editor scrolled on screen stays one
+//! slide, extended with the lines scrolled into view, instead of every
+//! scrolled frame becoming a slide of its own.
+//!
+//! A frame that differs from the one before it is checked for a vertical
+//! shift of the picture: rows of both frames are summed up in a few blocks
+//! across, the shift that lines most of them up is found by trying each, and
+//! the frames compared again with it applied. Bars at the top and bottom that
+//! stay put while the rest scrolls, like an editor's tab bar and status line,
+//! are left out and framed around the stitched page. The whole width has to
+//! scroll otherwise, so a file tree beside the code stops it being recognised.
+
+use image::{DynamicImage, GenericImageView, GrayImage, RgbImage};
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use crate::compare::Comparer;
+use crate::dedup::Decision;
+use crate::error::Error;
+
+/// Blocks each row is summed up in
+const BLOCKS: u32 = 16;
+/// Difference in mean brightness (0 to 255) within which two blocks count as lined up
+const TOLERANCE: f32 = 2.0;
+/// Share of the blocks that must line up for a shift to be checked
+const MIN_MATCH: f64 = 0.9;
+/// Share of the page that must still be on screen after a scroll
+const MIN_OVERLAP: f64 = 0.25;
+
+/// How a frame moved against the one before it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Scroll {
+    /// Rows the page moved up by, negative when it moved down
+    pub rows: i32,
+    /// Rows at the top and bottom of the frame that stayed put
+    pub top: u32,
+    pub bottom: u32,
+}
+
+/// Mean brightness of each block of each row
+fn profile(image: &GrayImage) -> Vec<[f32; BLOCKS as usize]> {
+    let (width, height) = image.dimensions();
+    let block_width = width.div_ceil(BLOCKS);
+    (0..height)
+        .map(|y| {
+            let mut row = [0.0; BLOCKS as usize];
+            for (block, mean) in row.iter_mut().enumerate() {
+                let x0 = block as u32 * block_width;
+                let x1 = (x0 + block_width).min(width);
+                let sum: u32 = (x0..x1).map(|x| image.get_pixel(x, y)[0] as u32).sum();
+                *mean = sum as f32 / (x1 - x0).max(1) as f32;
+            }
+            row
+        })
+        .collect()
+}
+
+fn rows_match(a: &[f32; BLOCKS as usize], b: &[f32; BLOCKS as usize]) -> bool {
+    a.iter().zip(b).all(|(a, b)| (a - b).abs() <= TOLERANCE)
+}
+
+/// Whether `current` shows `previous` scrolled, judged by `comparer` at `threshold` once lined up
+pub fn find_scroll(comparer: &mut Comparer, previous: &DynamicImage, current: &DynamicImage, threshold: f64) -> Option<Scroll> {
+    if previous.dimensions() != current.dimensions() {
+        return None;
+    }
+    let (width, height) = current.dimensions();
+    let (before, after) = (profile(&previous.to_luma8()), profile(&current.to_luma8()));
+
+    let top = before.iter().zip(&after).take_while(|(a, b)| rows_match(a, b)).count() as u32;
+    let bottom = before.iter().zip(&after).rev().take_while(|(a, b)| rows_match(a, b)).count() as u32;
+    let page = height.saturating_sub(top + bottom);
+    if page == 0 || page < height / 2 {
+        return None;
+    }
+    let (before, after) = (&before[top as usize..(top + page) as usize], &after[top as usize..(top + page) as usize]);
+
+    let min_overlap = ((MIN_OVERLAP * page as f64).ceil() as u32).max(1);
+    let mut best: Option<(f64, i32)> = None;
+    for shift in 1..=page - min_overlap {
+        for rows in [shift as i32, -(shift as i32)] {
+            let overlap = (page - shift) as usize;
+            // Row `y` of the frame now shows what row `y + rows` did before
+            let (old, new) = if rows > 0 { (&before[shift as usize..], &after[..overlap]) } else { (&before[..overlap], &after[shift as usize..]) };
+            let matching: usize = old
+                .iter()
+                .zip(new)
+                .map(|(a, b)| a.iter().zip(b).filter(|(a, b)| (*a - *b).abs() <= TOLERANCE).count())
+                .sum();
+            let score = matching as f64 / (overlap * BLOCKS as usize) as f64;
+            if best.is_none_or(|(best, _)| score > best) {
+                best = Some((score, rows));
+            }
+        }
+    }
+    let (score, rows) = best?;
+    if score < MIN_MATCH {
+        return None;
+    }
+
+    let shift = rows.unsigned_abs();
+    let overlap = page - shift;
+    let (old_y, new_y) = if rows > 0 { (top + shift, top) } else { (top, top + shift) };
+    let old = previous.crop_imm(0, old_y, width, overlap);
+    let new = current.crop_imm(0, new_y, width, overlap);
+    (comparer.difference_ratio(&old, &new) <= threshold).then_some(Scroll { rows, top, bottom })
+}
+
+/// A page as scrolled so far
+pub struct Page {
+    /// The bars above and below the page, from the frame the slide appeared at
+    header: RgbImage,
+    footer: RgbImage,
+    body: RgbImage,
+    /// Row of `body` at the top of the screen
+    view: i64,
+}
+
+impl Page {
+    /// The page shown in `image`, which `scroll` is the first scroll of
+    pub fn new(image: &RgbImage, scroll: Scroll) -> Self {
+        let (width, height) = image.dimensions();
+        let page = height - scroll.top - scroll.bottom;
+        Page {
+            header: image::imageops::crop_imm(image, 0, 0, width, scroll.top).to_image(),
+            footer: image::imageops::crop_imm(image, 0, height - scroll.bottom, width, scroll.bottom).to_image(),
+            body: image::imageops::crop_imm(image, 0, scroll.top, width, page).to_image(),
+            view: 0,
+        }
+    }
+
+    /// Move the view by `scroll` and take the rows it brings into view from `image`
+    pub fn extend(&mut self, image: &RgbImage, scroll: Scroll) {
+        let width = self.body.width();
+        let page = image.height().saturating_sub(self.header.height() + self.footer.height());
+        if image.width() != width || page == 0 {
+            return;
+        }
+        let visible = image::imageops::crop_imm(image, 0, self.header.height(), width, page).to_image();
+        self.view += scroll.rows as i64;
+        let mut body = None;
+        if self.view < 0 {
+            let added = (-self.view) as u32;
+            let mut grown = RgbImage::new(width, self.body.height() + added);
+            image::imageops::replace(&mut grown, &self.body, 0, added as i64);
+            body = Some(grown);
+            self.view = 0;
+        }
+        let end = self.view as u32 + page;
+        let current = body.as_ref().unwrap_or(&self.body).height();
+        if end > current {
+            let mut grown = RgbImage::new(width, end);
+            image::imageops::replace(&mut grown, body.as_ref().unwrap_or(&self.body), 0, 0);
+            body = Some(grown);
+        }
+        if let Some(body) = body {
+            self.body = body;
+        }
+        // What is on screen now is the latest state of those rows
+        image::imageops::replace(&mut self.body, &visible, 0, self.view);
+    }
+
+    /// The whole page between the bars
+    pub fn stitched(&self) -> RgbImage {
+        let width = self.body.width();
+        let height = self.header.height() + self.body.height() + self.footer.height();
+        let mut stitched = RgbImage::new(width, height);
+        image::imageops::replace(&mut stitched, &self.header, 0, 0);
+        image::imageops::replace(&mut stitched, &self.body, 0, self.header.height() as i64);
+        image::imageops::replace(&mut stitched, &self.footer, 0, (self.header.height() + self.body.height()) as i64);
+        stitched
+    }
+}
+
+/// Builds up the pages of the slides that were scrolled and writes them over the kept frames
+#[derive(Default)]
+pub struct Stitcher {
+    /// Position and frame the current slide appeared at
+    start: Option<(usize, DynamicImage)>,
+    /// Pages by the position their slide appeared at
+    pages: HashMap<usize, Page>,
+}
+
+impl Stitcher {
+    /// Take the decision on the frame at `position`, `image`, into account
+    pub fn observe(&mut self, position: usize, decision: &Decision, image: &DynamicImage) {
+        if decision.is_kept() {
+            self.start = Some((position, image.clone()));
+            return;
+        }
+        let (Some(scroll), Some((start, first))) = (decision.scroll, self.start.as_ref()) else {
+            return;
+        };
+        let image = image.to_rgb8();
+        self.pages.entry(*start).or_insert_with(|| Page::new(&first.to_rgb8(), scroll)).extend(&image, scroll);
+    }
+
+    /// Write the stitched page of each slide in `kept` that was scrolled over its file
+    pub fn write(&mut self, kept: &[(usize, PathBuf)]) -> Result<(), Error> {
+        for (start, path) in kept {
+            if let Some(page) = self.pages.remove(start) {
+                page.stitched()
+                    .save(path)
+                    .map_err(|e| std::io::Error::other(format!("Error saving image: {}", e)))?;
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,686 @@
+//! A small REST front end so the extractor can run as a shared service.
+//!
+//! - `POST /jobs` with the video as the request body, or a JSON body
+//!   `{"url": "..."}` for an http or https URL ffmpeg fetches, queues a job.
+//!   Local paths and ffmpeg's other protocols would let any client read the
+//!   server's files or reach into its network, so they are only accepted when
+//!   the server is started with `allow_local_inputs`.
+//!   `fps`, `threshold` and `low_threshold` may be set in the query string.
+//! - `GET /jobs` and `GET /jobs/{id}` report status and progress.
+//! - `GET /jobs/{id}/log` returns the job's log, also while it runs.
+//! - `GET /jobs/{id}/manifest`, `/jobs/{id}/slides/{file}` and
+//!   `/jobs/{id}/archive` return the results of a finished job.
+//! - `DELETE /jobs/{id}` cancels a queued or running job.
+//! - `GET /metrics` exposes Prometheus metrics.
+//!
+//! Jobs run on a bounded pool of workers and survive a server restart: queued
+//! and running ones are picked up again, and completed ones are served from
+//! the record each leaves in its directory. With a webhook configured, a JSON
+//! notification is POSTed as each job completes.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, Cursor, Error};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use tiny_http::{Header, Method, Request, Response, Server};
+use url::Url;
+
+use crate::archive::write_archive;
+use crate::batch::JOB_LOG_FILE;
+use crate::config::{Config, SidecarFormat};
+use crate::manifest::Manifest;
+use crate::metrics::{self, JobOutcome};
+use crate::pipeline::run_with;
+use crate::progress::{CancellationToken, Progress};
+use crate::queue::{JobQueue, QueuedJob};
+use crate::webhook::{self, JobCompletion, Outcome};
+
+/// How the server is set up
+#[derive(Debug, Clone)]
+pub struct ServeOptions {
+    /// Address to listen on
+    pub listen: String,
+    /// Directory holding uploaded videos, job outputs and the job queue
+    pub data_dir: PathBuf,
+    /// Jobs processed at the same time
+    pub workers: usize,
+    /// Threads each job's ffmpeg may use, all cores when unset
+    pub ffmpeg_threads: Option<u32>,
+    /// Bytes each job should stay within, unlimited when unset
+    pub max_memory: Option<u64>,
+    /// URL that receives a JSON notification as each job completes
+    pub webhook: Option<String>,
+    /// Base URL clients reach this server on, for links in notifications
+    /// (defaults to `http://` plus the listen address)
+    pub public_url: Option<String>,
+    /// Accept local paths and any protocol ffmpeg knows as `{"url": ...}`, for a server only
+    /// trusted clients reach
+    pub allow_local_inputs: bool,
+}
+
+/// Lifecycle of a job
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum JobState {
+    Queued,
+    Running,
+    Finished,
+    Failed,
+    Cancelled,
+}
+
+/// What `GET /jobs/{id}` reports
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct JobStatus {
+    pub(crate) id: u64,
+    pub(crate) state: JobState,
+    #[serde(skip_deserializing)]
+    progress: Option<Progress>,
+    slides: Option<usize>,
+    error: Option<String>,
+}
+
+pub(crate) struct Job {
+    pub(crate) status: JobStatus,
+    output_dir: PathBuf,
+    log_file: Option<PathBuf>,
+    pub(crate) cancel: CancellationToken,
+    manifest: Option<Manifest>,
+    sidecars: Option<SidecarFormat>,
+}
+
+impl Job {
+    pub(crate) fn queued(id: u64, config: &Config) -> Self {
+        Job {
+            status: JobStatus { id, state: JobState::Queued, progress: None, slides: None, error: None },
+            output_dir: config.output_dir.clone(),
+            log_file: config.log_file.clone(),
+            cancel: CancellationToken::new(),
+            manifest: None,
+            sidecars: config.sidecars,
+        }
+    }
+
+    /// A job an earlier run completed, from the record it left in `job_dir`
+    fn completed(job_dir: &Path) -> Option<Self> {
+        let record: JobRecord = serde_json::from_slice(&fs::read(job_dir.join(JOB_RECORD_FILE)).ok()?).ok()?;
+        let manifest = match record.status.state {
+            JobState::Finished => Some(Manifest::read(&record.output_dir).ok()?),
+            _ => None,
+        };
+        Some(Job {
+            status: record.status,
+            output_dir: record.output_dir,
+            log_file: Some(job_dir.join(JOB_LOG_FILE)),
+            cancel: CancellationToken::new(),
+            manifest,
+            sidecars: record.sidecars,
+        })
+    }
+
+    /// Leave a record of the completed job next to its log, for `completed` after a restart
+    fn write_record(&self) -> Result<(), Error> {
+        let Some(job_dir) = self.log_file.as_deref().and_then(Path::parent) else {
+            return Ok(());
+        };
+        let record = JobRecord { status: self.status.clone(), output_dir: self.output_dir.clone(), sidecars: self.sidecars };
+        fs::write(job_dir.join(JOB_RECORD_FILE), serde_json::to_vec_pretty(&record)?)
+    }
+
+    /// Stop the job if it has not completed yet
+    pub(crate) fn cancel(&self) {
+        if matches!(self.status.state, JobState::Queued | JobState::Running) {
+            self.cancel.cancel();
+        }
+    }
+}
+
+/// File in a job's directory recording how it completed
+const JOB_RECORD_FILE: &str = "job.json";
+
+/// What is kept of a completed job, enough to serve its status and results again
+#[derive(Serialize, Deserialize)]
+struct JobRecord {
+    status: JobStatus,
+    output_dir: PathBuf,
+    sidecars: Option<SidecarFormat>,
+}
+
+/// Body of `POST /jobs` when the video is fetched by ffmpeg instead of uploaded
+#[derive(Debug, Deserialize)]
+struct CreateFromUrl {
+    url: String,
+}
+
+pub(crate) type JobMap = Arc<Mutex<HashMap<u64, Arc<Mutex<Job>>>>>;
+
+struct State {
+    data_dir: PathBuf,
+    ffmpeg_threads: Option<u32>,
+    max_memory: Option<u64>,
+    allow_local_inputs: bool,
+    next_id: Mutex<u64>,
+    jobs: JobMap,
+    queue: JobQueue,
+}
+
+type HttpResponse = Response<Cursor<Vec<u8>>>;
+
+/// Listen for requests and serve jobs until the process is stopped
+pub fn serve(options: &ServeOptions) -> Result<(), Error> {
+    let data_dir = options.data_dir.as_path();
+    fs::create_dir_all(data_dir)?;
+
+    let (mut queue, jobs, last_id) = open_jobs(data_dir)?;
+
+    let worker_jobs = Arc::clone(&jobs);
+    let webhook = options.webhook.clone();
+    let public_url = options
+        .public_url
+        .clone()
+        .unwrap_or_else(|| format!("http://{}", options.listen));
+    queue.start(options.workers, move |queued| {
+        let input_file = queued.config.input_file.display().to_string();
+        let Some(status) = run_job(&worker_jobs, queued) else {
+            return;
+        };
+        if let Some(url) = &webhook {
+            webhook::notify_in_background(url, completion(&status, input_file, &public_url));
+        }
+    });
+
+    let state = Arc::new(State {
+        data_dir: data_dir.to_path_buf(),
+        ffmpeg_threads: options.ffmpeg_threads,
+        max_memory: options.max_memory,
+        allow_local_inputs: options.allow_local_inputs,
+        next_id: Mutex::new(last_id + 1),
+        jobs,
+        queue,
+    });
+
+    let server = Server::http(options.listen.as_str()).map_err(Error::other)?;
+    tracing::info!("Listening on http://{} with {} worker(s)", options.listen, options.workers.max(1));
+
+    for request in server.incoming_requests() {
+        let state = Arc::clone(&state);
+        // Uploads and archives can take a while, don't hold up other clients
+        thread::spawn(move || handle(&state, request));
+    }
+
+    Ok(())
+}
+
+/// The job queue in `data_dir`, the jobs it restored from an earlier run and the highest job id taken
+pub(crate) fn open_jobs(data_dir: &Path) -> Result<(JobQueue, JobMap, u64), Error> {
+    let queue = JobQueue::open(&data_dir.join("queue"))?;
+
+    // Never reuse the directory of a job from an earlier server run
+    let last_id = fs::read_dir(data_dir)?
+        .filter_map(Result::ok)
+        .filter_map(|entry| entry.file_name().to_str()?.strip_prefix("job-")?.parse::<u64>().ok())
+        .max()
+        .unwrap_or(0)
+        .max(queue.last_id());
+
+    // Jobs queued before a restart are visible again before any of them starts
+    let jobs: JobMap = Arc::default();
+    for queued in queue.pending() {
+        jobs.lock().unwrap().insert(queued.id, Arc::new(Mutex::new(Job::queued(queued.id, &queued.config))));
+    }
+    if !jobs.lock().unwrap().is_empty() {
+        tracing::info!("Restored {} queued job(s).", jobs.lock().unwrap().len());
+    }
+
+    // Completed ones are still there to be looked at and downloaded
+    let mut completed = 0;
+    for entry in fs::read_dir(data_dir)?.filter_map(Result::ok) {
+        let Some(id) = entry.file_name().to_str().and_then(|name| name.strip_prefix("job-")?.parse::<u64>().ok()) else {
+            continue;
+        };
+        if jobs.lock().unwrap().contains_key(&id) {
+            continue;
+        }
+        if let Some(job) = Job::completed(&entry.path()).filter(|job| job.status.id == id) {
+            jobs.lock().unwrap().insert(id, Arc::new(Mutex::new(job)));
+            completed += 1;
+        }
+    }
+    if completed > 0 {
+        tracing::info!("Restored {} completed job(s).", completed);
+    }
+
+    Ok((queue, jobs, last_id))
+}
+
+fn handle(state: &State, mut request: Request) {
+    let url = request.url().to_string();
+    let response = route(state, &mut request);
+    if let Err(e) = request.respond(response) {
+        tracing::warn!("Failed to send response for {}: {}", url, e);
+    }
+}
+
+/// The response to `request`
+fn route(state: &State, request: &mut Request) -> HttpResponse {
+    let url = request.url().to_string();
+    let (path, query) = url.split_once('?').unwrap_or((url.as_str(), ""));
+    let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+
+    match (request.method(), segments.as_slice()) {
+        (Method::Post, ["jobs"]) => create_job(state, request, query),
+        (Method::Get, ["jobs"]) => list_jobs(state),
+        (Method::Get, ["metrics"]) => metrics_page(state),
+        (Method::Get, ["jobs", id]) => with_job(state, id, |job| json(200, &job.status)),
+        (Method::Delete, ["jobs", id]) => with_job(state, id, cancel_job),
+        (Method::Get, ["jobs", id, "log"]) => with_job(state, id, log_file),
+        (Method::Get, ["jobs", id, "manifest"]) => with_job(state, id, |job| match &job.manifest {
+            Some(manifest) => json(200, manifest),
+            None => not_ready(),
+        }),
+        (Method::Get, ["jobs", id, "slides", file]) => with_job(state, id, |job| slide_file(job, file)),
+        (Method::Get, ["jobs", id, "archive"]) => archive(state, id),
+        _ => error(404, "Not found"),
+    }
+}
+
+fn create_job(state: &State, request: &mut Request, query: &str) -> HttpResponse {
+    let id = {
+        let mut next_id = state.next_id.lock().unwrap();
+        let id = *next_id;
+        *next_id += 1;
+        id
+    };
+
+    let job_dir = state.data_dir.join(format!("job-{}", id));
+    if let Err(e) = fs::create_dir_all(job_dir.join("slides")) {
+        return error(500, &format!("Could not create job directory: {}", e));
+    }
+
+    let mut config = match job_config(&job_dir, request, query, state.allow_local_inputs) {
+        Ok(config) => config,
+        Err(response) => {
+            // Nothing will ever run in it, don't leave it lying around
+            let _ = fs::remove_dir_all(&job_dir);
+            return response;
+        }
+    };
+    config.ffmpeg_threads = state.ffmpeg_threads;
+    config.max_memory = state.max_memory;
+    config.log_file = Some(job_dir.join(JOB_LOG_FILE));
+
+    let job = Arc::new(Mutex::new(Job::queued(id, &config)));
+    let status = job.lock().unwrap().status.clone();
+    state.jobs.lock().unwrap().insert(id, job);
+
+    if let Err(e) = state.queue.submit(QueuedJob { id, config }) {
+        state.jobs.lock().unwrap().remove(&id);
+        return error(500, &format!("Could not queue job: {}", e));
+    }
+
+    json(201, &status)
+}
+
+/// Store the upload (or take the URL) and apply query string overrides
+fn job_config(job_dir: &Path, request: &mut Request, query: &str, allow_local_inputs: bool) -> Result<Config, HttpResponse> {
+    let is_json = request.headers().iter().any(|h| {
+        h.field.equiv("Content-Type") && h.value.as_str().starts_with("application/json")
+    });
+
+    let input_file = if is_json {
+        match serde_json::from_reader::<_, CreateFromUrl>(request.as_reader()) {
+            Ok(body) => match input_url(&body.url, allow_local_inputs) {
+                Ok(input) => input,
+                Err(message) => return Err(error(400, &message)),
+            },
+            Err(e) => return Err(error(400, &format!("Invalid job request: {}", e))),
+        }
+    } else {
+        let input_path = job_dir.join("input");
+        let written = File::create(&input_path).and_then(|mut file| io::copy(request.as_reader(), &mut file));
+        match written {
+            Ok(0) => return Err(error(400, "Request body is empty, upload a video or send {\"url\": ...}")),
+            Ok(_) => input_path,
+            Err(e) => return Err(error(500, &format!("Could not store upload: {}", e))),
+        }
+    };
+
+    let mut config = Config::new(input_file);
+    config.output_dir = job_dir.join("slides");
+    for (key, value) in query.split('&').filter_map(|pair| pair.split_once('=')) {
+        let parsed = match key {
+            "fps" => value.parse().map(|fps| config.fps = fps).is_ok(),
+            "threshold" => value.parse().map(|threshold| config.threshold = threshold).is_ok(),
+            "low_threshold" => value.parse().map(|threshold| config.low_threshold = Some(threshold)).is_ok(),
+            _ => true,
+        };
+        if !parsed {
+            return Err(error(400, &format!("Invalid value for {}: {}", key, value)));
+        }
+    }
+    check_settings(&config).map_err(|message| error(400, &message))?;
+
+    Ok(config)
+}
+
+/// Fail on a job's `fps`, `threshold` or `low_threshold` that no run could make sense of
+pub(crate) fn check_settings(config: &Config) -> Result<(), String> {
+    if config.fps == 0 {
+        return Err("Invalid value for fps: 0, sample at least one frame per second".to_string());
+    }
+    let thresholds = [("threshold", Some(config.threshold)), ("low_threshold", config.low_threshold)];
+    match thresholds.iter().find(|(_, value)| value.is_some_and(|value| !(0.0..=1.0).contains(&value))) {
+        Some((key, Some(value))) => Err(format!("Invalid value for {}: {}, it is a share from 0 to 1", key, value)),
+        _ => Ok(()),
+    }
+}
+
+/// What ffmpeg should open for `{"url": ...}`: an http or https URL, or anything at all with
+/// `allow_local_inputs`
+fn input_url(value: &str, allow_local_inputs: bool) -> Result<PathBuf, String> {
+    if allow_local_inputs {
+        return Ok(PathBuf::from(value));
+    }
+    match Url::parse(value) {
+        Ok(url) if matches!(url.scheme(), "http" | "https") && url.has_host() => Ok(PathBuf::from(value)),
+        Ok(url) => Err(format!("Only http and https URLs can be fetched, not {}:", url.scheme())),
+        Err(e) => Err(format!("Invalid URL {:?}: {}", value, e)),
+    }
+}
+
+/// Run a job from the queue, returning its final status
+pub(crate) fn run_job(jobs: &JobMap, queued: QueuedJob) -> Option<JobStatus> {
+    let job = jobs.lock().unwrap().get(&queued.id).cloned()?;
+
+    let cancel = {
+        let mut job = job.lock().unwrap();
+        if job.cancel.is_cancelled() {
+            // Cancelled while it was still waiting for a worker
+            metrics::job_completed(JobOutcome::Cancelled);
+            job.status.state = JobState::Cancelled;
+            if let Err(e) = job.write_record() {
+                tracing::warn!("Could not record the outcome of job {}, it will be gone after a restart: {}", job.status.id, e);
+            }
+            return Some(job.status.clone());
+        }
+        job.status.state = JobState::Running;
+        job.cancel.clone()
+    };
+
+    let result = run_with(&queued.config, |progress| job.lock().unwrap().status.progress = Some(progress), &cancel);
+
+    let mut job = job.lock().unwrap();
+    match result {
+        Ok(manifest) => {
+            metrics::job_completed(JobOutcome::Finished);
+            job.status.state = JobState::Finished;
+            job.status.slides = Some(manifest.slides.len());
+            job.manifest = Some(manifest);
+        }
+        Err(_) if cancel.is_cancelled() => {
+            metrics::job_completed(JobOutcome::Cancelled);
+            job.status.state = JobState::Cancelled;
+        }
+        Err(e) => {
+            metrics::job_completed(JobOutcome::Failed);
+            job.status.state = JobState::Failed;
+            job.status.error = Some(e.to_string());
+        }
+    }
+    if let Err(e) = job.write_record() {
+        tracing::warn!("Could not record the outcome of job {}, it will be gone after a restart: {}", job.status.id, e);
+    }
+    Some(job.status.clone())
+}
+
+/// Webhook notification for a job that reached its final state
+fn completion(status: &JobStatus, input_file: String, public_url: &str) -> JobCompletion {
+    let outcome = match status.state {
+        JobState::Finished => Outcome::Finished,
+        JobState::Cancelled => Outcome::Cancelled,
+        _ => Outcome::Failed,
+    };
+    let finished = outcome == Outcome::Finished;
+    JobCompletion {
+        video_id: status.id.to_string(),
+        input_file,
+        outcome,
+        slide_count: status.slides,
+        manifest_path: None,
+        manifest_url: finished.then(|| format!("{}/jobs/{}/manifest", public_url.trim_end_matches('/'), status.id)),
+        error: status.error.clone(),
+    }
+}
+
+fn list_jobs(state: &State) -> HttpResponse {
+    let mut statuses: Vec<JobStatus> = state
+        .jobs
+        .lock()
+        .unwrap()
+        .values()
+        .map(|job| job.lock().unwrap().status.clone())
+        .collect();
+    statuses.sort_by_key(|status| status.id);
+    json(200, &statuses)
+}
+
+fn metrics_page(state: &State) -> HttpResponse {
+    let mut queued = 0;
+    let mut running = 0;
+    for job in state.jobs.lock().unwrap().values() {
+        match job.lock().unwrap().status.state {
+            JobState::Queued => queued += 1,
+            JobState::Running => running += 1,
+            _ => {}
+        }
+    }
+
+    Response::from_string(metrics::render(&[("queued", queued), ("running", running)]))
+        .with_header(header("Content-Type", "text/plain; version=0.0.4"))
+}
+
+fn find_job(state: &State, id: &str) -> Option<Arc<Mutex<Job>>> {
+    id.parse::<u64>().ok().and_then(|id| state.jobs.lock().unwrap().get(&id).cloned())
+}
+
+fn with_job(state: &State, id: &str, f: impl FnOnce(&Job) -> HttpResponse) -> HttpResponse {
+    match find_job(state, id) {
+        Some(job) => f(&job.lock().unwrap()),
+        None => error(404, "No such job"),
+    }
+}
+
+fn cancel_job(job: &Job) -> HttpResponse {
+    job.cancel();
+    json(202, &job.status)
+}
+
+fn log_file(job: &Job) -> HttpResponse {
+    let contents = job.log_file.as_ref().map(fs::read).unwrap_or_else(|| Ok(Vec::new()));
+    match contents {
+        Ok(data) => Response::from_data(data).with_header(header("Content-Type", "text/plain; charset=utf-8")),
+        // Nothing has been logged until the job starts
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Response::from_data(Vec::new()),
+        Err(e) => error(500, &format!("Could not read log: {}", e)),
+    }
+}
+
+fn slide_file(job: &Job, file: &str) -> HttpResponse {
+    let Some(manifest) = &job.manifest else {
+        return not_ready();
+    };
+    // Only serve files the manifest lists, which also rules out path traversal
+    if !manifest.slides.iter().any(|slide| slide.file == file) {
+        return error(404, "No such slide");
+    }
+
+    match fs::read(job.output_dir.join(file)) {
+        Ok(data) => Response::from_data(data).with_header(header("Content-Type", "image/png")),
+        Err(e) => error(500, &format!("Could not read slide: {}", e)),
+    }
+}
+
+fn archive(state: &State, id: &str) -> HttpResponse {
+    let Some(job) = find_job(state, id) else {
+        return error(404, "No such job");
+    };
+    // Zipping takes a while, don't hold up the job's status requests and worker meanwhile
+    let (id, output_dir, manifest, sidecars) = {
+        let job = job.lock().unwrap();
+        let Some(manifest) = job.manifest.clone() else {
+            return not_ready();
+        };
+        (job.status.id, job.output_dir.clone(), manifest, job.sidecars)
+    };
+
+    match write_archive(&output_dir, &manifest, sidecars, Cursor::new(Vec::new())) {
+        Ok(zip) => Response::from_data(zip.into_inner())
+            .with_header(header("Content-Type", "application/zip"))
+            .with_header(header(
+                "Content-Disposition",
+                &format!("attachment; filename=\"job-{}.zip\"", id),
+            )),
+        Err(e) => error(500, &format!("Could not build archive: {}", e)),
+    }
+}
+
+fn header(field: &str, value: &str) -> Header {
+    Header::from_bytes(field.as_bytes(), value.as_bytes()).expect("static header is valid")
+}
+
+fn json<T: Serialize>(status: u16, body: &T) -> HttpResponse {
+    match serde_json::to_vec_pretty(body) {
+        Ok(data) => Response::from_data(data)
+            .with_status_code(status)
+            .with_header(header("Content-Type", "application/json")),
+        Err(e) => error(500, &e.to_string()),
+    }
+}
+
+fn error(status: u16, message: &str) -> HttpResponse {
+    let body = serde_json::json!({ "error": message }).to_string();
+    Response::from_string(body)
+        .with_status_code(status)
+        .with_header(header("Content-Type", "application/json"))
+}
+
+fn not_ready() -> HttpResponse {
+    error(409, "Job has not finished")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tiny_http::TestRequest;
+
+    fn state(data_dir: &Path) -> State {
+        let (queue, jobs, last_id) = open_jobs(data_dir).unwrap();
+        State {
+            data_dir: data_dir.to_path_buf(),
+            ffmpeg_threads: None,
+            max_memory: None,
+            allow_local_inputs: false,
+            next_id: Mutex::new(last_id + 1),
+            jobs,
+            queue,
+        }
+    }
+
+    /// Status code and body of the answer to `request`
+    fn send(state: &State, request: TestRequest) -> (u16, serde_json::Value) {
+        let response = route(state, &mut request.into());
+        let status = response.status_code().0;
+        let body = serde_json::from_reader(response.into_reader()).unwrap_or_default();
+        (status, body)
+    }
+
+    fn create(path: &str, url: &'static str) -> TestRequest {
+        TestRequest::new()
+            .with_method(Method::Post)
+            .with_path(path)
+            .with_header(header("Content-Type", "application/json"))
+            .with_body(url)
+    }
+
+    #[test]
+    fn queues_url_jobs_and_reports_them() {
+        let data_dir = tempfile::tempdir().unwrap();
+        let state = state(data_dir.path());
+        let (status, body) = send(&state, create("/jobs?fps=2&threshold=0.02", r#"{"url": "https://example.com/talk.mp4"}"#));
+        assert_eq!(status, 201);
+        assert_eq!(body["id"], 1);
+        assert_eq!(body["state"], "queued");
+        assert_eq!(state.queue.pending()[0].config.fps, 2);
+
+        let (status, body) = send(&state, TestRequest::new().with_path("/jobs/1"));
+        assert_eq!((status, &body["state"]), (200, &serde_json::json!("queued")));
+        assert_eq!(send(&state, TestRequest::new().with_path("/jobs")).1.as_array().map(Vec::len), Some(1));
+        assert_eq!(send(&state, TestRequest::new().with_path("/jobs/1/manifest")).0, 409);
+        assert_eq!(send(&state, TestRequest::new().with_path("/jobs/2")).0, 404);
+        assert_eq!(send(&state, TestRequest::new().with_path("/jobs/x/log")).0, 404);
+        assert_eq!(send(&state, TestRequest::new().with_path("/nowhere")).0, 404);
+        assert_eq!(send(&state, TestRequest::new().with_method(Method::Delete).with_path("/jobs/1")).0, 202);
+        assert!(state.jobs.lock().unwrap()[&1].lock().unwrap().cancel.is_cancelled());
+    }
+
+    #[test]
+    fn rejects_local_inputs_and_bad_settings() {
+        let data_dir = tempfile::tempdir().unwrap();
+        let state = state(data_dir.path());
+        for request in [
+            create("/jobs", r#"{"url": "/etc/passwd"}"#),
+            create("/jobs", r#"{"url": "file:///etc/passwd"}"#),
+            create("/jobs", r#"{"url": "concat:a.mp4|b.mp4"}"#),
+            create("/jobs", r#"{"link": "https://example.com/talk.mp4"}"#),
+            create("/jobs?fps=0", r#"{"url": "https://example.com/talk.mp4"}"#),
+            create("/jobs?threshold=5", r#"{"url": "https://example.com/talk.mp4"}"#),
+            create("/jobs?low_threshold=-0.1", r#"{"url": "https://example.com/talk.mp4"}"#),
+            create("/jobs?fps=fast", r#"{"url": "https://example.com/talk.mp4"}"#),
+            TestRequest::new().with_method(Method::Post).with_path("/jobs"),
+        ] {
+            let (status, body) = send(&state, request);
+            assert_eq!(status, 400, "{}", body);
+        }
+        assert!(state.jobs.lock().unwrap().is_empty());
+        // Nothing is left behind of the rejected jobs
+        assert!(fs::read_dir(data_dir.path()).unwrap().filter_map(Result::ok).all(|entry| entry.file_name() == "queue"));
+    }
+
+    #[test]
+    fn serves_completed_jobs_after_a_restart() {
+        let data_dir = tempfile::tempdir().unwrap();
+        let job_dir = data_dir.path().join("job-4");
+        fs::create_dir_all(&job_dir).unwrap();
+        let mut job = Job::queued(4, &Config::new("talk.mp4"));
+        job.log_file = Some(job_dir.join(JOB_LOG_FILE));
+        job.status.state = JobState::Failed;
+        job.status.error = Some("ffmpeg failed".to_string());
+        job.write_record().unwrap();
+
+        let state = state(data_dir.path());
+        let (status, body) = send(&state, TestRequest::new().with_path("/jobs/4"));
+        assert_eq!(status, 200);
+        assert_eq!(body["state"], "failed");
+        assert_eq!(body["error"], "ffmpeg failed");
+        // New jobs don't reuse its directory
+        assert_eq!(*state.next_id.lock().unwrap(), 5);
+    }
+
+    #[test]
+    fn checks_settings() {
+        let mut config = Config::new("talk.mp4");
+        assert_eq!(check_settings(&config), Ok(()));
+        config.low_threshold = Some(1.5);
+        assert!(check_settings(&config).unwrap_err().contains("low_threshold"));
+        config.low_threshold = None;
+        config.threshold = f64::NAN;
+        assert!(check_settings(&config).is_err());
+        config.threshold = 0.0;
+        config.fps = 0;
+        assert!(check_settings(&config).unwrap_err().contains("fps"));
+    }
+}
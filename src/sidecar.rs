@@ -0,0 +1,92 @@
+//! `--sidecars`: a small file next to each kept slide with what the manifest
+//! says about it, for tools that read one file per image rather than the
+//! whole manifest.
+//!
+//! The sidecar is named after the image (`frame_000042.png` gets
+//! `frame_000042.json`) and holds the slide's manifest entry plus how long
+//! it was on screen in total, including the text read off it with `--ocr`.
+
+use serde::Serialize;
+use serde_json::Value;
+use std::io;
+use std::path::Path;
+
+use crate::config::{Config, SidecarFormat};
+use crate::lock::write_atomic;
+use crate::manifest::{Manifest, Slide};
+
+#[derive(Serialize)]
+struct Sidecar<'a> {
+    #[serde(flatten)]
+    slide: &'a Slide,
+    /// Seconds on screen, over every time it was shown
+    #[serde(skip_serializing_if = "Option::is_none")]
+    duration: Option<f64>,
+}
+
+/// Where the sidecar of the slide stored as `file` goes, relative to the output directory
+pub fn file_name(file: &str, format: SidecarFormat) -> String {
+    Path::new(file).with_extension(format.extension()).to_string_lossy().into_owned()
+}
+
+/// The sidecar of `slide`
+pub fn contents(slide: &Slide, format: SidecarFormat) -> Result<String, io::Error> {
+    let duration = (!slide.shown.is_empty()).then(|| slide.shown.iter().map(|interval| interval.end - interval.start).sum());
+    let sidecar = Sidecar { slide, duration };
+    match format {
+        SidecarFormat::Json => serde_json::to_string_pretty(&sidecar).map_err(io::Error::other),
+        SidecarFormat::Yaml => {
+            let mut yaml = String::new();
+            write_yaml(&mut yaml, &serde_json::to_value(&sidecar).map_err(io::Error::other)?, 0);
+            Ok(yaml)
+        }
+    }
+}
+
+/// Block-style YAML for `value`, which is an object at the top level; strings
+/// are double-quoted, with JSON's escapes, which YAML reads the same way
+fn write_yaml(out: &mut String, value: &Value, indent: usize) {
+    let pad = " ".repeat(indent);
+    match value {
+        Value::Object(map) => {
+            for (key, value) in map {
+                match value {
+                    Value::Object(inner) if !inner.is_empty() => {
+                        out.push_str(&format!("{}{}:\n", pad, key));
+                        write_yaml(out, value, indent + 2);
+                    }
+                    Value::Array(items) if !items.is_empty() => {
+                        out.push_str(&format!("{}{}:\n", pad, key));
+                        write_yaml(out, value, indent);
+                    }
+                    _ => out.push_str(&format!("{}{}: {}\n", pad, key, value)),
+                }
+            }
+        }
+        Value::Array(items) => {
+            for item in items {
+                match item {
+                    Value::Object(inner) if !inner.is_empty() => {
+                        // The first key shares the dash's line, the rest line up under it
+                        let mut nested = String::new();
+                        write_yaml(&mut nested, item, indent + 2);
+                        out.push_str(&format!("{}- {}", pad, &nested[indent + 2..]));
+                    }
+                    _ => out.push_str(&format!("{}- {}\n", pad, item)),
+                }
+            }
+        }
+        _ => out.push_str(&format!("{}{}\n", pad, value)),
+    }
+}
+
+/// Write a sidecar next to every kept slide of `manifest` if `config.sidecars` asks for it
+pub fn write_sidecars(config: &Config, manifest: &Manifest) -> Result<(), io::Error> {
+    let Some(format) = config.sidecars else {
+        return Ok(());
+    };
+    for slide in &manifest.slides {
+        write_atomic(&config.output_dir.join(file_name(&slide.file, format)), contents(slide, format)?)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,389 @@
+//! Where the sampled frames of a run come from.
+//!
+//! The command line tool samples the video with ffmpeg, but the pipeline only
+//! needs a sequence of images, so it can equally be fed a directory of
+//! pre-extracted frames or images generated in memory.
+
+use image::{DynamicImage, ImageFormat, ImageReader, RgbImage};
+use std::collections::VecDeque;
+use std::fs;
+use std::io::{self, BufRead, BufReader};
+use std::path::{Path, PathBuf};
+use std::process::Child;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::thread::{self, JoinHandle};
+
+use crate::config::{Config, WorkspacePolicy};
+use crate::error::Error;
+use crate::extract::{check_input, extract_frames, move_file, pipe_command, StderrTail, Watchdog, POLL_INTERVAL};
+use crate::memory::fit_budget;
+use crate::metrics;
+use crate::pipeline::{is_frame_file, sort_frames};
+use crate::progress::{CancellationToken, Progress};
+use crate::runlog::RunLog;
+use crate::workspace::Workspace;
+
+/// One sampled frame, in the order it appeared in the video
+pub struct Frame {
+    pub image: DynamicImage,
+    /// File name the frame is stored under if it is kept as a slide
+    pub name: String,
+    /// Where the frame already lives on disk, if anywhere
+    pub path: Option<PathBuf>,
+}
+
+/// Decode the frame stored at `path`, by its extension or, without a known one, its contents.
+///
+/// Nothing is cached: each frame is read when it is asked for and its memory goes with
+/// the image, so thousands of frames read in a row are never held at once.
+pub fn open_frame(path: &Path) -> Result<DynamicImage, image::ImageError> {
+    let mut reader = ImageReader::open(path)?;
+    if ImageFormat::from_path(path).is_err() {
+        reader = reader.with_guessed_format()?;
+    }
+    reader.decode()
+}
+
+/// Supplies the frames the pipeline deduplicates
+pub trait FrameSource {
+    /// Get the frames ready (e.g. run ffmpeg); called once before the first `next_frame`
+    fn prepare(&mut self, _progress: &dyn Fn(Progress), _cancel: &CancellationToken) -> Result<(), Error> {
+        Ok(())
+    }
+
+    /// Frames still to come, when known up front
+    fn remaining(&self) -> Option<usize> {
+        None
+    }
+
+    /// The next frame, or `None` once there are no more
+    fn next_frame(&mut self) -> Result<Option<Frame>, Error>;
+
+    /// Store a frame that was kept as `output_dir/name` and return where it ended up
+    fn keep(&mut self, name: &str, _path: Option<&Path>, image: &DynamicImage, output_dir: &Path) -> Result<PathBuf, Error> {
+        let destination = output_dir.join(name);
+        image
+            .save(&destination)
+            .map_err(|e| io::Error::other(format!("Error saving image: {}", e)))?;
+        Ok(destination)
+    }
+
+    /// Dispose of a frame that was found to repeat the previous slide
+    fn discard(&mut self, _path: Option<&Path>) -> Result<(), Error> {
+        Ok(())
+    }
+
+    /// The run finished without error; called once, after everything was written
+    fn succeeded(&mut self) {}
+}
+
+/// Frames already on disk as PNG files, read in file name order.
+///
+/// Each file is only decoded when its frame is asked for, so no more than the
+/// frame being compared and the one before it are held in memory at a time.
+/// The files are left alone; kept frames are copied to the output directory.
+pub struct DirectorySource {
+    frames: VecDeque<PathBuf>,
+}
+
+impl DirectorySource {
+    pub fn open(dir: impl AsRef<Path>) -> Result<Self, io::Error> {
+        let mut frames: Vec<PathBuf> = fs::read_dir(dir)?
+            .filter_map(Result::ok)
+            .map(|entry| entry.path())
+            .filter(|path| is_frame_file(path))
+            .collect();
+
+        sort_frames(&mut frames);
+
+        Ok(DirectorySource { frames: frames.into() })
+    }
+}
+
+impl FrameSource for DirectorySource {
+    fn remaining(&self) -> Option<usize> {
+        Some(self.frames.len())
+    }
+
+    fn next_frame(&mut self) -> Result<Option<Frame>, Error> {
+        let Some(path) = self.frames.pop_front() else {
+            return Ok(None);
+        };
+
+        let image = match open_frame(&path) {
+            Ok(image) => image,
+            Err(source) => return Err(Error::BadFrame { path, source }),
+        };
+        let name = path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+        Ok(Some(Frame { image, name, path: Some(path) }))
+    }
+
+    fn keep(&mut self, name: &str, path: Option<&Path>, _image: &DynamicImage, output_dir: &Path) -> Result<PathBuf, Error> {
+        let destination = output_dir.join(name);
+        if let Some(path) = path {
+            if path != destination {
+                fs::copy(path, &destination)?;
+            }
+        }
+        Ok(destination)
+    }
+}
+
+/// Frames sampled from the input video by ffmpeg into a workspace; kept ones
+/// are moved to the output directory and the rest go with the workspace when
+/// the source is dropped
+pub struct FfmpegSource {
+    config: Config,
+    log: RunLog,
+    frames_dir: Option<Workspace>,
+    frames: Option<DirectorySource>,
+}
+
+impl FfmpegSource {
+    pub fn new(config: &Config) -> Result<Self, io::Error> {
+        Ok(FfmpegSource {
+            config: config.clone(),
+            log: RunLog::open(config.log_file.as_deref())?,
+            frames_dir: None,
+            frames: None,
+        })
+    }
+}
+
+impl FrameSource for FfmpegSource {
+    fn prepare(&mut self, progress: &dyn Fn(Progress), cancel: &CancellationToken) -> Result<(), Error> {
+        fit_budget(&mut self.config, &self.log);
+        let frames_dir = self.frames_dir.insert(Workspace::create(&self.config)?);
+        extract_frames(&self.config, frames_dir.path(), &self.log, progress, cancel)?;
+        self.frames = Some(DirectorySource::open(frames_dir.path())?);
+        Ok(())
+    }
+
+    fn remaining(&self) -> Option<usize> {
+        self.frames.as_ref().and_then(|frames| frames.remaining())
+    }
+
+    fn next_frame(&mut self) -> Result<Option<Frame>, Error> {
+        match self.frames.as_mut() {
+            Some(frames) => frames.next_frame(),
+            None => Err(Error::Io(io::Error::other("frames requested before ffmpeg ran"))),
+        }
+    }
+
+    fn keep(&mut self, name: &str, path: Option<&Path>, _image: &DynamicImage, output_dir: &Path) -> Result<PathBuf, Error> {
+        let destination = output_dir.join(name);
+        if let Some(path) = path {
+            move_file(path, &destination)?;
+        }
+        Ok(destination)
+    }
+
+    fn discard(&mut self, path: Option<&Path>) -> Result<(), Error> {
+        match path {
+            // Remove non-unique frame, unless the workspace is kept to look at them
+            Some(path) if self.config.workspace != WorkspacePolicy::Keep => Ok(fs::remove_file(path)?),
+            _ => Ok(()),
+        }
+    }
+
+    fn succeeded(&mut self) {
+        if let Some(frames_dir) = &mut self.frames_dir {
+            frames_dir.succeeded();
+        }
+    }
+}
+
+/// Frames ffmpeg decodes straight into memory with `--in-memory`, so nothing
+/// but the kept slides touches the disk; for short clips processed over and
+/// over, where writing and deleting every sampled frame dominates.
+///
+/// At most `PIPE_BUFFER` frames wait to be compared at a time; ffmpeg is held
+/// up while the queue is full. A `Watchdog` kills it on `--ffmpeg-timeout` and
+/// `--stall-timeout`, as when sampling to disk.
+pub struct PipeSource {
+    config: Config,
+    log: RunLog,
+    child: Option<Child>,
+    frames: Option<Receiver<Result<DynamicImage, io::Error>>>,
+    stderr: Option<JoinHandle<StderrTail>>,
+    watchdog: Watchdog,
+    position: usize,
+}
+
+/// Decoded frames waiting to be compared in a `PipeSource`
+const PIPE_BUFFER: usize = 8;
+
+impl PipeSource {
+    pub fn new(config: &Config) -> Result<Self, io::Error> {
+        Ok(PipeSource {
+            config: config.clone(),
+            log: RunLog::open(config.log_file.as_deref())?,
+            child: None,
+            frames: None,
+            stderr: None,
+            watchdog: Watchdog::new(config),
+            position: 0,
+        })
+    }
+
+    /// Wait for ffmpeg to exit once it stopped sending frames, failing if it did not succeed
+    fn finish(&mut self) -> Result<(), Error> {
+        let Some(mut child) = self.child.take() else {
+            return Ok(());
+        };
+        let status = child.wait()?;
+        let stderr = self.stderr.take().and_then(|reader| reader.join().ok()).unwrap_or_default();
+        if !status.success() {
+            metrics::ffmpeg_failed();
+            return Err(Error::ffmpeg_failed(&self.config.input_file, status, stderr.into_string()));
+        }
+        self.log.info("Frames decoded successfully.");
+        Ok(())
+    }
+}
+
+/// Read one binary PPM (`P6`, 8 bits per channel) from `reader`, `None` at the end of the stream
+fn read_ppm(reader: &mut impl BufRead) -> Result<Option<DynamicImage>, io::Error> {
+    let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, format!("ffmpeg sent {}", message));
+
+    // Magic number, width, height and maximum value, separated by whitespace
+    let mut fields = Vec::with_capacity(4);
+    let mut field = String::new();
+    let mut byte = [0];
+    while fields.len() < 4 {
+        if reader.read(&mut byte)? == 0 {
+            return match (fields.is_empty() && field.is_empty(), fields.len()) {
+                (true, _) => Ok(None),
+                _ => Err(invalid("a truncated frame header")),
+            };
+        }
+        match byte[0] {
+            b' ' | b'\t' | b'\n' | b'\r' if !field.is_empty() => fields.push(std::mem::take(&mut field)),
+            b' ' | b'\t' | b'\n' | b'\r' => {}
+            other => field.push(other as char),
+        }
+    }
+    let number = |text: &str| text.parse::<u32>().map_err(|_| invalid("a malformed frame header"));
+    if fields[0] != "P6" || number(&fields[3])? != 255 {
+        return Err(invalid("a frame that is not 8-bit PPM"));
+    }
+    let (width, height) = (number(&fields[1])?, number(&fields[2])?);
+
+    let mut pixels = vec![0; width as usize * height as usize * 3];
+    reader.read_exact(&mut pixels)?;
+    let image = RgbImage::from_raw(width, height, pixels).ok_or_else(|| invalid("a frame of the wrong size"))?;
+    Ok(Some(DynamicImage::ImageRgb8(image)))
+}
+
+impl FrameSource for PipeSource {
+    fn prepare(&mut self, _progress: &dyn Fn(Progress), _cancel: &CancellationToken) -> Result<(), Error> {
+        check_input(&self.config.input_file)?;
+        fit_budget(&mut self.config, &self.log);
+        let mut child = pipe_command(&self.config).spawn().map_err(Error::ffmpeg_spawn)?;
+
+        // Drain stderr so ffmpeg never blocks on it, keeping the end for the error message
+        let stderr = child.stderr.take().expect("stderr is piped");
+        let stderr_log = self.log.clone();
+        self.stderr = Some(thread::spawn(move || {
+            let mut tail = StderrTail::default();
+            for line in BufReader::new(stderr).lines().map_while(Result::ok) {
+                tail.push(&stderr_log, line);
+            }
+            tail
+        }));
+
+        let stdout = child.stdout.take().expect("stdout is piped");
+        let (frames_tx, frames_rx) = mpsc::sync_channel(PIPE_BUFFER);
+        thread::spawn(move || {
+            let mut reader = BufReader::new(stdout);
+            while let Some(frame) = read_ppm(&mut reader).transpose() {
+                let failed = frame.is_err();
+                if frames_tx.send(frame).is_err() || failed {
+                    break;
+                }
+            }
+        });
+        self.child = Some(child);
+        self.frames = Some(frames_rx);
+        self.watchdog = Watchdog::new(&self.config);
+        Ok(())
+    }
+
+    fn next_frame(&mut self) -> Result<Option<Frame>, Error> {
+        let Some(frames) = self.frames.as_ref() else {
+            return Err(Error::Io(io::Error::other("frames requested before ffmpeg ran")));
+        };
+        let frame = loop {
+            match frames.recv_timeout(POLL_INTERVAL) {
+                Ok(frame) => break Some(frame),
+                Err(RecvTimeoutError::Disconnected) => break None,
+                Err(RecvTimeoutError::Timeout) => {}
+            }
+            if let Some(reason) = self.watchdog.expired() {
+                if let Some(child) = self.child.as_mut() {
+                    child.kill()?;
+                    child.wait()?;
+                }
+                self.child = None;
+                let stderr = self.stderr.take().and_then(|reader| reader.join().ok()).unwrap_or_default();
+                metrics::ffmpeg_failed();
+                return Err(Error::FfmpegStalled {
+                    input: self.config.input_file.display().to_string(),
+                    reason,
+                    stderr: stderr.into_string(),
+                });
+            }
+        };
+
+        match frame {
+            Some(image) => {
+                self.position += 1;
+                self.watchdog.frames(self.position);
+                Ok(Some(Frame { image: image?, name: format!("frame_{:06}.png", self.position), path: None }))
+            }
+            None => {
+                self.finish()?;
+                Ok(None)
+            }
+        }
+    }
+}
+
+impl Drop for PipeSource {
+    fn drop(&mut self) {
+        // A run that stopped early, e.g. cancelled, leaves ffmpeg waiting to write
+        if let Some(mut child) = self.child.take() {
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+    }
+}
+
+/// Frames handed over as decoded images, e.g. synthetic ones in tests.
+///
+/// They are named `frame_000001.png`, `frame_000002.png`, ... like ffmpeg's, and
+/// only the kept ones are ever written to disk.
+pub struct MemorySource {
+    frames: VecDeque<DynamicImage>,
+    position: usize,
+}
+
+impl MemorySource {
+    pub fn new(frames: impl IntoIterator<Item = DynamicImage>) -> Self {
+        MemorySource { frames: frames.into_iter().collect(), position: 0 }
+    }
+}
+
+impl FrameSource for MemorySource {
+    fn remaining(&self) -> Option<usize> {
+        Some(self.frames.len())
+    }
+
+    fn next_frame(&mut self) -> Result<Option<Frame>, Error> {
+        let Some(image) = self.frames.pop_front() else {
+            return Ok(None);
+        };
+        self.position += 1;
+        Ok(Some(Frame { image, name: format!("frame_{:06}.png", self.position), path: None }))
+    }
+}
@@ -0,0 +1,116 @@
+//! `--diarize`: tag each slide with who was speaking while it was up, for
+//! panels and talks with several presenters, so the slides can be told apart
+//! by speaker in the manifest and the transcript.
+//!
+//! Diarization is left to a dedicated tool; what it found is read as RTTM,
+//! the format they share, with one `SPEAKER` line per turn giving its start,
+//! length and speaker. It is read from a file the tool wrote beforehand, or
+//! from what a command prints when given the recording's audio as a 16kHz
+//! mono WAV file after its own arguments, e.g. a small pyannote.audio script.
+//! Speakers keep the tool's labels, like `SPEAKER_00`; each slide gets the one
+//! who spoke longest while it was on screen.
+
+use std::collections::BTreeSet;
+use std::fs;
+use std::io::{self, ErrorKind};
+use std::process::{Command, Stdio};
+
+use crate::audio;
+use crate::config::{Config, Diarization};
+use crate::error::Error;
+use crate::manifest::{Interval, Manifest};
+use crate::runlog::RunLog;
+use crate::workspace::Workspace;
+
+/// Sample rate diarization tools expect
+const SAMPLE_RATE: usize = 16000;
+
+/// One stretch of a speaker talking
+struct Turn {
+    start: f64,
+    end: f64,
+    speaker: String,
+}
+
+/// Tag the slides of `manifest` with their speaker, if `config.diarize` asks for it
+pub fn tag_speakers(config: &Config, manifest: &mut Manifest, log: &RunLog) -> Result<(), Error> {
+    let Some(diarization) = &config.diarize else {
+        return Ok(());
+    };
+    let rttm = match diarization {
+        Diarization::Rttm { path } => fs::read_to_string(path)
+            .map_err(|e| io::Error::new(e.kind(), format!("Could not read the diarization {}: {}", path.display(), e)))?,
+        Diarization::Command { command } => run(config, command)?,
+    };
+    let turns = parse(&rttm);
+    if turns.is_empty() {
+        log.warn(format_args!("The diarization has no speaker turns, leaving the slides untagged"));
+        return Ok(());
+    }
+
+    for i in 0..manifest.slides.len() {
+        let slide = &manifest.slides[i];
+        let shown = if slide.shown.is_empty() {
+            let end = manifest.slides.get(i + 1).map_or(f64::INFINITY, |next| next.timestamp);
+            vec![Interval { start: slide.timestamp, end }]
+        } else {
+            slide.shown.clone()
+        };
+        manifest.slides[i].speaker = longest_speaker(&turns, &shown);
+    }
+    let tagged = manifest.slides.iter().filter(|slide| slide.speaker.is_some()).count();
+    let speakers: BTreeSet<&str> = manifest.slides.iter().filter_map(|slide| slide.speaker.as_deref()).collect();
+    log.info(format_args!("Tagged {} of {} slide(s) with their speaker, {} speaker(s).", tagged, manifest.slides.len(), speakers.len()));
+    Ok(())
+}
+
+/// What `command` prints given `config.input_file`'s audio as a WAV file
+fn run(config: &Config, command: &str) -> Result<String, Error> {
+    let mut words = command.split_whitespace();
+    let program = words.next().ok_or_else(|| io::Error::new(ErrorKind::InvalidInput, "the diarization command is empty"))?;
+    let mut workspace = Workspace::create(config)?;
+    let wav = workspace.path().join("audio.wav");
+    audio::write_wav(config, SAMPLE_RATE, &wav)?;
+
+    let output = Command::new(program).args(words).arg(&wav).stdin(Stdio::null()).output().map_err(|e| match e.kind() {
+        ErrorKind::NotFound => io::Error::new(ErrorKind::NotFound, format!("{} was not found", program)),
+        _ => e,
+    })?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(io::Error::other(format!("The diarization command failed ({}): {}", output.status, stderr.trim())).into());
+    }
+    workspace.succeeded();
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// The speaker turns of an RTTM document; other kinds of line and malformed ones are skipped
+fn parse(rttm: &str) -> Vec<Turn> {
+    rttm.lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            if fields.len() < 8 || fields[0] != "SPEAKER" {
+                return None;
+            }
+            let start: f64 = fields[3].parse().ok()?;
+            let duration: f64 = fields[4].parse().ok()?;
+            Some(Turn { start, end: start + duration, speaker: fields[7].to_string() })
+        })
+        .collect()
+}
+
+/// The speaker talking longest across `shown`, `None` if nobody does
+fn longest_speaker(turns: &[Turn], shown: &[Interval]) -> Option<String> {
+    let mut totals: Vec<(&str, f64)> = Vec::new();
+    for turn in turns {
+        let overlap: f64 = shown.iter().map(|interval| (turn.end.min(interval.end) - turn.start.max(interval.start)).max(0.0)).sum();
+        if overlap <= 0.0 {
+            continue;
+        }
+        match totals.iter_mut().find(|(speaker, _)| *speaker == turn.speaker) {
+            Some((_, total)) => *total += overlap,
+            None => totals.push((&turn.speaker, overlap)),
+        }
+    }
+    totals.into_iter().max_by(|a, b| a.1.total_cmp(&b.1)).map(|(speaker, _)| speaker.to_string())
+}
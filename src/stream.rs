@@ -0,0 +1,643 @@
+//! Non-blocking variant of the pipeline for embedding in async services.
+//!
+//! ffmpeg is driven through `tokio::process` and files are touched through
+//! `tokio::fs`; only frame decoding and comparison, which are CPU-bound, hop
+//! onto the blocking pool one frame at a time.
+//!
+//! Frames are always sampled by one ffmpeg into the disk and compared as they
+//! are, so options that change how they are sampled or what they are turned
+//! into, like `--segments` or `--whiteboard`, are rejected.
+
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::process::Command;
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+use tokio_stream::Stream;
+use tracing::Instrument;
+
+use crate::confidence::{self, Evidence};
+use crate::boundaries;
+use crate::canvas;
+use crate::catalog;
+use crate::changes;
+use crate::classify;
+use crate::config::{Config, SyncMode, WorkspacePolicy};
+use crate::dedup::{Decision, Deduplicator, Settled, Stabilizer};
+use crate::error::Error;
+use crate::fingerprint::{fingerprint, previous_run};
+use crate::identity;
+use crate::idle;
+use crate::limits::OutputBudget;
+use crate::links;
+use crate::live;
+use crate::lock::lock_output;
+use crate::extract::{
+    check_input, check_piped, extract_command, parse_progress_frames, resume_point, retry_delay, should_resume, should_retry,
+    StderrTail, Watchdog,
+};
+use crate::manifest::{Manifest, Slide};
+use crate::memory::fit_budget;
+use crate::metadata;
+use crate::metrics;
+use crate::monitors;
+use crate::ocr;
+use crate::output;
+use crate::pauses;
+use crate::rate;
+use crate::report;
+use crate::revisits::{self, Revisits};
+use crate::pipeline::{
+    build_manifest, frame_score, handle_bad_frame, is_frame_file, record_provenance, report_skipped, report_threshold,
+    name_template, resolution_change, slide_entry, slide_file_name, sort_frames, Processed,
+};
+use crate::preflight;
+use crate::progress::{CancellationToken, Progress, Stage};
+use crate::runlog::RunLog;
+use crate::scroll::Stitcher;
+use crate::zoom::{self, Zooms};
+use crate::sidecar;
+use crate::speakers;
+use crate::summarize;
+use crate::source::open_frame;
+use crate::sync;
+use crate::workspace::Workspace;
+
+/// How often a running ffmpeg child is checked against the cancellation token
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+/// Events buffered before a slow consumer holds the pipeline back
+const CHANNEL_DEPTH: usize = 64;
+
+/// Something that happened during an async run
+#[derive(Debug)]
+pub enum SlideEvent {
+    Progress(Progress),
+    /// A frame was kept as a new slide
+    Slide(Slide),
+    /// The run finished and the manifest has been written
+    Finished(Manifest),
+    /// The run stopped early; no further events follow
+    Failed(Error),
+}
+
+/// Start the pipeline on the current tokio runtime and stream its events.
+///
+/// Dropping the stream does not stop the run; cancel the token for that.
+pub fn run_stream(config: Config, cancel: CancellationToken) -> impl Stream<Item = SlideEvent> {
+    let (tx, rx) = mpsc::channel(CHANNEL_DEPTH);
+
+    let span = tracing::info_span!("extract", input = %config.input_file.display());
+    tokio::spawn(
+        async move {
+            let event = match run_decks(&config, &tx, &cancel).await {
+                Ok(manifest) => SlideEvent::Finished(manifest),
+                Err(e) => SlideEvent::Failed(e),
+            };
+            let _ = tx.send(event).await;
+        }
+        .instrument(span),
+    );
+
+    ReceiverStream::new(rx)
+}
+
+async fn send(tx: &mpsc::Sender<SlideEvent>, event: SlideEvent) {
+    // A consumer that went away is not an error, the run just carries on unobserved
+    let _ = tx.send(event).await;
+}
+
+async fn progress(tx: &mpsc::Sender<SlideEvent>, stage: Stage, done: usize, total: Option<usize>) {
+    send(tx, SlideEvent::Progress(Progress { stage, done, total })).await;
+}
+
+/// Fail if `config` asks for something only the blocking pipeline does
+fn check_supported(config: &Config) -> Result<(), io::Error> {
+    let unsupported = [
+        (config.whiteboard, "--whiteboard"),
+        (config.ink_pause.is_some(), "--ink-pause"),
+        (config.adaptive.is_some(), "--adaptive"),
+        (config.segments > 1, "--segments"),
+        (config.in_memory, "--in-memory"),
+        (config.frame_cache.is_some(), "--frame-cache"),
+    ];
+    match unsupported.iter().find(|&&(set, _)| set) {
+        Some((_, option)) => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{} is not supported by the async pipeline", option),
+        )),
+        None => Ok(()),
+    }
+}
+
+/// Run the pipeline once, or once per monitor when the recording is split into several
+async fn run_decks(
+    config: &Config,
+    tx: &mpsc::Sender<SlideEvent>,
+    cancel: &CancellationToken,
+) -> Result<Manifest, Error> {
+    check_piped(config)?;
+    check_supported(config)?;
+    // Probes the input, keep it off the async workers
+    let (decks_config, decks_log) = (config.clone(), RunLog::open(config.log_file.as_deref())?);
+    let decks = tokio::task::spawn_blocking(move || monitors::decks(&rate::resolve(&decks_config, &decks_log)?, &decks_log))
+        .await
+        .map_err(io::Error::from)??;
+    if let [(None, deck)] = decks.as_slice() {
+        return run_async(deck, tx, cancel).await;
+    }
+
+    let mut manifests = Vec::new();
+    for (monitor, deck) in decks {
+        let monitor = monitor.expect("split decks are numbered");
+
+        // Slides are reported relative to the whole recording's output directory
+        let (deck_tx, mut deck_rx) = mpsc::channel(CHANNEL_DEPTH);
+        let forward_tx = tx.clone();
+        let forwarder = tokio::spawn(
+            async move {
+                while let Some(mut event) = deck_rx.recv().await {
+                    if let SlideEvent::Slide(slide) = &mut event {
+                        monitors::on_monitor(slide, monitor);
+                    }
+                    send(&forward_tx, event).await;
+                }
+            }
+            .in_current_span(),
+        );
+        let manifest = run_async(&deck, &deck_tx, cancel).await;
+        drop(deck_tx);
+        let _ = forwarder.await;
+        manifests.push((monitor, manifest?));
+    }
+
+    let combine_config = config.clone();
+    tokio::task::spawn_blocking(move || monitors::combine_decks(&combine_config, manifests))
+        .await
+        .map_err(io::Error::from)?
+}
+
+async fn run_async(
+    config: &Config,
+    tx: &mpsc::Sender<SlideEvent>,
+    cancel: &CancellationToken,
+) -> Result<Manifest, Error> {
+    let log = RunLog::open(config.log_file.as_deref())?;
+    output::check_destination(config)?;
+    let _lock = lock_output(&config.output_dir)?;
+
+    // Reads a couple of megabytes, keep it off the async workers
+    let check_config = config.clone();
+    let (fingerprint, previous) = tokio::task::spawn_blocking(move || {
+        let fingerprint = fingerprint(&check_config);
+        let previous = fingerprint.as_ref().filter(|_| !check_config.force).and_then(|f| previous_run(&check_config, f));
+        (fingerprint, previous)
+    })
+    .await
+    .map_err(io::Error::from)?;
+    if let Some(previous) = previous {
+        report_skipped(config, &previous, &log);
+        return Ok(previous);
+    }
+
+    // Work out the camera offset up front so a sync failure doesn't waste an extraction
+    let camera_offset = match (config.camera_file.clone(), config.sync) {
+        (Some(camera_file), SyncMode::Audio) => {
+            progress(tx, Stage::Syncing, 0, None).await;
+            let start = Instant::now();
+            let offset = estimate_offset(&config.input_file, &camera_file, config.max_sync_offset).await?;
+            metrics::stage_finished(Stage::Syncing, start.elapsed());
+            log.info(format_args!("Camera recording is offset by {:.2}s from the screen recording.", offset));
+            offset
+        }
+        _ => config.camera_offset,
+    };
+
+    // Removed with whatever frames are left in it when the run ends, unless --workspace keeps it
+    let mut frames_dir = Workspace::create(config)?;
+
+    let start = Instant::now();
+    tokio::fs::create_dir_all(&config.output_dir).await?;
+    extract_frames(config, frames_dir.path(), &log, tx, cancel).await?;
+    metrics::stage_finished(Stage::Extracting, start.elapsed());
+
+    let start = Instant::now();
+    let processed = process_frames(config, frames_dir.path(), &log, camera_offset, tx, cancel).await?;
+    metrics::stage_finished(Stage::Comparing, start.elapsed());
+
+    let mut manifest = build_manifest(config, camera_offset, processed.kept);
+    zoom::attach(&mut manifest, processed.zooms);
+    manifest.bad_frames = processed.bad_frames;
+    manifest.frames = processed.scores;
+    manifest.resolution_changes = processed.resolution_changes;
+    manifest.fingerprint = fingerprint;
+    revisits::record_intervals(config, &mut manifest, &processed.appearances);
+    confidence::score(&mut manifest, &processed.evidence);
+    boundaries::assign_decks(config, &mut manifest, &processed.evidence, &log);
+    let (analysis_config, analysis_log) = (config.clone(), log.clone());
+    let manifest = tokio::task::spawn_blocking(move || {
+        // Before anything that goes by what a slide says
+        ocr::read_text(&analysis_config, &mut manifest, &analysis_log)?;
+        idle::trim(&analysis_config, &mut manifest, &analysis_log)?;
+        confidence::filter(&analysis_config, &mut manifest, &analysis_log)?;
+        pauses::snap(&analysis_config, &mut manifest, &analysis_log)?;
+        speakers::tag_speakers(&analysis_config, &mut manifest, &analysis_log)?;
+        changes::record_changes(&analysis_config, &mut manifest)?;
+        identity::assign_ids(&analysis_config.output_dir, &mut manifest)?;
+        classify::tag_kinds(&analysis_config.output_dir, &mut manifest)?;
+        summarize::summarize_slides(&analysis_config, &analysis_config.output_dir, &mut manifest, &analysis_log)?;
+        catalog::describe(&analysis_config.input_file, &mut manifest);
+        canvas::pad_slides(&analysis_config, &manifest, &analysis_log)?;
+        metadata::tag_slides(&analysis_config, &manifest)?;
+        links::find_links(&analysis_config, &mut manifest, &analysis_log)?;
+        boundaries::split_output(&analysis_config, &mut manifest)?;
+        sidecar::write_sidecars(&analysis_config, &manifest)?;
+        manifest.write(&analysis_config.output_dir)?;
+        links::write_links(&analysis_config.output_dir, &manifest)?;
+        if analysis_config.report {
+            report::write(&analysis_config.output_dir, &manifest)?;
+        }
+        live::finish(&analysis_config, &manifest)?;
+        Ok::<_, Error>(manifest)
+    })
+    .await
+    .map_err(io::Error::from)??;
+    log.info(format_args!("Kept {} slide(s) in {}", manifest.slides.len(), config.output_dir.display()));
+    if !manifest.bad_frames.is_empty() {
+        log.warn(format_args!("Left out {} frame(s) that could not be decoded.", manifest.bad_frames.len()));
+    }
+
+    // Uploads are plain blocking HTTP calls, keep them off the async workers
+    let (publish_config, publish_manifest) = (config.clone(), manifest.clone());
+    tokio::task::spawn_blocking(move || output::publish(&publish_config, &publish_manifest, &log))
+        .await
+        .map_err(io::Error::from)??;
+
+    frames_dir.succeeded();
+    Ok(manifest)
+}
+
+async fn estimate_offset(screen_file: &Path, camera_file: &Path, max_offset: f64) -> Result<f64, Error> {
+    check_input(screen_file)?;
+    check_input(camera_file)?;
+    let screen_output = Command::from(sync::audio_command(screen_file))
+        .output()
+        .await
+        .map_err(Error::ffmpeg_spawn)?;
+    let camera_output = Command::from(sync::audio_command(camera_file))
+        .output()
+        .await
+        .map_err(Error::ffmpeg_spawn)?;
+
+    let screen_file = screen_file.to_path_buf();
+    let camera_file = camera_file.to_path_buf();
+    tokio::task::spawn_blocking(move || {
+        let screen = sync::audio_envelope(&screen_file, screen_output)?;
+        let camera = sync::audio_envelope(&camera_file, camera_output)?;
+        sync::best_offset(&screen, &camera, max_offset)
+    })
+    .await
+    .map_err(io::Error::from)?
+}
+
+/// Extract frames from the video into `frames_dir` using ffmpeg without blocking the runtime
+async fn extract_frames(
+    config: &Config,
+    frames_dir: &Path,
+    log: &RunLog,
+    tx: &mpsc::Sender<SlideEvent>,
+    cancel: &CancellationToken,
+) -> Result<(), Error> {
+    check_input(&config.input_file)?;
+    if !config.skip_space_check {
+        // ffprobe is quick, not worth an async version
+        let (check_config, check_dir, check_log) = (config.clone(), frames_dir.to_path_buf(), log.clone());
+        tokio::task::spawn_blocking(move || preflight::check_space(&check_config, &check_dir, &check_log))
+            .await
+            .map_err(io::Error::from)??;
+    }
+
+    // Probes the input too, keep it off the async workers
+    let (mut fitted, fit_log) = (config.clone(), log.clone());
+    let config = &tokio::task::spawn_blocking(move || {
+        fit_budget(&mut fitted, &fit_log);
+        fitted
+    })
+    .await
+    .map_err(io::Error::from)?;
+
+    let (mut attempt, mut resume_after) = (0, 0);
+    loop {
+        match run_ffmpeg(config, frames_dir, false, resume_after, log, tx, cancel).await {
+            Err(e) if should_resume(config, &e, attempt) => {
+                attempt += 1;
+                let resume_dir = frames_dir.to_path_buf();
+                resume_after = tokio::task::spawn_blocking(move || resume_point(&resume_dir)).await.map_err(io::Error::from)??;
+                let delay = retry_delay(config, attempt);
+                log.warn(format_args!("{}", e));
+                log.warn(format_args!(
+                    "Retrying in {:.0}s from {:.1}s ({} of {}).",
+                    delay.as_secs_f64(),
+                    resume_after as f64 / config.fps as f64,
+                    attempt,
+                    config.retries
+                ));
+                let until = tokio::time::Instant::now() + delay;
+                while tokio::time::Instant::now() < until {
+                    if cancel.is_cancelled() {
+                        return Err(Error::Cancelled);
+                    }
+                    tokio::time::sleep_until(until.min(tokio::time::Instant::now() + POLL_INTERVAL)).await;
+                }
+            }
+            Err(e) if should_retry(config, &e) => {
+                log.warn(format_args!("{}", e));
+                log.warn("Retrying with -err_detect ignore_err.");
+                let mut entries = tokio::fs::read_dir(frames_dir).await?;
+                while let Some(entry) = entries.next_entry().await? {
+                    tokio::fs::remove_file(entry.path()).await?;
+                }
+                return run_ffmpeg(config, frames_dir, true, 0, log, tx, cancel).await;
+            }
+            outcome => return outcome,
+        }
+    }
+}
+
+/// One ffmpeg run of `extract_frames`
+async fn run_ffmpeg(
+    config: &Config,
+    frames_dir: &Path,
+    lenient: bool,
+    resume_after: usize,
+    log: &RunLog,
+    tx: &mpsc::Sender<SlideEvent>,
+    cancel: &CancellationToken,
+) -> Result<(), Error> {
+    let mut command = Command::from(extract_command(config, frames_dir, lenient, resume_after));
+    let mut child = command.kill_on_drop(true).spawn().map_err(Error::ffmpeg_spawn)?;
+    let stdout = child.stdout.take().expect("stdout is piped");
+    let mut lines = BufReader::new(stdout).lines();
+
+    // Drain stderr so ffmpeg never blocks on it, keeping the end for the error message
+    let stderr = child.stderr.take().expect("stderr is piped");
+    let stderr_log = log.clone();
+    let stderr_reader = tokio::spawn(
+        async move {
+            let mut tail = StderrTail::default();
+            let mut lines = BufReader::new(stderr).lines();
+            while let Ok(Some(line)) = lines.next_line().await {
+                tail.push(&stderr_log, line);
+            }
+            tail
+        }
+        .in_current_span(),
+    );
+
+    let mut watchdog = Watchdog::new(config);
+    loop {
+        tokio::select! {
+            line = lines.next_line() => match line? {
+                Some(line) => {
+                    if let Some(frames) = parse_progress_frames(&line) {
+                        watchdog.frames(frames);
+                        progress(tx, Stage::Extracting, resume_after + frames, None).await;
+                    }
+                }
+                None => break,
+            },
+            _ = tokio::time::sleep(POLL_INTERVAL) => {}
+        }
+
+        if cancel.is_cancelled() {
+            child.kill().await?;
+            return Err(Error::Cancelled);
+        }
+        if let Some(reason) = watchdog.expired() {
+            child.kill().await?;
+            let stderr = stderr_reader.await.unwrap_or_default();
+            metrics::ffmpeg_failed();
+            return Err(Error::FfmpegStalled { input: config.input_file.display().to_string(), reason, stderr: stderr.into_string() });
+        }
+    }
+
+    let status = child.wait().await?;
+    let stderr = stderr_reader.await.unwrap_or_default();
+    if !status.success() {
+        metrics::ffmpeg_failed();
+        return Err(Error::ffmpeg_failed(&config.input_file, status, stderr.into_string()));
+    }
+    log.info("Frames extracted successfully.");
+
+    Ok(())
+}
+
+/// Move a kept frame from the frames directory to the output directory as `name`
+async fn move_to_output(config: &Config, frame: &Path, name: &str) -> Result<PathBuf, io::Error> {
+    let destination = config.output_dir.join(name);
+    if tokio::fs::rename(frame, &destination).await.is_err() {
+        // Different filesystems
+        tokio::fs::copy(frame, &destination).await?;
+        tokio::fs::remove_file(frame).await?;
+    }
+    Ok(destination)
+}
+
+/// Remove a frame that was not kept, unless the workspace is kept to look at them
+async fn discard(config: &Config, frame: &Path) -> Result<(), io::Error> {
+    if config.workspace != WorkspacePolicy::Keep {
+        tokio::fs::remove_file(frame).await?;
+    }
+    Ok(())
+}
+
+/// Process extracted frames and filter out non-unique frames without blocking the runtime
+async fn process_frames(
+    config: &Config,
+    frames_dir: &Path,
+    log: &RunLog,
+    camera_offset: f64,
+    tx: &mpsc::Sender<SlideEvent>,
+    cancel: &CancellationToken,
+) -> Result<Processed, Error> {
+    let mut frame_files = Vec::new();
+    let mut entries = tokio::fs::read_dir(frames_dir).await?;
+    while let Some(entry) = entries.next_entry().await? {
+        let path = entry.path();
+        if is_frame_file(&path) {
+            frame_files.push(path);
+        }
+    }
+
+    sort_frames(&mut frame_files);
+
+    let total = frame_files.len();
+    let mut dedup = Deduplicator::new(config, log);
+    let mut budget = OutputBudget::new(config);
+    let mut stabilizer = Stabilizer::new(config);
+    let mut revisits = Revisits::new(config, log);
+    let mut stitcher = Stitcher::default();
+    let mut zooms = Zooms::default();
+    let template = name_template(config)?;
+    let mut kept = Vec::new();
+    let mut bad_frames = Vec::new();
+    let mut scores = Vec::new();
+    let mut resolution_changes = Vec::new();
+    let mut evidence = Vec::new();
+    let mut appearances = Vec::new();
+    // The decision on the frame the pending slide appeared at
+    let mut change = None;
+    // Path, score and position of the frame waiting for the slide to settle
+    let mut held: Option<(PathBuf, usize, usize)> = None;
+
+    for (position, frame) in frame_files.into_iter().enumerate() {
+        if cancel.is_cancelled() {
+            return Err(Error::Cancelled);
+        }
+
+        // Decoding and comparing is CPU-bound, so do it off the async workers
+        let path = frame.clone();
+        let (returned, decision) = tokio::task::spawn_blocking(move || match open_frame(&path) {
+            Ok(current_image) => {
+                let decision = dedup.observe(current_image);
+                (dedup, Ok(decision))
+            }
+            Err(source) => (dedup, Err(Error::BadFrame { path, source })),
+        })
+        .await
+        .map_err(io::Error::from)?;
+        dedup = returned;
+
+        let decision = match decision {
+            Ok(decision) => decision,
+            Err(Error::BadFrame { path, source: e }) => {
+                bad_frames.push(handle_bad_frame(config, log, path, e)?);
+                progress(tx, Stage::Comparing, position + 1, Some(total)).await;
+                continue;
+            }
+            Err(e) => return Err(e),
+        };
+
+        decision.log(log, &frame);
+        if config.stitch_scrolls {
+            stitcher.observe(position, &decision, dedup.reference().expect("the frame was just observed"));
+        }
+        if config.save_zooms {
+            zooms.observe(position, &decision, dedup.reference().expect("the frame was just observed"));
+        }
+        let file = frame.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+        if config.report {
+            report::save_thumbnail(&config.output_dir, &file, dedup.reference().expect("the frame was just observed"))?;
+        }
+        scores.push(frame_score(config, position, &file, decision));
+        resolution_changes.extend(resolution_change(config, position, &file, decision));
+        if decision.is_kept() {
+            change = Some(decision);
+        }
+
+        let settled = stabilizer.settle(position, &decision);
+        if settled != Settled::Drop {
+            if let Some((held_frame, _, _)) = held.take() {
+                // Otherwise the held frame just makes way for a later one of the same slide
+                if decision.is_kept() {
+                    log.debug(format_args!("Dropping frame {:?}, the slide was still changing.", held_frame));
+                }
+                metrics::frame_examined(false);
+                discard(config, &held_frame).await?;
+            }
+        }
+        match settled {
+            Settled::Drop => {
+                metrics::frame_examined(false);
+                discard(config, &frame).await?;
+            }
+            Settled::Hold => held = Some((frame, scores.len() - 1, position)),
+            Settled::Commit { start } => match examine_commit(&mut revisits, change, &dedup).await? {
+                Committed::Revisit(earlier) => {
+                    log.debug(format_args!("Frame {} shows slide {} again.", file, earlier + 1));
+                    metrics::frame_examined(false);
+                    discard(config, &frame).await?;
+                    appearances.push((start, earlier));
+                }
+                Committed::New(slide_evidence) => {
+                    metrics::frame_examined(true);
+                    scores.last_mut().expect("just pushed").kept = true;
+                    appearances.push((start, kept.len()));
+                    evidence.push(slide_evidence);
+                    let name = slide_file_name(config, template.as_ref(), kept.len() + 1, start, position, &file);
+                    let destination = move_to_output(config, &frame, &name).await?;
+                    budget.record(&destination, position + 1, Some(total), &mut dedup, log)?;
+                    let slide = slide_entry(config, camera_offset, kept.len() + 1, start, &destination);
+                    send(tx, SlideEvent::Slide(slide)).await;
+                    kept.push((start, destination));
+                    live::update(config, &kept)?;
+                }
+            },
+        }
+
+        progress(tx, Stage::Comparing, position + 1, Some(total)).await;
+    }
+
+    // The video ended before the last slide settled, it is the best there is
+    if let (Some(start), Some((frame, score, held_position))) = (stabilizer.finish(), held.take()) {
+        let file = frame.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+        match examine_commit(&mut revisits, change, &dedup).await? {
+            Committed::Revisit(earlier) => {
+                log.debug(format_args!("Frame {} shows slide {} again.", file, earlier + 1));
+                metrics::frame_examined(false);
+                discard(config, &frame).await?;
+                appearances.push((start, earlier));
+            }
+            Committed::New(slide_evidence) => {
+                metrics::frame_examined(true);
+                scores[score].kept = true;
+                appearances.push((start, kept.len()));
+                evidence.push(slide_evidence);
+                let name = slide_file_name(config, template.as_ref(), kept.len() + 1, start, held_position, &file);
+                let destination = move_to_output(config, &frame, &name).await?;
+                budget.record(&destination, total, Some(total), &mut dedup, log)?;
+                let slide = slide_entry(config, camera_offset, kept.len() + 1, start, &destination);
+                send(tx, SlideEvent::Slide(slide)).await;
+                kept.push((start, destination));
+                live::update(config, &kept)?;
+            }
+        }
+    }
+
+    stitcher.write(&kept)?;
+    let zooms = zooms.write(&kept, &config.output_dir)?;
+    report_threshold(&dedup, log);
+    record_provenance(config, &mut scores, &appearances);
+    Ok(Processed { kept, bad_frames, scores, resolution_changes, evidence, appearances, zooms })
+}
+
+/// What a committed frame turned out to be
+enum Committed {
+    /// The slide at this position among the kept ones, shown again
+    Revisit(usize),
+    /// A new slide, with what its confidence is scored from
+    New(Evidence),
+}
+
+/// Look at the frame `dedup` observed last once its slide is committed, off the async workers
+async fn examine_commit(
+    revisits: &mut Option<Revisits>,
+    change: Option<Decision>,
+    dedup: &Deduplicator,
+) -> Result<Committed, io::Error> {
+    let image = dedup.reference().expect("the committed frame was observed last").clone();
+    let mut tracker = revisits.take();
+    let (tracker, committed) = tokio::task::spawn_blocking(move || {
+        let committed = match tracker.as_mut().and_then(|tracker| tracker.recognize(&image)) {
+            Some(earlier) => Committed::Revisit(earlier),
+            None => Committed::New(Evidence::new(change, &image)),
+        };
+        (tracker, committed)
+    })
+    .await
+    .map_err(io::Error::from)?;
+    *revisits = tracker;
+    Ok(committed)
+}
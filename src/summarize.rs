@@ -0,0 +1,160 @@
+//! `--summarize`: a short title and summary for each slide from an
+//! OpenAI-compatible chat completions API, for the manifest, the exports'
+//! headings and chapters, and `{title}` in `--name-template`.
+//!
+//! Each slide's OCR text, and with `--summarize-images` a copy of the slide
+//! 512 pixels wide, is sent to `<URL>/chat/completions` with the key from
+//! `OPENAI_API_KEY` as a bearer token, asking for a JSON object with a title
+//! and summary. Nothing is sent unless asked to. A slide the API gives no
+//! answer for is warned about and left without a title.
+
+use base64::Engine as _;
+use image::codecs::jpeg::JpegEncoder;
+use image::imageops::FilterType;
+use image::GenericImageView;
+use serde_json::{json, Value};
+use std::collections::HashSet;
+use std::env;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::time::Duration;
+
+use crate::config::Config;
+use crate::error::Error;
+use crate::manifest::{Manifest, Slide};
+use crate::naming::{slug, TITLE_PLACEHOLDER};
+use crate::runlog::RunLog;
+use crate::source::open_frame;
+
+/// Model asked unless `--summarize-model` says otherwise
+pub const DEFAULT_MODEL: &str = "gpt-4o-mini";
+/// Environment variable holding the API key
+const API_KEY_VAR: &str = "OPENAI_API_KEY";
+/// Width of the copy of a slide sent with `--summarize-images`
+const IMAGE_WIDTH: u32 = 512;
+/// How long one answer may take
+const TIMEOUT: Duration = Duration::from_secs(120);
+/// What `{title}` becomes for a slide without a title
+const UNTITLED: &str = "untitled";
+
+const INSTRUCTIONS: &str = "You are given the text read off one presentation slide with OCR, which may be garbled. \
+Answer with a JSON object only: {\"title\": a title for the slide of at most eight words, \
+\"summary\": one or two sentences on what the slide says}.";
+
+/// Title and summarize every slide of `manifest` in `output_dir` that has no title yet, if
+/// `config.summarize` asks for it, then fill in `{title}` in the slides' file names
+pub fn summarize_slides(config: &Config, output_dir: &Path, manifest: &mut Manifest, log: &RunLog) -> Result<(), Error> {
+    let Some(url) = &config.summarize else {
+        return Ok(());
+    };
+    let key = env::var(API_KEY_VAR).ok().filter(|key| !key.is_empty());
+    let model = config.summarize_model.as_deref().unwrap_or(DEFAULT_MODEL);
+    let endpoint = format!("{}/chat/completions", url.trim_end_matches('/'));
+    let mut titled = 0;
+    for slide in manifest.slides.iter_mut().filter(|slide| slide.title.is_none()) {
+        let image = if config.summarize_images { Some(image_url(&output_dir.join(&slide.file))?) } else { None };
+        match ask(&endpoint, key.as_deref(), model, slide.text.as_deref().unwrap_or_default(), image.as_deref()) {
+            Ok((title, summary)) => {
+                slide.title = Some(title);
+                slide.summary = summary;
+                titled += 1;
+            }
+            Err(e) => log.warn(format_args!("No title for slide {}: {}", slide.index, e)),
+        }
+    }
+    log.info(format_args!("Titled {} slide(s) with {}.", titled, model));
+    name_slides(output_dir, manifest)?;
+    Ok(())
+}
+
+/// A data URL of a small JPEG of the image at `path`
+fn image_url(path: &Path) -> Result<String, Error> {
+    let image = open_frame(path).map_err(|source| Error::BadFrame { path: path.to_path_buf(), source })?;
+    let (width, height) = image.dimensions();
+    let height = ((height as f64 * IMAGE_WIDTH as f64 / width.max(1) as f64).round() as u32).max(1);
+    let small = image.resize_exact(IMAGE_WIDTH, height, FilterType::Triangle).to_rgb8();
+    let mut jpeg = Vec::new();
+    small
+        .write_with_encoder(JpegEncoder::new_with_quality(&mut jpeg, 80))
+        .map_err(|e| io::Error::other(format!("Error encoding {}: {}", path.display(), e)))?;
+    Ok(format!("data:image/jpeg;base64,{}", base64::engine::general_purpose::STANDARD.encode(jpeg)))
+}
+
+/// The title and summary `model` at `endpoint` gives a slide showing `text`, and `image` if given
+fn ask(endpoint: &str, key: Option<&str>, model: &str, text: &str, image: Option<&str>) -> Result<(String, Option<String>), io::Error> {
+    let text = if text.trim().is_empty() { "(no text was read off this slide)" } else { text };
+    let mut content = vec![json!({ "type": "text", "text": text })];
+    if let Some(image) = image {
+        content.push(json!({ "type": "image_url", "image_url": { "url": image } }));
+    }
+    let body = json!({
+        "model": model,
+        "messages": [
+            { "role": "system", "content": INSTRUCTIONS },
+            { "role": "user", "content": content },
+        ],
+    });
+    let mut request = ureq::post(endpoint).timeout(TIMEOUT);
+    if let Some(key) = key {
+        request = request.set("Authorization", &format!("Bearer {}", key));
+    }
+    let response: Value = match request.send_json(body) {
+        Ok(response) => response.into_json()?,
+        Err(ureq::Error::Status(code, response)) => {
+            return Err(io::Error::other(format!("{} answered {}: {}", endpoint, code, response.into_string().unwrap_or_default())));
+        }
+        Err(e) => return Err(io::Error::other(e.to_string())),
+    };
+    let answer = response["choices"][0]["message"]["content"]
+        .as_str()
+        .ok_or_else(|| io::Error::other(format!("{} answered without a message", endpoint)))?;
+    parse_answer(answer).ok_or_else(|| io::Error::other(format!("the answer has no title: {:?}", answer)))
+}
+
+/// The title and summary in `answer`, a JSON object possibly in a code block; its first line
+/// as the title if it isn't one
+fn parse_answer(answer: &str) -> Option<(String, Option<String>)> {
+    let json = answer.trim().trim_start_matches("```json").trim_start_matches("```").trim_end_matches("```");
+    let clean = |value: &Value| value.as_str().map(str::trim).filter(|s| !s.is_empty()).map(str::to_string);
+    match serde_json::from_str::<Value>(json) {
+        Ok(value) => clean(&value["title"]).map(|title| (title, clean(&value["summary"]))),
+        Err(_) => answer.lines().map(str::trim).find(|line| !line.is_empty()).map(|title| (title.to_string(), None)),
+    }
+}
+
+/// Rename the slides named with `{title}` after their titles, and their close-ups and diffs with them
+fn name_slides(output_dir: &Path, manifest: &mut Manifest) -> Result<(), io::Error> {
+    let mut taken: HashSet<String> = manifest.slides.iter().map(|slide| slide.file.clone()).collect();
+    for slide in manifest.slides.iter_mut().filter(|slide| slide.file.contains(TITLE_PLACEHOLDER)) {
+        let title = slide.title.as_deref().map(slug).filter(|slug| !slug.is_empty());
+        let title = title.as_deref().unwrap_or(UNTITLED);
+        let mut file = slide.file.replace(TITLE_PLACEHOLDER, title);
+        // Two slides with the same title
+        let mut n = 2;
+        while taken.contains(&file) {
+            file = slide.file.replace(TITLE_PLACEHOLDER, &format!("{}-{}", title, n));
+            n += 1;
+        }
+        rename(output_dir, slide, &file)?;
+        taken.insert(file);
+    }
+    Ok(())
+}
+
+/// Rename `slide`'s image to `file`, and its close-ups and diff to match
+fn rename(output_dir: &Path, slide: &mut Slide, file: &str) -> Result<(), io::Error> {
+    let (old_stem, new_stem) = (stem(&slide.file), stem(file));
+    fs::rename(output_dir.join(&slide.file), output_dir.join(file))?;
+    slide.file = file.to_string();
+    for extra in slide.zooms.iter_mut().chain(slide.diff.as_mut()) {
+        let renamed = extra.replacen(&old_stem, &new_stem, 1);
+        fs::rename(output_dir.join(&*extra), output_dir.join(&renamed))?;
+        *extra = renamed;
+    }
+    Ok(())
+}
+
+fn stem(file: &str) -> String {
+    Path::new(file).file_stem().unwrap_or_default().to_string_lossy().into_owned()
+}
@@ -0,0 +1,125 @@
+use std::io;
+use std::path::Path;
+use std::process::{Command, Output};
+
+use crate::error::Error;
+use crate::extract::check_input;
+
+/// Sample rate ffmpeg resamples the audio track to before analysis
+const SAMPLE_RATE: usize = 8000;
+/// Samples folded into one envelope value (20ms blocks, 50 values per second)
+const BLOCK_SIZE: usize = 160;
+/// Envelope values per second of audio
+const ENVELOPE_RATE: f64 = (SAMPLE_RATE / BLOCK_SIZE) as f64;
+/// Only the start of each recording is needed to find the offset
+const ANALYSIS_SECONDS: u32 = 600;
+
+/// Build the ffmpeg invocation that decodes the first minutes of an audio
+/// track to raw mono PCM on stdout
+pub fn audio_command(input_file: &Path) -> Command {
+    let mut command = Command::new("ffmpeg");
+    command
+        .arg("-v")
+        .arg("error")
+        .arg("-i")
+        .arg(input_file)
+        .arg("-t")
+        .arg(ANALYSIS_SECONDS.to_string())
+        .arg("-vn")
+        .arg("-ac")
+        .arg("1")
+        .arg("-ar")
+        .arg(SAMPLE_RATE.to_string())
+        .arg("-f")
+        .arg("s16le")
+        .arg("-");
+    command
+}
+
+/// Turn the output of `audio_command` into a normalized energy envelope
+pub fn audio_envelope(input_file: &Path, output: Output) -> Result<Vec<f64>, Error> {
+    if !output.status.success() {
+        return Err(Error::ffmpeg_failed(
+            input_file,
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim(),
+        ));
+    }
+
+    let samples: Vec<f64> = output
+        .stdout
+        .chunks_exact(2)
+        .map(|b| i16::from_le_bytes([b[0], b[1]]) as f64)
+        .collect();
+
+    let mut envelope: Vec<f64> = samples
+        .chunks(BLOCK_SIZE)
+        .map(|block| (block.iter().map(|s| s * s).sum::<f64>() / block.len() as f64).sqrt())
+        .collect();
+
+    // Normalize so loud and quiet microphones correlate on equal terms
+    let len = envelope.len().max(1) as f64;
+    let mean = envelope.iter().sum::<f64>() / len;
+    let std_dev = (envelope.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / len).sqrt();
+    if std_dev > 0.0 {
+        for v in envelope.iter_mut() {
+            *v = (*v - mean) / std_dev;
+        }
+    }
+
+    Ok(envelope)
+}
+
+/// Estimate how many seconds the camera clock runs ahead of the screen clock
+/// by cross-correlating the audio energy of both recordings.
+///
+/// A slide shown at screen time `t` appears at camera time `t + offset`.
+pub fn estimate_offset(screen_file: &Path, camera_file: &Path, max_offset: f64) -> Result<f64, Error> {
+    check_input(screen_file)?;
+    check_input(camera_file)?;
+    let screen = audio_envelope(screen_file, audio_command(screen_file).output().map_err(Error::ffmpeg_spawn)?)?;
+    let camera = audio_envelope(camera_file, audio_command(camera_file).output().map_err(Error::ffmpeg_spawn)?)?;
+    best_offset(&screen, &camera, max_offset)
+}
+
+/// Find the lag between two envelopes with the strongest correlation
+pub fn best_offset(screen: &[f64], camera: &[f64], max_offset: f64) -> Result<f64, Error> {
+    let max_lag = (max_offset * ENVELOPE_RATE) as i64;
+    // Require a decent overlap so edge lags with a handful of samples can't win
+    let min_overlap = (screen.len().min(camera.len()) / 4).max(1);
+
+    let mut best: Option<(i64, f64)> = None;
+    for lag in -max_lag..=max_lag {
+        let mut sum = 0.0;
+        let mut overlap = 0;
+        for (i, s) in screen.iter().enumerate() {
+            let j = i as i64 + lag;
+            if j < 0 {
+                continue;
+            }
+            match camera.get(j as usize) {
+                Some(c) => {
+                    sum += s * c;
+                    overlap += 1;
+                }
+                None => break,
+            }
+        }
+
+        if overlap < min_overlap {
+            continue;
+        }
+
+        let score = sum / overlap as f64;
+        if best.is_none_or(|(_, best_score)| score > best_score) {
+            best = Some((lag, score));
+        }
+    }
+
+    match best {
+        Some((lag, _)) => Ok(lag as f64 / ENVELOPE_RATE),
+        None => Err(Error::Io(io::Error::other(
+            "Audio tracks are too short to cross-correlate, pass --camera-offset instead",
+        ))),
+    }
+}
@@ -0,0 +1,87 @@
+use image::{DynamicImage, GenericImageView};
+
+use crate::compare::{sampled, Comparer};
+
+/// Edge length in pixels of the square tiles text is looked for in
+const TILE_SIZE: u32 = 16;
+/// Difference in brightness between neighbouring pixels that makes an edge, 0 to 255
+const EDGE_CONTRAST: i16 = 48;
+/// Share of a tile's pixels that must be edges for it to hold text; fewer is background or a lone line
+const MIN_EDGE_RATIO: f64 = 0.04;
+/// Above this share of edges a tile is a photo or noise rather than text
+const MAX_EDGE_RATIO: f64 = 0.5;
+
+/// Which tiles of `image`, row by row, look like text: many sharp edges, but not everywhere
+pub fn text_tiles(image: &DynamicImage) -> Vec<bool> {
+    let luma = image.to_luma8();
+    let (width, height) = luma.dimensions();
+    let columns = width.div_ceil(TILE_SIZE);
+    let mut edges = vec![0u32; (columns * height.div_ceil(TILE_SIZE)) as usize];
+    for y in 0..height {
+        for x in 1..width {
+            let contrast = luma.get_pixel(x, y)[0] as i16 - luma.get_pixel(x - 1, y)[0] as i16;
+            if contrast.abs() > EDGE_CONTRAST {
+                edges[((y / TILE_SIZE) * columns + x / TILE_SIZE) as usize] += 1;
+            }
+        }
+    }
+    edges
+        .iter()
+        .enumerate()
+        .map(|(index, &count)| {
+            let (tx, ty) = (index as u32 % columns, index as u32 / columns);
+            let pixels = (((tx + 1) * TILE_SIZE).min(width) - tx * TILE_SIZE) * (((ty + 1) * TILE_SIZE).min(height) - ty * TILE_SIZE);
+            let ratio = count as f64 / pixels.max(1) as f64;
+            (MIN_EDGE_RATIO..=MAX_EDGE_RATIO).contains(&ratio)
+        })
+        .collect()
+}
+
+/// Counts differing pixels in areas that look like text `weight` times, so a
+/// one-word edit on a mostly empty slide is not lost among the unchanged background
+pub struct TextWeighting {
+    weight: f64,
+    /// Text tiles of the frame compared last, the reference of the next comparison
+    last: Option<((u32, u32), Vec<bool>)>,
+}
+
+impl TextWeighting {
+    pub fn new(weight: f64) -> Self {
+        TextWeighting { weight: weight.max(1.0), last: None }
+    }
+
+    /// Compare two frames and return the weighted share of differing pixels, text in either frame counting
+    pub fn difference_ratio(&mut self, comparer: &mut Comparer, img1: &DynamicImage, img2: &DynamicImage) -> f64 {
+        let dimensions = img1.dimensions();
+        if dimensions != img2.dimensions() {
+            self.last = Some((img2.dimensions(), text_tiles(img2)));
+            return 1.0;
+        }
+        let text1 = match self.last.take() {
+            Some((size, tiles)) if size == dimensions => tiles,
+            _ => text_tiles(img1),
+        };
+        let text2 = text_tiles(img2);
+
+        let (width, height) = dimensions;
+        let columns = width.div_ceil(TILE_SIZE);
+        let stride = comparer.stride();
+        let tile_diffs = comparer.tile_diffs(img1, img2, TILE_SIZE);
+
+        let (mut diff, mut total) = (0.0, 0.0);
+        for (index, &diffs) in tile_diffs.iter().enumerate() {
+            let (tx, ty) = (index as u32 % columns, index as u32 / columns);
+            let pixels = sampled(tx * TILE_SIZE, ((tx + 1) * TILE_SIZE).min(width), stride)
+                * sampled(ty * TILE_SIZE, ((ty + 1) * TILE_SIZE).min(height), stride);
+            let weight = if text1[index] || text2[index] { self.weight } else { 1.0 };
+            diff += weight * diffs as f64;
+            total += weight * pixels as f64;
+        }
+        self.last = Some((dimensions, text2));
+
+        if total == 0.0 {
+            return 0.0;
+        }
+        diff / total
+    }
+}
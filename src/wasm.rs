@@ -0,0 +1,51 @@
+//! Browser bindings for the slide detection core.
+//!
+//! There is no ffmpeg here: the page decodes the video itself (e.g. with
+//! WebCodecs) and hands each sampled frame over as a byte buffer, in order.
+//! The decisions are exactly the ones the command line tool would make.
+//!
+//! ```js
+//! const detector = new SlideDetector(0.01, false, 3);
+//! if (detector.pushRgba(frame.codedWidth, frame.codedHeight, pixels)) { ... }
+//! ```
+
+use image::{DynamicImage, RgbaImage};
+use wasm_bindgen::prelude::*;
+
+use crate::config::Config;
+use crate::dedup::Deduplicator;
+use crate::runlog::RunLog;
+
+/// Decides, frame by frame, which frames start a new slide
+#[wasm_bindgen]
+pub struct SlideDetector {
+    dedup: Deduplicator,
+}
+
+#[wasm_bindgen]
+impl SlideDetector {
+    /// Same meaning as `--threshold`, `--ignore-embedded-video` and `--motion-streak`
+    #[wasm_bindgen(constructor)]
+    pub fn new(threshold: f64, ignore_embedded_video: bool, motion_streak: u32) -> SlideDetector {
+        let mut config = Config::new(String::new());
+        config.threshold = threshold;
+        config.ignore_embedded_video = ignore_embedded_video;
+        config.motion_streak = motion_streak;
+        SlideDetector { dedup: Deduplicator::new(&config, &RunLog::default()) }
+    }
+
+    /// Judge the next frame given as tightly packed RGBA pixels; true if it is kept as a slide
+    #[wasm_bindgen(js_name = pushRgba)]
+    pub fn push_rgba(&mut self, width: u32, height: u32, pixels: Vec<u8>) -> Result<bool, JsError> {
+        let image = RgbaImage::from_raw(width, height, pixels)
+            .ok_or_else(|| JsError::new("pixel buffer does not match width * height * 4"))?;
+        Ok(self.dedup.observe(DynamicImage::ImageRgba8(image)).is_kept())
+    }
+
+    /// Judge the next frame given as an encoded image (PNG, JPEG, ...); true if it is kept as a slide
+    #[wasm_bindgen(js_name = pushEncoded)]
+    pub fn push_encoded(&mut self, data: &[u8]) -> Result<bool, JsError> {
+        let image = image::load_from_memory(data).map_err(|e| JsError::new(&e.to_string()))?;
+        Ok(self.dedup.observe(image).is_kept())
+    }
+}
@@ -0,0 +1,148 @@
+//! Notifications of finished work: a JSON POST per job to `--webhook`, and a
+//! summary of a whole batch to Slack or by email.
+
+use serde::Serialize;
+use std::io::{Error, ErrorKind, Write};
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::Duration;
+
+use crate::batch::BatchResult;
+use crate::links::clock;
+
+/// Attempts made before a webhook delivery is given up on
+const ATTEMPTS: u32 = 3;
+/// Delay before the first retry, doubled for each one after it
+const RETRY_DELAY: Duration = Duration::from_secs(1);
+
+/// How a job ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Outcome {
+    Finished,
+    Failed,
+    Cancelled,
+}
+
+/// JSON body POSTed to the webhook when a job completes
+#[derive(Debug, Clone, Serialize)]
+pub struct JobCompletion {
+    /// Job id in server mode, output directory name in batch mode
+    pub video_id: String,
+    pub input_file: String,
+    pub outcome: Outcome,
+    pub slide_count: Option<usize>,
+    /// Where the manifest was written on the machine that ran the job
+    pub manifest_path: Option<String>,
+    /// Where the manifest can be fetched from, in server mode
+    pub manifest_url: Option<String>,
+    pub error: Option<String>,
+}
+
+/// POST the completion to `url`, retrying a few times on failure
+pub fn notify(url: &str, completion: &JobCompletion) -> Result<(), Error> {
+    post(url, completion)
+}
+
+/// POST `body` as JSON to `url`, retrying a few times on failure
+fn post(url: &str, body: &impl Serialize) -> Result<(), Error> {
+    let mut delay = RETRY_DELAY;
+    let mut attempt = 1;
+    loop {
+        match ureq::post(url).send_json(body) {
+            Ok(_) => return Ok(()),
+            Err(e) if attempt >= ATTEMPTS => {
+                return Err(Error::other(format!("Webhook {} failed after {} attempts: {}", url, attempt, e)));
+            }
+            Err(_) => {
+                thread::sleep(delay);
+                delay *= 2;
+                attempt += 1;
+            }
+        }
+    }
+}
+
+/// Deliver a completion without holding up the caller; failures are only reported
+pub fn notify_in_background(url: &str, completion: JobCompletion) {
+    let url = url.to_string();
+    thread::spawn(move || {
+        if let Err(e) = notify(&url, &completion) {
+            tracing::warn!("{}", e);
+        }
+    });
+}
+
+/// What a finished batch reports to the people waiting on it
+#[derive(Debug, Clone)]
+pub struct BatchSummary<'a> {
+    pub results: &'a [BatchResult],
+    pub elapsed: Duration,
+}
+
+impl BatchSummary<'_> {
+    fn failed(&self) -> impl Iterator<Item = &BatchResult> {
+        self.results.iter().filter(|result| result.error.is_some())
+    }
+
+    /// One line fit for a mail subject
+    pub fn headline(&self) -> String {
+        format!("Slide extraction: {} video(s), {} failed", self.results.len(), self.failed().count())
+    }
+
+    /// Counts, then every failure with its reason
+    pub fn text(&self) -> String {
+        let slides: usize = self.results.iter().filter_map(|result| result.slides).sum();
+        let mut text = format!(
+            "Batch finished after {}: {} video(s) processed, {} slide(s) extracted, {} failed.\n",
+            clock(self.elapsed.as_secs_f64()),
+            self.results.len(),
+            slides,
+            self.failed().count()
+        );
+        if self.failed().next().is_some() {
+            text.push_str("\nFailed:\n");
+            for result in self.failed() {
+                text.push_str(&format!("- {}: {}\n", result.input_file.display(), result.error.as_deref().unwrap_or_default()));
+            }
+        }
+        text
+    }
+}
+
+/// Post the summary to a Slack incoming webhook
+pub fn notify_slack(url: &str, summary: &BatchSummary) -> Result<(), Error> {
+    post(url, &serde_json::json!({ "text": summary.text() }))
+}
+
+/// Mail the summary to `recipients` with the system's `sendmail`, which
+/// brings the relay, TLS and authentication of the local mail setup
+pub fn send_email(recipients: &[String], summary: &BatchSummary) -> Result<(), Error> {
+    let mut child = Command::new("sendmail")
+        .arg("-t")
+        .arg("-i")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| match e.kind() {
+            ErrorKind::NotFound => Error::new(ErrorKind::NotFound, "sendmail was not found"),
+            _ => e,
+        })?;
+    let message = format!(
+        "To: {}\nSubject: {}\nContent-Type: text/plain; charset=utf-8\n\n{}",
+        recipients.join(", "),
+        summary.headline(),
+        summary.text()
+    );
+    child.stdin.take().expect("stdin is piped").write_all(message.as_bytes())?;
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        return Err(Error::other(format!(
+            "sendmail failed ({}): {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(())
+}
@@ -0,0 +1,258 @@
+//! The working directory each run samples its frames into, under `--tmp-dir`
+//! or the system's temp directory, and `gc` for the ones left behind.
+//!
+//! A workspace is removed when its run ends unless `--workspace` says to
+//! keep it. Runs killed outright leave theirs behind regardless; `gc` removes
+//! those once they are old enough, skipping workspaces a live run still holds.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use tempfile::TempDir;
+
+use crate::config::{Config, WorkspacePolicy};
+use crate::error::format_size;
+use crate::lock::{try_lock_dir, DirLock, LOCK_FILE};
+
+/// Start of the name of every workspace directory
+pub const WORKSPACE_PREFIX: &str = "videoslides-";
+
+/// A run's working directory, locked while the run holds it and removed or
+/// left behind according to `config.workspace` when dropped
+pub struct Workspace {
+    root: Option<TempDir>,
+    frames: PathBuf,
+    lock: Option<DirLock>,
+    policy: WorkspacePolicy,
+    cap: Option<u64>,
+    succeeded: bool,
+}
+
+impl Workspace {
+    /// Fresh workspace under `config.tmp_dir`, or the system's temp directory
+    pub fn create(config: &Config) -> Result<Self, io::Error> {
+        let parent = config.tmp_dir.clone().unwrap_or_else(std::env::temp_dir);
+        fs::create_dir_all(&parent)?;
+        let root = tempfile::Builder::new().prefix(WORKSPACE_PREFIX).tempdir_in(parent)?;
+        let lock = try_lock_dir(root.path())?;
+        let frames = root.path().join("frames");
+        fs::create_dir(&frames)?;
+        Ok(Workspace { root: Some(root), frames, lock, policy: config.workspace, cap: config.workspace_cap, succeeded: false })
+    }
+
+    /// Directory the sampled frames go into
+    pub fn path(&self) -> &Path {
+        &self.frames
+    }
+
+    /// Mark the run as finished without error, for `--workspace keep-on-error`
+    pub fn succeeded(&mut self) {
+        self.succeeded = true;
+    }
+}
+
+impl Drop for Workspace {
+    fn drop(&mut self) {
+        let keep = match self.policy {
+            WorkspacePolicy::Keep => true,
+            WorkspacePolicy::KeepOnError => !self.succeeded,
+            WorkspacePolicy::Delete => false,
+        };
+        // Release the lock first, an open file would keep the directory from being removed on Windows
+        self.lock.take();
+        let Some(root) = self.root.take() else {
+            return;
+        };
+        if !keep {
+            return;
+        }
+
+        let root = root.into_path();
+        tracing::info!("Left the working directory {} behind", root.display());
+        let (Some(cap), Some(parent)) = (self.cap, root.parent()) else {
+            return;
+        };
+        let options = GcOptions { dir: parent.to_path_buf(), older_than: None, max_size: Some(cap), dry_run: false };
+        match collect(&options, Some(&root)) {
+            Ok(removed) if !removed.is_empty() => tracing::info!(
+                "Removed {} older working director(ies) to stay within {}",
+                removed.len(),
+                format_size(cap)
+            ),
+            Ok(_) => {}
+            Err(e) => tracing::warn!("Could not trim the working directories in {}: {}", parent.display(), e),
+        }
+    }
+}
+
+/// What `gc` removes
+#[derive(Debug, Clone)]
+pub struct GcOptions {
+    /// Directory holding the workspaces, `--tmp-dir` of the runs
+    pub dir: PathBuf,
+    /// Remove every workspace untouched for this long
+    pub older_than: Option<Duration>,
+    /// Then remove the oldest of the rest until they take at most this many bytes together
+    pub max_size: Option<u64>,
+    /// Only report what would be removed
+    pub dry_run: bool,
+}
+
+/// A workspace `gc` removed
+#[derive(Debug, Clone, PartialEq)]
+pub struct Removed {
+    pub path: PathBuf,
+    /// Bytes it took
+    pub size: u64,
+}
+
+/// Remove the workspaces in `options.dir` that no run holds and are too old or don't fit in `options.max_size`
+pub fn gc(options: &GcOptions) -> Result<Vec<Removed>, io::Error> {
+    collect(options, None)
+}
+
+/// `gc`, never removing `keep` but counting it towards the size
+fn collect(options: &GcOptions, keep: Option<&Path>) -> Result<Vec<Removed>, io::Error> {
+    let mut workspaces = Vec::new();
+    for entry in fs::read_dir(&options.dir)? {
+        let entry = entry?;
+        let name = entry.file_name();
+        if !entry.file_type()?.is_dir() || !name.to_string_lossy().starts_with(WORKSPACE_PREFIX) {
+            continue;
+        }
+        let path = entry.path();
+        let created = entry.metadata()?.modified()?;
+        // A run still holds it
+        let Some(lock) = try_lock_dir(&path)? else {
+            continue;
+        };
+        // An empty workspace is as old as the directory
+        let (size, modified) = match usage(&path)? {
+            (size, Some(modified)) => (size, modified),
+            (size, None) => (size, created),
+        };
+        workspaces.push((path, size, modified, lock));
+    }
+    workspaces.sort_by_key(|&(_, _, modified, _)| modified);
+
+    let now = SystemTime::now();
+    let mut total: u64 = workspaces.iter().map(|&(_, size, _, _)| size).sum();
+    let mut removed = Vec::new();
+    for (path, size, modified, lock) in workspaces {
+        if keep == Some(path.as_path()) {
+            continue;
+        }
+        let age = now.duration_since(modified).unwrap_or_default();
+        let too_old = options.older_than.is_some_and(|older_than| age >= older_than);
+        let too_big = options.max_size.is_some_and(|max_size| total > max_size);
+        if !too_old && !too_big {
+            continue;
+        }
+        if !options.dry_run {
+            drop(lock);
+            fs::remove_dir_all(&path)?;
+        }
+        total -= size;
+        removed.push(Removed { path, size });
+    }
+    Ok(removed)
+}
+
+/// Bytes of the files under `path` and the last time one of them changed, `None` if there are none
+fn usage(path: &Path) -> Result<(u64, Option<SystemTime>), io::Error> {
+    let (mut size, mut modified) = (0, None);
+    for entry in fs::read_dir(path)? {
+        let entry = entry?;
+        let metadata = entry.metadata()?;
+        let (entry_size, entry_modified) = if metadata.is_dir() {
+            usage(&entry.path())?
+        } else if entry.file_name() == LOCK_FILE {
+            // Taking the lock must not make the workspace look fresh
+            continue;
+        } else {
+            (metadata.len(), Some(metadata.modified()?))
+        };
+        size += entry_size;
+        modified = modified.max(entry_modified);
+    }
+    Ok((size, modified))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HOUR: Duration = Duration::from_secs(3600);
+
+    /// A workspace left behind in `dir` with a frame of `size` bytes last written `age` ago
+    fn left_behind(dir: &Path, name: &str, size: usize, age: Duration) -> PathBuf {
+        let path = dir.join(format!("{}{}", WORKSPACE_PREFIX, name));
+        fs::create_dir_all(path.join("frames")).unwrap();
+        let frame = path.join("frames").join("frame_000001.png");
+        fs::write(&frame, vec![0; size]).unwrap();
+        fs::File::options().write(true).open(&frame).unwrap().set_modified(SystemTime::now() - age).unwrap();
+        path
+    }
+
+    fn options(dir: &Path) -> GcOptions {
+        GcOptions { dir: dir.to_path_buf(), older_than: None, max_size: None, dry_run: false }
+    }
+
+    #[test]
+    fn gc_removes_old_workspaces_no_run_holds() {
+        let dir = tempfile::tempdir().unwrap();
+        let old = left_behind(dir.path(), "old", 10, 48 * HOUR);
+        let recent = left_behind(dir.path(), "recent", 10, HOUR);
+        let unrelated = dir.path().join("photos");
+        fs::create_dir(&unrelated).unwrap();
+        let mut config = Config::new("talk.mp4");
+        config.tmp_dir = Some(dir.path().to_path_buf());
+        let held = Workspace::create(&config).unwrap();
+
+        let options = GcOptions { older_than: Some(24 * HOUR), dry_run: true, ..options(dir.path()) };
+        assert_eq!(gc(&options).unwrap(), vec![Removed { path: old.clone(), size: 10 }]);
+        assert!(old.exists());
+
+        let options = GcOptions { older_than: Some(Duration::ZERO), dry_run: false, ..options };
+        let mut removed: Vec<PathBuf> = gc(&options).unwrap().into_iter().map(|removed| removed.path).collect();
+        removed.sort();
+        assert_eq!(removed, vec![old.clone(), recent.clone()]);
+        assert!(!old.exists() && !recent.exists());
+        assert!(held.path().exists() && unrelated.exists());
+    }
+
+    #[test]
+    fn gc_removes_the_oldest_until_the_rest_fit() {
+        let dir = tempfile::tempdir().unwrap();
+        let oldest = left_behind(dir.path(), "a", 100, 3 * HOUR);
+        let older = left_behind(dir.path(), "b", 100, 2 * HOUR);
+        let newest = left_behind(dir.path(), "c", 100, HOUR);
+        let removed = gc(&GcOptions { max_size: Some(150), ..options(dir.path()) }).unwrap();
+        assert_eq!(removed, vec![Removed { path: oldest, size: 100 }, Removed { path: older, size: 100 }]);
+        assert!(newest.exists());
+    }
+
+    /// Whether a workspace under `dir` is still there after a run with `policy` ends
+    fn left_after(dir: &Path, policy: WorkspacePolicy, succeeded: bool) -> bool {
+        let mut config = Config::new("talk.mp4");
+        config.tmp_dir = Some(dir.to_path_buf());
+        config.workspace = policy;
+        let mut workspace = Workspace::create(&config).unwrap();
+        if succeeded {
+            workspace.succeeded();
+        }
+        let root = workspace.path().parent().unwrap().to_path_buf();
+        drop(workspace);
+        root.exists()
+    }
+
+    #[test]
+    fn workspaces_go_as_the_policy_says() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(!left_after(dir.path(), WorkspacePolicy::Delete, false));
+        assert!(left_after(dir.path(), WorkspacePolicy::Keep, true));
+        assert!(left_after(dir.path(), WorkspacePolicy::KeepOnError, false));
+        assert!(!left_after(dir.path(), WorkspacePolicy::KeepOnError, true));
+    }
+}
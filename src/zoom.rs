@@ -0,0 +1,163 @@
+//! `--ignore-zoom`: a presenter zooming into part of a slide, or panning
+//! around it zoomed in, stays on that slide instead of every step of the zoom
+//! becoming a slide of its own.
+//!
+//! A frame that differs from the one before it is looked for in the frame the
+//! slide appeared at, at a range of scales: both are shrunk to thumbnails,
+//! and the frame's is slid over the slide's at each scale. Where it lines up
+//! closely enough, the frame is a close-up of the slide. A frame without
+//! enough detail to line up, like a zoom into an empty corner, is not
+//! recognised. With `--save-zooms` the closest view of each zoom is kept next
+//! to the slides, in `zooms/`.
+
+use image::imageops::FilterType;
+use image::{DynamicImage, GenericImageView, GrayImage};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::dedup::Decision;
+use crate::error::Error;
+use crate::manifest::Manifest;
+
+/// Width of the thumbnails compared
+const THUMBNAIL_WIDTH: u32 = 128;
+/// Smallest and largest zoom looked for, and the step between those tried
+const MIN_SCALE: f64 = 1.15;
+const MAX_SCALE: f64 = 4.0;
+const SCALE_STEP: f64 = 1.05;
+/// Correlation of the thumbnails (-1 to 1) from which a frame is a close-up
+const MIN_CORRELATION: f64 = 0.9;
+/// Standard deviation of brightness (0 to 255) a close-up needs to be lined up at all
+const MIN_DETAIL: f64 = 8.0;
+/// Subdirectory of the output directory the close-ups are saved in
+pub const ZOOMS_DIR: &str = "zooms";
+
+/// How far `current` is zoomed into `slide`, if it is a close-up of it
+pub fn find_zoom(slide: &DynamicImage, current: &DynamicImage) -> Option<f64> {
+    let (width, height) = slide.dimensions();
+    if current.dimensions() != (width, height) || width == 0 {
+        return None;
+    }
+    let thumbnail_height = ((THUMBNAIL_WIDTH as f64 * height as f64 / width as f64).round() as u32).max(1);
+    let slide = slide.resize_exact(THUMBNAIL_WIDTH, thumbnail_height, FilterType::Triangle).to_luma8();
+    // Shrunk further for each scale from there, rather than from the whole frame every time
+    let current = current.resize_exact(THUMBNAIL_WIDTH, thumbnail_height, FilterType::Triangle);
+
+    let mut best: Option<(f64, f64)> = None;
+    let mut scale = MIN_SCALE;
+    while scale <= MAX_SCALE {
+        let (view_width, view_height) =
+            ((THUMBNAIL_WIDTH as f64 / scale).round() as u32, (thumbnail_height as f64 / scale).round() as u32);
+        if view_width < 8 || view_height < 8 {
+            break;
+        }
+        let view = current.resize_exact(view_width, view_height, FilterType::Triangle).to_luma8();
+        let (mean, deviation) = statistics(view.as_raw());
+        if deviation < MIN_DETAIL {
+            return None;
+        }
+        for y in 0..=thumbnail_height - view_height {
+            for x in 0..=THUMBNAIL_WIDTH - view_width {
+                let correlation = correlate(&slide, x, y, &view, mean, deviation);
+                if best.is_none_or(|(best, _)| correlation > best) {
+                    best = Some((correlation, scale));
+                }
+            }
+        }
+        scale *= SCALE_STEP;
+    }
+    best.filter(|&(correlation, _)| correlation >= MIN_CORRELATION).map(|(_, scale)| scale)
+}
+
+/// Mean and standard deviation of `values`
+fn statistics(values: &[u8]) -> (f64, f64) {
+    let count = values.len().max(1) as f64;
+    let mean = values.iter().map(|&value| value as f64).sum::<f64>() / count;
+    let variance = values.iter().map(|&value| (value as f64 - mean).powi(2)).sum::<f64>() / count;
+    (mean, variance.sqrt())
+}
+
+/// Normalised cross-correlation of `view` with the area of `slide` at `x`, `y` it covers
+fn correlate(slide: &GrayImage, x: u32, y: u32, view: &GrayImage, mean: f64, deviation: f64) -> f64 {
+    let (width, height) = view.dimensions();
+    let count = (width * height) as f64;
+    let (mut sum, mut squares, mut products) = (0.0, 0.0, 0.0);
+    for vy in 0..height {
+        for vx in 0..width {
+            let a = slide.get_pixel(x + vx, y + vy)[0] as f64;
+            let b = view.get_pixel(vx, vy)[0] as f64;
+            sum += a;
+            squares += a * a;
+            products += a * (b - mean);
+        }
+    }
+    let slide_mean = sum / count;
+    let slide_deviation = (squares / count - slide_mean * slide_mean).max(0.0).sqrt();
+    if slide_deviation < f64::EPSILON {
+        return 0.0;
+    }
+    products / count / (slide_deviation * deviation)
+}
+
+/// Keeps the closest view of each zoom into a slide, to be saved with `--save-zooms`
+#[derive(Default)]
+pub struct Zooms {
+    /// Position the current slide appeared at
+    start: Option<usize>,
+    /// The closest view of the zoom going on, and how far it is zoomed in
+    closest: Option<(f64, DynamicImage)>,
+    /// Closest views of finished zooms, by the position their slide appeared at
+    views: Vec<(usize, DynamicImage)>,
+}
+
+impl Zooms {
+    /// Take the decision on the frame at `position`, `image`, into account
+    pub fn observe(&mut self, position: usize, decision: &Decision, image: &DynamicImage) {
+        match decision.zoom {
+            Some(scale) => {
+                if self.closest.as_ref().is_none_or(|(closest, _)| scale > *closest) {
+                    self.closest = Some((scale, image.clone()));
+                }
+            }
+            None => self.finish(),
+        }
+        if decision.is_kept() {
+            self.start = Some(position);
+        }
+    }
+
+    fn finish(&mut self) {
+        if let (Some(start), Some((_, image))) = (self.start, self.closest.take()) {
+            self.views.push((start, image));
+        }
+    }
+
+    /// Save the views of the slides in `kept` into `output_dir`'s `zooms` directory as
+    /// `<slide>_zoom.png`, `<slide>_zoom_2.png` and so on, returning their paths by slide
+    pub fn write(&mut self, kept: &[(usize, PathBuf)], output_dir: &Path) -> Result<Vec<Vec<String>>, Error> {
+        self.finish();
+        let mut saved = vec![Vec::new(); kept.len()];
+        for (start, image) in self.views.drain(..) {
+            let Some(slide) = kept.iter().position(|(kept_start, _)| *kept_start == start) else {
+                continue;
+            };
+            let stem = kept[slide].1.file_stem().unwrap_or_default().to_string_lossy().into_owned();
+            let name = match saved[slide].len() {
+                0 => format!("{}_zoom.png", stem),
+                n => format!("{}_zoom_{}.png", stem, n + 1),
+            };
+            fs::create_dir_all(output_dir.join(ZOOMS_DIR))?;
+            let path = output_dir.join(ZOOMS_DIR).join(&name);
+            image.save(&path).map_err(|e| std::io::Error::other(format!("Error saving image: {}", e)))?;
+            saved[slide].push(format!("{}/{}", ZOOMS_DIR, name));
+        }
+        Ok(saved)
+    }
+}
+
+/// Record the close-ups saved for each slide of `manifest`, in the order of `saved`
+pub fn attach(manifest: &mut Manifest, saved: Vec<Vec<String>>) {
+    for (slide, zooms) in manifest.slides.iter_mut().zip(saved) {
+        slide.zooms = zooms;
+    }
+}
@@ -0,0 +1,76 @@
+//! Synthetic frames through `run_with_source`, without ffmpeg
+
+use image::{DynamicImage, Rgb, RgbImage};
+use video_slide_extractor::{run_with_source, CancellationToken, Config, DirectorySource, Manifest, MemorySource};
+
+/// A 64x48 frame filled with `color`, with a bar at `bar` tenths down it to tell slides apart
+fn frame(color: [u8; 3], bar: u32) -> DynamicImage {
+    let mut image = RgbImage::from_pixel(64, 48, Rgb(color));
+    for y in bar * 4..bar * 4 + 4 {
+        for x in 0..64 {
+            image.put_pixel(x, y, Rgb([0, 0, 0]));
+        }
+    }
+    DynamicImage::ImageRgb8(image)
+}
+
+/// Three slides, each on screen for a few frames
+fn talk() -> Vec<DynamicImage> {
+    let (first, second, third) = (frame([240, 240, 240], 1), frame([30, 90, 200], 5), frame([200, 60, 40], 9));
+    vec![first.clone(), first.clone(), first, second.clone(), second, third.clone(), third.clone(), third]
+}
+
+fn config(output_dir: &std::path::Path) -> Config {
+    let mut config = Config::new("talk.mp4");
+    config.output_dir = output_dir.to_path_buf();
+    config
+}
+
+fn check_slides(config: &Config, manifest: &Manifest) {
+    let timestamps: Vec<f64> = manifest.slides.iter().map(|slide| slide.timestamp).collect();
+    assert_eq!(timestamps, vec![0.0, 3.0, 5.0]);
+    for (index, slide) in manifest.slides.iter().enumerate() {
+        assert_eq!(slide.index, index + 1);
+        assert!(config.output_dir.join(&slide.file).is_file(), "{} was not written", slide.file);
+    }
+    assert_eq!(manifest.frames.len(), 8);
+    assert_eq!(manifest.frames.iter().filter(|frame| frame.kept).count(), 3);
+    assert!(manifest.bad_frames.is_empty());
+
+    let written = Manifest::read(&config.output_dir).expect("the manifest was written");
+    assert_eq!(written.slides.len(), 3);
+    assert_eq!(written.slides[1].file, manifest.slides[1].file);
+}
+
+#[test]
+fn keeps_one_frame_per_slide_from_memory() {
+    let output = tempfile::tempdir().unwrap();
+    let config = config(output.path());
+    let manifest = run_with_source(&config, &mut MemorySource::new(talk()), |_| {}, &CancellationToken::new()).unwrap();
+    check_slides(&config, &manifest);
+    assert_eq!(manifest.slides[0].file, "frame_000001.png");
+}
+
+#[test]
+fn keeps_one_frame_per_slide_from_a_directory() {
+    let frames = tempfile::tempdir().unwrap();
+    for (i, image) in talk().into_iter().enumerate() {
+        image.save(frames.path().join(format!("frame_{}.png", i + 1))).unwrap();
+    }
+    let output = tempfile::tempdir().unwrap();
+    let config = config(output.path());
+    let mut source = DirectorySource::open(frames.path()).unwrap();
+    let manifest = run_with_source(&config, &mut source, |_| {}, &CancellationToken::new()).unwrap();
+    check_slides(&config, &manifest);
+    // The frames are copied, not moved
+    assert_eq!(std::fs::read_dir(frames.path()).unwrap().count(), 8);
+}
+
+#[test]
+fn stops_when_cancelled() {
+    let output = tempfile::tempdir().unwrap();
+    let cancel = CancellationToken::new();
+    cancel.cancel();
+    let result = run_with_source(&config(output.path()), &mut MemorySource::new(talk()), |_| {}, &cancel);
+    assert!(matches!(result, Err(video_slide_extractor::Error::Cancelled)));
+}